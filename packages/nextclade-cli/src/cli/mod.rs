@@ -5,5 +5,6 @@ pub mod nextclade_loop;
 pub mod nextclade_ordered_writer;
 pub mod nextclade_read_annotation;
 pub mod nextclade_seq_sort;
+pub mod nextclade_tree;
 pub mod print_help_markdown;
 pub mod verbosity;
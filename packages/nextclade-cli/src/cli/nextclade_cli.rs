@@ -3,6 +3,7 @@ use crate::cli::nextclade_dataset_list::nextclade_dataset_list;
 use crate::cli::nextclade_loop::nextclade_run;
 use crate::cli::nextclade_read_annotation::nextclade_read_annotation;
 use crate::cli::nextclade_seq_sort::nextclade_seq_sort;
+use crate::cli::nextclade_tree::{nextclade_tree_info, nextclade_tree_query};
 use crate::cli::print_help_markdown::print_help_markdown;
 use crate::cli::verbosity::Verbosity;
 use crate::io::http_client::ProxyConfig;
@@ -13,6 +14,7 @@ use clap_complete_fig::Fig;
 use eyre::{ContextCompat, Report, WrapErr, eyre};
 use itertools::Itertools;
 use nextclade::io::console::CliColorMode;
+use nextclade::io::distance_matrix::DistanceMatrixFormat;
 use nextclade::io::fs::add_extension;
 use nextclade::run::params::NextcladeInputParamsOptional;
 use nextclade::schema::schema::{NextcladeSchemaArgs, cli_handle_schema};
@@ -96,6 +98,11 @@ pub enum NextcladeCommands {
   /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade sort --help`.
   Sort(Box<NextcladeSortArgs>),
 
+  /// Inspect a reference tree: print its summary or find nodes carrying given mutations
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade tree --help`.
+  Tree(Box<NextcladeTreeArgs>),
+
   /// Read genome annotation and present it in Nextclade's internal formats. This is mostly only useful for Nextclade maintainers and the most curious users. Note that these internal formats have no stability guarantees and can be changed at any time without notice.
   ///
   /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade sort --help`.
@@ -286,8 +293,28 @@ pub enum NextcladeOutputSelection {
   Ndjson,
   Csv,
   Tsv,
+  MutationsTsv,
+  Xlsx,
+  Sqlite,
+  Summary,
+  SummaryMd,
+  CladeMutations,
+  CladeMutationsJson,
+  Consensus,
+  ConsensusSupport,
+  SiteCoverage,
+  SiteCoverageBedgraph,
+  Bed,
+  Sam,
+  Bam,
   Tree,
   TreeNwk,
+  TreePruned,
+  TreePb,
+  TreeDataset,
+  Jplace,
+  Clusters,
+  DistanceMatrix,
   Translations,
   Gff,
   Tbl,
@@ -303,14 +330,39 @@ impl NextcladeOutputSelection {
       Self::Ndjson       => "--output-ndjson",
       Self::Csv          => "--output-csv",
       Self::Tsv          => "--output-tsv",
+      Self::MutationsTsv => "--output-mutations-tsv",
+      Self::Xlsx         => "--output-xlsx",
+      Self::Sqlite       => "--output-sqlite",
+      Self::Summary      => "--output-summary",
+      Self::SummaryMd    => "--output-summary-md",
+      Self::CladeMutations => "--output-clade-mutations",
+      Self::CladeMutationsJson => "--output-clade-mutations-json",
+      Self::Consensus    => "--output-consensus",
+      Self::ConsensusSupport => "--output-consensus-support",
+      Self::SiteCoverage => "--output-site-coverage",
+      Self::SiteCoverageBedgraph => "--output-site-coverage-bedgraph",
+      Self::Bed          => "--output-bed",
+      Self::Sam          => "--output-sam",
+      Self::Bam          => "--output-bam",
       Self::Tree         => "--output-tree",
       Self::TreeNwk      => "--output-tree-nwk",
+      Self::TreePruned   => "--output-tree-pruned",
+      Self::TreePb       => "--output-tree-pb",
+      Self::TreeDataset  => "--output-tree-dataset",
+      Self::Jplace       => "--output-jplace",
+      Self::Clusters     => "--output-clusters",
+      Self::DistanceMatrix => "--output-distance-matrix",
       Self::Translations => "--output-translations",
       Self::Gff          => "--output-annotation-gff",
       Self::Tbl          => "--output-annotation-tbl",
     }
   }
 
+  /// Whether the output is only written when selected explicitly, but not with `all` or with the default selection
+  pub const fn is_opt_in(self) -> bool {
+    matches!(self, Self::DistanceMatrix)
+  }
+
   #[rustfmt::skip]
   #[allow(clippy::missing_const_for_fn)]
   pub fn is_output_set(self, args: &NextcladeRunOutputArgs) -> bool {
@@ -321,8 +373,28 @@ impl NextcladeOutputSelection {
       Self::Ndjson       => args.output_ndjson.is_some(),
       Self::Csv          => args.output_csv.is_some(),
       Self::Tsv          => args.output_tsv.is_some(),
+      Self::MutationsTsv => args.output_mutations_tsv.is_some(),
+      Self::Xlsx         => args.output_xlsx.is_some(),
+      Self::Sqlite       => args.output_sqlite.is_some(),
+      Self::Summary      => args.output_summary.is_some(),
+      Self::SummaryMd    => args.output_summary_md.is_some(),
+      Self::CladeMutations => args.output_clade_mutations.is_some(),
+      Self::CladeMutationsJson => args.output_clade_mutations_json.is_some(),
+      Self::Consensus    => args.output_consensus.is_some(),
+      Self::ConsensusSupport => args.output_consensus_support.is_some(),
+      Self::SiteCoverage => args.output_site_coverage.is_some(),
+      Self::SiteCoverageBedgraph => args.output_site_coverage_bedgraph.is_some(),
+      Self::Bed          => args.output_bed.is_some(),
+      Self::Sam          => args.output_sam.is_some(),
+      Self::Bam          => args.output_bam.is_some(),
       Self::Tree         => args.output_tree.is_some(),
       Self::TreeNwk      => args.output_tree_nwk.is_some(),
+      Self::TreePruned   => args.output_tree_pruned.is_some(),
+      Self::TreePb       => args.output_tree_pb.is_some(),
+      Self::TreeDataset  => args.output_tree_dataset.is_some(),
+      Self::Jplace       => args.output_jplace.is_some(),
+      Self::Clusters     => args.output_clusters.is_some(),
+      Self::DistanceMatrix => args.output_distance_matrix.is_some(),
       Self::Translations => args.output_translations.is_some(),
       Self::Gff          => args.output_annotation_gff.is_some(),
       Self::Tbl          => args.output_annotation_tbl.is_some(),
@@ -386,10 +458,15 @@ pub struct NextcladeRunInputArgs {
   #[clap(value_hint = ValueHint::FilePath)]
   pub input_ref: Option<PathBuf>,
 
-  /// Path to Auspice JSON v2 file containing reference tree.
+  /// Path to a file containing reference tree, in Auspice JSON v2, Newick, Nexus or UShER mutation-annotated tree
+  /// (protobuf) format.
   ///
   /// See https://nextstrain.org/docs/bioinformatics/data-formats.
   ///
+  /// The format is detected from the file contents. Newick and Nexus trees do not contain mutations, so they require
+  /// node sequences (`--input-tree-sequences`) or node data (`--input-tree-node-data`) and are converted to Auspice JSON.
+  /// Mutation-annotated trees are detected by the extension `.pb` or by their contents (also inside dataset archives and in downloaded datasets) and are converted to Auspice JSON as well.
+  ///
   /// Overrides path to `tree.json` in the dataset (`--input-dataset`).
   ///
   /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
@@ -397,6 +474,28 @@ pub struct NextcladeRunInputArgs {
   #[clap(value_hint = ValueHint::FilePath)]
   pub input_tree: Option<PathBuf>,
 
+  /// Path to a FASTA file containing sequences of the nodes of a Newick or Nexus reference tree (`--input-tree`),
+  /// aligned to the reference sequence.
+  ///
+  /// Sequences are matched to the tree nodes by name. These can be ancestral sequences (e.g. from `augur ancestral` or
+  /// IQ-TREE), or sequences of the tips only, in which case the sequences of the internal nodes are reconstructed using
+  /// parsimony. Mutations on the branches of the tree are derived from these sequences.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree_sequences: Option<PathBuf>,
+
+  /// Path to a node data JSON file for a Newick or Nexus reference tree (`--input-tree`), as produced by augur.
+  ///
+  /// Node data is used as a source of nucleotide mutations (`muts`), amino acid mutations (`aa_muts`), node sequences
+  /// (`sequence`), clades (`clade_membership`) and other text attributes of tree nodes.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree_node_data: Option<PathBuf>,
+
   /// Path to a JSON file containing configuration and data specific to a pathogen.
   ///
   /// Overrides path to `pathogen.json` in the dataset (`--input-dataset`).
@@ -524,7 +623,8 @@ pub struct NextcladeRunOutputArgs {
   ///
   /// Should contain a comma-separated list of names of output files to produce.
   ///
-  /// If 'all' is present in the list, then all other entries are ignored and all outputs are produced.
+  /// If 'all' is present in the list, then all outputs are produced, except for 'distance-matrix', which is only produced
+  /// when listed explicitly.
   ///
   /// Only valid together with `--output-all` flag.
   #[clap(
@@ -566,6 +666,14 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_translations: Option<String>,
 
+  /// Write `--output-fasta` and `--output-translations` as multiple sequence alignments which retain insertions.
+  ///
+  /// By default, insertions are stripped from aligned sequences and peptides, such that they are in coordinates of the reference sequence. With this flag, insertions of all sequences are merged into insertion columns, and sequences which lack an insertion have gaps in the corresponding columns. The reference sequence, if included with `--include-reference`, has gaps in all insertion columns.
+  ///
+  /// Note that the insertion columns are only known after all sequences are analyzed, so the sequences are kept in memory and the files are only written at the end of the run.
+  #[clap(long)]
+  pub msa_with_insertions: bool,
+
   /// Path to output Newline-delimited JSON (NDJSON) results file.
   ///
   /// This file format is most suitable for further machine processing of the results. By contrast to plain json, it can be streamed line-by line, so much bigger outputs are feasible.
@@ -622,13 +730,231 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_tsv: Option<PathBuf>,
 
-  /// Restricts columns written into tabular output files (CSV and TSV).
+  /// Path to output TSV file with mutations in long format: one row per mutation of each sequence (delimiter: tab)
+  ///
+  /// Each row contains the type of the mutation (nucleotide substitution, deletion or insertion, amino acid substitution, deletion or insertion, or frame shift), its position, reference and query characters, CDS and codons, as well as whether it is private, a reversion or labeled, and whether it is private relative to clade founders and to reference nodes.
+  ///
+  /// Sequences which failed to be analyzed are not written into this file.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_mutations_tsv: Option<PathBuf>,
+
+  /// Path to output Excel workbook (XLSX) with results.
+  ///
+  /// The workbook contains sheets: "summary" (counts of sequences, QC statuses and clades), "results" (same columns as in CSV and TSV outputs), "mutations" (one row per mutation of each sequence, as in `--output-mutations-tsv`, split across sheets "mutations_2", "mutations_3" etc. when exceeding the row limit of a sheet), "qc" (scores and statuses of each QC rule) and "errors" (sequences which failed to be analyzed).
+  ///
+  /// The whole workbook is kept in memory until the end of the run, so for large runs prefer the TSV outputs.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_xlsx: Option<PathBuf>,
+
+  /// Path to output SQLite database with results.
+  ///
+  /// The database contains normalized tables: "runs", "sequences", "clade_attributes", "qc", "nuc_substitutions", "nuc_deletions", "aa_substitutions", "aa_deletions", "insertions", "frame_shifts", "pcr_primer_changes" and "phenotype_values". Sequences which failed to be analyzed are recorded in the "sequences" table, with the "errors" column set.
+  ///
+  /// If the database already exists, the results are appended to it. Each run is recorded in the "runs" table, along with the dataset name and version, so that results of multiple runs can be queried together.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_sqlite: Option<PathBuf>,
+
+  /// Path to output JSON file with summary of the run.
+  ///
+  /// The summary aggregates results over all sequences: frequencies of clades and of other clade-like attributes (e.g. lineages), number of sequences in each QC status (overall and per QC rule), most frequent nucleotide and amino acid substitutions, histograms of coverage of each CDS, most frequent frame shifts and premature stop codons, and number of failed sequences by error category.
+  ///
+  /// See also: `--output-summary-md`.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_summary: Option<PathBuf>,
+
+  /// Path to output Markdown file with summary of the run.
+  ///
+  /// Contains the same information as the file produced by `--output-summary`, in the form of human-readable tables.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_summary_md: Option<PathBuf>,
+
+  /// Path to output TSV file with prevalence of mutations in each clade (delimiter: tab)
+  ///
+  /// For each clade assigned to the sequences, lists every nucleotide and amino acid substitution, deletion and insertion found in sequences of that clade, along with the number and the fraction of sequences of the clade which have it. For substitutions and deletions, it also reports in how many sequences the mutation is private relative to the clade founder node (i.e. emerged within the clade), and in how many it is inherited from the clade founder (i.e. is clade-defining).
+  ///
+  /// Sequences without clade and sequences which failed to be analyzed are not counted.
+  ///
+  /// See also: `--output-clade-mutations-json`.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_clade_mutations: Option<PathBuf>,
+
+  /// Path to output JSON file with prevalence of mutations in each clade.
+  ///
+  /// Contains the same information as the file produced by `--output-clade-mutations`, grouped by clade.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_clade_mutations_json: Option<PathBuf>,
+
+  /// Path to output FASTA file with consensus sequence of each clade.
+  ///
+  /// For each clade assigned to the sequences, the consensus is computed from the aligned sequences of that clade, site by site. At each site, the smallest set of the most frequent nucleotides reaching `--consensus-threshold` is called, as a single nucleotide or as an IUPAC ambiguity code. Missing sites (`N` and unsequenced ends) and other ambiguous nucleotides are not counted. Sites not covered by any sequence are called as `N`. The consensus sequences are aligned to the reference sequence, i.e. deletions are written as gaps and insertions are omitted.
+  ///
+  /// Sequences can be grouped by another clade-like attribute instead, using `--consensus-attr`. Sequences without clade and sequences which failed to be analyzed are not included.
+  ///
+  /// See also: `--output-consensus-support`.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
   ///
-  /// Should contain a comma-separated list of individual column names and/or column category names to include into both CSV and TSV outputs.
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_consensus: Option<PathBuf>,
+
+  /// Path to output TSV file with per-site support counts of consensus sequences of each clade (delimiter: tab)
   ///
-  /// If this flag is omitted, or if category 'all' is present in the list, then all other entries are ignored and all columns are written.
+  /// For each clade and each site of the reference sequence, contains the reference and the consensus nucleotides, and the number of sequences of the clade with each of nucleotides A, C, G, T and with a gap at this site. See `--output-consensus`.
   ///
-  /// Only valid together with one or multiple of flags: `--output-csv`, `--output-tsv`, `--output-all`.
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_consensus_support: Option<PathBuf>,
+
+  /// Minimum combined frequency of nucleotides called at a site of `--output-consensus`, in range (0, 1].
+  ///
+  /// With the default value, the majority nucleotide is called. Higher values result in more ambiguity codes at sites where sequences of the clade differ.
+  #[clap(long, default_value_t = 0.5)]
+  pub consensus_threshold: f64,
+
+  /// Name of the clade-like attribute to group sequences by in `--output-consensus`.
+  ///
+  /// Either "clade" or one of the clade-like attributes defined in the dataset (e.g. "Nextclade_pango").
+  #[clap(long, default_value = "clade")]
+  pub consensus_attr: String,
+
+  /// Path to output TSV file with per-site coverage profile across all sequences (delimiter: tab)
+  ///
+  /// For each site of the reference sequence, contains the number of sequences in which the site is sequenced (i.e. within the alignment range), missing (`N`), deleted, ambiguous, and has each of nucleotides A, C, G and T, as well as the fraction of sequences covering the site (sequenced and not `N`). This helps to monitor amplicon dropouts: sites which are missing in many sequences usually point to failing primers.
+  ///
+  /// Sequences which failed to be analyzed are not counted.
+  ///
+  /// See also: `--output-site-coverage-bedgraph`.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_site_coverage: Option<PathBuf>,
+
+  /// Path to output bedGraph file with fraction of sequences covering each site of the reference sequence.
+  ///
+  /// A site is covered if it is sequenced and is not `N`. Adjacent sites with the same coverage are merged into one interval. The file can be loaded into genome browsers, such as IGV or UCSC Genome Browser, along with the reference sequence. See `--output-site-coverage`.
+  ///
+  /// For file format description see: https://genome.ucsc.edu/goldenPath/help/bedgraph.html
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_site_coverage_bedgraph: Option<PathBuf>,
+
+  /// Path to output BED file with ranges of each sequence on the reference sequence.
+  ///
+  /// Contains one track per category of ranges: alignment range, missing ranges (`N`), ranges of other ambiguous nucleotides, deletions, frame shifts and unsequenced ranges of CDSs. The name of each interval is the sequence name, followed by the CDS name for frame shifts and unsequenced ranges of CDSs. The file can be loaded into genome browsers, such as IGV, along with the reference sequence, or processed with bedtools.
+  ///
+  /// For file format description see: https://genome.ucsc.edu/FAQ/FAQformat.html#format1
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_bed: Option<PathBuf>,
+
+  /// Path to output pairwise alignments of sequences to the reference sequence, in SAM format.
+  ///
+  /// Unlike `--output-fasta`, this output retains insertions: each record contains the sequence as aligned (i.e. reverse complemented if `--retry-reverse-complement` found it to be), along with CIGAR string with matches, insertions and deletions. Insertions before the beginning and after the end of the reference sequence are soft clipped. Reverse complemented sequences have flag 16 set, and the alignment score is written as `AS` tag. Sequences which failed to be aligned are written as unmapped records. Only the first word of sequence names is used, because SAM format does not allow whitespace in names.
+  ///
+  /// For file format description see: https://samtools.github.io/hts-specs/SAMv1.pdf
+  ///
+  /// See also: `--output-bam`.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_sam: Option<PathBuf>,
+
+  /// Path to output pairwise alignments of sequences to the reference sequence, in BAM format.
+  ///
+  /// Contains the same records as `--output-sam`, in binary, BGZF-compressed form. The records are not sorted; sort and index the file with `samtools sort` and `samtools index` before loading it into genome browsers, such as IGV.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// Use "-" to write to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_bam: Option<PathBuf>,
+
+  /// Restricts columns written into tabular output files (CSV, TSV and the "results" sheet of XLSX).
+  ///
+  /// Should contain a comma-separated list of individual column names and/or column category names to include into CSV, TSV and XLSX outputs.
+  ///
+  /// If this flag is omitted, then all columns are written, except for the opt-in category 'ambiguous-aa'. If category 'all' is present in the list, then all other entries are ignored and all columns are written.
+  ///
+  /// Only valid together with one or multiple of flags: `--output-csv`, `--output-tsv`, `--output-xlsx`, `--output-all`.
   #[clap(
     long,
     short = 'C',
@@ -659,6 +985,7 @@ pub struct NextcladeRunOutputArgs {
   ///
   /// Due to format limitations, it is only feasible to construct the tree for at most a few hundred to a few thousand
   /// sequences. If the tree is not needed, omitting this flag reduces processing time and memory consumption.
+  /// For larger batches, consider `--output-tree-pruned`.
   ///
   /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
   ///
@@ -669,6 +996,29 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_tree: Option<PathBuf>,
 
+  /// Path to output phylogenetic tree with input sequences placed onto it, pruned to the parts of the reference tree
+  /// around the placed sequences, in Auspice JSON V2 format.
+  ///
+  /// For each placed sequence, the tree contains the sequence itself, all of its ancestors up to the root, and the
+  /// nearest reference tips within its clade (see `--output-tree-pruned-neighbors`). The remaining parts of the reference
+  /// tree are collapsed into summary nodes, with "Node type" attribute set to "Collapsed". Unlike `--output-tree`, this
+  /// output remains feasible for large batches of sequences.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_pruned: Option<PathBuf>,
+
+  /// Number of nearest reference tips to keep around each placed sequence in `--output-tree-pruned`.
+  ///
+  /// The tips are searched within the clade of the placed sequence, i.e. not beyond the earliest ancestor with the same clade.
+  #[clap(long, default_value_t = 5)]
+  pub output_tree_pruned_neighbors: usize,
+
   /// Path to output phylogenetic tree with input sequences placed onto it, in Newick format (New Hampshire tree format)
   ///
   /// For file format description see: https://en.wikipedia.org/wiki/Newick_format
@@ -682,6 +1032,125 @@ pub struct NextcladeRunOutputArgs {
   #[clap(value_hint = ValueHint::AnyPath)]
   pub output_tree_nwk: Option<PathBuf>,
 
+  /// Path to output phylogenetic tree with input sequences placed onto it, as UShER mutation-annotated tree (protobuf)
+  ///
+  /// The file can be used with UShER, matUtils and Taxonium. Only substitutions between nucleotides A, C, G and T are
+  /// included, and branch lengths are the numbers of these substitutions. Clades are annotated on the nodes where they
+  /// begin.
+  ///
+  /// For file format description see: https://usher-wiki.readthedocs.io/en/latest/UShER.html#mutation-annotated-tree-mat
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_pb: Option<PathBuf>,
+
+  /// Path to output phylogenetic tree with input sequences placed onto it, in Auspice JSON V2 format, suitable as the
+  /// reference tree of subsequent runs.
+  ///
+  /// This allows to maintain a growing tree, for example of an outbreak, where new sequences are placed against
+  /// previously placed ones. Pass this file to `--input-tree` of the next run. Unlike `--output-tree`, branch mutations
+  /// are made consistent with the tree after attachment, the placed sequences become "Reference" nodes, and nodes
+  /// created during placement inherit clade and placement prior of their parent nodes, if they have none.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_tree_dataset: Option<PathBuf>,
+
+  /// Exclude sequences with bad QC status from `--output-tree-dataset`.
+  ///
+  /// These sequences are still included in the other tree outputs, where they are placed after all other sequences.
+  #[clap(long)]
+  pub output_tree_dataset_exclude_bad_qc: bool,
+
+  /// Path to output placements of input sequences on the reference tree, in jplace format.
+  ///
+  /// The file contains the reference tree in Newick format with numbered edges, and for each sequence, the edges
+  /// leading to its equally good placement candidates, weighted by their placement priors. Pendant lengths are derived
+  /// from private mutations of the sequence. This allows to analyze Nextclade placements with tools which consume
+  /// jplace files, such as gappa or guppy.
+  ///
+  /// For file format description see: https://doi.org/10.1371/journal.pone.0031009
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_jplace: Option<PathBuf>,
+
+  /// Path to output putative transmission clusters among input sequences, in TSV format.
+  ///
+  /// Sequences are clustered using the tree with input sequences placed onto it. The distance between two sequences is
+  /// the number of nucleotide substitutions on the path between them in this tree, not counting deletions, ambiguous
+  /// nucleotides and missing data. Two sequences belong to the same cluster if they are connected by a chain of
+  /// sequences, each at most `--clusters-max-snp-distance` away from the next one. Each row of the file contains
+  /// cluster ID, number of sequences and comma-separated sequence names. Clusters of a single sequence are omitted.
+  ///
+  /// Cluster IDs are also added to the output trees, as "transmission_cluster" node attribute.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_clusters: Option<PathBuf>,
+
+  /// Maximum number of nucleotide substitutions between two sequences linked in `--output-clusters`.
+  #[clap(long, default_value_t = 2)]
+  pub clusters_max_snp_distance: usize,
+
+  /// Path to output matrix of pairwise nucleotide distances between input sequences.
+  ///
+  /// The distance between two sequences is the number of sites where their aligned sequences differ, computed from
+  /// substitutions, deletions, ambiguous nucleotides and missing ranges relative to the reference sequence, without
+  /// realignment. Insertions are not counted. Missing sites (`N` and unsequenced ends) are not counted, unless
+  /// `--distance-matrix-count-missing` is set. The format is set with `--distance-matrix-format`.
+  ///
+  /// The number of pairs grows quadratically with the number of sequences, so this output is only feasible for up to a few
+  /// thousand sequences.
+  ///
+  /// Takes precedence over paths configured with `--output-all`, `--output-basename` and `--output-selection`.
+  ///
+  /// If the provided file path ends with one of the supported extensions: "gz", "bz2", "xz", "zst", then the file will be written compressed. Use "-" to write the uncompressed to standard output (stdout).
+  ///
+  /// If the required directory tree does not exist, it will be created.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub output_distance_matrix: Option<PathBuf>,
+
+  /// Format of `--output-distance-matrix`.
+  #[clap(long, value_enum, default_value_t = DistanceMatrixFormat::default())]
+  pub distance_matrix_format: DistanceMatrixFormat,
+
+  /// Omit pairs of sequences with distance above this value from `--output-distance-matrix` in `sparse-tsv` format.
+  #[clap(long)]
+  pub distance_matrix_max_distance: Option<usize>,
+
+  /// Count sites which are missing in only one of the sequences as differences in `--output-distance-matrix`.
+  ///
+  /// By default, sites which are missing in either of the sequences are not counted, so that sequences with low coverage
+  /// do not appear distant from well-covered ones.
+  #[clap(long)]
+  pub distance_matrix_count_missing: bool,
+
+  /// Do not count sites which contain an ambiguous nucleotide in either of the sequences in `--output-distance-matrix`.
+  #[clap(long)]
+  pub distance_matrix_ignore_ambiguous: bool,
+
   /// Path to output annotation for query sequences in GFF3 format (EXPERIMENTAL)
   ///
   /// This output contains annotation of genetic features (genes and CDSes) for each query sequence.
@@ -827,6 +1296,200 @@ pub struct NextcladeSortArgs {
   pub proxy_config: ProxyConfig,
 }
 
+#[derive(Parser, Debug)]
+pub struct NextcladeTreeArgs {
+  #[clap(subcommand)]
+  pub command: NextcladeTreeCommands,
+}
+
+#[derive(Subcommand, Debug)]
+#[clap(verbatim_doc_comment)]
+pub enum NextcladeTreeCommands {
+  /// Print summary of the reference tree: number of nodes, clades with their founder nodes and defining mutations, consistency of the root sequence with the reference sequence, divergence units, placement masks and reference node search configuration.
+  ///
+  /// The tree is loaded and preprocessed the same way as during `nextclade run`, from the same dataset or individual input files. Only the reference sequence, the reference tree and the files needed to interpret it are read.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade tree info --help`.
+  Info(NextcladeTreeInfoArgs),
+
+  /// Find nodes of the reference tree carrying given mutations
+  ///
+  /// The tree is loaded and preprocessed the same way as during `nextclade run`, from the same dataset or individual input files. Only the reference sequence, the reference tree and the files needed to interpret it are read.
+  ///
+  /// For short help type: `nextclade -h`, for extended help type: `nextclade --help`. Each subcommand has its own help, for example: `nextclade tree query --help`.
+  Query(NextcladeTreeQueryArgs),
+}
+
+/// Inputs of the `tree` subcommands: the reference tree and the files needed to interpret it
+#[derive(Parser, Debug, Clone)]
+pub struct NextcladeTreeInputArgs {
+  /// Path to a directory or a zip file containing a dataset.
+  ///
+  /// See `nextclade run --help` for details. If this flag is not provided, `--input-ref` and `--input-tree` are required.
+  #[clap(long, short = 'D')]
+  #[clap(value_hint = ValueHint::AnyPath)]
+  pub input_dataset: Option<PathBuf>,
+
+  /// Name of the dataset to download and use.
+  ///
+  /// See `nextclade run --help` for details. This flag is mutually exclusive with `--input_dataset`
+  #[clap(long, short = 'd')]
+  pub dataset_name: Option<String>,
+
+  /// Path to a FASTA file containing reference sequence. This file should contain exactly 1 sequence.
+  ///
+  /// Overrides path to `reference.fasta` in the dataset (`--input-dataset`).
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'r')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_ref: Option<PathBuf>,
+
+  /// Path to a file containing reference tree, in Auspice JSON v2, Newick, Nexus or UShER mutation-annotated tree
+  /// (protobuf) format.
+  ///
+  /// Overrides path to `tree.json` in the dataset (`--input-dataset`).
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'a')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree: Option<PathBuf>,
+
+  /// Path to a FASTA file containing sequences of the nodes of a Newick or Nexus reference tree (`--input-tree`),
+  /// aligned to the reference sequence.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree_sequences: Option<PathBuf>,
+
+  /// Path to a node data JSON file for a Newick or Nexus reference tree (`--input-tree`), as produced by augur.
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_tree_node_data: Option<PathBuf>,
+
+  /// Path to a JSON file containing configuration and data specific to a pathogen.
+  ///
+  /// Overrides path to `pathogen.json` in the dataset (`--input-dataset`).
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'p')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_pathogen_json: Option<PathBuf>,
+
+  /// Path to a file containing genome annotation in GFF3 format.
+  ///
+  /// Genome annotation is used to find amino acid mutations on the tree. If not supplied, amino acid mutations are not
+  /// reported.
+  ///
+  /// Overrides genome annotation provided by the dataset (`--input-dataset` or `--dataset-name`).
+  ///
+  /// Supports the following compression formats: "gz", "bz2", "xz", "zst". Use "-" to read uncompressed data from standard input (stdin).
+  #[clap(long, short = 'm')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub input_annotation: Option<PathBuf>,
+
+  /// Use custom dataset server
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::Url)]
+  #[clap(default_value_t = Url::from_str(DATA_FULL_DOMAIN).expect("Invalid URL"))]
+  pub server: Url,
+}
+
+impl NextcladeTreeInputArgs {
+  /// Converts to inputs of `run`, so that the tree is read by the same dataset loaders
+  pub fn to_run_input_args(&self) -> NextcladeRunInputArgs {
+    let Self {
+      input_dataset,
+      dataset_name,
+      input_ref,
+      input_tree,
+      input_tree_sequences,
+      input_tree_node_data,
+      input_pathogen_json,
+      input_annotation,
+      server,
+    } = self.clone();
+
+    NextcladeRunInputArgs {
+      input_fastas: vec![],
+      input_fasta: None,
+      input_dataset,
+      dataset_name,
+      input_ref,
+      input_tree,
+      input_tree_sequences,
+      input_tree_node_data,
+      input_pathogen_json,
+      input_annotation,
+      cds_selection: None,
+      input_pcr_primers: None,
+      server,
+      input_root_seq: None,
+      reference: None,
+      input_qc_config: None,
+      input_virus_properties: None,
+      input_gene_map: None,
+      genemap: None,
+      genes: None,
+    }
+  }
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeTreeInfoArgs {
+  #[clap(flatten, next_help_heading = "Inputs")]
+  pub inputs: NextcladeTreeInputArgs,
+
+  /// Path to output JSON or YAML file.
+  ///
+  /// The format is chosen based on file extension: ".json" or ".yaml".
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub output: Option<PathBuf>,
+
+  /// Print console output in JSON format, rather than human-readable text.
+  #[clap(long)]
+  pub json: bool,
+}
+
+#[derive(Parser, Debug)]
+#[clap(verbatim_doc_comment)]
+pub struct NextcladeTreeQueryArgs {
+  #[clap(flatten, next_help_heading = "Inputs")]
+  pub inputs: NextcladeTreeInputArgs,
+
+  /// Comma-separated list of mutations to search for, relative to the reference sequence.
+  ///
+  /// Nucleotide mutations are written as `<ref><pos><qry>` (e.g. `C241T`) and amino acid mutations as `<cds>:<ref><pos><qry>` (e.g. `S:E484K`). Positions are 1-based. Only nodes carrying all of the mutations are reported.
+  #[clap(long, num_args=1.., use_value_delimiter = true)]
+  #[clap(value_hint = ValueHint::Other)]
+  pub mutation: Vec<String>,
+
+  /// Only report nodes on the branch to which the mutations occur, rather than all nodes carrying them.
+  #[clap(long)]
+  pub on_branch: bool,
+
+  /// Only report nodes belonging to this clade.
+  #[clap(long)]
+  #[clap(value_hint = ValueHint::Other)]
+  pub clade: Option<String>,
+
+  /// Path to output JSON or YAML file.
+  ///
+  /// The format is chosen based on file extension: ".json" or ".yaml".
+  #[clap(long, short = 'o')]
+  #[clap(value_hint = ValueHint::FilePath)]
+  pub output: Option<PathBuf>,
+
+  /// Print console output in JSON format, rather than human-readable table.
+  #[clap(long)]
+  pub json: bool,
+}
+
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[clap(verbatim_doc_comment)]
@@ -888,8 +1551,29 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
         output_json,
         output_csv,
         output_tsv,
+        output_mutations_tsv,
+        output_xlsx,
+        output_sqlite,
+        output_summary,
+        output_summary_md,
+        output_clade_mutations,
+        output_clade_mutations_json,
+        output_consensus,
+        output_consensus_support,
+        output_site_coverage,
+        output_site_coverage_bedgraph,
+        output_bed,
+        output_sam,
+        output_bam,
         output_tree,
         output_tree_nwk,
+        output_tree_pruned,
+        output_tree_pb,
+        output_tree_dataset,
+        output_jplace,
+        output_clusters,
+        output_distance_matrix,
+        distance_matrix_format,
         output_annotation_gff,
         output_annotation_tbl,
         ..
@@ -905,9 +1589,12 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
 
     let default_output_file_path = output_all.join(&output_basename);
 
-    // If `--output-selection` is empty or contains `all`, then fill it with all possible variants
+    // If `--output-selection` is empty or contains `all`, then fill it with all possible variants, except for the
+    // opt-in ones which are not listed explicitly
     if output_selection.is_empty() || output_selection.contains(&NextcladeOutputSelection::All) {
-      *output_selection = NextcladeOutputSelection::iter().collect_vec();
+      *output_selection = NextcladeOutputSelection::iter()
+        .filter(|output| !output.is_opt_in() || output_selection.contains(output))
+        .collect_vec();
     }
 
     // We use `Option::get_or_insert()` mutable method here in order
@@ -946,6 +1633,62 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
       output_tsv.get_or_insert(add_extension(&default_output_file_path, "tsv"));
     }
 
+    if output_selection.contains(&NextcladeOutputSelection::MutationsTsv) {
+      output_mutations_tsv.get_or_insert(add_extension(&default_output_file_path, "mutations.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Xlsx) {
+      output_xlsx.get_or_insert(add_extension(&default_output_file_path, "xlsx"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Sqlite) {
+      output_sqlite.get_or_insert(add_extension(&default_output_file_path, "sqlite"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Summary) {
+      output_summary.get_or_insert(add_extension(&default_output_file_path, "summary.json"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::SummaryMd) {
+      output_summary_md.get_or_insert(add_extension(&default_output_file_path, "summary.md"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::CladeMutations) {
+      output_clade_mutations.get_or_insert(add_extension(&default_output_file_path, "clade_mutations.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::CladeMutationsJson) {
+      output_clade_mutations_json.get_or_insert(add_extension(&default_output_file_path, "clade_mutations.json"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Consensus) {
+      output_consensus.get_or_insert(add_extension(&default_output_file_path, "consensus.fasta"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::ConsensusSupport) {
+      output_consensus_support.get_or_insert(add_extension(&default_output_file_path, "consensus_support.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::SiteCoverage) {
+      output_site_coverage.get_or_insert(add_extension(&default_output_file_path, "site_coverage.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::SiteCoverageBedgraph) {
+      output_site_coverage_bedgraph.get_or_insert(add_extension(&default_output_file_path, "site_coverage.bedgraph"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Bed) {
+      output_bed.get_or_insert(add_extension(&default_output_file_path, "bed"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Sam) {
+      output_sam.get_or_insert(add_extension(&default_output_file_path, "sam"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Bam) {
+      output_bam.get_or_insert(add_extension(&default_output_file_path, "bam"));
+    }
+
     if output_selection.contains(&NextcladeOutputSelection::Tree) {
       output_tree.get_or_insert(add_extension(&default_output_file_path, "auspice.json"));
     }
@@ -954,6 +1697,31 @@ pub fn nextclade_get_output_filenames(run_args: &mut NextcladeRunArgs) -> Result
       output_tree_nwk.get_or_insert(add_extension(&default_output_file_path, "nwk"));
     }
 
+    if output_selection.contains(&NextcladeOutputSelection::TreePruned) {
+      output_tree_pruned.get_or_insert(add_extension(&default_output_file_path, "pruned.auspice.json"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::TreePb) {
+      output_tree_pb.get_or_insert(add_extension(&default_output_file_path, "pb"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::TreeDataset) {
+      output_tree_dataset.get_or_insert(add_extension(&default_output_file_path, "dataset.auspice.json"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Jplace) {
+      output_jplace.get_or_insert(add_extension(&default_output_file_path, "jplace"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::Clusters) {
+      output_clusters.get_or_insert(add_extension(&default_output_file_path, "clusters.tsv"));
+    }
+
+    if output_selection.contains(&NextcladeOutputSelection::DistanceMatrix) {
+      let extension = format!("distance_matrix.{}", distance_matrix_format.extension());
+      output_distance_matrix.get_or_insert(add_extension(&default_output_file_path, extension));
+    }
+
     if output_selection.contains(&NextcladeOutputSelection::Gff) {
       output_annotation_gff.get_or_insert(add_extension(&default_output_file_path, "gff"));
     }
@@ -1161,13 +1929,18 @@ pub fn nextclade_check_column_config_args(run_args: &NextcladeRunArgs) -> Result
     output_all,
     output_csv,
     output_tsv,
+    output_xlsx,
     output_columns_selection,
     ..
   } = &run_args.outputs;
 
-  if !output_columns_selection.is_empty() && [output_all, output_csv, output_tsv].iter().all(|arg| arg.is_none()) {
+  if !output_columns_selection.is_empty()
+    && [output_all, output_csv, output_tsv, output_xlsx]
+      .iter()
+      .all(|arg| arg.is_none())
+  {
     return make_error!(
-      "The `--output-columns-selection` argument configures column-based output formats and can only be used when one or more of the column-based file outputs is requested, i.e. together with one or multiple of `--output-all`, `--output-csv`, `--output-tsv`."
+      "The `--output-columns-selection` argument configures column-based output formats and can only be used when one or more of the column-based file outputs is requested, i.e. together with one or multiple of `--output-all`, `--output-csv`, `--output-tsv`, `--output-xlsx`."
     );
   }
 
@@ -1204,6 +1977,10 @@ pub fn nextclade_parse_cli_args() -> Result<(), Report> {
       }
     },
     NextcladeCommands::Sort(seq_sort_args) => nextclade_seq_sort(&seq_sort_args),
+    NextcladeCommands::Tree(tree_command) => match tree_command.command {
+      NextcladeTreeCommands::Info(tree_info_args) => nextclade_tree_info(&tree_info_args),
+      NextcladeTreeCommands::Query(tree_query_args) => nextclade_tree_query(&tree_query_args),
+    },
     NextcladeCommands::ReadAnnotation(read_annotation_args) => nextclade_read_annotation(&read_annotation_args),
     NextcladeCommands::Schema(args) => cli_handle_schema(&args),
   }
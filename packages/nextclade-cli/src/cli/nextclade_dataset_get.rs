@@ -102,16 +102,21 @@ pub fn dataset_file_http_get(
   dataset: &Dataset,
   filename: impl AsRef<str>,
 ) -> Result<String, Report> {
+  let content = dataset_file_http_get_bytes(http, dataset, filename)?;
+  let content_string = String::from_utf8(content)?;
+  Ok(content_string)
+}
+
+pub fn dataset_file_http_get_bytes(
+  http: &HttpClient,
+  dataset: &Dataset,
+  filename: impl AsRef<str>,
+) -> Result<Vec<u8>, Report> {
   let filename = filename.as_ref();
   let url = dataset.file_path_latest(filename);
-
-  let content = http
+  http
     .get(&url)
-    .wrap_err_with(|| format!("when fetching dataset file '{filename}'"))?;
-
-  let content_string = String::from_utf8(content)?;
-
-  Ok(content_string)
+    .wrap_err_with(|| format!("when fetching dataset file '{filename}'"))
 }
 
 fn format_suggestions(candidates: impl Iterator<Item = impl AsRef<str> + Copy>, actual: impl AsRef<str>) -> String {
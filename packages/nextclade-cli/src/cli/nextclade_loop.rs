@@ -3,16 +3,28 @@ use crate::cli::nextclade_ordered_writer::NextcladeOrderedWriter;
 use crate::dataset::dataset_download::nextclade_get_inputs;
 use eyre::{ContextCompat, Report, WrapErr};
 use log::info;
+use nextclade::analyze::distance_matrix::{DistanceMatrixParams, DistanceMatrixSeq};
 use nextclade::analyze::pcr_primers::PcrPrimer;
 use nextclade::gene::gene_map_display::gene_map_to_table_string;
 use nextclade::graph::graph::Graph;
+use nextclade::io::distance_matrix::distance_matrix_write_to_file;
 use nextclade::io::fasta::{FastaReader, FastaRecord};
+use nextclade::io::jplace::jplace_write_to_file;
 use nextclade::io::json::{JsonPretty, json_write};
 use nextclade::io::nextclade_csv_column_config::CsvColumnConfig;
 use nextclade::io::nwk_writer::nwk_write_to_file;
+use nextclade::io::usher_mat::{convert_graph_to_usher_mat, usher_mat_write_to_file};
+use nextclade::io::xlsx::{book_save_to_file, results_to_excel_book};
+use nextclade::qc::qc_run::QcStatus;
 use nextclade::run::nextclade_wasm::{AnalysisInitialData, AnalysisOutput, Nextclade};
 use nextclade::tree::tree_builder::graph_attach_new_nodes_in_place;
-use nextclade::types::outputs::NextcladeOutputs;
+use nextclade::tree::tree_dataset::graph_prepare_dataset_tree_in_place;
+use nextclade::tree::tree_find_transmission_clusters::{
+  graph_annotate_transmission_clusters_in_place, graph_find_transmission_clusters, transmission_clusters_write_tsv,
+};
+use nextclade::tree::tree_prune::convert_graph_to_pruned_auspice_tree;
+use nextclade::types::outputs::{NextcladeErrorOutputs, NextcladeOutputs};
+use nextclade::utils::error::report_to_string;
 use nextclade::utils::option::OptionMapRefFallible;
 use std::sync::{Arc, Mutex};
 
@@ -25,7 +37,7 @@ pub struct NextcladeRecord {
 pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
   info!("Command-line arguments:\n{run_args:#?}");
 
-  let inputs = nextclade_get_inputs(&run_args, &run_args.inputs.cds_selection)?;
+  let inputs = nextclade_get_inputs(&run_args.inputs, &run_args.params, &run_args.inputs.cds_selection)?;
 
   if inputs.gene_map.is_empty() {
     // If there is no genome annotation, then we cannot emit these output files
@@ -53,8 +65,18 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
 
   let should_write_tree = run_args.outputs.output_tree.is_some()
     || run_args.outputs.output_tree_nwk.is_some()
+    || run_args.outputs.output_tree_pruned.is_some()
+    || run_args.outputs.output_tree_pb.is_some()
+    || run_args.outputs.output_tree_dataset.is_some()
+    || run_args.outputs.output_jplace.is_some()
+    || run_args.outputs.output_clusters.is_some()
     || run_args.outputs.output_graph.is_some();
+  let should_collect_outputs = should_write_tree || run_args.outputs.output_xlsx.is_some();
+  let should_collect_distance_matrix_seqs = run_args.outputs.output_distance_matrix.is_some();
+  let should_collect_errors = run_args.outputs.output_xlsx.is_some();
   let mut outputs = Vec::<NextcladeOutputs>::new();
+  let mut distance_matrix_seqs = Vec::<DistanceMatrixSeq>::new();
+  let mut errors = Vec::<NextcladeErrorOutputs>::new();
 
   let csv_column_config = CsvColumnConfig::new(&run_args.outputs.output_columns_selection)?;
 
@@ -70,7 +92,10 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
 
     let nextclade = &nextclade;
     let outputs = &mut outputs;
+    let errors = &mut errors;
+    let distance_matrix_seqs = &mut distance_matrix_seqs;
     let run_args = &run_args;
+    let csv_column_config = &csv_column_config;
 
     let thread_errors_cloned = Arc::clone(&thread_errors);
     s.spawn(move || {
@@ -102,13 +127,19 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
           for fasta_record in &fasta_receiver {
             info!("Processing sequence '{}'", fasta_record.seq_name);
 
-            let outputs_or_err = nextclade.run(&fasta_record).wrap_err_with(|| {
+            let mut outputs_or_err = nextclade.run(&fasta_record).wrap_err_with(|| {
               format!(
                 "When processing sequence #{} '{}'",
                 fasta_record.index, fasta_record.seq_name
               )
             });
 
+            if let Ok(AnalysisOutput { analysis_result, .. }) = &mut outputs_or_err
+              && nextclade.params.alignment.split_chimeras
+            {
+              nextclade.run_chimera_parts_in_place(&fasta_record, analysis_result);
+            }
+
             // Important: **all** records should be sent into this channel, without skipping.
             // In in-order mode, writer that receives from this channel expects a contiguous stream of indices. Gaps in
             // the indices will cause writer to stall waiting for the missing index and the buffering queue to grow. Any
@@ -143,14 +174,22 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
         } = nextclade.get_initial_data();
 
         let mut output_writer = NextcladeOrderedWriter::new(
+          &nextclade.ref_record.seq_name,
+          &nextclade.ref_seq,
           &nextclade.gene_map,
           &clade_node_attr_key_descs,
           &phenotype_attr_descs,
           &ref_nodes,
           &aa_motif_keys,
-          &csv_column_config,
+          csv_column_config,
           &run_args.outputs,
           &nextclade.params,
+          &nextclade.dataset_name,
+          nextclade
+            .virus_properties
+            .version
+            .as_ref()
+            .map(|version| version.tag.as_str()),
         )
         .wrap_err("When creating output writer")?;
 
@@ -161,14 +200,37 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
         }
 
         for record in result_receiver {
-          if should_write_tree && let Ok(AnalysisOutput { analysis_result, .. }) = &record.outputs_or_err {
-            outputs.push(analysis_result.clone());
+          match &record.outputs_or_err {
+            Ok(AnalysisOutput { analysis_result, .. }) => {
+              if should_collect_distance_matrix_seqs {
+                distance_matrix_seqs.push(DistanceMatrixSeq::from_outputs(
+                  analysis_result,
+                  nextclade.ref_seq.len(),
+                ));
+              }
+              if should_collect_outputs {
+                outputs.push(analysis_result.clone());
+              }
+            }
+            Err(report) if should_collect_errors => errors.push(NextcladeErrorOutputs {
+              index: record.index,
+              seq_name: record.seq_name.clone(),
+              errors: vec![report_to_string(report)],
+            }),
+            _ => {}
           }
           output_writer
             .write_record(record)
             .wrap_err("When writing output record")?;
         }
 
+        // Other threads report their errors before closing the channels, so by now it is known whether the run has
+        // succeeded. On failure, the writer is dropped instead, which finalizes output files but does not commit the
+        // SQLite transaction.
+        if thread_errors_cloned.lock().unwrap().is_empty() {
+          output_writer.finish().wrap_err("When finalizing output writer")?;
+        }
+
         Ok::<_, Report>(())
       })();
 
@@ -178,9 +240,28 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
     });
   });
 
-  let mut errors = Arc::try_unwrap(thread_errors).unwrap_or_default().into_inner()?;
-  if !errors.is_empty() {
-    return Err(errors.remove(0));
+  let mut thread_errors = Arc::try_unwrap(thread_errors).unwrap_or_default().into_inner()?;
+  if !thread_errors.is_empty() {
+    return Err(thread_errors.remove(0));
+  }
+
+  if let Some(output_xlsx) = &run_args.outputs.output_xlsx {
+    let mut book = results_to_excel_book(&outputs, &errors, &nextclade.get_initial_data(), &csv_column_config)?;
+    book_save_to_file(&mut book, output_xlsx)?;
+  }
+
+  if let Some(output_distance_matrix) = &run_args.outputs.output_distance_matrix {
+    let params = DistanceMatrixParams {
+      count_missing: run_args.outputs.distance_matrix_count_missing,
+      ignore_ambiguous: run_args.outputs.distance_matrix_ignore_ambiguous,
+    };
+    distance_matrix_write_to_file(
+      output_distance_matrix,
+      &distance_matrix_seqs,
+      params,
+      run_args.outputs.distance_matrix_format,
+      run_args.outputs.distance_matrix_max_distance,
+    )?;
   }
 
   if should_write_tree {
@@ -188,20 +269,78 @@ pub fn nextclade_run(mut run_args: NextcladeRunArgs) -> Result<(), Report> {
       ref_seq, params, graph, ..
     } = nextclade;
     if let Some(mut graph) = graph {
-      graph_attach_new_nodes_in_place(&mut graph, outputs, ref_seq.len(), &params.tree_builder)?;
+      // Placements refer to the reference tree, so they are written before new nodes are attached
+      if let Some(output_jplace) = run_args.outputs.output_jplace {
+        let invocation = std::env::args().collect::<Vec<_>>().join(" ");
+        jplace_write_to_file(output_jplace, &graph, &outputs, &invocation)?;
+      }
+
+      // Sequences with bad QC are excluded from the dataset tree by attaching them last: the dataset tree is a snapshot
+      // of the graph taken before that, which is only copied if other outputs need the graph with all sequences.
+      let mut dataset_graph = None;
+      if run_args.outputs.output_tree_dataset.is_some() && run_args.outputs.output_tree_dataset_exclude_bad_qc {
+        let (good_outputs, bad_outputs): (Vec<_>, Vec<_>) = outputs
+          .into_iter()
+          .partition(|output| !matches!(output.qc.overall_status, QcStatus::Bad));
+
+        graph_attach_new_nodes_in_place(&mut graph, good_outputs, ref_seq.len(), &params.tree_builder)?;
+
+        let needs_full_graph = run_args.outputs.output_clusters.is_some()
+          || run_args.outputs.output_tree.is_some()
+          || run_args.outputs.output_tree_pruned.is_some()
+          || run_args.outputs.output_tree_nwk.is_some()
+          || run_args.outputs.output_tree_pb.is_some()
+          || run_args.outputs.output_graph.is_some();
+
+        if needs_full_graph {
+          dataset_graph = Some(graph.clone());
+          graph_attach_new_nodes_in_place(&mut graph, bad_outputs, ref_seq.len(), &params.tree_builder)?;
+        }
+      } else {
+        graph_attach_new_nodes_in_place(&mut graph, outputs, ref_seq.len(), &params.tree_builder)?;
+      }
+
+      if let Some(output_clusters) = run_args.outputs.output_clusters {
+        let clusters = graph_find_transmission_clusters(&graph, run_args.outputs.clusters_max_snp_distance)?;
+        graph_annotate_transmission_clusters_in_place(&mut graph, &clusters)?;
+        // The dataset graph is a copy of the graph taken before some of the sequences were attached, so it shares node
+        // keys with the graph
+        if let Some(dataset_graph) = &mut dataset_graph {
+          graph_annotate_transmission_clusters_in_place(dataset_graph, &clusters)?;
+        }
+        transmission_clusters_write_tsv(output_clusters, &clusters)?;
+      }
 
       if let Some(output_tree) = run_args.outputs.output_tree {
         let tree = Graph::to_auspice_tree(&graph)?;
         json_write(output_tree, &tree, JsonPretty(true))?;
       }
 
+      if let Some(output_tree_pruned) = run_args.outputs.output_tree_pruned {
+        let tree = convert_graph_to_pruned_auspice_tree(&graph, run_args.outputs.output_tree_pruned_neighbors)?;
+        json_write(output_tree_pruned, &tree, JsonPretty(true))?;
+      }
+
       if let Some(output_tree_nwk) = run_args.outputs.output_tree_nwk {
         nwk_write_to_file(output_tree_nwk, &graph)?;
       }
 
+      if let Some(output_tree_pb) = run_args.outputs.output_tree_pb {
+        let mat = convert_graph_to_usher_mat(&graph, &ref_seq)?;
+        usher_mat_write_to_file(output_tree_pb, &mat)?;
+      }
+
       if let Some(output_graph) = run_args.outputs.output_graph {
         json_write(output_graph, &graph, JsonPretty(true))?;
       }
+
+      // Modifies the graph, so it is written last
+      if let Some(output_tree_dataset) = run_args.outputs.output_tree_dataset {
+        let dataset_graph = dataset_graph.get_or_insert(graph);
+        graph_prepare_dataset_tree_in_place(dataset_graph)?;
+        let tree = Graph::to_auspice_tree(dataset_graph)?;
+        json_write(output_tree_dataset, &tree, JsonPretty(true))?;
+      }
     }
   }
 
@@ -1,18 +1,28 @@
 use crate::cli::nextclade_cli::NextcladeRunOutputArgs;
 use crate::cli::nextclade_loop::NextcladeRecord;
-use eyre::{Report, WrapErr};
+use crate::io::results_sqlite::NextcladeResultsSqliteWriter;
+use eyre::Report;
 use itertools::Itertools;
 use log::{info, warn};
-use nextclade::alphabet::nuc::from_nuc_seq;
+use nextclade::alphabet::nuc::{Nuc, from_nuc_seq, to_nuc_seq};
 use nextclade::analyze::virus_properties::PhenotypeAttrDesc;
 use nextclade::gene::gene_map::GeneMap;
+use nextclade::io::bam::BamWriter;
+use nextclade::io::clade_consensus::CladeConsensusWriter;
+use nextclade::io::clade_mutation_prevalence::CladeMutationPrevalenceWriter;
 use nextclade::io::fasta::{FastaPeptideWriter, FastaRecord, FastaWriter};
+use nextclade::io::fasta_msa::{FastaMsaWriter, FastaPeptideMsaWriter};
 use nextclade::io::genbank_tbl::GenbankTblFileWriter;
 use nextclade::io::gff3_writer::Gff3FileWriter;
 use nextclade::io::ndjson::NdjsonFileWriter;
+use nextclade::io::nextclade_bed::NextcladeBedWriter;
 use nextclade::io::nextclade_csv::NextcladeResultsCsvFileWriter;
 use nextclade::io::nextclade_csv_column_config::CsvColumnConfig;
+use nextclade::io::nextclade_mutations_tsv::NextcladeMutationsTsvFileWriter;
 use nextclade::io::results_json::ResultsJsonWriter;
+use nextclade::io::run_summary::RunSummaryWriter;
+use nextclade::io::sam::SamWriter;
+use nextclade::io::site_coverage::SiteCoverageWriter;
 use nextclade::run::nextclade_wasm::AnalysisOutput;
 use nextclade::run::params::NextcladeInputParams;
 use nextclade::translate::translate_genes::Translation;
@@ -26,19 +36,33 @@ use std::collections::HashMap;
 pub struct NextcladeOrderedWriter {
   fasta_writer: Option<FastaWriter>,
   fasta_peptide_writer: Option<FastaPeptideWriter>,
+  fasta_msa_writer: Option<FastaMsaWriter<Nuc>>,
+  fasta_peptide_msa_writer: Option<FastaPeptideMsaWriter>,
   output_json_writer: Option<ResultsJsonWriter>,
   output_ndjson_writer: Option<NdjsonFileWriter>,
   output_csv_writer: Option<NextcladeResultsCsvFileWriter>,
   output_tsv_writer: Option<NextcladeResultsCsvFileWriter>,
+  output_mutations_tsv_writer: Option<NextcladeMutationsTsvFileWriter>,
+  output_sqlite_writer: Option<NextcladeResultsSqliteWriter>,
+  output_summary_writer: Option<RunSummaryWriter>,
+  output_clade_mutations_writer: Option<CladeMutationPrevalenceWriter>,
+  output_consensus_writer: Option<CladeConsensusWriter>,
+  output_site_coverage_writer: Option<SiteCoverageWriter>,
+  output_bed_writer: Option<NextcladeBedWriter>,
+  output_sam_writer: Option<SamWriter>,
+  output_bam_writer: Option<BamWriter>,
   output_gff_writer: Option<Gff3FileWriter>,
   output_tbl_writer: Option<GenbankTblFileWriter>,
   expected_index: usize,
   queue: HashMap<usize, NextcladeRecord>,
   in_order: bool,
+  is_finished: bool,
 }
 
 impl NextcladeOrderedWriter {
   pub fn new(
+    ref_seq_name: &str,
+    ref_seq: &[Nuc],
     gene_map: &GeneMap,
     clade_node_attr_descs: &[CladeNodeAttrKeyDesc],
     phenotype_attr_key_desc: &[PhenotypeAttrDesc],
@@ -47,12 +71,28 @@ impl NextcladeOrderedWriter {
     csv_column_config: &CsvColumnConfig,
     output_params: &NextcladeRunOutputArgs,
     params: &NextcladeInputParams,
+    dataset_name: &str,
+    dataset_version: Option<&str>,
   ) -> Result<Self, Report> {
-    let fasta_writer = output_params.output_fasta.map_ref_fallible(FastaWriter::from_path)?;
-
-    let fasta_peptide_writer = output_params
-      .output_translations
-      .map_ref_fallible(|output_translations| FastaPeptideWriter::new(gene_map, output_translations))?;
+    let (fasta_writer, fasta_msa_writer) = if output_params.msa_with_insertions {
+      let fasta_msa_writer = output_params.output_fasta.map_ref_fallible(FastaMsaWriter::from_path)?;
+      (None, fasta_msa_writer)
+    } else {
+      let fasta_writer = output_params.output_fasta.map_ref_fallible(FastaWriter::from_path)?;
+      (fasta_writer, None)
+    };
+
+    let (fasta_peptide_writer, fasta_peptide_msa_writer) = if output_params.msa_with_insertions {
+      let fasta_peptide_msa_writer = output_params
+        .output_translations
+        .map_ref_fallible(|output_translations| FastaPeptideMsaWriter::new(gene_map, output_translations))?;
+      (None, fasta_peptide_msa_writer)
+    } else {
+      let fasta_peptide_writer = output_params
+        .output_translations
+        .map_ref_fallible(|output_translations| FastaPeptideWriter::new(gene_map, output_translations))?;
+      (fasta_peptide_writer, None)
+    };
 
     let output_json_writer = output_params.output_json.map_ref_fallible(|output_json| {
       ResultsJsonWriter::new(output_json, clade_node_attr_descs, phenotype_attr_key_desc, ref_nodes)
@@ -89,6 +129,70 @@ impl NextcladeOrderedWriter {
       )
     })?;
 
+    let output_mutations_tsv_writer = output_params
+      .output_mutations_tsv
+      .map_ref_fallible(|output_mutations_tsv| {
+        NextcladeMutationsTsvFileWriter::new(output_mutations_tsv, clade_node_attr_descs, ref_nodes)
+      })?;
+
+    let output_sqlite_writer = output_params.output_sqlite.map_ref_fallible(|output_sqlite| {
+      NextcladeResultsSqliteWriter::new(output_sqlite, dataset_name, dataset_version)
+    })?;
+
+    let output_summary_writer = (output_params.output_summary.is_some() || output_params.output_summary_md.is_some())
+      .then(|| {
+        RunSummaryWriter::new(
+          output_params.output_summary.as_ref(),
+          output_params.output_summary_md.as_ref(),
+        )
+      });
+
+    let output_clade_mutations_writer = (output_params.output_clade_mutations.is_some()
+      || output_params.output_clade_mutations_json.is_some())
+    .then(|| {
+      CladeMutationPrevalenceWriter::new(
+        output_params.output_clade_mutations.as_ref(),
+        output_params.output_clade_mutations_json.as_ref(),
+      )
+    });
+
+    let output_consensus_writer = (output_params.output_consensus.is_some()
+      || output_params.output_consensus_support.is_some())
+    .then(|| {
+      CladeConsensusWriter::new(
+        ref_seq,
+        output_params.output_consensus.as_ref(),
+        output_params.output_consensus_support.as_ref(),
+        &output_params.consensus_attr,
+        output_params.consensus_threshold,
+      )
+    })
+    .transpose()?;
+
+    let output_site_coverage_writer = (output_params.output_site_coverage.is_some()
+      || output_params.output_site_coverage_bedgraph.is_some())
+    .then(|| {
+      SiteCoverageWriter::new(
+        ref_seq_name,
+        ref_seq,
+        output_params.output_site_coverage.as_ref(),
+        output_params.output_site_coverage_bedgraph.as_ref(),
+      )
+    });
+
+    let output_bed_writer = output_params
+      .output_bed
+      .as_ref()
+      .map(|output_bed| NextcladeBedWriter::new(output_bed, ref_seq_name, gene_map));
+
+    let output_sam_writer = output_params
+      .output_sam
+      .map_ref_fallible(|output_sam| SamWriter::new(output_sam, ref_seq_name, ref_seq.len()))?;
+
+    let output_bam_writer = output_params
+      .output_bam
+      .map_ref_fallible(|output_bam| BamWriter::new(output_bam, ref_seq_name, ref_seq.len()))?;
+
     let output_gff_writer = output_params
       .output_annotation_gff
       .map_ref_fallible(Gff3FileWriter::new)?;
@@ -100,15 +204,27 @@ impl NextcladeOrderedWriter {
     Ok(Self {
       fasta_writer,
       fasta_peptide_writer,
+      fasta_msa_writer,
+      fasta_peptide_msa_writer,
       output_json_writer,
       output_ndjson_writer,
       output_csv_writer,
       output_tsv_writer,
+      output_mutations_tsv_writer,
+      output_sqlite_writer,
+      output_summary_writer,
+      output_clade_mutations_writer,
+      output_consensus_writer,
+      output_site_coverage_writer,
+      output_bed_writer,
+      output_sam_writer,
+      output_bam_writer,
       output_tbl_writer,
       output_gff_writer,
       expected_index: 0,
       queue: HashMap::<usize, NextcladeRecord>::new(),
       in_order: params.general.in_order,
+      is_finished: false,
     })
   }
 
@@ -119,10 +235,17 @@ impl NextcladeOrderedWriter {
       fasta_writer.write(seq_name, seq, false)?;
     }
 
+    if let Some(fasta_msa_writer) = &mut self.fasta_msa_writer {
+      fasta_msa_writer.write(seq_name, &to_nuc_seq(seq)?, &[], false);
+    }
+
     ref_translation.cdses().try_for_each(|cds_tr| {
       if let Some(fasta_peptide_writer) = &mut self.fasta_peptide_writer {
         fasta_peptide_writer.write(seq_name, cds_tr)?;
       }
+      if let Some(fasta_peptide_msa_writer) = &mut self.fasta_peptide_msa_writer {
+        fasta_peptide_msa_writer.write(seq_name, cds_tr)?;
+      }
       Result::<(), Report>::Ok(())
     })?;
 
@@ -145,7 +268,9 @@ impl NextcladeOrderedWriter {
       }) => {
         let NextcladeOutputs {
           warnings,
+          nuc_warnings,
           is_reverse_complement,
+          insertions,
           ..
         } = &analysis_result;
 
@@ -159,6 +284,20 @@ impl NextcladeOrderedWriter {
           }
         }
 
+        if let Some(fasta_msa_writer) = &mut self.fasta_msa_writer {
+          fasta_msa_writer.write(&seq_name, &query, insertions, *is_reverse_complement);
+        }
+
+        if let Some(fasta_peptide_msa_writer) = &mut self.fasta_peptide_msa_writer {
+          for cds_tr in translation.cdses() {
+            fasta_peptide_msa_writer.write(&seq_name, cds_tr)?;
+          }
+        }
+
+        for warning in nuc_warnings {
+          info!("In sequence #{index} '{seq_name}': {}", warning.warning);
+        }
+
         for warning in warnings {
           info!("In sequence #{index} '{seq_name}': {}", warning.warning);
         }
@@ -171,6 +310,42 @@ impl NextcladeOrderedWriter {
           output_tsv_writer.write(&analysis_result)?;
         }
 
+        if let Some(output_mutations_tsv_writer) = &mut self.output_mutations_tsv_writer {
+          output_mutations_tsv_writer.write(&analysis_result)?;
+        }
+
+        if let Some(output_sqlite_writer) = &mut self.output_sqlite_writer {
+          output_sqlite_writer.write(&analysis_result)?;
+        }
+
+        if let Some(output_summary_writer) = &mut self.output_summary_writer {
+          output_summary_writer.write(&analysis_result);
+        }
+
+        if let Some(output_clade_mutations_writer) = &mut self.output_clade_mutations_writer {
+          output_clade_mutations_writer.write(&analysis_result);
+        }
+
+        if let Some(output_consensus_writer) = &mut self.output_consensus_writer {
+          output_consensus_writer.write(&query, &analysis_result);
+        }
+
+        if let Some(output_site_coverage_writer) = &mut self.output_site_coverage_writer {
+          output_site_coverage_writer.write(&analysis_result);
+        }
+
+        if let Some(output_bed_writer) = &mut self.output_bed_writer {
+          output_bed_writer.write(&analysis_result)?;
+        }
+
+        if let Some(output_sam_writer) = &mut self.output_sam_writer {
+          output_sam_writer.write(&seq_name, &query, &analysis_result)?;
+        }
+
+        if let Some(output_bam_writer) = &mut self.output_bam_writer {
+          output_bam_writer.write(&seq_name, &query, &analysis_result)?;
+        }
+
         if let Some(output_ndjson_writer) = &mut self.output_ndjson_writer {
           output_ndjson_writer.write(&analysis_result)?;
         }
@@ -209,6 +384,18 @@ impl NextcladeOrderedWriter {
         if let Some(output_json_writer) = &mut self.output_json_writer {
           output_json_writer.write_nuc_error(index, &seq_name, std::slice::from_ref(&cause));
         }
+        if let Some(output_sqlite_writer) = &mut self.output_sqlite_writer {
+          output_sqlite_writer.write_nuc_error(index, &seq_name, std::slice::from_ref(&cause))?;
+        }
+        if let Some(output_summary_writer) = &mut self.output_summary_writer {
+          output_summary_writer.write_nuc_error(&seq_name, &report);
+        }
+        if let Some(output_sam_writer) = &mut self.output_sam_writer {
+          output_sam_writer.write_nuc_error(&seq_name)?;
+        }
+        if let Some(output_bam_writer) = &mut self.output_bam_writer {
+          output_bam_writer.write_nuc_error(&seq_name)?;
+        }
       }
     }
 
@@ -254,18 +441,76 @@ impl NextcladeOrderedWriter {
     Ok(())
   }
 
-  /// Finalizes output by writing all queued records
+  /// Finalizes output by writing all queued records, completing the outputs which are written at the end of the run
+  /// and committing the records written into the SQLite database. Should only be called when the run has succeeded.
   pub fn finish(&mut self) -> Result<(), Report> {
+    if self.is_finished {
+      return Ok(());
+    }
+    self.is_finished = true;
+
+    self.finish_files()?;
+    if let Some(output_sqlite_writer) = &mut self.output_sqlite_writer {
+      output_sqlite_writer.finish()?;
+    }
+    Ok(())
+  }
+
+  /// Finalizes output files by writing all queued records and completing the outputs which are written at the end of
+  /// the run
+  fn finish_files(&mut self) -> Result<(), Report> {
     self.write_queued_records()?;
+    if let Some(fasta_msa_writer) = &mut self.fasta_msa_writer {
+      fasta_msa_writer.finish()?;
+    }
+    if let Some(fasta_peptide_msa_writer) = &mut self.fasta_peptide_msa_writer {
+      fasta_peptide_msa_writer.finish()?;
+    }
     if let Some(output_json_writer) = &mut self.output_json_writer {
       output_json_writer.finish()?;
     }
+    if let Some(output_summary_writer) = &self.output_summary_writer {
+      output_summary_writer.finish()?;
+    }
+    if let Some(output_clade_mutations_writer) = &self.output_clade_mutations_writer {
+      output_clade_mutations_writer.finish()?;
+    }
+    if let Some(output_consensus_writer) = &self.output_consensus_writer {
+      output_consensus_writer.finish()?;
+    }
+    if let Some(output_site_coverage_writer) = &self.output_site_coverage_writer {
+      output_site_coverage_writer.finish()?;
+    }
+    if let Some(output_bed_writer) = &self.output_bed_writer {
+      output_bed_writer.finish()?;
+    }
+    if let Some(output_sam_writer) = &mut self.output_sam_writer {
+      output_sam_writer.finish()?;
+    }
+    if let Some(output_bam_writer) = &mut self.output_bam_writer {
+      output_bam_writer.finish()?;
+    }
     Ok(())
   }
 }
 
 impl Drop for NextcladeOrderedWriter {
+  /// Writer which has not been finished belongs to a failed run. Output files are still finalized, so that they contain
+  /// the records written so far, but the records written into the SQLite database are rolled back. Errors are logged
+  /// rather than raised.
   fn drop(&mut self) {
-    self.finish().wrap_err("When finalizing output writer").unwrap();
+    if self.is_finished {
+      return;
+    }
+    self.is_finished = true;
+
+    if let Err(report) = self.finish_files() {
+      warn!("When finalizing output files: {}", report_to_string(&report));
+    }
+    if let Some(output_sqlite_writer) = &mut self.output_sqlite_writer
+      && let Err(report) = output_sqlite_writer.rollback()
+    {
+      warn!("When rolling back SQLite database: {}", report_to_string(&report));
+    }
   }
 }
@@ -0,0 +1,198 @@
+use crate::cli::nextclade_cli::{NextcladeTreeInfoArgs, NextcladeTreeInputArgs, NextcladeTreeQueryArgs};
+use crate::dataset::dataset_download::nextclade_get_inputs;
+use comfy_table::modifiers::{UTF8_ROUND_CORNERS, UTF8_SOLID_INNER_BORDERS};
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{ContentArrangement, Table};
+use eyre::{ContextCompat, Report, WrapErr};
+use itertools::Itertools;
+use nextclade::alphabet::nuc::to_nuc_seq;
+use nextclade::graph::graph::Graph;
+use nextclade::io::json::{JsonPretty, json_or_yaml_write, json_stringify};
+use nextclade::o;
+use nextclade::run::nextclade_wasm::NextcladeParams;
+use nextclade::run::params::{NextcladeInputParams, NextcladeInputParamsOptional};
+use nextclade::translate::translate_genes::Translation;
+use nextclade::translate::translate_genes_ref::translate_genes_ref;
+use nextclade::tree::tree::AuspiceGraph;
+use nextclade::tree::tree_info::{
+  TreeInfo, TreeMutationQuery, TreeQuery, TreeQueryMatch, graph_get_tree_info, graph_query_nodes,
+};
+use nextclade::tree::tree_preprocess::graph_preprocess_in_place;
+use std::str::FromStr;
+
+pub fn nextclade_tree_info(args: &NextcladeTreeInfoArgs) -> Result<(), Report> {
+  let (graph, root_sequence_matches_ref) = load_graph(&args.inputs)?;
+  let info = graph_get_tree_info(&graph, root_sequence_matches_ref)?;
+
+  if args.json {
+    println!("{}", json_stringify(&info, JsonPretty(true))?);
+  } else {
+    println!("{}", format_tree_info(&info));
+  }
+
+  if let Some(output) = &args.output {
+    json_or_yaml_write(output, &info)?;
+  }
+
+  Ok(())
+}
+
+pub fn nextclade_tree_query(args: &NextcladeTreeQueryArgs) -> Result<(), Report> {
+  let query = TreeQuery {
+    mutations: args
+      .mutation
+      .iter()
+      .map(|mutation| TreeMutationQuery::from_str(mutation).wrap_err("When parsing --mutation"))
+      .try_collect()?,
+    on_branch: args.on_branch,
+    clade: args.clade.clone(),
+  };
+
+  let (graph, _) = load_graph(&args.inputs)?;
+  let matches = graph_query_nodes(&graph, &query)?;
+
+  if args.json {
+    println!("{}", json_stringify(&matches, JsonPretty(true))?);
+  } else {
+    println!("{}", format_tree_query_matches(&matches));
+  }
+
+  if let Some(output) = &args.output {
+    json_or_yaml_write(output, &matches)?;
+  }
+
+  Ok(())
+}
+
+/// Loads and preprocesses reference tree, the same way as during the run, without preparing the rest of the analysis.
+/// Also returns whether the root sequence of the tree matches the reference sequence, if the tree contains one.
+fn load_graph(inputs: &NextcladeTreeInputArgs) -> Result<(AuspiceGraph, Option<bool>), Report> {
+  let params = NextcladeInputParamsOptional::default();
+
+  let NextcladeParams {
+    ref_record,
+    gene_map,
+    tree,
+    virus_properties,
+    ..
+  } = nextclade_get_inputs(&inputs.to_run_input_args(), &params, &None)?;
+
+  let tree = tree.wrap_err(
+    "Reference tree is required, but none was provided. Use --input-tree or a dataset which contains a reference tree.",
+  )?;
+
+  let root_sequence_matches_ref = tree.root_sequence().map(|root_seq| root_seq == ref_record.seq);
+
+  let ref_seq = to_nuc_seq(&ref_record.seq).wrap_err("When converting reference sequence")?;
+
+  let ref_translation = if gene_map.is_empty() {
+    Translation::default()
+  } else {
+    let params = NextcladeInputParams::from_optional(&params, &virus_properties)?;
+    translate_genes_ref(&ref_seq, &gene_map, &params.alignment).wrap_err("When translating reference sequence")?
+  };
+
+  let mut graph = Graph::from_auspice_tree(tree).wrap_err("When converting Auspice tree to Nextclade graph")?;
+  graph_preprocess_in_place(&mut graph, &ref_seq, &ref_translation).wrap_err("When preprocessing Nextclade graph")?;
+
+  Ok((graph, root_sequence_matches_ref))
+}
+
+fn format_tree_info(info: &TreeInfo) -> String {
+  let root_sequence = match info.root_sequence_matches_ref {
+    None => "not present",
+    Some(true) => "matches reference sequence",
+    Some(false) => "differs from reference sequence",
+  };
+
+  let placement_mask_ranges = if info.placement_mask_ranges.is_empty() {
+    o!("none")
+  } else {
+    info.placement_mask_ranges.iter().join(", ")
+  };
+
+  let ref_nodes = if info.ref_nodes.is_empty() {
+    o!("none")
+  } else {
+    let default = info.ref_nodes.default.as_deref().unwrap_or("__root__");
+    let search = info.ref_nodes.search.iter().map(|desc| &desc.name).join(", ");
+    format!("default: {default}; search: {search}")
+  };
+
+  let lines = [
+    format!("Nodes: {} ({} leaves)", info.num_nodes, info.num_leaves),
+    format!("Root sequence: {root_sequence}"),
+    format!(
+      "Divergence units: {} (max divergence: {})",
+      info.divergence_units.description(),
+      info.max_divergence
+    ),
+    format!("Placement mask ranges: {placement_mask_ranges}"),
+    format!("Reference nodes: {ref_nodes}"),
+    format!("Clades: {}", info.clades.len()),
+  ];
+
+  if info.clades.is_empty() {
+    return lines.join("\n");
+  }
+
+  let mut table = new_table();
+  table.set_header([
+    o!("clade"),
+    o!("nodes"),
+    o!("leaves"),
+    o!("founder"),
+    o!("founder mutations"),
+  ]);
+  for clade in &info.clades {
+    let founders = clade.founders.iter().map(|founder| &founder.node_name).join("\n");
+    let mutations = clade
+      .founders
+      .iter()
+      .map(|founder| {
+        founder
+          .nuc_mutations
+          .iter()
+          .map(ToString::to_string)
+          .chain(founder.aa_mutations.iter().map(ToString::to_string))
+          .join(", ")
+      })
+      .join("\n");
+    table.add_row([
+      clade.clade.clone(),
+      clade.num_nodes.to_string(),
+      clade.num_leaves.to_string(),
+      founders,
+      mutations,
+    ]);
+  }
+
+  format!("{}\n{table}", lines.join("\n"))
+}
+
+fn format_tree_query_matches(matches: &[TreeQueryMatch]) -> String {
+  if matches.is_empty() {
+    return o!("No matching nodes found");
+  }
+
+  let mut table = new_table();
+  table.set_header([o!("node"), o!("clade"), o!("leaf")]);
+  for m in matches {
+    table.add_row([
+      m.node_name.clone(),
+      m.clade.clone().unwrap_or_default(),
+      if m.is_leaf { o!("yes") } else { o!("no") },
+    ]);
+  }
+  table.to_string()
+}
+
+fn new_table() -> Table {
+  let mut table = Table::new();
+  table
+    .load_preset(UTF8_FULL)
+    .apply_modifier(UTF8_ROUND_CORNERS)
+    .apply_modifier(UTF8_SOLID_INNER_BORDERS)
+    .set_content_arrangement(ContentArrangement::Dynamic);
+  table
+}
@@ -1,20 +1,25 @@
-use crate::cli::nextclade_cli::{NextcladeRunArgs, NextcladeRunInputArgs};
-use crate::cli::nextclade_dataset_get::{dataset_file_http_get, dataset_http_get};
+use crate::cli::nextclade_cli::NextcladeRunInputArgs;
+use crate::cli::nextclade_dataset_get::{dataset_file_http_get, dataset_file_http_get_bytes, dataset_http_get};
 use crate::io::http_client::{HttpClient, ProxyConfig};
 use color_eyre::{Section, SectionExt};
 use eyre::{ContextCompat, Report, WrapErr, eyre};
 use itertools::Itertools;
 use log::{LevelFilter, warn};
+use nextclade::align::params::AlignPairwiseParams;
+use nextclade::alphabet::nuc::{Nuc, to_nuc_seq};
 use nextclade::analyze::virus_properties::VirusProperties;
 use nextclade::gene::gene_map::{GeneMap, filter_gene_map};
 use nextclade::io::dataset::{Dataset, DatasetsIndexJson};
-use nextclade::io::fasta::{read_one_fasta_from_file, read_one_fasta_from_str};
+use nextclade::io::fasta::{FastaRecord, read_many_fasta, read_one_fasta_from_file, read_one_fasta_from_str};
 use nextclade::io::file::create_file_or_stdout;
-use nextclade::io::fs::{ensure_dir, has_extension, read_file_to_string};
+use nextclade::io::fs::{ensure_dir, has_extension, read_file_to_bytes, read_file_to_string};
+use nextclade::io::usher_mat::{UsherMat, convert_usher_mat_to_auspice_tree, is_usher_mat_bytes, is_usher_mat_path};
 use nextclade::run::nextclade_wasm::{NextcladeParams, NextcladeParamsOptional};
+use nextclade::run::params::{NextcladeInputParams, NextcladeInputParamsOptional};
 use nextclade::tree::tree::{AuspiceTree, check_ref_seq_mismatch};
+use nextclade::tree::tree_import::{AugurNodeData, RefTreeFormat, RefTreeImportInputs, ref_tree_read_str};
 use nextclade::utils::fs::list_files_recursive;
-use nextclade::utils::option::OptionMapRefFallible;
+use nextclade::utils::option::{OptionMapRefFallible, find_some};
 use nextclade::utils::string::{Indent, format_list, surround_with_quotes};
 use nextclade::{make_error, make_internal_error, o};
 use std::collections::BTreeSet;
@@ -25,21 +30,22 @@ use std::path::Path;
 use zip::ZipArchive;
 
 pub fn nextclade_get_inputs(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
-  if let Some(dataset_name) = run_args.inputs.dataset_name.as_ref() {
-    dataset_str_download_and_load(run_args, cdses)
+  if let Some(dataset_name) = inputs.dataset_name.as_ref() {
+    dataset_str_download_and_load(inputs, params, cdses)
       .wrap_err_with(|| format!("When downloading dataset '{dataset_name}'"))
-  } else if let Some(input_dataset) = run_args.inputs.input_dataset.as_ref() {
+  } else if let Some(input_dataset) = inputs.input_dataset.as_ref() {
     if input_dataset.is_file() && has_extension(input_dataset, "zip") {
-      dataset_zip_load(run_args, input_dataset, cdses)
+      dataset_zip_load(inputs, params, input_dataset, cdses)
         .wrap_err_with(|| format!("When loading dataset from {}", input_dataset.display()))
     } else if input_dataset.is_file() && has_extension(input_dataset, "json") {
-      dataset_json_load(run_args, input_dataset, cdses)
+      dataset_json_load(inputs, params, input_dataset, cdses)
         .wrap_err_with(|| format!("When loading dataset from {}", input_dataset.display()))
     } else if input_dataset.is_dir() {
-      dataset_dir_load(run_args, input_dataset, cdses)
+      dataset_dir_load(inputs, params, input_dataset, cdses)
         .wrap_err_with(|| format!("When loading dataset from {}", input_dataset.display()))
     } else {
       make_error!(
@@ -49,7 +55,7 @@ pub fn nextclade_get_inputs(
       )
     }
   } else {
-    dataset_individual_files_load(run_args, cdses)
+    dataset_individual_files_load(inputs, params, cdses)
   }
 }
 
@@ -91,15 +97,31 @@ pub fn zip_read_str<R: Read + Seek>(zip: &mut ZipArchive<R>, name: impl AsRef<st
   Ok(s)
 }
 
+pub fn zip_read_bytes<R: Read + Seek>(zip: &mut ZipArchive<R>, name: impl AsRef<str>) -> Result<Vec<u8>, Report> {
+  let mut buf = vec![];
+  zip.by_name(name.as_ref())?.read_to_end(&mut buf)?;
+  Ok(buf)
+}
+
 pub fn read_from_path_or_zip(
   filepath: Option<&impl AsRef<Path>>,
   zip: &mut ZipArchive<BufReader<File>>,
   zip_filename: Option<&impl AsRef<str>>,
 ) -> Result<Option<String>, Report> {
+  read_bytes_from_path_or_zip(filepath, zip, zip_filename)?
+    .map(|bytes| Ok(String::from_utf8(bytes)?))
+    .transpose()
+}
+
+pub fn read_bytes_from_path_or_zip(
+  filepath: Option<&impl AsRef<Path>>,
+  zip: &mut ZipArchive<BufReader<File>>,
+  zip_filename: Option<&impl AsRef<str>>,
+) -> Result<Option<Vec<u8>>, Report> {
   if let Some(filepath) = filepath {
-    Ok(Some(read_file_to_string(filepath)?))
+    Ok(Some(read_file_to_bytes(filepath)?))
   } else if let Some(zip_filename) = zip_filename {
-    zip_read_str(zip, zip_filename)
+    zip_read_bytes(zip, zip_filename)
       .map(Some)
       .wrap_err_with(|| format!("When extracting file {:#?}", zip_filename.as_ref()))
       .with_section(|| {
@@ -112,7 +134,8 @@ pub fn read_from_path_or_zip(
 }
 
 pub fn dataset_zip_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   dataset_zip: impl AsRef<Path>,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
@@ -121,17 +144,13 @@ pub fn dataset_zip_load(
   let buf_file = BufReader::new(file);
   let mut zip = ZipArchive::new(buf_file)?;
 
-  let virus_properties = read_from_path_or_zip(
-    run_args.inputs.input_pathogen_json.as_ref(),
-    &mut zip,
-    Some(&"pathogen.json"),
-  )?
-  .map_ref_fallible(VirusProperties::from_str)
-  .wrap_err("When reading pathogen JSON from dataset")?
-  .ok_or_else(|| eyre!("Pathogen JSON must always be present in the dataset but not found."))?;
+  let virus_properties = read_from_path_or_zip(inputs.input_pathogen_json.as_ref(), &mut zip, Some(&"pathogen.json"))?
+    .map_ref_fallible(VirusProperties::from_str)
+    .wrap_err("When reading pathogen JSON from dataset")?
+    .ok_or_else(|| eyre!("Pathogen JSON must always be present in the dataset but not found."))?;
 
   let ref_record = read_from_path_or_zip(
-    run_args.inputs.input_ref.as_ref(),
+    inputs.input_ref.as_ref(),
     &mut zip,
     virus_properties.files.reference.as_ref(),
   )?
@@ -140,7 +159,7 @@ pub fn dataset_zip_load(
   .ok_or_else(|| eyre!("Reference sequence must always be present in the dataset but not found."))?;
 
   let gene_map = read_from_path_or_zip(
-    run_args.inputs.input_annotation.as_ref(),
+    inputs.input_annotation.as_ref(),
     &mut zip,
     virus_properties.files.genome_annotation.as_ref(),
   )?
@@ -149,13 +168,15 @@ pub fn dataset_zip_load(
   .map(|gene_map| filter_gene_map(gene_map, cdses.as_ref()))
   .unwrap_or_default();
 
-  let tree = read_from_path_or_zip(
-    run_args.inputs.input_tree.as_ref(),
+  let alignment_params = ref_tree_alignment_params(params, &virus_properties)?;
+
+  let tree = read_bytes_from_path_or_zip(
+    inputs.input_tree.as_ref(),
     &mut zip,
     virus_properties.files.tree_json.as_ref(),
   )?
-  .map_ref_fallible(AuspiceTree::from_str)
-  .wrap_err("When reading reference tree JSON from dataset")?;
+  .map_ref_fallible(|content| ref_tree_from_bytes(content, inputs, Some(&ref_record), &gene_map, &alignment_params))
+  .wrap_err("When reading reference tree from dataset")?;
 
   verify_dataset_files(&virus_properties, zip.file_names());
 
@@ -242,7 +263,8 @@ pub fn dataset_dir_download(
 }
 
 pub fn dataset_dir_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   dataset_dir: impl AsRef<Path>,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
@@ -254,7 +276,7 @@ pub fn dataset_dir_load(
     input_pathogen_json,
     input_annotation,
     ..
-  } = &run_args.inputs;
+  } = inputs;
 
   let input_pathogen_json = input_pathogen_json
     .clone()
@@ -289,6 +311,8 @@ pub fn dataset_dir_load(
     .map(|gen_map| filter_gene_map(gen_map, cdses.as_ref()))
     .unwrap_or_default();
 
+  let alignment_params = ref_tree_alignment_params(params, &virus_properties)?;
+
   let tree = input_tree
     .clone()
     .or_else(|| {
@@ -298,8 +322,8 @@ pub fn dataset_dir_load(
         .as_ref()
         .map(|tree_json| dataset_dir.join(tree_json))
     })
-    .map_ref_fallible(AuspiceTree::from_path)
-    .wrap_err("When reading reference tree JSON")?;
+    .map_ref_fallible(|filepath| ref_tree_from_path(filepath, inputs, Some(&ref_record), &gene_map, &alignment_params))
+    .wrap_err("When reading reference tree")?;
 
   let dataset_dir_files = list_files_recursive(dataset_dir)?
     .into_iter()
@@ -324,7 +348,8 @@ pub fn dataset_dir_load(
 }
 
 pub fn dataset_json_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   dataset_json: impl AsRef<Path>,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
@@ -336,7 +361,7 @@ pub fn dataset_json_load(
     input_pathogen_json,
     input_annotation,
     ..
-  } = &run_args.inputs;
+  } = inputs;
 
   let auspice_json = AuspiceTree::from_path(dataset_json).wrap_err("When reading Auspice JSON v2")?;
 
@@ -349,14 +374,29 @@ pub fn dataset_json_load(
       .map_ref_fallible(read_one_fasta_from_file)
       .wrap_err("When parsing reference sequence")?;
 
-    let tree = input_tree
-      .map_ref_fallible(AuspiceTree::from_path)
-      .wrap_err("When parsing reference tree Auspice JSON v2")?;
-
     let gene_map = input_annotation
       .map_ref_fallible(GeneMap::from_path)
       .wrap_err("When parsing genome annotation")?;
 
+    let alignment_params = {
+      let virus_properties = find_some(&[&virus_properties, &auspice_json.meta.extensions.nextclade.pathogen])
+        .cloned()
+        .unwrap_or_default();
+      ref_tree_alignment_params(params, &virus_properties)?
+    };
+
+    let tree = input_tree
+      .map_ref_fallible(|filepath| {
+        ref_tree_from_path(
+          filepath,
+          inputs,
+          ref_record.as_ref(),
+          gene_map.as_ref().unwrap_or(&GeneMap::new()),
+          &alignment_params,
+        )
+      })
+      .wrap_err("When parsing reference tree")?;
+
     if let (Some(tree), Some(ref_record)) = (&tree, &ref_record)
       && let Some(tree_ref) = tree.root_sequence()
     {
@@ -378,14 +418,14 @@ pub fn dataset_json_load(
 }
 
 pub fn dataset_individual_files_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
-  match (&run_args.inputs.input_dataset, &run_args.inputs.input_ref) {
+  match (&inputs.input_dataset, &inputs.input_ref) {
     (None, None) => make_error!("When `--input-dataset` is not specified, --input-ref is required"),
     (_, Some(input_ref)) => {
-      let virus_properties = run_args
-        .inputs
+      let virus_properties = inputs
         .input_pathogen_json
         .as_ref()
         .and_then(|input_pathogen_json| read_file_to_string(input_pathogen_json).ok())
@@ -395,8 +435,7 @@ pub fn dataset_individual_files_load(
 
       let ref_record = read_one_fasta_from_file(input_ref).wrap_err("When reading reference sequence")?;
 
-      let gene_map = run_args
-        .inputs
+      let gene_map = inputs
         .input_annotation
         .as_ref()
         .map_ref_fallible(GeneMap::from_path)
@@ -404,12 +443,15 @@ pub fn dataset_individual_files_load(
         .map(|gen_map| filter_gene_map(gen_map, cdses.as_ref()))
         .unwrap_or_default();
 
-      let tree = run_args
-        .inputs
+      let alignment_params = ref_tree_alignment_params(params, &virus_properties)?;
+
+      let tree = inputs
         .input_tree
         .as_ref()
-        .map_ref_fallible(AuspiceTree::from_path)
-        .wrap_err("When reading reference tree JSON")?;
+        .map_ref_fallible(|filepath| {
+          ref_tree_from_path(filepath, inputs, Some(&ref_record), &gene_map, &alignment_params)
+        })
+        .wrap_err("When reading reference tree")?;
 
       if let Some(tree) = &tree
         && let Some(tree_ref) = tree.root_sequence()
@@ -418,8 +460,7 @@ pub fn dataset_individual_files_load(
       }
 
       Ok(NextcladeParams {
-        dataset_name: run_args
-          .inputs
+        dataset_name: inputs
           .input_pathogen_json
           .as_ref()
           .map(|s| s.to_str().unwrap().to_owned())
@@ -434,6 +475,117 @@ pub fn dataset_individual_files_load(
   }
 }
 
+/// Reads reference tree file in any of the supported formats: Auspice JSON v2, Newick, Nexus or UShER
+/// mutation-annotated tree (detected by extension `.pb` or by content)
+pub fn ref_tree_from_path(
+  filepath: impl AsRef<Path>,
+  inputs: &NextcladeRunInputArgs,
+  ref_record: Option<&FastaRecord>,
+  gene_map: &GeneMap,
+  params: &AlignPairwiseParams,
+) -> Result<AuspiceTree, Report> {
+  let filepath = filepath.as_ref();
+  if is_usher_mat_path(filepath) {
+    let mat = UsherMat::from_path(filepath)?;
+    return ref_tree_from_usher_mat(&mat, ref_record, gene_map, params)
+      .wrap_err_with(|| format!("When reading reference tree file {}", filepath.display()));
+  }
+
+  let content = read_file_to_bytes(filepath)?;
+  ref_tree_from_bytes(&content, inputs, ref_record, gene_map, params)
+    .wrap_err_with(|| format!("When reading reference tree file {}", filepath.display()))
+}
+
+/// Parses reference tree in any of the supported formats, including binary UShER mutation-annotated tree
+pub fn ref_tree_from_bytes(
+  content: &[u8],
+  inputs: &NextcladeRunInputArgs,
+  ref_record: Option<&FastaRecord>,
+  gene_map: &GeneMap,
+  params: &AlignPairwiseParams,
+) -> Result<AuspiceTree, Report> {
+  if is_usher_mat_bytes(content) {
+    let mat = UsherMat::from_bytes(content).wrap_err("When parsing mutation-annotated tree")?;
+    return ref_tree_from_usher_mat(&mat, ref_record, gene_map, params);
+  }
+  let content = std::str::from_utf8(content).wrap_err("When reading reference tree as text")?;
+  ref_tree_from_str(content, inputs, ref_record, gene_map, params)
+}
+
+fn ref_tree_from_usher_mat(
+  mat: &UsherMat,
+  ref_record: Option<&FastaRecord>,
+  gene_map: &GeneMap,
+  params: &AlignPairwiseParams,
+) -> Result<AuspiceTree, Report> {
+  let ref_seq = get_ref_seq_for_tree_import(ref_record)?;
+  convert_usher_mat_to_auspice_tree(
+    mat,
+    &RefTreeImportInputs {
+      ref_seq: &ref_seq,
+      gene_map,
+      params,
+      sequences: None,
+      node_data: None,
+    },
+  )
+}
+
+/// Parses reference tree in any of the supported formats, converting Newick and Nexus trees to Auspice JSON
+pub fn ref_tree_from_str(
+  content: &str,
+  inputs: &NextcladeRunInputArgs,
+  ref_record: Option<&FastaRecord>,
+  gene_map: &GeneMap,
+  params: &AlignPairwiseParams,
+) -> Result<AuspiceTree, Report> {
+  if RefTreeFormat::guess_from_str(content) == RefTreeFormat::AuspiceJson {
+    return AuspiceTree::from_str(content);
+  }
+
+  let sequences = inputs
+    .input_tree_sequences
+    .as_ref()
+    .map_ref_fallible(|filepath| read_many_fasta(&[filepath]))
+    .wrap_err("When reading sequences of reference tree nodes")?;
+
+  let node_data = inputs
+    .input_tree_node_data
+    .as_ref()
+    .map_ref_fallible(|filepath| AugurNodeData::from_str(read_file_to_string(filepath)?))
+    .wrap_err("When reading node data of reference tree")?;
+
+  let ref_seq = get_ref_seq_for_tree_import(ref_record)?;
+
+  ref_tree_read_str(
+    content,
+    &RefTreeImportInputs {
+      ref_seq: &ref_seq,
+      gene_map,
+      params,
+      sequences: sequences.as_deref(),
+      node_data: node_data.as_ref(),
+    },
+  )
+}
+
+/// Alignment parameters used when importing reference tree, the same as will be used during the run
+fn ref_tree_alignment_params(
+  params: &NextcladeInputParamsOptional,
+  virus_properties: &VirusProperties,
+) -> Result<AlignPairwiseParams, Report> {
+  Ok(NextcladeInputParams::from_optional(params, virus_properties)?.alignment)
+}
+
+fn get_ref_seq_for_tree_import(ref_record: Option<&FastaRecord>) -> Result<Vec<Nuc>, Report> {
+  let Some(ref_record) = ref_record else {
+    return make_error!(
+      "Reference tree in Newick, Nexus or mutation-annotated tree format requires reference sequence (`--input-ref`)"
+    );
+  };
+  to_nuc_seq(&ref_record.seq).wrap_err("When reading reference sequence")
+}
+
 pub fn read_from_path_or_url(
   http: &HttpClient,
   dataset: &Dataset,
@@ -448,39 +600,51 @@ pub fn read_from_path_or_url(
   Ok(None)
 }
 
+pub fn read_bytes_from_path_or_url(
+  http: &HttpClient,
+  dataset: &Dataset,
+  filepath: &Option<impl AsRef<Path>>,
+  url: &Option<String>,
+) -> Result<Option<Vec<u8>>, Report> {
+  if let Some(filepath) = filepath {
+    return Ok(Some(read_file_to_bytes(filepath)?));
+  } else if let Some(url) = url {
+    return Ok(Some(dataset_file_http_get_bytes(http, dataset, url)?));
+  }
+  Ok(None)
+}
+
 pub fn dataset_str_download_and_load(
-  run_args: &NextcladeRunArgs,
+  inputs: &NextcladeRunInputArgs,
+  params: &NextcladeInputParamsOptional,
   cdses: &Option<Vec<String>>,
 ) -> Result<NextcladeParams, Report> {
   let verbose = log::max_level() > LevelFilter::Info;
-  let http = HttpClient::new(&run_args.inputs.server, &ProxyConfig::default(), verbose)?;
+  let http = HttpClient::new(&inputs.server, &ProxyConfig::default(), verbose)?;
 
-  let name = run_args
-    .inputs
+  let name = inputs
     .dataset_name
     .as_ref()
     .expect("Dataset name is expected, but got 'None'");
 
   let dataset = dataset_http_get(&http, name, None)?;
 
-  let virus_properties = read_from_path_or_url(
-    &http,
-    &dataset,
-    &run_args.inputs.input_pathogen_json,
-    &Some(o!("pathogen.json")),
-  )?
-  .map_ref_fallible(VirusProperties::from_str)
-  .wrap_err("When reading pathogen JSON from dataset")?
-  .ok_or_else(|| eyre!("Required file not found in dataset: 'pathogen.json'. Please report it to dataset authors."))?;
+  let virus_properties =
+    read_from_path_or_url(&http, &dataset, &inputs.input_pathogen_json, &Some(o!("pathogen.json")))?
+      .map_ref_fallible(VirusProperties::from_str)
+      .wrap_err("When reading pathogen JSON from dataset")?
+      .ok_or_else(|| {
+        eyre!("Required file not found in dataset: 'pathogen.json'. Please report it to dataset authors.")
+      })?;
 
-  let ref_record = read_from_path_or_url(&http, &dataset, &run_args.inputs.input_ref, &dataset.files.reference)?
+  let ref_record = read_from_path_or_url(&http, &dataset, &inputs.input_ref, &dataset.files.reference)?
     .map_ref_fallible(read_one_fasta_from_str)?
     .wrap_err("When reading reference sequence from dataset")?;
 
   let gene_map = read_from_path_or_url(
     &http,
     &dataset,
-    &run_args.inputs.input_annotation,
+    &inputs.input_annotation,
     &dataset.files.genome_annotation,
   )?
   .map_ref_fallible(GeneMap::from_str)
@@ -488,8 +652,10 @@ pub fn dataset_str_download_and_load(
   .map(|gene_map| filter_gene_map(gene_map, cdses.as_ref()))
   .unwrap_or_default();
 
-  let tree = read_from_path_or_url(&http, &dataset, &run_args.inputs.input_tree, &dataset.files.tree_json)?
-    .map_ref_fallible(AuspiceTree::from_str)
+  let alignment_params = ref_tree_alignment_params(params, &virus_properties)?;
+
+  let tree = read_bytes_from_path_or_url(&http, &dataset, &inputs.input_tree, &dataset.files.tree_json)?
+    .map_ref_fallible(|content| ref_tree_from_bytes(content, inputs, Some(&ref_record), &gene_map, &alignment_params))
     .wrap_err("When reading reference tree from dataset")?;
 
   if let Some(tree) = &tree
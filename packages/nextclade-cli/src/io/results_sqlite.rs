@@ -0,0 +1,527 @@
+use eyre::{Report, WrapErr};
+use nextclade::alphabet::aa::from_aa_seq;
+use nextclade::alphabet::nuc::from_nuc_seq;
+use nextclade::analyze::private_mutations::PrivateMutations;
+use nextclade::coord::position::PositionLike;
+use nextclade::io::file::is_path_stdout;
+use nextclade::io::fs::ensure_dir;
+use nextclade::make_error;
+use nextclade::types::outputs::NextcladeOutputs;
+use nextclade::utils::datetime::date_iso_now;
+use nextclade::utils::info::this_package_version_str;
+use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+
+/// Database schema. All statements are idempotent, so that subsequent runs can append to an existing database.
+///
+/// All positions are 1-based. Ranges are represented by the first position and the length.
+const SCHEMA: &str = r"
+CREATE TABLE IF NOT EXISTS runs (
+  id INTEGER PRIMARY KEY,
+  started_at TEXT NOT NULL,
+  nextclade_version TEXT NOT NULL,
+  dataset_name TEXT NOT NULL,
+  dataset_version TEXT
+);
+
+CREATE TABLE IF NOT EXISTS sequences (
+  id INTEGER PRIMARY KEY,
+  run_id INTEGER NOT NULL REFERENCES runs(id),
+  seq_index INTEGER NOT NULL,
+  seq_name TEXT NOT NULL,
+  clade TEXT,
+  coverage REAL,
+  divergence REAL,
+  alignment_start INTEGER,
+  alignment_end INTEGER,
+  total_substitutions INTEGER,
+  total_deletions INTEGER,
+  total_insertions INTEGER,
+  total_frame_shifts INTEGER,
+  total_missing INTEGER,
+  total_non_acgtns INTEGER,
+  qc_overall_score REAL,
+  qc_overall_status TEXT,
+  errors TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_sequences_run_id ON sequences(run_id);
+CREATE INDEX IF NOT EXISTS idx_sequences_seq_name ON sequences(seq_name);
+CREATE INDEX IF NOT EXISTS idx_sequences_clade ON sequences(clade);
+
+CREATE TABLE IF NOT EXISTS clade_attributes (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  name TEXT NOT NULL,
+  value TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_clade_attributes_sequence_id ON clade_attributes(sequence_id);
+CREATE INDEX IF NOT EXISTS idx_clade_attributes_name_value ON clade_attributes(name, value);
+
+CREATE TABLE IF NOT EXISTS qc (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  rule TEXT NOT NULL,
+  score REAL NOT NULL,
+  status TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_qc_sequence_id ON qc(sequence_id);
+
+CREATE TABLE IF NOT EXISTS nuc_substitutions (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  pos INTEGER NOT NULL,
+  ref_nuc TEXT NOT NULL,
+  qry_nuc TEXT NOT NULL,
+  is_private INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_nuc_substitutions_sequence_id ON nuc_substitutions(sequence_id);
+CREATE INDEX IF NOT EXISTS idx_nuc_substitutions_pos ON nuc_substitutions(pos, qry_nuc);
+
+CREATE TABLE IF NOT EXISTS nuc_deletions (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  pos INTEGER NOT NULL,
+  length INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_nuc_deletions_sequence_id ON nuc_deletions(sequence_id);
+CREATE INDEX IF NOT EXISTS idx_nuc_deletions_pos ON nuc_deletions(pos);
+
+CREATE TABLE IF NOT EXISTS aa_substitutions (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  cds TEXT NOT NULL,
+  pos INTEGER NOT NULL,
+  ref_aa TEXT NOT NULL,
+  qry_aa TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_aa_substitutions_sequence_id ON aa_substitutions(sequence_id);
+CREATE INDEX IF NOT EXISTS idx_aa_substitutions_cds_pos ON aa_substitutions(cds, pos, qry_aa);
+
+CREATE TABLE IF NOT EXISTS aa_deletions (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  cds TEXT NOT NULL,
+  pos INTEGER NOT NULL,
+  ref_aa TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_aa_deletions_sequence_id ON aa_deletions(sequence_id);
+CREATE INDEX IF NOT EXISTS idx_aa_deletions_cds_pos ON aa_deletions(cds, pos);
+
+CREATE TABLE IF NOT EXISTS insertions (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  cds TEXT,
+  pos INTEGER NOT NULL,
+  ins TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_insertions_sequence_id ON insertions(sequence_id);
+
+CREATE TABLE IF NOT EXISTS frame_shifts (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  cds TEXT NOT NULL,
+  codon_pos INTEGER NOT NULL,
+  codon_length INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_frame_shifts_sequence_id ON frame_shifts(sequence_id);
+
+CREATE TABLE IF NOT EXISTS pcr_primer_changes (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  primer_name TEXT NOT NULL,
+  pos INTEGER NOT NULL,
+  ref_nuc TEXT NOT NULL,
+  qry_nuc TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_pcr_primer_changes_sequence_id ON pcr_primer_changes(sequence_id);
+
+CREATE TABLE IF NOT EXISTS phenotype_values (
+  sequence_id INTEGER NOT NULL REFERENCES sequences(id),
+  name TEXT NOT NULL,
+  cds TEXT NOT NULL,
+  value REAL NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_phenotype_values_sequence_id ON phenotype_values(sequence_id);
+";
+
+/// Writes results into an SQLite database.
+///
+/// Each run is recorded in the `runs` table and all sequences of the run refer to it, so that results of multiple runs
+/// can be accumulated in the same database. All records of a run are written in a single transaction, which is
+/// committed in `finish()`.
+pub struct NextcladeResultsSqliteWriter {
+  filepath: PathBuf,
+  conn: Connection,
+  run_id: i64,
+  is_finished: bool,
+}
+
+impl NextcladeResultsSqliteWriter {
+  pub fn new(filepath: impl AsRef<Path>, dataset_name: &str, dataset_version: Option<&str>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    if is_path_stdout(filepath) {
+      return make_error!("SQLite database cannot be written to standard output. Please provide a file path.");
+    }
+    ensure_dir(filepath)?;
+
+    let conn =
+      Connection::open(filepath).wrap_err_with(|| format!("When opening SQLite database: {}", filepath.display()))?;
+
+    Self::from_connection(conn, filepath, dataset_name, dataset_version)
+  }
+
+  fn from_connection(
+    conn: Connection,
+    filepath: &Path,
+    dataset_name: &str,
+    dataset_version: Option<&str>,
+  ) -> Result<Self, Report> {
+    conn
+      .execute_batch(SCHEMA)
+      .wrap_err_with(|| format!("When creating tables in SQLite database: {}", filepath.display()))?;
+
+    conn.execute_batch("PRAGMA foreign_keys = ON; BEGIN TRANSACTION;")?;
+
+    conn.execute(
+      "INSERT INTO runs (started_at, nextclade_version, dataset_name, dataset_version) VALUES (?1, ?2, ?3, ?4)",
+      params![
+        date_iso_now(),
+        this_package_version_str(),
+        dataset_name,
+        dataset_version
+      ],
+    )?;
+    let run_id = conn.last_insert_rowid();
+
+    Ok(Self {
+      filepath: filepath.to_owned(),
+      conn,
+      run_id,
+      is_finished: false,
+    })
+  }
+
+  pub fn write(&self, output: &NextcladeOutputs) -> Result<(), Report> {
+    self
+      .write_impl(output)
+      .wrap_err_with(|| format!("When writing sequence '{}' into SQLite database", output.seq_name))
+  }
+
+  fn write_impl(&self, output: &NextcladeOutputs) -> Result<(), Report> {
+    let conn = &self.conn;
+
+    conn
+      .prepare_cached(
+        "INSERT INTO sequences (
+          run_id, seq_index, seq_name, clade, coverage, divergence, alignment_start, alignment_end,
+          total_substitutions, total_deletions, total_insertions, total_frame_shifts, total_missing, total_non_acgtns,
+          qc_overall_score, qc_overall_status
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+      )?
+      .execute(params![
+        self.run_id,
+        output.index,
+        output.seq_name,
+        output.clade,
+        output.coverage,
+        output.divergence,
+        output.alignment_range.begin.as_usize() + 1,
+        output.alignment_range.end.as_usize(),
+        output.total_substitutions,
+        output.total_deletions,
+        output.total_insertions,
+        output.total_frame_shifts,
+        output.total_missing,
+        output.total_non_acgtns,
+        output.qc.overall_score,
+        output.qc.overall_status.to_string(),
+      ])?;
+    let sequence_id = conn.last_insert_rowid();
+
+    let mut stmt =
+      conn.prepare_cached("INSERT INTO clade_attributes (sequence_id, name, value) VALUES (?1, ?2, ?3)")?;
+    for (name, value) in &output.custom_node_attributes {
+      stmt.execute(params![sequence_id, name, value])?;
+    }
+
+    let qc = &output.qc;
+    let rules = [
+      ("missingData", qc.missing_data.as_ref().map(|r| (r.score, &r.status))),
+      ("mixedSites", qc.mixed_sites.as_ref().map(|r| (r.score, &r.status))),
+      (
+        "privateMutations",
+        qc.private_mutations.as_ref().map(|r| (r.score, &r.status)),
+      ),
+      ("snpClusters", qc.snp_clusters.as_ref().map(|r| (r.score, &r.status))),
+      ("frameShifts", qc.frame_shifts.as_ref().map(|r| (r.score, &r.status))),
+      ("stopCodons", qc.stop_codons.as_ref().map(|r| (r.score, &r.status))),
+    ];
+    let mut stmt = conn.prepare_cached("INSERT INTO qc (sequence_id, rule, score, status) VALUES (?1, ?2, ?3, ?4)")?;
+    for (rule, result) in rules {
+      if let Some((score, status)) = result {
+        stmt.execute(params![sequence_id, rule, score, status.to_string()])?;
+      }
+    }
+
+    let mut stmt = conn.prepare_cached(
+      "INSERT INTO nuc_substitutions (sequence_id, pos, ref_nuc, qry_nuc, is_private) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    let private_mutations = PrivateMutations {
+      nuc: &output.private_nuc_mutations,
+      aa: &output.private_aa_mutations,
+    };
+    for sub in &output.substitutions {
+      let is_private = private_mutations.is_private_nuc_sub(sub);
+      stmt.execute(params![
+        sequence_id,
+        sub.pos.as_usize() + 1,
+        sub.ref_nuc.to_string(),
+        sub.qry_nuc.to_string(),
+        is_private
+      ])?;
+    }
+
+    let mut stmt = conn.prepare_cached("INSERT INTO nuc_deletions (sequence_id, pos, length) VALUES (?1, ?2, ?3)")?;
+    for del in &output.deletions {
+      stmt.execute(params![sequence_id, del.range().begin.as_usize() + 1, del.len()])?;
+    }
+
+    let mut stmt = conn.prepare_cached(
+      "INSERT INTO aa_substitutions (sequence_id, cds, pos, ref_aa, qry_aa) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for sub in &output.aa_substitutions {
+      stmt.execute(params![
+        sequence_id,
+        sub.cds_name,
+        sub.pos.as_usize() + 1,
+        sub.ref_aa.to_string(),
+        sub.qry_aa.to_string()
+      ])?;
+    }
+
+    let mut stmt =
+      conn.prepare_cached("INSERT INTO aa_deletions (sequence_id, cds, pos, ref_aa) VALUES (?1, ?2, ?3, ?4)")?;
+    for del in &output.aa_deletions {
+      stmt.execute(params![
+        sequence_id,
+        del.cds_name,
+        del.pos.as_usize() + 1,
+        del.ref_aa.to_string()
+      ])?;
+    }
+
+    let mut stmt =
+      conn.prepare_cached("INSERT INTO insertions (sequence_id, cds, pos, ins) VALUES (?1, ?2, ?3, ?4)")?;
+    for ins in &output.insertions {
+      stmt.execute(params![
+        sequence_id,
+        None::<String>,
+        ins.pos + 1,
+        from_nuc_seq(&ins.ins)
+      ])?;
+    }
+    for ins in &output.aa_insertions {
+      stmt.execute(params![sequence_id, ins.cds, ins.pos + 1, from_aa_seq(&ins.ins)])?;
+    }
+
+    let mut stmt = conn
+      .prepare_cached("INSERT INTO frame_shifts (sequence_id, cds, codon_pos, codon_length) VALUES (?1, ?2, ?3, ?4)")?;
+    for frame_shift in &output.frame_shifts {
+      stmt.execute(params![
+        sequence_id,
+        frame_shift.cds_name,
+        frame_shift.codon.begin.as_usize() + 1,
+        frame_shift.codon.len()
+      ])?;
+    }
+
+    let mut stmt = conn.prepare_cached(
+      "INSERT INTO pcr_primer_changes (sequence_id, primer_name, pos, ref_nuc, qry_nuc) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for change in &output.pcr_primer_changes {
+      for sub in &change.substitutions {
+        stmt.execute(params![
+          sequence_id,
+          change.primer.name,
+          sub.pos.as_usize() + 1,
+          sub.ref_nuc.to_string(),
+          sub.qry_nuc.to_string()
+        ])?;
+      }
+    }
+
+    let mut stmt =
+      conn.prepare_cached("INSERT INTO phenotype_values (sequence_id, name, cds, value) VALUES (?1, ?2, ?3, ?4)")?;
+    for phenotype in output.phenotype_values.iter().flatten() {
+      stmt.execute(params![sequence_id, phenotype.name, phenotype.cds, phenotype.value])?;
+    }
+
+    Ok(())
+  }
+
+  pub fn write_nuc_error(&self, index: usize, seq_name: &str, errors: &[String]) -> Result<(), Report> {
+    self
+      .conn
+      .prepare_cached("INSERT INTO sequences (run_id, seq_index, seq_name, errors) VALUES (?1, ?2, ?3, ?4)")?
+      .execute(params![self.run_id, index, seq_name, errors.join(";")])
+      .wrap_err_with(|| format!("When writing sequence '{seq_name}' into SQLite database"))?;
+    Ok(())
+  }
+
+  /// Commits all records of the run
+  pub fn finish(&mut self) -> Result<(), Report> {
+    if !self.is_finished {
+      self
+        .conn
+        .execute_batch("COMMIT;")
+        .wrap_err_with(|| format!("When writing SQLite database: {}", self.filepath.display()))?;
+      self.is_finished = true;
+    }
+    Ok(())
+  }
+
+  /// Discards all records of the run
+  pub fn rollback(&mut self) -> Result<(), Report> {
+    if !self.is_finished {
+      self
+        .conn
+        .execute_batch("ROLLBACK;")
+        .wrap_err_with(|| format!("When writing SQLite database: {}", self.filepath.display()))?;
+      self.is_finished = true;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tests::TempDir;
+  use nextclade::analyze::aa_sub::AaSub;
+  use nextclade::analyze::find_private_nuc_mutations::PrivateNucMutations;
+  use nextclade::analyze::nuc_del::NucDelRange;
+  use nextclade::analyze::nuc_sub::NucSub;
+  use nextclade::coord::range::NucRefGlobalRange;
+  use nextclade::o;
+  use pretty_assertions::assert_eq;
+  use std::collections::BTreeMap;
+  use std::str::FromStr;
+
+  fn writer_in_memory() -> Result<NextcladeResultsSqliteWriter, Report> {
+    let conn = Connection::open_in_memory()?;
+    NextcladeResultsSqliteWriter::from_connection(conn, Path::new(":memory:"), "sars-cov-2", None)
+  }
+
+  #[test]
+  fn writes_sequence_and_its_mutations() -> Result<(), Report> {
+    let sub = NucSub::from_str("A10C")?;
+    let output = NextcladeOutputs {
+      index: 3,
+      seq_name: o!("seq"),
+      clade: Some(o!("20A")),
+      coverage: 0.5,
+      alignment_range: NucRefGlobalRange::from_usize(4, 100),
+      substitutions: vec![sub.clone(), NucSub::from_str("G20T")?, NucSub::from_str("A30G")?],
+      total_substitutions: 3,
+      deletions: vec![NucDelRange::from_usize(29, 32)],
+      total_deletions: 3,
+      aa_substitutions: vec![AaSub::from_str("S:N501Y")?],
+      private_nuc_mutations: PrivateNucMutations {
+        // Nearest node has T30, so the private mutation starts from T rather than from the reference letter
+        private_substitutions: vec![sub, NucSub::from_str("T30G")?],
+        ..PrivateNucMutations::default()
+      },
+      custom_node_attributes: BTreeMap::from([(o!("lineage"), o!("B.1"))]),
+      ..NextcladeOutputs::default()
+    };
+
+    let mut writer = writer_in_memory()?;
+    writer.write(&output)?;
+    writer.finish()?;
+    let conn = &writer.conn;
+
+    let sequence: (i64, String, Option<String>, f64, i64, i64, i64, i64, String) = conn.query_row(
+      "SELECT seq_index, seq_name, clade, coverage, alignment_start, alignment_end, total_substitutions, \
+       total_deletions, qc_overall_status FROM sequences",
+      [],
+      |row| {
+        Ok((
+          row.get(0)?,
+          row.get(1)?,
+          row.get(2)?,
+          row.get(3)?,
+          row.get(4)?,
+          row.get(5)?,
+          row.get(6)?,
+          row.get(7)?,
+          row.get(8)?,
+        ))
+      },
+    )?;
+    assert_eq!(sequence, (3, o!("seq"), Some(o!("20A")), 0.5, 5, 100, 3, 3, o!("good")));
+
+    let subs: Vec<(i64, String, String, bool)> = conn
+      .prepare("SELECT pos, ref_nuc, qry_nuc, is_private FROM nuc_substitutions ORDER BY pos")?
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+      .collect::<Result<_, _>>()?;
+    assert_eq!(
+      subs,
+      vec![
+        (10, o!("A"), o!("C"), true),
+        (20, o!("G"), o!("T"), false),
+        (30, o!("A"), o!("G"), true)
+      ]
+    );
+
+    let del: (i64, i64) = conn.query_row("SELECT pos, length FROM nuc_deletions", [], |row| {
+      Ok((row.get(0)?, row.get(1)?))
+    })?;
+    assert_eq!(del, (30, 3));
+
+    let aa_sub: (String, i64, String, String) =
+      conn.query_row("SELECT cds, pos, ref_aa, qry_aa FROM aa_substitutions", [], |row| {
+        Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+      })?;
+    assert_eq!(aa_sub, (o!("S"), 501, o!("N"), o!("Y")));
+
+    let attr: (String, String) = conn.query_row("SELECT name, value FROM clade_attributes", [], |row| {
+      Ok((row.get(0)?, row.get(1)?))
+    })?;
+    assert_eq!(attr, (o!("lineage"), o!("B.1")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn discards_records_of_rolled_back_run() -> Result<(), Report> {
+    let mut writer = writer_in_memory()?;
+    writer.write_nuc_error(0, "bad", &["sequence is too short".to_owned()])?;
+    writer.rollback()?;
+
+    let counts: (i64, i64) = writer.conn.query_row(
+      "SELECT (SELECT COUNT(*) FROM runs), (SELECT COUNT(*) FROM sequences)",
+      [],
+      |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(counts, (0, 0));
+    Ok(())
+  }
+
+  #[test]
+  fn appends_runs_to_existing_database() -> Result<(), Report> {
+    let dir = TempDir::new();
+    let filepath = dir.path().join("results.sqlite");
+
+    for version in ["2024-01-01--00-00-00Z", "2024-01-08--00-00-00Z"] {
+      let mut writer = NextcladeResultsSqliteWriter::new(&filepath, "flu", Some(version))?;
+      writer.write_nuc_error(0, "bad", &["sequence is too short".to_owned()])?;
+      writer.finish()?;
+    }
+
+    let conn = Connection::open(&filepath)?;
+    let runs: Vec<(i64, Option<String>)> = conn
+      .prepare("SELECT runs.id, runs.dataset_version FROM sequences JOIN runs ON runs.id = sequences.run_id")?
+      .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+      .collect::<Result<_, _>>()?;
+
+    assert_eq!(
+      runs,
+      vec![
+        (1, Some("2024-01-01--00-00-00Z".to_owned())),
+        (2, Some("2024-01-08--00-00-00Z".to_owned()))
+      ]
+    );
+    Ok(())
+  }
+}
@@ -6,9 +6,35 @@ pub mod io;
 mod tests {
   use ctor::ctor;
   use nextclade::utils::global_init::{GlobalInitConfig, global_init};
+  use std::path::{Path, PathBuf};
+  use std::sync::atomic::{AtomicUsize, Ordering};
 
   #[ctor]
   fn init() {
     global_init(&GlobalInitConfig::default());
   }
+
+  /// Unique temporary directory, which is removed along with its contents when dropped, including when the test fails
+  pub struct TempDir(PathBuf);
+
+  impl TempDir {
+    pub fn new() -> Self {
+      static COUNTER: AtomicUsize = AtomicUsize::new(0);
+      let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+      let dir = std::env::temp_dir().join(format!("nextclade-cli-test-{}-{id}", std::process::id()));
+      std::fs::create_dir_all(&dir).unwrap();
+      Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+      &self.0
+    }
+  }
+
+  impl Drop for TempDir {
+    #[allow(unused_must_use)]
+    fn drop(&mut self) {
+      std::fs::remove_dir_all(&self.0);
+    }
+  }
 }
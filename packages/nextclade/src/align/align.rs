@@ -5,6 +5,7 @@ use crate::align::params::AlignPairwiseParams;
 use crate::align::score_matrix::{ScoreMatrixResult, score_matrix};
 use crate::align::seed_alignment::create_alignment_band;
 use crate::align::seed_match::{CodonSpacedIndex, SeedMatchesResult, get_seed_matches_maybe_reverse_complement};
+use crate::align::substitution_matrix::SubstitutionScores;
 use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
@@ -18,12 +19,14 @@ fn align_pairwise<T: Letter<T>>(
   qry_seq: &[T],
   ref_seq: &[T],
   gap_open_close: &[i32],
+  substitution_scores: &SubstitutionScores,
   params: &AlignPairwiseParams,
   stripes: &[Stripe],
 ) -> AlignmentOutput<T> {
   trace!("Align pairwise: started. Params: {params:?}");
 
-  let ScoreMatrixResult { scores, paths } = score_matrix(qry_seq, ref_seq, gap_open_close, stripes, params);
+  let ScoreMatrixResult { scores, paths } =
+    score_matrix(qry_seq, ref_seq, gap_open_close, stripes, substitution_scores, params);
 
   backtrace(qry_seq, ref_seq, &scores, &paths)
 }
@@ -36,6 +39,7 @@ pub fn align_nuc(
   ref_seq: &[Nuc],
   seed_index: &CodonSpacedIndex,
   gap_open_close: &[i32],
+  substitution_scores: &SubstitutionScores,
   params: &AlignPairwiseParams,
 ) -> Result<AlignmentOutput<Nuc>, Report> {
   let qry_len = qry_seq.len();
@@ -53,13 +57,21 @@ pub fn align_nuc(
     trace!(
       "When processing sequence #{index} '{seq_name}': In nucleotide alignment: Band construction: short sequences, using full matrix"
     );
-    return Ok(align_pairwise(qry_seq, ref_seq, gap_open_close, params, &stripes));
+    return Ok(align_pairwise(
+      qry_seq,
+      ref_seq,
+      gap_open_close,
+      substitution_scores,
+      params,
+      &stripes,
+    ));
   }
 
   // otherwise, determine seed matches roughly regularly spaced along the query sequence
   let SeedMatchesResult {
     qry_seq,
     seed_matches,
+    chimera,
     is_reverse_complement,
   } = get_seed_matches_maybe_reverse_complement(qry_seq, ref_seq, seed_index, params)
     .wrap_err("When calculating seed matches")?;
@@ -101,7 +113,7 @@ pub fn align_nuc(
     };
   }
 
-  let mut alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, params, &stripes);
+  let mut alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, substitution_scores, params, &stripes);
 
   while alignment.hit_boundary && attempt < params.max_alignment_attempts {
     info!(
@@ -128,7 +140,7 @@ pub fn align_nuc(
       break;
     }
     // realign
-    alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, params, &stripes);
+    alignment = align_pairwise(&qry_seq, ref_seq, gap_open_close, substitution_scores, params, &stripes);
   }
   // report success/failure of broadening of band width
   if alignment.hit_boundary {
@@ -149,6 +161,7 @@ pub fn align_nuc(
     );
   }
   alignment.is_reverse_complement = is_reverse_complement;
+  alignment.chimera = chimera;
   Ok(alignment)
 }
 
@@ -157,13 +170,14 @@ pub fn align_aa(
   qry_seq: &[Aa],
   ref_seq: &[Aa],
   gap_open_close: &[i32],
+  substitution_scores: &SubstitutionScores,
   params: &AlignPairwiseParams,
   band_width: usize,
   mean_shift: i32,
 ) -> AlignmentOutput<Aa> {
   let stripes = simple_stripes(mean_shift, band_width, ref_seq.len(), qry_seq.len());
 
-  align_pairwise(qry_seq, ref_seq, gap_open_close, params, &stripes)
+  align_pairwise(qry_seq, ref_seq, gap_open_close, substitution_scores, params, &stripes)
 }
 
 #[cfg(test)]
@@ -185,6 +199,7 @@ mod tests {
   struct Context {
     params: AlignPairwiseParams,
     gap_open_close: GapScoreMap,
+    substitution_scores: SubstitutionScores,
   }
 
   #[fixture]
@@ -202,7 +217,13 @@ mod tests {
     let dummy_ref_seq = vec![Nuc::Gap; 100];
     let gap_open_close = get_gap_open_close_scores_codon_aware(&dummy_ref_seq, &gene_map, &params);
 
-    Context { params, gap_open_close }
+    let substitution_scores = SubstitutionScores::new::<Nuc>(&params).unwrap();
+
+    Context {
+      params,
+      gap_open_close,
+      substitution_scores,
+    }
   }
 
   #[fixture]
@@ -221,7 +242,13 @@ mod tests {
     let ref_seq = to_nuc_seq(fs::read_to_string(ref_path).unwrap().trim()).unwrap();
     let gap_open_close = get_gap_open_close_scores_codon_aware(&ref_seq, &gene_map, &params);
 
-    Context { params, gap_open_close }
+    let substitution_scores = SubstitutionScores::new::<Nuc>(&params).unwrap();
+
+    Context {
+      params,
+      gap_open_close,
+      substitution_scores,
+    }
   }
 
   #[rstest]
@@ -236,6 +263,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -258,6 +286,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -280,6 +309,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -303,6 +333,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -326,6 +357,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -348,6 +380,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -371,6 +404,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -394,6 +428,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -416,6 +451,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -438,6 +474,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -460,6 +497,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -483,6 +521,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -505,6 +544,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -534,6 +574,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &SubstitutionScores::new::<Nuc>(&params)?,
       &params,
     )?;
 
@@ -556,6 +597,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -579,6 +621,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -602,6 +645,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -624,6 +668,7 @@ mod tests {
       &ref_seq,
       &CodonSpacedIndex::from_sequence(&ref_seq),
       &ctx.gap_open_close,
+      &ctx.substitution_scores,
       &ctx.params,
     )?;
 
@@ -640,7 +685,7 @@ mod tests {
     let ref_aln = to_nuc_seq("CTTGGAGGTTCCGTGGCT----AGATAACAGAACATTCTTGGAATGCTGATCTTTATAAGCTCATGCGACACTTCGCATGGTG---AGCCTTTGT")?;
     let qry_aln = to_nuc_seq("CTTGGAGGTTCCGTGGCTATAAAGATAACAGAACATTCTTGGAATGCTGATC-----AAGCTCATGGGACANNNNNCATGGTGGACAGCCTTTGT")?;
 
-    let result = align_nuc(0, "", &qry_seq, &ref_seq, &CodonSpacedIndex::from_sequence(&ref_seq), &ctx.gap_open_close, &ctx.params)?;
+    let result = align_nuc(0, "", &qry_seq, &ref_seq, &CodonSpacedIndex::from_sequence(&ref_seq), &ctx.gap_open_close, &ctx.substitution_scores, &ctx.params)?;
 
     assert_eq!(from_nuc_seq(&ref_aln), from_nuc_seq(&result.ref_seq));
     assert_eq!(from_nuc_seq(&qry_aln), from_nuc_seq(&result.qry_seq));
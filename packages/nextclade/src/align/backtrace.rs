@@ -1,4 +1,5 @@
 use crate::align::band_2d::Band2d;
+use crate::align::chimera::AlignmentChimera;
 use crate::align::score_matrix::{BOUNDARY, MATCH, QRY_GAP_EXTEND, QRY_GAP_MATRIX, REF_GAP_EXTEND, REF_GAP_MATRIX};
 use crate::alphabet::letter::Letter;
 use serde::{Deserialize, Serialize};
@@ -10,6 +11,8 @@ pub struct AlignmentOutput<T> {
   pub alignment_score: i32,
   pub is_reverse_complement: bool,
   pub hit_boundary: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub chimera: Option<AlignmentChimera>,
 }
 
 pub fn backtrace<T: Letter<T>>(
@@ -92,6 +95,7 @@ pub fn backtrace<T: Letter<T>>(
     alignment_score: scores[(num_rows - 1, num_cols - 1)],
     is_reverse_complement: false,
     hit_boundary,
+    chimera: None,
   }
 }
 
@@ -138,6 +142,7 @@ mod tests {
       alignment_score: 18,
       is_reverse_complement: false,
       hit_boundary: false,
+      chimera: None,
     };
 
     let output = backtrace(&qry_seq, &ref_seq, &scores, &paths);
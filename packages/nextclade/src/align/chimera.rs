@@ -0,0 +1,231 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::align::seed_match::{SeedMatch2, chain_seeds};
+use crate::coord::range::{NucQryGlobalRange, NucRefGlobalRange};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of additional seed chains to search for, beyond the primary (optimal) chain
+const MAX_SECONDARY_CHAINS: usize = 4;
+
+/// A block of chained seed matches which lie on approximately the same diagonal of the alignment matrix
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SeedChainBlock {
+  /// Range of the block in query sequence coordinates (in the orientation used for alignment)
+  pub qry_range: NucQryGlobalRange,
+  /// Range of the block in reference sequence coordinates
+  pub ref_range: NucRefGlobalRange,
+  /// Diagonal offset of the block (query position minus reference position), averaged over its seed matches
+  pub offset: isize,
+  /// Total length of seed matches in the block
+  pub seed_length: usize,
+  /// Whether the block belongs to the seed chain which was used to construct the alignment band
+  pub is_primary: bool,
+}
+
+/// Multiple incompatible seed chains detected in a query sequence. This is typical for chimeric sequences, such as
+/// assembly misjoins, or sequences containing pieces of different segments.
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlignmentChimera {
+  /// Blocks of seed matches, sorted by their position in the query sequence
+  pub blocks: Vec<SeedChainBlock>,
+}
+
+impl AlignmentChimera {
+  pub fn describe(&self) -> String {
+    let blocks = self
+      .blocks
+      .iter()
+      .map(|block| format!("query {} to reference {}", block.qry_range, block.ref_range))
+      .join(", ");
+    format!(
+      "Sequence is likely chimeric: seed matching found {} incompatible blocks: {blocks}. The alignment is constructed from the primary block(s) only, and the rest of the sequence is likely reported as long insertions and deletions.",
+      self.blocks.len()
+    )
+  }
+}
+
+/// Detects incompatible chains of seed matches.
+///
+/// The primary chain (the one used to construct the alignment band) is split into blocks wherever the diagonal offset
+/// between consecutive seeds jumps by more than a threshold. Then, the seed matches which are not covered by the
+/// primary chain and which are on a distant diagonal are chained again, to find blocks which could not be included
+/// into the primary chain (e.g. blocks in the wrong order relative to the reference).
+///
+/// Returns `None` if less than 2 blocks of sufficient length are found.
+pub fn find_chimeric_seed_chains(
+  matches: &[SeedMatch2],
+  primary_chain: &[SeedMatch2],
+  params: &AlignPairwiseParams,
+) -> Option<AlignmentChimera> {
+  if primary_chain.is_empty() {
+    return None;
+  }
+
+  let min_shift = params.chimera_min_offset_shift as isize;
+
+  let mut blocks = split_chain_into_blocks(primary_chain, min_shift, true);
+
+  let mut remaining = matches
+    .iter()
+    .filter(|m| !overlaps_chain_in_qry(m, primary_chain))
+    .filter(|m| (m.offset - nearest_offset(m, primary_chain)).abs() >= min_shift)
+    .cloned()
+    .collect_vec();
+
+  for _ in 0..MAX_SECONDARY_CHAINS {
+    if remaining.is_empty() {
+      break;
+    }
+    let chain = chain_seeds(&remaining);
+    remaining.retain(|m| !overlaps_chain_in_qry(m, &chain));
+    blocks.extend(split_chain_into_blocks(&chain, min_shift, false));
+  }
+
+  let blocks = blocks
+    .into_iter()
+    .filter(|block| block.seed_length >= params.chimera_min_block_length)
+    .sorted_by_key(|block| (block.qry_range.begin, block.qry_range.end))
+    .collect_vec();
+
+  (blocks.len() > 1).then_some(AlignmentChimera { blocks })
+}
+
+/// Splits a chain of seeds into blocks wherever the offset between consecutive seeds changes by at least `min_shift`
+fn split_chain_into_blocks(chain: &[SeedMatch2], min_shift: isize, is_primary: bool) -> Vec<SeedChainBlock> {
+  let mut groups: Vec<Vec<&SeedMatch2>> = vec![];
+  for seed in chain {
+    match groups.last_mut() {
+      Some(group) if (seed.offset - group.last().unwrap().offset).abs() < min_shift => group.push(seed),
+      _ => groups.push(vec![seed]),
+    }
+  }
+
+  groups
+    .into_iter()
+    .map(|group| {
+      let first = group[0];
+      let last = group[group.len() - 1];
+      let seed_length = group.iter().map(|seed| seed.length).sum::<usize>();
+      let offset_sum = group
+        .iter()
+        .map(|seed| seed.offset * seed.length as isize)
+        .sum::<isize>();
+      let offset = if seed_length > 0 {
+        offset_sum / seed_length as isize
+      } else {
+        first.offset
+      };
+      SeedChainBlock {
+        qry_range: NucQryGlobalRange::from_usize(first.qry_pos, last.qry_pos + last.length),
+        ref_range: NucRefGlobalRange::from_usize(first.ref_pos, last.ref_pos + last.length),
+        offset,
+        seed_length,
+        is_primary,
+      }
+    })
+    .collect_vec()
+}
+
+fn overlaps_chain_in_qry(seed: &SeedMatch2, chain: &[SeedMatch2]) -> bool {
+  chain
+    .iter()
+    .any(|other| seed.qry_pos < other.qry_pos + other.length && other.qry_pos < seed.qry_pos + seed.length)
+}
+
+/// Finds offset of the seed in the chain which is the closest to the given seed in query coordinates
+fn nearest_offset(seed: &SeedMatch2, chain: &[SeedMatch2]) -> isize {
+  chain
+    .iter()
+    .min_by_key(|other| {
+      if other.qry_pos + other.length <= seed.qry_pos {
+        seed.qry_pos - (other.qry_pos + other.length)
+      } else {
+        other.qry_pos.saturating_sub(seed.qry_pos + seed.length)
+      }
+    })
+    .map_or(seed.offset, |other| other.offset)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn seed(qry_pos: usize, ref_pos: usize, length: usize) -> SeedMatch2 {
+    SeedMatch2 {
+      ref_pos,
+      qry_pos,
+      length,
+      offset: qry_pos as isize - ref_pos as isize,
+    }
+  }
+
+  fn params() -> AlignPairwiseParams {
+    AlignPairwiseParams {
+      chimera_min_block_length: 100,
+      chimera_min_offset_shift: 1000,
+      ..AlignPairwiseParams::default()
+    }
+  }
+
+  #[rstest]
+  fn finds_no_chimera_in_collinear_chain() {
+    let chain = vec![seed(0, 0, 200), seed(300, 310, 200), seed(600, 600, 200)];
+    assert_eq!(find_chimeric_seed_chains(&chain, &chain, &params()), None);
+  }
+
+  #[rstest]
+  fn splits_primary_chain_at_large_offset_shift() {
+    // Query consists of reference 0..500 followed by reference 3000..3500
+    let chain = vec![seed(0, 0, 500), seed(500, 3000, 500)];
+    let chimera = find_chimeric_seed_chains(&chain, &chain, &params()).unwrap();
+    assert_eq!(
+      chimera.blocks,
+      vec![
+        SeedChainBlock {
+          qry_range: NucQryGlobalRange::from_usize(0, 500),
+          ref_range: NucRefGlobalRange::from_usize(0, 500),
+          offset: 0,
+          seed_length: 500,
+          is_primary: true,
+        },
+        SeedChainBlock {
+          qry_range: NucQryGlobalRange::from_usize(500, 1000),
+          ref_range: NucRefGlobalRange::from_usize(3000, 3500),
+          offset: -2500,
+          seed_length: 500,
+          is_primary: true,
+        },
+      ]
+    );
+  }
+
+  #[rstest]
+  fn finds_block_incompatible_with_primary_chain() {
+    // Query consists of reference 3000..3500 followed by reference 0..400, which cannot be chained together
+    let matches = vec![seed(0, 3000, 500), seed(500, 0, 400)];
+    let primary_chain = vec![seed(0, 3000, 500)];
+    let chimera = find_chimeric_seed_chains(&matches, &primary_chain, &params()).unwrap();
+    assert_eq!(
+      chimera
+        .blocks
+        .iter()
+        .map(|b| (b.qry_range.clone(), b.is_primary))
+        .collect_vec(),
+      vec![
+        (NucQryGlobalRange::from_usize(0, 500), true),
+        (NucQryGlobalRange::from_usize(500, 900), false),
+      ]
+    );
+  }
+
+  #[rstest]
+  fn ignores_short_blocks() {
+    let matches = vec![seed(0, 3000, 500), seed(500, 0, 40)];
+    let primary_chain = vec![seed(0, 3000, 500)];
+    assert_eq!(find_chimeric_seed_chains(&matches, &primary_chain, &params()), None);
+  }
+}
@@ -107,6 +107,7 @@ mod tests {
           proteins: vec![],
           exceptions: vec![],
           attributes: indexmap! {},
+          edits: vec![],
           compat_is_gene: false,
           color: None,
         })
@@ -1,6 +1,7 @@
 pub mod align;
 pub mod backtrace;
 pub mod band_2d;
+pub mod chimera;
 pub mod gap_open;
 pub mod insertions_strip;
 pub mod params;
@@ -10,3 +11,4 @@ pub mod score_matrix_aa;
 pub mod score_matrix_nuc;
 pub mod seed_alignment;
 pub mod seed_match;
+pub mod substitution_matrix;
@@ -1,7 +1,11 @@
+use crate::align::substitution_matrix::{SubstitutionMatrix, SubstitutionScores};
+use crate::alphabet::aa::Aa;
+use crate::alphabet::nuc::Nuc;
+use crate::translate::genetic_code::GeneticCode;
 use crate::utils::any::AnyType;
 use crate::{make_error, o};
 use clap::{Parser, ValueEnum};
-use eyre::Report;
+use eyre::{Report, WrapErr};
 use itertools::Itertools;
 use optfield::optfield;
 use ordered_float::OrderedFloat;
@@ -83,6 +87,18 @@ pub struct AlignPairwiseParams {
   #[clap(long)]
   pub score_match: i32,
 
+  /// Substitution matrix for nucleotide alignment: either a name of a built-in matrix (`simple`, `transition-transversion`) or a path to a file with a custom matrix in the NCBI/EMBOSS text format.
+  ///
+  /// Pairs of nucleotides which are not listed in a custom matrix (e.g. ambiguous nucleotides) are scored as in the `simple` matrix, using `--score-match` and `--penalty-mismatch`. Note that scores in the matrix should be balanced against the gap penalties.
+  #[clap(long, value_name = "NAME_OR_PATH")]
+  pub nuc_substitution_matrix: SubstitutionMatrix,
+
+  /// Substitution matrix for amino acid alignment: either a name of a built-in matrix (`simple`, `blosum62`, `blosum80`, `pam250`) or a path to a file with a custom matrix in the NCBI/EMBOSS text format.
+  ///
+  /// Pairs of amino acids which are not listed in a custom matrix are scored as in the `simple` matrix, using `--score-match` and `--penalty-mismatch`. Note that scores in the matrix should be balanced against the gap penalties.
+  #[clap(long, value_name = "NAME_OR_PATH")]
+  pub aa_substitution_matrix: SubstitutionMatrix,
+
   /// Maximum area of the band in the alignment matrix. Alignments with large bands are slow to compute and require substantial memory. Alignment of sequences requiring bands with area larger than this value, will not be attempted and a warning will be emitted.
   #[clap(long)]
   pub max_band_area: u64,
@@ -97,6 +113,12 @@ pub struct AlignPairwiseParams {
   #[clap(num_args=0..=1, default_missing_value = "true")]
   pub no_translate_past_stop: bool,
 
+  /// Genetic code (NCBI translation table number) used for translation of all CDSes, e.g. 2 for vertebrate mitochondrial code or 11 for bacterial code. The `transl_table` attribute of a CDS in the genome annotation takes precedence over this value.
+  ///
+  /// When a genetic code is set, the first codon of a CDS is translated as methionine if it is one of the alternative start codons of this genetic code. When not set, the standard genetic code is used, without alternative start codons. In both cases, ambiguous codons are resolved if all possible codons encode the same amino acid.
+  #[clap(long)]
+  pub genetic_code: Option<u8>,
+
   // Internal alignment parameter
   #[clap(skip)]
   pub left_terminal_gaps_free: bool,
@@ -147,6 +169,23 @@ pub struct AlignPairwiseParams {
   #[clap(long)]
   pub max_alignment_attempts: usize,
 
+  /// Minimum total length of seed matches in a block for it to be considered a separate part of a chimeric sequence.
+  #[clap(long)]
+  pub chimera_min_block_length: usize,
+
+  /// Minimum difference between diagonal offsets (in nucleotides) of two blocks of seed matches for them to be considered incompatible.
+  ///
+  /// If the query sequence contains two or more such blocks, it is reported as likely chimeric (e.g. an assembly misjoin, or a sequence containing pieces of different segments), rather than silently aligned with huge insertions and deletions.
+  #[clap(long)]
+  pub chimera_min_offset_shift: usize,
+
+  /// Split sequences which are detected as chimeric into separate sub-records, one per block of seed matches, and analyze each of them independently.
+  ///
+  /// Results of the sub-records are nested into the results of the original record, in the `chimeraParts` field of the JSON and NDJSON outputs. Sub-records are named by appending the suffix `_part<N>` to the sequence ID.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub split_chimeras: bool,
+
   // The following args are deprecated and are kept for backwards compatibility (to emit errors if they are set)
   /// REMOVED
   #[clap(long, hide_long_help = true, hide_short_help = true)]
@@ -185,9 +224,12 @@ impl Default for AlignPairwiseParams {
       penalty_gap_open_out_of_frame: 8,
       penalty_mismatch: 1,
       score_match: 3,
+      nuc_substitution_matrix: SubstitutionMatrix::default(),
+      aa_substitution_matrix: SubstitutionMatrix::default(),
       max_band_area: 500_000_000, // requires around 500Mb for paths, 2GB for the scores
       retry_reverse_complement: false,
       no_translate_past_stop: false,
+      genetic_code: None,
       left_terminal_gaps_free: true,
       right_terminal_gaps_free: true,
       gap_alignment_side: GapAlignmentSide::default(),
@@ -200,6 +242,9 @@ impl Default for AlignPairwiseParams {
       allowed_mismatches: 8, // Ns count as mismatches
       window_size: 30,
       max_alignment_attempts: 3,
+      chimera_min_block_length: 300,
+      chimera_min_offset_shift: 1000,
+      split_chimeras: false,
 
       // The following args are deprecated and are kept for backwards compatibility (to emit errors if they are set)
       max_indel: None,
@@ -272,6 +317,13 @@ Refer to user documentation for more details."#
       );
     }
 
+    if let Some(genetic_code) = self.genetic_code {
+      GeneticCode::from_id(genetic_code)?;
+    }
+
+    SubstitutionScores::new::<Nuc>(self).wrap_err("When validating nucleotide substitution matrix")?;
+    SubstitutionScores::new::<Aa>(self).wrap_err("When validating amino acid substitution matrix")?;
+
     Ok(())
   }
 }
@@ -1,5 +1,6 @@
 use crate::align::band_2d::{Band2d, Stripe};
 use crate::align::params::{AlignPairwiseParams, GapAlignmentSide};
+use crate::align::substitution_matrix::SubstitutionScores;
 use crate::alphabet::letter::Letter;
 use log::trace;
 
@@ -25,6 +26,7 @@ pub fn score_matrix<T: Letter<T>>(
   ref_seq: &[T],
   gap_open_close: &[i32],
   stripes: &[Stripe],
+  substitution_scores: &SubstitutionScores,
   params: &AlignPairwiseParams,
 ) -> ScoreMatrixResult {
   assert!(gap_open_close.len() > 0);
@@ -122,10 +124,8 @@ pub fn score_matrix<T: Letter<T>>(
             // no need to look-up match score since unknown matches with everything.
             // reduce match score by 1 to de-prioritize matches with unknown states.
             scores[(ri - 1, qpos - 1)] + params.score_match - 1
-          } else if T::lookup_match_score(qry_seq[qpos - 1], ref_seq[ri - 1]) > 0 {
-            scores[(ri - 1, qpos - 1)] + params.score_match
           } else {
-            scores[(ri - 1, qpos - 1)] - params.penalty_mismatch
+            scores[(ri - 1, qpos - 1)] + substitution_scores.lookup(qry_seq[qpos - 1], ref_seq[ri - 1])
           };
           origin = MATCH;
         } else {
@@ -218,6 +218,7 @@ mod tests {
   struct Context {
     params: AlignPairwiseParams,
     gap_open_close: GapScoreMap,
+    substitution_scores: SubstitutionScores,
   }
 
   #[fixture]
@@ -232,7 +233,13 @@ mod tests {
     let dummy_ref_seq = vec![Nuc::Gap; 100];
     let gap_open_close = get_gap_open_close_scores_codon_aware(&dummy_ref_seq, &gene_map, &params);
 
-    Context { params, gap_open_close }
+    let substitution_scores = SubstitutionScores::new::<Nuc>(&params).unwrap();
+
+    Context {
+      params,
+      gap_open_close,
+      substitution_scores,
+    }
   }
 
   #[rstest]
@@ -246,7 +253,14 @@ mod tests {
     let mut stripes = simple_stripes(mean_shift, band_width, ref_seq.len(), qry_seq.len());
     stripes[2].end = stripes[2].end - 1;
     stripes[8].begin = stripes[8].begin + 1;
-    let result = score_matrix(&qry_seq, &ref_seq, &ctx.gap_open_close, &stripes, &ctx.params);
+    let result = score_matrix(
+      &qry_seq,
+      &ref_seq,
+      &ctx.gap_open_close,
+      &stripes,
+      &ctx.substitution_scores,
+      &ctx.params,
+    );
 
     #[rustfmt::skip]
     let expected_scores = Band2d::<i32>::with_data(
@@ -303,7 +317,14 @@ mod tests {
     stripes[8].begin = stripes[8].begin + 1;
 
     ctx.params.gap_alignment_side = GapAlignmentSide::Right;
-    let result = score_matrix(&qry_seq, &ref_seq, &ctx.gap_open_close, &stripes, &ctx.params);
+    let result = score_matrix(
+      &qry_seq,
+      &ref_seq,
+      &ctx.gap_open_close,
+      &stripes,
+      &ctx.substitution_scores,
+      &ctx.params,
+    );
 
     #[rustfmt::skip]
     let expected_scores = Band2d::<i32>::with_data(
@@ -1,3 +1,4 @@
+use crate::align::chimera::{AlignmentChimera, find_chimeric_seed_chains};
 use crate::align::params::AlignPairwiseParams;
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::{Nuc, from_nuc_seq};
@@ -322,7 +323,7 @@ impl CodonSpacedIndex {
 /// TODO: Currently, overlap leads to exclusivity. We should add matches chopped at overlap start/end points.
 /// Input matches are already merged
 /// Optional TODO: Use binary search tree instead of vecs
-fn chain_seeds(matches: &[SeedMatch2]) -> Vec<SeedMatch2> {
+pub fn chain_seeds(matches: &[SeedMatch2]) -> Vec<SeedMatch2> {
   #[derive(Clone, Copy, Debug)]
   struct Triplet {
     ref_end: usize,
@@ -454,12 +455,17 @@ fn chain_seeds(matches: &[SeedMatch2]) -> Vec<SeedMatch2> {
   merged_seeds
 }
 
+pub struct SeedChainResult {
+  pub seed_matches: Vec<SeedMatch2>,
+  pub chimera: Option<AlignmentChimera>,
+}
+
 pub fn get_seed_matches2(
   qry_seq: &[Nuc],
   ref_seq: &[Nuc],
   seed_index: &CodonSpacedIndex,
   params: &AlignPairwiseParams,
-) -> Result<Vec<SeedMatch2>, Report> {
+) -> Result<SeedChainResult, Report> {
   let matches = seed_index
     .extended_matches(qry_seq, ref_seq, params)
     .into_iter()
@@ -495,12 +501,15 @@ pub fn get_seed_matches2(
     }
   }
 
-  Ok(seed_matches)
+  let chimera = find_chimeric_seed_chains(&matches, &seed_matches, params);
+
+  Ok(SeedChainResult { seed_matches, chimera })
 }
 
 pub struct SeedMatchesResult<'a> {
   pub qry_seq: Cow<'a, [Nuc]>,
   pub seed_matches: Vec<SeedMatch2>,
+  pub chimera: Option<AlignmentChimera>,
   pub is_reverse_complement: bool,
 }
 
@@ -512,19 +521,22 @@ pub fn get_seed_matches_maybe_reverse_complement<'a>(
   params: &AlignPairwiseParams,
 ) -> Result<SeedMatchesResult<'a>, Report> {
   match get_seed_matches2(qry_seq, ref_seq, seed_index, params) {
-    Ok(seed_matches) => Ok(SeedMatchesResult {
+    Ok(SeedChainResult { seed_matches, chimera }) => Ok(SeedMatchesResult {
       qry_seq: Cow::Borrowed(qry_seq),
       seed_matches,
+      chimera,
       is_reverse_complement: false,
     }),
     Err(report) => {
       if params.retry_reverse_complement {
         let mut rev_complement = qry_seq.to_owned();
         reverse_complement_in_place(&mut rev_complement);
-        let seed_matches = get_seed_matches2(&rev_complement, ref_seq, seed_index, params).map_err(|_| report)?;
+        let SeedChainResult { seed_matches, chimera } =
+          get_seed_matches2(&rev_complement, ref_seq, seed_index, params).map_err(|_| report)?;
         Ok(SeedMatchesResult {
           qry_seq: Cow::Owned(rev_complement),
           seed_matches,
+          chimera,
           is_reverse_complement: true,
         })
       } else {
@@ -0,0 +1,498 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::alphabet::letter::Letter;
+use crate::io::fs::read_file_to_string;
+use crate::make_error;
+use clap::ValueEnum;
+use eyre::{Report, WrapErr, eyre};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Built-in substitution matrices, which define scores of aligned pairs of nucleotides or amino acids.
+#[derive(ValueEnum, Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubstitutionMatrixName {
+  /// Matching letters (taking ambiguity codes into account) score `--score-match`, and all other pairs are penalized with `--penalty-mismatch`. This is the default.
+  Simple,
+  /// Nucleotides only. As `simple`, but transversions are penalized with twice the `--penalty-mismatch`, while transitions (A<->G and C<->T) are penalized with `--penalty-mismatch`.
+  TransitionTransversion,
+  /// Amino acids only. BLOSUM62 matrix.
+  Blosum62,
+  /// Amino acids only. BLOSUM80 matrix, suitable for closely related sequences.
+  Blosum80,
+  /// Amino acids only. PAM250 matrix.
+  Pam250,
+}
+
+/// Substitution matrix with explicitly provided scores.
+///
+/// Pairs of letters which are not listed in the matrix (e.g. ambiguous nucleotides) are scored as in the `simple` matrix.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomSubstitutionMatrix {
+  /// Letters labeling rows and columns of the matrix, one character per letter, e.g. "ACGT"
+  pub letters: String,
+
+  /// Rows of scores, in the same order as `letters`. Row corresponds to the query letter and column to the reference letter.
+  pub scores: Vec<Vec<i32>>,
+}
+
+/// Substitution matrix used during alignment: either one of the built-in matrices, by name, or a custom matrix.
+///
+/// In CLI arguments the value is either a name of a built-in matrix or a path to a file containing a matrix in the
+/// NCBI/EMBOSS text format (as used for BLOSUM and PAM matrices). In pathogen.json the value is either a name of a
+/// built-in matrix or an object with fields `letters` and `scores`.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum SubstitutionMatrix {
+  Builtin(SubstitutionMatrixName),
+  Custom(CustomSubstitutionMatrix),
+}
+
+impl Default for SubstitutionMatrix {
+  fn default() -> Self {
+    Self::Builtin(SubstitutionMatrixName::Simple)
+  }
+}
+
+impl FromStr for SubstitutionMatrix {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Ok(name) = SubstitutionMatrixName::from_str(s, true) {
+      return Ok(Self::Builtin(name));
+    }
+    let custom = CustomSubstitutionMatrix::from_path(s)?;
+    Ok(Self::Custom(custom))
+  }
+}
+
+impl SubstitutionMatrix {
+  pub fn to_custom(&self, params: &AlignPairwiseParams) -> Option<CustomSubstitutionMatrix> {
+    match self {
+      Self::Builtin(SubstitutionMatrixName::Simple) => None,
+      Self::Builtin(SubstitutionMatrixName::TransitionTransversion) => {
+        Some(transition_transversion(params.score_match, params.penalty_mismatch))
+      }
+      Self::Builtin(SubstitutionMatrixName::Blosum62) => Some(from_aa_table(BLOSUM62)),
+      Self::Builtin(SubstitutionMatrixName::Blosum80) => Some(from_aa_table(BLOSUM80)),
+      Self::Builtin(SubstitutionMatrixName::Pam250) => Some(from_aa_table(PAM250)),
+      Self::Custom(custom) => Some(custom.clone()),
+    }
+  }
+}
+
+impl CustomSubstitutionMatrix {
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let content = read_file_to_string(filepath).wrap_err_with(|| {
+      format!(
+        "When reading substitution matrix file '{}'. Expected either a path to a file or one of the names of built-in matrices: {}",
+        filepath.display(),
+        SubstitutionMatrixName::value_variants()
+          .iter()
+          .filter_map(ValueEnum::to_possible_value)
+          .map(|value| value.get_name().to_owned())
+          .join(", ")
+      )
+    })?;
+    Self::from_str(&content).wrap_err_with(|| format!("When parsing substitution matrix file '{}'", filepath.display()))
+  }
+
+  /// Checks that the matrix is square and that its letters belong to the alphabet of `T`
+  pub fn validate<T: Letter<T>>(&self) -> Result<(), Report> {
+    let letters = self.letters.chars().collect_vec();
+
+    let duplicates = letters.iter().duplicates().join(", ");
+    if !duplicates.is_empty() {
+      return make_error!("Substitution matrix contains duplicate letters: {duplicates}");
+    }
+
+    for letter in &letters {
+      let parsed = T::from_string(&letter.to_string())
+        .wrap_err_with(|| format!("Substitution matrix contains unknown letter: '{letter}'"))?;
+      if parsed.is_gap() {
+        return make_error!("Substitution matrix should not contain the gap character '{letter}'");
+      }
+    }
+
+    let missing = T::REQUIRED_LETTERS
+      .chars()
+      .filter(|letter| !letters.contains(letter))
+      .join(", ");
+    if !missing.is_empty() {
+      return make_error!("Substitution matrix is missing the following required letters: {missing}");
+    }
+
+    if self.scores.len() != letters.len() {
+      return make_error!(
+        "Substitution matrix has {} letters, but {} rows of scores",
+        letters.len(),
+        self.scores.len()
+      );
+    }
+
+    for (letter, row) in letters.iter().zip(&self.scores) {
+      if row.len() != letters.len() {
+        return make_error!(
+          "Substitution matrix has {} letters, but the row for letter '{letter}' has {} scores",
+          letters.len(),
+          row.len()
+        );
+      }
+    }
+
+    Ok(())
+  }
+}
+
+impl FromStr for CustomSubstitutionMatrix {
+  type Err = Report;
+
+  /// Parses substitution matrix in the NCBI/EMBOSS text format: lines starting with `#` are comments, the first line
+  /// contains letters labeling the columns, and each of the following lines contains a letter labeling the row,
+  /// followed by the scores.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut lines = s
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let header = lines
+      .next()
+      .ok_or_else(|| eyre!("Substitution matrix is empty"))?
+      .split_whitespace()
+      .map(parse_letter)
+      .collect::<Result<Vec<char>, Report>>()?;
+
+    let mut rows = lines
+      .map(|line| {
+        let mut tokens = line.split_whitespace();
+        let letter = parse_letter(tokens.next().unwrap_or_default())?;
+        let scores = tokens
+          .map(|token| {
+            token
+              .parse::<i32>()
+              .wrap_err_with(|| format!("When parsing score '{token}' in the row for letter '{letter}'"))
+          })
+          .collect::<Result<Vec<i32>, Report>>()?;
+        Ok((letter, scores))
+      })
+      .collect::<Result<Vec<(char, Vec<i32>)>, Report>>()?;
+
+    let row_letters: BTreeSet<char> = rows.iter().map(|(letter, _)| *letter).collect();
+    let col_letters: BTreeSet<char> = header.iter().copied().collect();
+    if rows.len() != row_letters.len() || row_letters != col_letters {
+      return make_error!(
+        "Substitution matrix should have exactly one row for each of the letters in the header line '{}', but found rows for letters '{}'",
+        header.iter().join(" "),
+        rows.iter().map(|(letter, _)| letter).join(" ")
+      );
+    }
+
+    // Put rows into the same order as columns
+    rows.sort_by_key(|(letter, _)| header.iter().position(|l| l == letter));
+
+    Ok(Self {
+      letters: header.into_iter().collect(),
+      scores: rows.into_iter().map(|(_, scores)| scores).collect(),
+    })
+  }
+}
+
+fn parse_letter(token: &str) -> Result<char, Report> {
+  match token.chars().collect_vec().as_slice() {
+    [letter] => Ok(letter.to_ascii_uppercase()),
+    _ => make_error!("Expected a single letter in substitution matrix, but found: '{token}'"),
+  }
+}
+
+/// Substitution scores for all pairs of letters of an alphabet, prepared for fast lookup during alignment
+#[derive(Clone, Debug)]
+pub struct SubstitutionScores {
+  scores: Vec<i32>,
+  num_letters: usize,
+}
+
+impl SubstitutionScores {
+  pub fn new<T: Letter<T>>(params: &AlignPairwiseParams) -> Result<Self, Report> {
+    let num_letters = T::NUM_LETTERS;
+
+    // Start with the simple match/mismatch scoring for all pairs
+    let mut scores = vec![0; num_letters * num_letters];
+    for (x, y) in T::iter().cartesian_product(T::iter()) {
+      scores[T::matrix_index(x) * num_letters + T::matrix_index(y)] = if T::lookup_match_score(x, y) > 0 {
+        params.score_match
+      } else {
+        -params.penalty_mismatch
+      };
+    }
+
+    // Override scores for pairs listed in the substitution matrix
+    let matrix = T::substitution_matrix(params);
+    if let Some(custom) = matrix.to_custom(params) {
+      custom
+        .validate::<T>()
+        .wrap_err("When preparing substitution matrix for alignment")?;
+
+      let letters: Vec<T> = custom
+        .letters
+        .chars()
+        .map(|letter| T::from_string(&letter.to_string()))
+        .try_collect()?;
+
+      for (x, row) in letters.iter().zip(&custom.scores) {
+        for (y, score) in letters.iter().zip(row) {
+          scores[T::matrix_index(*x) * num_letters + T::matrix_index(*y)] = *score;
+        }
+      }
+    }
+
+    Ok(Self { scores, num_letters })
+  }
+
+  #[inline]
+  pub fn lookup<T: Letter<T>>(&self, x: T, y: T) -> i32 {
+    self.scores[T::matrix_index(x) * self.num_letters + T::matrix_index(y)]
+  }
+}
+
+/// Transition/transversion-weighted nucleotide matrix, scaled according to the match and mismatch scores
+fn transition_transversion(score_match: i32, penalty_mismatch: i32) -> CustomSubstitutionMatrix {
+  let letters = "ACGT";
+  let is_transition = |x: char, y: char| matches!((x, y), ('A', 'G') | ('G', 'A') | ('C', 'T') | ('T', 'C'));
+  let scores = letters
+    .chars()
+    .map(|x| {
+      letters
+        .chars()
+        .map(|y| {
+          if x == y {
+            score_match
+          } else if is_transition(x, y) {
+            -penalty_mismatch
+          } else {
+            -2 * penalty_mismatch
+          }
+        })
+        .collect_vec()
+    })
+    .collect_vec();
+  CustomSubstitutionMatrix {
+    letters: letters.to_owned(),
+    scores,
+  }
+}
+
+const AA_LETTERS: &str = "ABCDEFGHIJKLMNOPQRSTUVWYZX*";
+const NUM_AA_LETTERS: usize = 27;
+
+fn from_aa_table(table: &[i32; NUM_AA_LETTERS * NUM_AA_LETTERS]) -> CustomSubstitutionMatrix {
+  CustomSubstitutionMatrix {
+    letters: AA_LETTERS.to_owned(),
+    scores: table.chunks(NUM_AA_LETTERS).map(<[i32]>::to_vec).collect(),
+  }
+}
+
+#[rustfmt::skip]
+static BLOSUM62: &[i32; NUM_AA_LETTERS * NUM_AA_LETTERS] = &[
+  /*             A   B   C   D   E   F   G   H   I   J   K   L   M   N   O   P   Q   R   S   T   U   V   W   Y   Z   X   * */
+  /* A */   4, -2,  0, -2, -1, -2,  0, -2, -1, -1, -1, -1, -1, -2,  0, -1, -1, -1,  1,  0,  0,  0, -3, -2, -1,  0, -4,
+  /* B */  -2,  4, -3,  4,  1, -3, -1,  0, -3, -4,  0, -4, -3,  3, -1, -2,  0, -1,  0, -1, -1, -3, -4, -3,  1, -1, -4,
+  /* C */   0, -3,  9, -3, -4, -2, -3, -3, -1, -1, -3, -1, -1, -3, -2, -3, -3, -3, -1, -1, -2, -1, -2, -2, -3, -2, -4,
+  /* D */  -2,  4, -3,  6,  2, -3, -1, -1, -3, -4, -1, -4, -3,  1, -1, -1,  0, -2,  0, -1, -1, -3, -4, -3,  1, -1, -4,
+  /* E */  -1,  1, -4,  2,  5, -3, -2,  0, -3, -3,  1, -3, -2,  0, -1, -1,  2,  0,  0, -1, -1, -2, -3, -2,  4, -1, -4,
+  /* F */  -2, -3, -2, -3, -3,  6, -3, -1,  0,  0, -3,  0,  0, -3, -1, -4, -3, -3, -2, -2, -1, -1,  1,  3, -3, -1, -4,
+  /* G */   0, -1, -3, -1, -2, -3,  6, -2, -4, -4, -2, -4, -3,  0, -1, -2, -2, -2,  0, -2, -1, -3, -2, -3, -2, -1, -4,
+  /* H */  -2,  0, -3, -1,  0, -1, -2,  8, -3, -3, -1, -3, -2,  1, -1, -2,  0,  0, -1, -2, -1, -3, -2,  2,  0, -1, -4,
+  /* I */  -1, -3, -1, -3, -3,  0, -4, -3,  4,  3, -3,  2,  1, -3, -1, -3, -3, -3, -2, -1, -1,  3, -3, -1, -3, -1, -4,
+  /* J */  -1, -4, -1, -4, -3,  0, -4, -3,  3,  3, -3,  3,  2, -3, -1, -3, -3, -3, -2, -1, -1,  2, -3, -1, -3, -1, -4,
+  /* K */  -1,  0, -3, -1,  1, -3, -2, -1, -3, -3,  5, -2, -1,  0, -1, -1,  1,  2,  0, -1, -1, -2, -3, -2,  1, -1, -4,
+  /* L */  -1, -4, -1, -4, -3,  0, -4, -3,  2,  3, -2,  4,  2, -3, -1, -3, -2, -2, -2, -1, -1,  1, -2, -1, -3, -1, -4,
+  /* M */  -1, -3, -1, -3, -2,  0, -3, -2,  1,  2, -1,  2,  5, -2, -1, -2,  0, -1, -1, -1, -1,  1, -1, -1, -1, -1, -4,
+  /* N */  -2,  3, -3,  1,  0, -3,  0,  1, -3, -3,  0, -3, -2,  6, -1, -2,  0,  0,  1,  0, -1, -3, -4, -2,  0, -1, -4,
+  /* O */   0, -1, -2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -2, -1, -1,  0,  0, -1, -1, -2, -1, -1, -1, -4,
+  /* P */  -1, -2, -3, -1, -1, -4, -2, -2, -3, -3, -1, -3, -2, -2, -2,  7, -1, -2, -1, -1, -2, -2, -4, -3, -1, -2, -4,
+  /* Q */  -1,  0, -3,  0,  2, -3, -2,  0, -3, -3,  1, -2,  0,  0, -1, -1,  5,  1,  0, -1, -1, -2, -2, -1,  3, -1, -4,
+  /* R */  -1, -1, -3, -2,  0, -3, -2,  0, -3, -3,  2, -2, -1,  0, -1, -2,  1,  5, -1, -1, -1, -3, -3, -2,  0, -1, -4,
+  /* S */   1,  0, -1,  0,  0, -2,  0, -1, -2, -2,  0, -2, -1,  1,  0, -1,  0, -1,  4,  1,  0, -2, -3, -2,  0,  0, -4,
+  /* T */   0, -1, -1, -1, -1, -2, -2, -2, -1, -1, -1, -1, -1,  0,  0, -1, -1, -1,  1,  5,  0,  0, -2, -2, -1,  0, -4,
+  /* U */   0, -1, -2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -2, -1, -1,  0,  0, -1, -1, -2, -1, -1, -1, -4,
+  /* V */   0, -3, -1, -3, -2, -1, -3, -3,  3,  2, -2,  1,  1, -3, -1, -2, -2, -3, -2,  0, -1,  4, -3, -1, -2, -1, -4,
+  /* W */  -3, -4, -2, -4, -3,  1, -2, -2, -3, -3, -3, -2, -1, -4, -2, -4, -2, -3, -3, -2, -2, -3, 11,  2, -3, -2, -4,
+  /* Y */  -2, -3, -2, -3, -2,  3, -3,  2, -1, -1, -2, -1, -1, -2, -1, -3, -1, -2, -2, -2, -1, -1,  2,  7, -2, -1, -4,
+  /* Z */  -1,  1, -3,  1,  4, -3, -2,  0, -3, -3,  1, -3, -1,  0, -1, -1,  3,  0,  0, -1, -1, -2, -3, -2,  4, -1, -4,
+  /* X */   0, -1, -2, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -2, -1, -1,  0,  0, -1, -1, -2, -1, -1, -1, -4,
+  /* * */  -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4, -4,  1,
+];
+
+/// Letters absent from the original BLOSUM80 table are derived as in the BLOSUM62 table above: J (I or L) scores the
+/// mean of the I and L scores, rounded half away from zero, and O and U score as X.
+#[rustfmt::skip]
+static BLOSUM80: &[i32; NUM_AA_LETTERS * NUM_AA_LETTERS] = &[
+  /*             A   B   C   D   E   F   G   H   I   J   K   L   M   N   O   P   Q   R   S   T   U   V   W   Y   Z   X   * */
+  /* A */   5, -2, -1, -2, -1, -3,  0, -2, -2, -2, -1, -2, -1, -2, -1, -1, -1, -2,  1,  0, -1,  0, -3, -2, -1, -1, -6,
+  /* B */  -2,  5, -4,  5,  1, -4, -1, -1, -4, -4, -1, -4, -3,  5, -2, -2,  0, -1,  0, -1, -2, -4, -5, -3,  0, -2, -6,
+  /* C */  -1, -4,  9, -4, -5, -3, -4, -4, -2, -2, -4, -2, -2, -3, -3, -4, -4, -4, -2, -1, -3, -1, -3, -3, -4, -3, -6,
+  /* D */  -2,  5, -4,  6,  1, -4, -2, -2, -4, -5, -1, -5, -4,  1, -2, -2, -1, -2, -1, -1, -2, -4, -6, -4,  1, -2, -6,
+  /* E */  -1,  1, -5,  1,  6, -4, -3,  0, -4, -4,  1, -4, -2, -1, -1, -2,  2, -1,  0, -1, -1, -3, -4, -3,  4, -1, -6,
+  /* F */  -3, -4, -3, -4, -4,  6, -4, -2, -1, -1, -4,  0,  0, -4, -2, -4, -4, -4, -3, -2, -2, -1,  0,  3, -4, -2, -6,
+  /* G */   0, -1, -4, -2, -3, -4,  6, -3, -5, -5, -2, -4, -4, -1, -2, -3, -2, -3, -1, -2, -2, -4, -4, -4, -3, -2, -6,
+  /* H */  -2, -1, -4, -2,  0, -2, -3,  8, -4, -4, -1, -3, -2,  0, -2, -3,  1,  0, -1, -2, -2, -4, -3,  2,  0, -2, -6,
+  /* I */  -2, -4, -2, -4, -4, -1, -5, -4,  5,  3, -3,  1,  1, -4, -2, -4, -3, -3, -3, -1, -2,  3, -3, -2, -4, -2, -6,
+  /* J */  -2, -4, -2, -5, -4, -1, -5, -4,  3,  3, -3,  3,  2, -4, -2, -4, -3, -3, -3, -2, -2,  2, -3, -2, -4, -2, -6,
+  /* K */  -1, -1, -4, -1,  1, -4, -2, -1, -3, -3,  5, -3, -2,  0, -1, -1,  1,  2, -1, -1, -1, -3, -4, -3,  1, -1, -6,
+  /* L */  -2, -4, -2, -5, -4,  0, -4, -3,  1,  3, -3,  4,  2, -4, -2, -3, -3, -3, -3, -2, -2,  1, -2, -2, -3, -2, -6,
+  /* M */  -1, -3, -2, -4, -2,  0, -4, -2,  1,  2, -2,  2,  6, -3, -1, -3,  0, -2, -2, -1, -1,  1, -2, -2, -2, -1, -6,
+  /* N */  -2,  5, -3,  1, -1, -4, -1,  0, -4, -4,  0, -4, -3,  6, -1, -3,  0, -1,  0,  0, -1, -4, -4, -3,  0, -1, -6,
+  /* O */  -1, -2, -3, -2, -1, -2, -2, -2, -2, -2, -1, -2, -1, -1, -1, -2, -1, -1, -1, -1, -1, -1, -3, -2, -1, -1, -6,
+  /* P */  -1, -2, -4, -2, -2, -4, -3, -3, -4, -4, -1, -3, -3, -3, -2,  8, -2, -2, -1, -2, -2, -3, -5, -4, -2, -2, -6,
+  /* Q */  -1,  0, -4, -1,  2, -4, -2,  1, -3, -3,  1, -3,  0,  0, -1, -2,  6,  1,  0, -1, -1, -3, -3, -2,  3, -1, -6,
+  /* R */  -2, -1, -4, -2, -1, -4, -3,  0, -3, -3,  2, -3, -2, -1, -1, -2,  1,  6, -1, -1, -1, -3, -4, -3,  0, -1, -6,
+  /* S */   1,  0, -2, -1,  0, -3, -1, -1, -3, -3, -1, -3, -2,  0, -1, -1,  0, -1,  5,  1, -1, -2, -4, -2,  0, -1, -6,
+  /* T */   0, -1, -1, -1, -1, -2, -2, -2, -1, -2, -1, -2, -1,  0, -1, -2, -1, -1,  1,  5, -1,  0, -4, -2, -1, -1, -6,
+  /* U */  -1, -2, -3, -2, -1, -2, -2, -2, -2, -2, -1, -2, -1, -1, -1, -2, -1, -1, -1, -1, -1, -1, -3, -2, -1, -1, -6,
+  /* V */   0, -4, -1, -4, -3, -1, -4, -4,  3,  2, -3,  1,  1, -4, -1, -3, -3, -3, -2,  0, -1,  4, -3, -2, -3, -1, -6,
+  /* W */  -3, -5, -3, -6, -4,  0, -4, -3, -3, -3, -4, -2, -2, -4, -3, -5, -3, -4, -4, -4, -3, -3, 11,  2, -4, -3, -6,
+  /* Y */  -2, -3, -3, -4, -3,  3, -4,  2, -2, -2, -3, -2, -2, -3, -2, -4, -2, -3, -2, -2, -2, -2,  2,  7, -3, -2, -6,
+  /* Z */  -1,  0, -4,  1,  4, -4, -3,  0, -4, -4,  1, -3, -2,  0, -1, -2,  3,  0,  0, -1, -1, -3, -4, -3,  4, -1, -6,
+  /* X */  -1, -2, -3, -2, -1, -2, -2, -2, -2, -2, -1, -2, -1, -1, -1, -2, -1, -1, -1, -1, -1, -1, -3, -2, -1, -1, -6,
+  /* * */  -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6, -6,  1,
+];
+
+#[rustfmt::skip]
+static PAM250: &[i32; NUM_AA_LETTERS * NUM_AA_LETTERS] = &[
+  /*             A   B   C   D   E   F   G   H   I   J   K   L   M   N   O   P   Q   R   S   T   U   V   W   Y   Z   X   * */
+  /* A */   2,  0, -2,  0,  0, -3,  1, -1, -1, -2, -1, -2, -1,  0,  0,  1,  0, -2,  1,  1,  0,  0, -6, -3,  0,  0, -8,
+  /* B */   0,  3, -4,  3,  3, -4,  0,  1, -2, -3,  1, -3, -2,  2, -1, -1,  1, -1,  0,  0, -1, -2, -5, -3,  2, -1, -8,
+  /* C */  -2, -4, 12, -5, -5, -4, -3, -3, -2, -4, -5, -6, -5, -4, -3, -3, -5, -4,  0, -2, -3, -2, -8,  0, -5, -3, -8,
+  /* D */   0,  3, -5,  4,  3, -6,  1,  1, -2, -3,  0, -4, -3,  2, -1, -1,  2, -1,  0,  0, -1, -2, -7, -4,  3, -1, -8,
+  /* E */   0,  3, -5,  3,  4, -5,  0,  1, -2, -3,  0, -3, -2,  1, -1, -1,  2, -1,  0,  0, -1, -2, -7, -4,  3, -1, -8,
+  /* F */  -3, -4, -4, -6, -5,  9, -5, -2,  1,  2, -5,  2,  0, -3, -2, -5, -5, -4, -3, -3, -2, -1,  0,  7, -5, -2, -8,
+  /* G */   1,  0, -3,  1,  0, -5,  5, -2, -3, -4, -2, -4, -3,  0, -1,  0, -1, -3,  1,  0, -1, -1, -7, -5,  0, -1, -8,
+  /* H */  -1,  1, -3,  1,  1, -2, -2,  6, -2, -2,  0, -2, -2,  2, -1,  0,  3,  2, -1, -1, -1, -2, -3,  0,  2, -1, -8,
+  /* I */  -1, -2, -2, -2, -2,  1, -3, -2,  5,  4, -2,  2,  2, -2, -1, -2, -2, -2, -1,  0, -1,  4, -5, -1, -2, -1, -8,
+  /* J */  -2, -3, -4, -3, -3,  2, -4, -2,  4,  4, -3,  4,  3, -3, -1, -3, -2, -3, -2, -1, -1,  3, -4, -1, -3, -1, -8,
+  /* K */  -1,  1, -5,  0,  0, -5, -2,  0, -2, -3,  5, -3,  0,  1, -1, -1,  1,  3,  0,  0, -1, -2, -3, -4,  0, -1, -8,
+  /* L */  -2, -3, -6, -4, -3,  2, -4, -2,  2,  4, -3,  6,  4, -3, -1, -3, -2, -3, -3, -2, -1,  2, -2, -1, -3, -1, -8,
+  /* M */  -1, -2, -5, -3, -2,  0, -3, -2,  2,  3,  0,  4,  6, -2, -1, -2, -1,  0, -2, -1, -1,  2, -4, -2, -2, -1, -8,
+  /* N */   0,  2, -4,  2,  1, -3,  0,  2, -2, -3,  1, -3, -2,  2,  0,  0,  1,  0,  1,  0,  0, -2, -4, -2,  1,  0, -8,
+  /* O */   0, -1, -3, -1, -1, -2, -1, -1, -1, -1, -1, -1, -1,  0, -1, -1, -1, -1,  0,  0, -1, -1, -4, -2, -1, -1, -8,
+  /* P */   1, -1, -3, -1, -1, -5,  0,  0, -2, -3, -1, -3, -2,  0, -1,  6,  0,  0,  1,  0, -1, -1, -6, -5,  0, -1, -8,
+  /* Q */   0,  1, -5,  2,  2, -5, -1,  3, -2, -2,  1, -2, -1,  1, -1,  0,  4,  1, -1, -1, -1, -2, -5, -4,  3, -1, -8,
+  /* R */  -2, -1, -4, -1, -1, -4, -3,  2, -2, -3,  3, -3,  0,  0, -1,  0,  1,  6,  0, -1, -1, -2,  2, -4,  0, -1, -8,
+  /* S */   1,  0,  0,  0,  0, -3,  1, -1, -1, -2,  0, -3, -2,  1,  0,  1, -1,  0,  2,  1,  0, -1, -2, -3,  0,  0, -8,
+  /* T */   1,  0, -2,  0,  0, -3,  0, -1,  0, -1,  0, -2, -1,  0,  0,  0, -1, -1,  1,  3,  0,  0, -5, -3, -1,  0, -8,
+  /* U */   0, -1, -3, -1, -1, -2, -1, -1, -1, -1, -1, -1, -1,  0, -1, -1, -1, -1,  0,  0, -1, -1, -4, -2, -1, -1, -8,
+  /* V */   0, -2, -2, -2, -2, -1, -1, -2,  4,  3, -2,  2,  2, -2, -1, -1, -2, -2, -1,  0, -1,  4, -6, -2, -2, -1, -8,
+  /* W */  -6, -5, -8, -7, -7,  0, -7, -3, -5, -4, -3, -2, -4, -4, -4, -6, -5,  2, -2, -5, -4, -6, 17,  0, -6, -4, -8,
+  /* Y */  -3, -3,  0, -4, -4,  7, -5,  0, -1, -1, -4, -1, -2, -2, -2, -5, -4, -4, -3, -3, -2, -2,  0, 10, -4, -2, -8,
+  /* Z */   0,  2, -5,  3,  3, -5,  0,  2, -2, -3,  0, -3, -2,  1, -1,  0,  3,  0,  0, -1, -1, -2, -6, -4,  3, -1, -8,
+  /* X */   0, -1, -3, -1, -1, -2, -1, -1, -1, -1, -1, -1, -1,  0, -1, -1, -1, -1,  0,  0, -1, -1, -4, -2, -1, -1, -8,
+  /* * */  -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8, -8,  1,
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::Aa;
+  use crate::alphabet::nuc::Nuc;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  fn parses_ncbi_matrix_format() -> Result<(), Report> {
+    let matrix = CustomSubstitutionMatrix::from_str(
+      "# Example matrix\n   A  C  G  T\nA  5 -4 -4 -4\nG -4 -4  5 -4\nC -4  5 -4 -4\nT -4 -4 -4  5\n",
+    )?;
+    assert_eq!(
+      matrix,
+      CustomSubstitutionMatrix {
+        letters: "ACGT".to_owned(),
+        scores: vec![
+          vec![5, -4, -4, -4],
+          vec![-4, 5, -4, -4],
+          vec![-4, -4, 5, -4],
+          vec![-4, -4, -4, 5],
+        ],
+      }
+    );
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_matrix_with_missing_rows() {
+    let result = CustomSubstitutionMatrix::from_str("  A  C  G  T\nA 1 0 0 0\nC 0 1 0 0\n");
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "Substitution matrix should have exactly one row for each of the letters in the header line 'A C G T', but found rows for letters 'A C'"
+    );
+  }
+
+  #[rstest]
+  fn uses_simple_scores_by_default() -> Result<(), Report> {
+    let params = AlignPairwiseParams::default();
+    let scores = SubstitutionScores::new::<Nuc>(&params)?;
+    assert_eq!(scores.lookup(Nuc::A, Nuc::A), 3);
+    assert_eq!(scores.lookup(Nuc::A, Nuc::R), 3);
+    assert_eq!(scores.lookup(Nuc::A, Nuc::C), -1);
+    Ok(())
+  }
+
+  #[rstest]
+  fn uses_transition_transversion_scores() -> Result<(), Report> {
+    let params = AlignPairwiseParams {
+      nuc_substitution_matrix: SubstitutionMatrix::Builtin(SubstitutionMatrixName::TransitionTransversion),
+      ..AlignPairwiseParams::default()
+    };
+    let scores = SubstitutionScores::new::<Nuc>(&params)?;
+    assert_eq!(scores.lookup(Nuc::A, Nuc::A), 3);
+    assert_eq!(scores.lookup(Nuc::A, Nuc::G), -1);
+    assert_eq!(scores.lookup(Nuc::A, Nuc::C), -2);
+    assert_eq!(scores.lookup(Nuc::A, Nuc::R), 3);
+    Ok(())
+  }
+
+  #[rstest]
+  fn uses_blosum62_scores() -> Result<(), Report> {
+    let params = AlignPairwiseParams {
+      aa_substitution_matrix: SubstitutionMatrix::Builtin(SubstitutionMatrixName::Blosum62),
+      ..AlignPairwiseParams::default()
+    };
+    let scores = SubstitutionScores::new::<Aa>(&params)?;
+    assert_eq!(scores.lookup(Aa::W, Aa::W), 11);
+    assert_eq!(scores.lookup(Aa::A, Aa::R), -1);
+    assert_eq!(scores.lookup(Aa::Stop, Aa::Stop), 1);
+    Ok(())
+  }
+
+  #[rstest]
+  fn uses_blosum80_scores() -> Result<(), Report> {
+    let params = AlignPairwiseParams {
+      aa_substitution_matrix: SubstitutionMatrix::Builtin(SubstitutionMatrixName::Blosum80),
+      ..AlignPairwiseParams::default()
+    };
+    let scores = SubstitutionScores::new::<Aa>(&params)?;
+    assert_eq!(scores.lookup(Aa::W, Aa::W), 11);
+    assert_eq!(scores.lookup(Aa::A, Aa::R), -2);
+    assert_eq!(scores.lookup(Aa::I, Aa::L), 1);
+    assert_eq!(scores.lookup(Aa::Stop, Aa::A), -6);
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_amino_acid_matrix_for_nucleotides() {
+    let params = AlignPairwiseParams {
+      nuc_substitution_matrix: SubstitutionMatrix::Builtin(SubstitutionMatrixName::Blosum62),
+      ..AlignPairwiseParams::default()
+    };
+    let result = SubstitutionScores::new::<Nuc>(&params);
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "When preparing substitution matrix for alignment: Substitution matrix contains unknown letter: 'E': Unknown nucleotide: E"
+    );
+  }
+}
@@ -1,13 +1,28 @@
+use crate::align::params::AlignPairwiseParams;
 use crate::align::score_matrix_aa::lookup_aa_scoring_matrix;
+use crate::align::substitution_matrix::SubstitutionMatrix;
 use crate::alphabet::letter::{Letter, ScoreMatrixLookup};
 use crate::make_error;
 use eyre::{Report, WrapErr, eyre};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use strum_macros::EnumIter;
 
 #[repr(u8)]
 #[derive(
-  Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize, schemars::JsonSchema, Default,
+  Debug,
+  Clone,
+  Copy,
+  Eq,
+  PartialEq,
+  PartialOrd,
+  Ord,
+  Hash,
+  Serialize,
+  Deserialize,
+  schemars::JsonSchema,
+  Default,
+  EnumIter,
 )]
 pub enum Aa {
   A,
@@ -53,9 +68,21 @@ impl Aa {
 }
 
 impl ScoreMatrixLookup<Aa> for Aa {
+  const NUM_LETTERS: usize = 28;
+  const REQUIRED_LETTERS: &'static str = "ACDEFGHIKLMNPQRSTVWY";
+
   fn lookup_match_score(x: Aa, y: Aa) -> i32 {
     lookup_aa_scoring_matrix(x, y)
   }
+
+  #[inline]
+  fn matrix_index(x: Aa) -> usize {
+    x as usize
+  }
+
+  fn substitution_matrix(params: &AlignPairwiseParams) -> &SubstitutionMatrix {
+    &params.aa_substitution_matrix
+  }
 }
 
 impl Display for Aa {
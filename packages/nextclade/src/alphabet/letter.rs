@@ -1,15 +1,30 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::align::substitution_matrix::SubstitutionMatrix;
 use color_eyre::{Section, SectionExt};
 use eyre::{Report, WrapErr};
 use serde::{Deserialize, Deserializer, Serializer};
 use std::fmt::Display;
+use strum::IntoEnumIterator;
 
 /// Allows to lookup scores for nucleotides and amino acids in a generic way
 pub trait ScoreMatrixLookup<T> {
+  /// Number of letters in the alphabet, i.e. number of rows and columns of the scoring matrices
+  const NUM_LETTERS: usize;
+
+  /// Letters which are required to be present in a custom substitution matrix
+  const REQUIRED_LETTERS: &'static str;
+
   fn lookup_match_score(x: T, y: T) -> i32;
+
+  /// Index of the letter in the rows and columns of the scoring matrices
+  fn matrix_index(x: T) -> usize;
+
+  /// Selects substitution matrix for this alphabet from alignment parameters
+  fn substitution_matrix(params: &AlignPairwiseParams) -> &SubstitutionMatrix;
 }
 
 /// Generic representation of a character defining nucleotide or amino acid
-pub trait Letter<L>: Copy + Display + Eq + Ord + ScoreMatrixLookup<L> {
+pub trait Letter<L>: Copy + Display + Eq + Ord + ScoreMatrixLookup<L> + IntoEnumIterator {
   const GAP: L;
   const UNKNOWN: L;
 
@@ -1,14 +1,29 @@
+use crate::align::params::AlignPairwiseParams;
 use crate::align::score_matrix_nuc::lookup_nuc_scoring_matrix;
+use crate::align::substitution_matrix::SubstitutionMatrix;
 use crate::alphabet::letter::{Letter, ScoreMatrixLookup};
 use crate::make_error;
 use eyre::{Report, WrapErr, eyre};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use strum_macros::EnumIter;
 
 /// A nucleotide
 #[repr(u8)]
 #[derive(
-  Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize, schemars::JsonSchema, Hash, Default,
+  Debug,
+  Clone,
+  Copy,
+  Eq,
+  PartialEq,
+  PartialOrd,
+  Ord,
+  Serialize,
+  Deserialize,
+  schemars::JsonSchema,
+  Hash,
+  Default,
+  EnumIter,
 )]
 pub enum Nuc {
   T,
@@ -42,12 +57,46 @@ impl Nuc {
   pub const fn is_acgtn(self) -> bool {
     matches!(self, Nuc::A | Nuc::C | Nuc::G | Nuc::T | Nuc::N)
   }
+
+  /// Lists canonical nucleotides (A, C, G, T) compatible with a given, possibly ambiguous, nucleotide
+  pub const fn canonical_nucs(self) -> &'static [Nuc] {
+    match self {
+      Nuc::A => &[Nuc::A],
+      Nuc::C => &[Nuc::C],
+      Nuc::G => &[Nuc::G],
+      Nuc::T => &[Nuc::T],
+      Nuc::W => &[Nuc::A, Nuc::T],
+      Nuc::S => &[Nuc::C, Nuc::G],
+      Nuc::M => &[Nuc::A, Nuc::C],
+      Nuc::K => &[Nuc::G, Nuc::T],
+      Nuc::R => &[Nuc::A, Nuc::G],
+      Nuc::Y => &[Nuc::C, Nuc::T],
+      Nuc::B => &[Nuc::C, Nuc::G, Nuc::T],
+      Nuc::D => &[Nuc::A, Nuc::G, Nuc::T],
+      Nuc::H => &[Nuc::A, Nuc::C, Nuc::T],
+      Nuc::V => &[Nuc::A, Nuc::C, Nuc::G],
+      Nuc::N => &[Nuc::A, Nuc::C, Nuc::G, Nuc::T],
+      Nuc::Gap => &[],
+    }
+  }
 }
 
 impl ScoreMatrixLookup<Nuc> for Nuc {
+  const NUM_LETTERS: usize = 16;
+  const REQUIRED_LETTERS: &'static str = "ACGT";
+
   fn lookup_match_score(x: Nuc, y: Nuc) -> i32 {
     lookup_nuc_scoring_matrix(x, y)
   }
+
+  #[inline]
+  fn matrix_index(x: Nuc) -> usize {
+    x as usize
+  }
+
+  fn substitution_matrix(params: &AlignPairwiseParams) -> &SubstitutionMatrix {
+    &params.nuc_substitution_matrix
+  }
 }
 
 impl Display for Nuc {
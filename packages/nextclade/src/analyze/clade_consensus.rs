@@ -0,0 +1,187 @@
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::PositionLike;
+use crate::make_error;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::Report;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+
+/// Name of the attribute which groups sequences by clade
+pub const CLADE_CONSENSUS_ATTR_CLADE: &str = "clade";
+
+/// Nucleotides counted towards consensus, in the order of columns in the per-site support counts
+pub const CLADE_CONSENSUS_NUCS: [Nuc; 5] = [Nuc::A, Nuc::C, Nuc::G, Nuc::T, Nuc::Gap];
+
+/// Consensus sequence of a group of sequences (e.g. of a clade), aligned to the reference sequence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CladeConsensus {
+  /// Value of the grouping attribute (e.g. clade name)
+  pub group: String,
+  pub num_seqs: usize,
+  pub seq: Vec<Nuc>,
+
+  /// Number of sequences supporting each of `CLADE_CONSENSUS_NUCS`, for each site of the reference sequence
+  pub support: Vec<[usize; 5]>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct GroupCounts {
+  num_seqs: usize,
+  support: Vec<[usize; 5]>,
+}
+
+/// Accumulates per-site nucleotide counts for each group of sequences (by clade or by other clade-like attribute), one
+/// sequence at a time, and computes consensus sequences from them.
+///
+/// Missing sites (ranges of `N` and unsequenced ends) do not count. Other ambiguous nucleotides do not count either, as
+/// they do not support any particular nucleotide.
+#[derive(Clone, Debug)]
+pub struct CladeConsensusBuilder {
+  attr: String,
+  threshold: f64,
+  groups: BTreeMap<String, GroupCounts>,
+}
+
+impl CladeConsensusBuilder {
+  pub fn new(attr: impl AsRef<str>, threshold: f64) -> Result<Self, Report> {
+    if !(threshold > 0.0 && threshold <= 1.0) {
+      return make_error!("Consensus threshold is expected to be in range (0, 1], but got {threshold}");
+    }
+    Ok(Self {
+      attr: attr.as_ref().to_owned(),
+      threshold,
+      groups: BTreeMap::new(),
+    })
+  }
+
+  /// Adds a sequence, aligned to the reference sequence, along with its analysis results. Sequences which do not have
+  /// the grouping attribute are ignored.
+  pub fn add_output(&mut self, aligned_seq: &[Nuc], output: &NextcladeOutputs) {
+    let group = if self.attr == CLADE_CONSENSUS_ATTR_CLADE {
+      output.clade.as_ref()
+    } else {
+      output.custom_node_attributes.get(&self.attr)
+    };
+    let Some(group) = group else {
+      return;
+    };
+
+    let counts = self.groups.entry(group.clone()).or_default();
+    counts.num_seqs += 1;
+    if counts.support.len() < aligned_seq.len() {
+      counts.support.resize(aligned_seq.len(), [0; 5]);
+    }
+
+    let begin = output.alignment_range.begin.as_usize();
+    let end = output.alignment_range.end.as_usize();
+    let mut missing = output.missing.iter().map(|m| m.range.to_std()).peekable();
+    for (pos, nuc) in aligned_seq.iter().enumerate().take(end).skip(begin) {
+      while missing.next_if(|range| range.end <= pos).is_some() {}
+      if missing.peek().is_some_and(|range| range.contains(&pos)) {
+        continue;
+      }
+      if let Some(i) = CLADE_CONSENSUS_NUCS.iter().position(|n| n == nuc) {
+        counts.support[pos][i] += 1;
+      }
+    }
+  }
+
+  pub fn build(&self) -> Vec<CladeConsensus> {
+    self
+      .groups
+      .iter()
+      .map(|(group, counts)| CladeConsensus {
+        group: group.clone(),
+        num_seqs: counts.num_seqs,
+        seq: counts
+          .support
+          .iter()
+          .map(|support| consensus_nuc(support, self.threshold))
+          .collect(),
+        support: counts.support.clone(),
+      })
+      .collect()
+  }
+}
+
+/// Calls consensus nucleotide at a site, given the number of sequences supporting each of `CLADE_CONSENSUS_NUCS`.
+///
+/// Takes the smallest set of the most frequent nucleotides whose combined frequency reaches the threshold, along with
+/// the nucleotides which are as frequent as the least frequent one in the set. A single nucleotide is called as is,
+/// multiple nucleotides as the corresponding IUPAC ambiguity code. A gap is called only if it is the only nucleotide in
+/// the set, otherwise it is discarded. Sites without support are called as `N`.
+pub fn consensus_nuc(support: &[usize; 5], threshold: f64) -> Nuc {
+  let total: usize = support.iter().sum();
+  if total == 0 {
+    return Nuc::N;
+  }
+
+  let sorted = CLADE_CONSENSUS_NUCS
+    .iter()
+    .zip(support)
+    .filter(|(_, count)| **count > 0)
+    .sorted_by(|(_, count1), (_, count2)| count2.cmp(count1))
+    .collect_vec();
+
+  let mut called = vec![];
+  let mut cumulative = 0;
+  for (nuc, count) in sorted {
+    let is_tie = called.last().is_some_and(|(_, last)| last == &count);
+    if !called.is_empty() && !is_tie && cumulative as f64 >= threshold * total as f64 {
+      break;
+    }
+    cumulative += count;
+    called.push((*nuc, count));
+  }
+
+  let nucs = called
+    .into_iter()
+    .map(|(nuc, _)| nuc)
+    .filter(|nuc| nuc != &Nuc::Gap)
+    .collect_vec();
+  match nucs.as_slice() {
+    [] => Nuc::Gap,
+    [nuc] => *nuc,
+    nucs => ambiguous_nuc(nucs),
+  }
+}
+
+/// Finds IUPAC ambiguity code for a set of canonical nucleotides
+fn ambiguous_nuc(nucs: &[Nuc]) -> Nuc {
+  let has = |nuc: Nuc| nucs.contains(&nuc);
+  match (has(Nuc::A), has(Nuc::C), has(Nuc::G), has(Nuc::T)) {
+    (true, false, false, false) => Nuc::A,
+    (false, true, false, false) => Nuc::C,
+    (false, false, true, false) => Nuc::G,
+    (false, false, false, true) => Nuc::T,
+    (true, false, false, true) => Nuc::W,
+    (false, true, true, false) => Nuc::S,
+    (true, true, false, false) => Nuc::M,
+    (false, false, true, true) => Nuc::K,
+    (true, false, true, false) => Nuc::R,
+    (false, true, false, true) => Nuc::Y,
+    (false, true, true, true) => Nuc::B,
+    (true, false, true, true) => Nuc::D,
+    (true, true, false, true) => Nuc::H,
+    (true, true, true, false) => Nuc::V,
+    _ => Nuc::N,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn calls_consensus_nucleotides() {
+    assert_eq!(consensus_nuc(&[0, 0, 0, 0, 0], 0.5), Nuc::N);
+    assert_eq!(consensus_nuc(&[7, 0, 3, 0, 0], 0.5), Nuc::A);
+    assert_eq!(consensus_nuc(&[7, 0, 3, 0, 0], 0.75), Nuc::R);
+    assert_eq!(consensus_nuc(&[5, 0, 5, 0, 0], 0.5), Nuc::R);
+    assert_eq!(consensus_nuc(&[4, 3, 3, 0, 0], 0.5), Nuc::V);
+    assert_eq!(consensus_nuc(&[1, 0, 0, 0, 9], 0.5), Nuc::Gap);
+    assert_eq!(consensus_nuc(&[4, 0, 0, 0, 6], 0.75), Nuc::A);
+    assert_eq!(consensus_nuc(&[1, 1, 1, 1, 0], 1.0), Nuc::N);
+  }
+}
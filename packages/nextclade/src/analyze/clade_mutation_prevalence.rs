@@ -0,0 +1,329 @@
+use crate::analyze::find_clade_founder::CladeNodeAttrFounderInfo;
+use crate::analyze::private_mutations::PrivateMutations;
+use crate::coord::position::PositionLike;
+use crate::io::nextclade_csv_row::{format_aa_insertions, format_nuc_insertions};
+use crate::types::outputs::NextcladeOutputs;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::slice::from_ref;
+use strum_macros::Display;
+
+/// Kind of mutation. The order of variants defines the order of mutations in the outputs.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[strum(serialize_all = "camelCase")]
+pub enum CladeMutationKind {
+  NucSub,
+  NucDel,
+  NucIns,
+  AaSub,
+  AaDel,
+  AaIns,
+}
+
+/// Prevalence of one mutation among sequences of a clade
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CladeMutationPrevalence {
+  #[serde(rename = "type")]
+  pub kind: CladeMutationKind,
+
+  /// Mutation in the same notation as in the results table
+  pub mutation: String,
+
+  /// Name of the CDS, for amino acid mutations
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub cds: Option<String>,
+
+  /// 1-based position: nucleotide position in the reference, or codon position in the CDS. For ranges, the beginning
+  /// of the range.
+  pub pos: usize,
+
+  /// Number of sequences of the clade which have this mutation
+  pub count: usize,
+
+  /// Fraction of sequences of the clade which have this mutation
+  pub frequency: f64,
+
+  /// Number of sequences in which this mutation is private relative to the clade founder node, i.e. has emerged
+  /// within the clade. Not determined for insertions.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub count_private: Option<usize>,
+
+  /// Number of sequences in which this mutation is inherited from the clade founder node, i.e. is clade-defining.
+  /// Not determined for insertions.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub count_clade_defining: Option<usize>,
+}
+
+/// Prevalence of mutations among sequences of a clade
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CladeMutations {
+  pub clade: String,
+  pub num_seqs: usize,
+  pub mutations: Vec<CladeMutationPrevalence>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct MutationKey {
+  kind: CladeMutationKind,
+  cds: Option<String>,
+  pos: usize,
+  mutation: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct MutationCounts {
+  count: usize,
+  count_private: usize,
+  count_clade_defining: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct CladeCounts {
+  num_seqs: usize,
+  mutations: BTreeMap<MutationKey, MutationCounts>,
+}
+
+/// Accumulates prevalence of mutations in each clade, one sequence at a time. Sequences without clade are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct CladeMutationPrevalenceBuilder {
+  clades: BTreeMap<String, CladeCounts>,
+}
+
+impl CladeMutationPrevalenceBuilder {
+  pub fn add_output(&mut self, output: &NextcladeOutputs) {
+    let Some(clade) = &output.clade else {
+      return;
+    };
+
+    let clade_counts = self.clades.entry(clade.clone()).or_default();
+    clade_counts.num_seqs += 1;
+
+    for (key, is_private) in find_mutations(output, output.clade_founder_info.as_ref()) {
+      let counts = clade_counts.mutations.entry(key).or_default();
+      counts.count += 1;
+      match is_private {
+        Some(true) => counts.count_private += 1,
+        Some(false) => counts.count_clade_defining += 1,
+        None => {}
+      }
+    }
+  }
+
+  pub fn build(&self) -> Vec<CladeMutations> {
+    self
+      .clades
+      .iter()
+      .map(|(clade, counts)| CladeMutations {
+        clade: clade.clone(),
+        num_seqs: counts.num_seqs,
+        mutations: counts
+          .mutations
+          .iter()
+          .map(|(key, mutation_counts)| {
+            let is_determined = !matches!(key.kind, CladeMutationKind::NucIns | CladeMutationKind::AaIns);
+            CladeMutationPrevalence {
+              kind: key.kind,
+              mutation: key.mutation.clone(),
+              cds: key.cds.clone(),
+              pos: key.pos,
+              count: mutation_counts.count,
+              frequency: mutation_counts.count as f64 / counts.num_seqs as f64,
+              count_private: is_determined.then_some(mutation_counts.count_private),
+              count_clade_defining: is_determined.then_some(mutation_counts.count_clade_defining),
+            }
+          })
+          .collect(),
+      })
+      .collect()
+  }
+}
+
+/// Lists mutations of a sequence, along with whether each of them is private relative to the clade founder node.
+/// Privateness is unknown (`None`) for insertions, as well as when the clade founder is not known.
+fn find_mutations(
+  output: &NextcladeOutputs,
+  founder: Option<&CladeNodeAttrFounderInfo>,
+) -> Vec<(MutationKey, Option<bool>)> {
+  let founder = founder.map(PrivateMutations::from_founder);
+
+  let nuc_subs = output.substitutions.iter().map(|sub| {
+    let key = MutationKey {
+      kind: CladeMutationKind::NucSub,
+      cds: None,
+      pos: sub.pos.as_usize() + 1,
+      mutation: sub.to_string(),
+    };
+    let is_private = founder.map(|founder| founder.is_private_nuc_sub(sub));
+    (key, is_private)
+  });
+
+  let nuc_dels = output.deletions.iter().map(|del| {
+    let range = del.range();
+    let key = MutationKey {
+      kind: CladeMutationKind::NucDel,
+      cds: None,
+      pos: range.begin.as_usize() + 1,
+      mutation: range.to_string(),
+    };
+    let is_private = founder.map(|founder| founder.is_private_nuc_del(del));
+    (key, is_private)
+  });
+
+  let nuc_ins = output.insertions.iter().map(|ins| {
+    let key = MutationKey {
+      kind: CladeMutationKind::NucIns,
+      cds: None,
+      pos: (ins.pos + 1) as usize,
+      mutation: format_nuc_insertions(from_ref(ins), ""),
+    };
+    (key, None)
+  });
+
+  let aa_subs = output.aa_substitutions.iter().map(|sub| {
+    let key = MutationKey {
+      kind: CladeMutationKind::AaSub,
+      cds: Some(sub.cds_name.clone()),
+      pos: sub.pos.as_usize() + 1,
+      mutation: sub.to_string(),
+    };
+    let is_private = founder.map(|founder| founder.is_private_aa_sub(sub));
+    (key, is_private)
+  });
+
+  let aa_dels = output.aa_deletions.iter().map(|del| {
+    let key = MutationKey {
+      kind: CladeMutationKind::AaDel,
+      cds: Some(del.cds_name.clone()),
+      pos: del.pos.as_usize() + 1,
+      mutation: del.to_string(),
+    };
+    let is_private = founder.map(|founder| founder.is_private_aa_del(del));
+    (key, is_private)
+  });
+
+  let aa_ins = output.aa_insertions.iter().map(|ins| {
+    let key = MutationKey {
+      kind: CladeMutationKind::AaIns,
+      cds: Some(ins.cds.clone()),
+      pos: (ins.pos + 1) as usize,
+      mutation: format_aa_insertions(from_ref(ins), ""),
+    };
+    (key, None)
+  });
+
+  nuc_subs
+    .chain(nuc_dels)
+    .chain(nuc_ins)
+    .chain(aa_subs)
+    .chain(aa_dels)
+    .chain(aa_ins)
+    .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::align::insertions_strip::Insertion;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
+  use crate::analyze::nuc_sub::NucSub;
+  use crate::graph::node::GraphNodeKey;
+  use crate::o;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use std::str::FromStr;
+
+  fn founder(private_substitutions: &[&str]) -> Result<CladeNodeAttrFounderInfo, Report> {
+    Ok(CladeNodeAttrFounderInfo {
+      key: o!("clade"),
+      value: o!("20A"),
+      node_key: GraphNodeKey::default(),
+      node_name: o!("founder"),
+      nuc_mutations: PrivateNucMutations {
+        private_substitutions: private_substitutions
+          .iter()
+          .map(|sub| NucSub::from_str(sub))
+          .collect::<Result<_, _>>()?,
+        ..PrivateNucMutations::default()
+      },
+      aa_mutations: BTreeMap::new(),
+    })
+  }
+
+  fn output(
+    clade: Option<&str>,
+    substitutions: &[&str],
+    founder: Option<CladeNodeAttrFounderInfo>,
+  ) -> Result<NextcladeOutputs, Report> {
+    Ok(NextcladeOutputs {
+      clade: clade.map(str::to_owned),
+      substitutions: substitutions
+        .iter()
+        .map(|sub| NucSub::from_str(sub))
+        .collect::<Result<_, _>>()?,
+      clade_founder_info: founder,
+      ..NextcladeOutputs::default()
+    })
+  }
+
+  #[test]
+  fn splits_mutations_into_private_and_clade_defining() -> Result<(), Report> {
+    let mut builder = CladeMutationPrevalenceBuilder::default();
+
+    // Founder has G at position 20, so the private mutation starts from G rather than from the reference letter
+    builder.add_output(&output(Some("20A"), &["A10T", "A20C"], Some(founder(&["G20C"])?))?);
+    builder.add_output(&output(Some("20A"), &["A10T", "A30G"], Some(founder(&["A30G"])?))?);
+
+    // Privateness is unknown without founder, and is not determined for insertions
+    let mut without_founder = output(Some("20A"), &["A10T"], None)?;
+    without_founder.insertions = vec![Insertion {
+      pos: 3,
+      ins: to_nuc_seq("CC")?,
+    }];
+    builder.add_output(&without_founder);
+
+    // Sequences without clade are ignored
+    builder.add_output(&output(None, &["A10T"], None)?);
+
+    let sub = |mutation: &str, pos: usize, count: usize, count_private: usize, count_clade_defining: usize| {
+      CladeMutationPrevalence {
+        kind: CladeMutationKind::NucSub,
+        mutation: mutation.to_owned(),
+        cds: None,
+        pos,
+        count,
+        frequency: count as f64 / 3.0,
+        count_private: Some(count_private),
+        count_clade_defining: Some(count_clade_defining),
+      }
+    };
+
+    assert_eq!(
+      builder.build(),
+      vec![CladeMutations {
+        clade: o!("20A"),
+        num_seqs: 3,
+        mutations: vec![
+          sub("A10T", 10, 3, 0, 2),
+          sub("A20C", 20, 1, 1, 0),
+          sub("A30G", 30, 1, 1, 0),
+          CladeMutationPrevalence {
+            kind: CladeMutationKind::NucIns,
+            mutation: o!("4:CC"),
+            cds: None,
+            pos: 4,
+            count: 1,
+            frequency: 1.0 / 3.0,
+            count_private: None,
+            count_clade_defining: None,
+          },
+        ],
+      }]
+    );
+    Ok(())
+  }
+}
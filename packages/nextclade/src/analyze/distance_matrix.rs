@@ -0,0 +1,242 @@
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::letter_ranges::NucRange;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
+use crate::coord::range::NucRefGlobalRange;
+use crate::types::outputs::NextcladeOutputs;
+use itertools::{EitherOrBoth, Itertools};
+use std::collections::BTreeMap;
+
+/// Parameters of pairwise distance calculation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DistanceMatrixParams {
+  /// Count sites which are missing (`N` or not sequenced) in only one of the sequences as differences
+  pub count_missing: bool,
+
+  /// Do not count sites which contain an ambiguous nucleotide in either of the sequences
+  pub ignore_ambiguous: bool,
+}
+
+/// Query sequence, represented by its differences from the reference sequence
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DistanceMatrixSeq {
+  pub seq_name: String,
+
+  /// Sites where the sequence differs from the reference (substitutions, deletions and ambiguous nucleotides), except
+  /// for missing sites
+  pub changes: BTreeMap<NucRefGlobalPosition, Nuc>,
+
+  /// Sorted, non-overlapping ranges of missing sites, including unsequenced parts at the ends
+  pub missing: Vec<NucRefGlobalRange>,
+}
+
+impl DistanceMatrixSeq {
+  pub fn from_outputs(output: &NextcladeOutputs, ref_seq_len: usize) -> Self {
+    Self::new(
+      &output.seq_name,
+      &output.substitutions,
+      &output.deletions,
+      &output.missing,
+      &output.non_acgtns,
+      &output.alignment_range,
+      ref_seq_len,
+    )
+  }
+
+  pub fn new(
+    seq_name: &str,
+    substitutions: &[NucSub],
+    deletions: &[NucDelRange],
+    missing: &[NucRange],
+    non_acgtns: &[NucRange],
+    alignment_range: &NucRefGlobalRange,
+    ref_seq_len: usize,
+  ) -> Self {
+    let mut changes = BTreeMap::new();
+    for sub in substitutions {
+      changes.insert(sub.pos, sub.qry_nuc);
+    }
+    for del in deletions {
+      changes.extend(del.range().iter().map(|pos| (pos, Nuc::Gap)));
+    }
+    for non_acgtn in non_acgtns {
+      changes.extend(non_acgtn.range.iter().map(|pos| (pos, non_acgtn.letter)));
+    }
+
+    let missing = [
+      NucRefGlobalRange::from_usize(0, alignment_range.begin.as_usize()),
+      NucRefGlobalRange::from_usize(
+        alignment_range.end.as_usize(),
+        ref_seq_len.max(alignment_range.end.as_usize()),
+      ),
+    ]
+    .into_iter()
+    .chain(missing.iter().map(|missing| missing.range.clone()))
+    .filter(|range| !range.is_empty())
+    .sorted_by_key(|range| range.begin)
+    .collect();
+
+    Self {
+      seq_name: seq_name.to_owned(),
+      changes,
+      missing,
+    }
+  }
+
+  fn is_missing(&self, pos: NucRefGlobalPosition) -> bool {
+    let i = self.missing.partition_point(|range| range.end <= pos);
+    self.missing.get(i).is_some_and(|range| range.contains(pos))
+  }
+
+  fn num_missing(&self) -> usize {
+    self.missing.iter().map(NucRefGlobalRange::len).sum()
+  }
+}
+
+/// Calculates number of sites where two query sequences differ, as in the pairwise comparison of aligned sequences.
+///
+/// Sites which are missing (`N` or not sequenced) in either of the sequences are not counted, unless
+/// `params.count_missing` is set. Then a missing site differs from any other character, except for another missing site.
+pub fn calculate_distance(a: &DistanceMatrixSeq, b: &DistanceMatrixSeq, params: DistanceMatrixParams) -> usize {
+  let is_ambiguous = |nuc: &Nuc| !nuc.is_acgt() && !nuc.is_gap();
+
+  let num_changed = a
+    .changes
+    .iter()
+    .merge_join_by(&b.changes, |(pos_a, _), (pos_b, _)| pos_a.cmp(pos_b))
+    .filter(|change| {
+      let (pos, nuc_a, nuc_b) = match change {
+        EitherOrBoth::Both((pos, nuc_a), (_, nuc_b)) => (**pos, Some(*nuc_a), Some(*nuc_b)),
+        EitherOrBoth::Left((pos, nuc_a)) => (**pos, Some(*nuc_a), None),
+        EitherOrBoth::Right((pos, nuc_b)) => (**pos, None, Some(*nuc_b)),
+      };
+      // Differences with missing sites are counted separately
+      if a.is_missing(pos) || b.is_missing(pos) {
+        return false;
+      }
+      if params.ignore_ambiguous && (nuc_a.is_some_and(is_ambiguous) || nuc_b.is_some_and(is_ambiguous)) {
+        return false;
+      }
+      // A site is either changed in both sequences, or one of the sequences has the reference nucleotide
+      nuc_a != nuc_b
+    })
+    .count();
+
+  let num_missing = if params.count_missing {
+    a.num_missing() + b.num_missing() - 2 * intersection_len(&a.missing, &b.missing)
+  } else {
+    0
+  };
+
+  num_changed + num_missing
+}
+
+/// Calculates distances between all pairs of query sequences. The result is a symmetric matrix.
+pub fn calculate_distance_matrix(seqs: &[DistanceMatrixSeq], params: DistanceMatrixParams) -> Vec<Vec<usize>> {
+  let mut matrix = vec![vec![0; seqs.len()]; seqs.len()];
+  for (i, a) in seqs.iter().enumerate() {
+    for (j, b) in seqs.iter().enumerate().skip(i + 1) {
+      let distance = calculate_distance(a, b, params);
+      matrix[i][j] = distance;
+      matrix[j][i] = distance;
+    }
+  }
+  matrix
+}
+
+/// Calculates total length of the intersection of two sorted lists of non-overlapping ranges
+fn intersection_len(a: &[NucRefGlobalRange], b: &[NucRefGlobalRange]) -> usize {
+  let (mut i, mut j) = (0, 0);
+  let mut len = 0;
+  while let (Some(range_a), Some(range_b)) = (a.get(i), b.get(j)) {
+    let begin = range_a.begin.max(range_b.begin);
+    let end = range_a.end.min(range_b.end);
+    len += end.as_usize().saturating_sub(begin.as_usize());
+    if range_a.end < range_b.end {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  len
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use std::str::FromStr;
+
+  fn seq(
+    subs: &[&str],
+    dels: &[(usize, usize)],
+    missing: &[(usize, usize)],
+    ambiguous: &[(usize, Nuc)],
+  ) -> Result<DistanceMatrixSeq, Report> {
+    let to_ranges = |ranges: &[(usize, usize)], letter: Nuc| {
+      ranges
+        .iter()
+        .map(|(begin, end)| NucRange {
+          range: NucRefGlobalRange::from_usize(*begin, *end),
+          letter,
+        })
+        .collect_vec()
+    };
+    Ok(DistanceMatrixSeq::new(
+      "seq",
+      &subs
+        .iter()
+        .map(|sub| NucSub::from_str(sub))
+        .try_collect::<_, Vec<_>, _>()?,
+      &dels
+        .iter()
+        .map(|(begin, end)| NucDelRange::from_usize(*begin, *end))
+        .collect_vec(),
+      &to_ranges(missing, Nuc::N),
+      &ambiguous
+        .iter()
+        .flat_map(|(pos, letter)| to_ranges(&[(*pos, pos + 1)], *letter))
+        .collect_vec(),
+      &NucRefGlobalRange::from_usize(0, 20),
+      20,
+    ))
+  }
+
+  #[test]
+  fn counts_substitutions_and_deletions() -> Result<(), Report> {
+    let a = seq(&["A1C", "A2C"], &[(10, 12)], &[], &[])?;
+    let b = seq(&["A1C", "A2G", "A3C"], &[(11, 13)], &[], &[])?;
+    // Differences at 0-based positions: 1, 2 (substitutions), 10, 12 (deletions)
+    assert_eq!(calculate_distance(&a, &b, DistanceMatrixParams::default()), 4);
+    assert_eq!(
+      calculate_distance_matrix(&[a.clone(), b, a], DistanceMatrixParams::default()),
+      vec![vec![0, 4, 0], vec![4, 0, 4], vec![0, 4, 0]]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn optionally_counts_missing_and_ignores_ambiguous_sites() -> Result<(), Report> {
+    let a = seq(&["A1C"], &[], &[(2, 6)], &[(8, Nuc::R)])?;
+    let b = seq(&["A3C"], &[], &[(4, 7)], &[])?;
+
+    // Differences at 0-based positions: 0 (substitution), 2, 3, 6 (missing in one of the sequences), 8 (ambiguous)
+    let params = DistanceMatrixParams {
+      count_missing: true,
+      ignore_ambiguous: false,
+    };
+    assert_eq!(calculate_distance(&a, &b, params), 5);
+
+    assert_eq!(calculate_distance(&a, &b, DistanceMatrixParams::default()), 2);
+
+    let params = DistanceMatrixParams {
+      count_missing: false,
+      ignore_ambiguous: true,
+    };
+    assert_eq!(calculate_distance(&a, &b, params), 1);
+    Ok(())
+  }
+}
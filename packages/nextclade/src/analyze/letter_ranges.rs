@@ -133,3 +133,37 @@ pub fn find_aa_letter_ranges(translation: &Translation, letter: Aa) -> Vec<CdsAa
     })
     .collect()
 }
+
+/// Finds contiguous ranges of unknown amino acids (`X`). Ambiguous amino acids are excluded from these ranges, because
+/// they are reported separately (see `CdsTranslation::ambiguous_aas`, which is sorted by position).
+pub fn find_unknown_aa_ranges(translation: &Translation) -> Vec<CdsAaRange> {
+  translation
+    .cdses()
+    .filter_map(|cds_tr| {
+      let mut ambiguous_positions = cds_tr.ambiguous_aas.iter().map(|amb| amb.pos.as_usize()).peekable();
+
+      let mut ranges: Vec<AaRange> = vec![];
+      for (pos, aa) in cds_tr.seq.iter().enumerate() {
+        while ambiguous_positions.next_if(|amb_pos| *amb_pos < pos).is_some() {}
+        if *aa != Aa::X || ambiguous_positions.peek() == Some(&pos) {
+          continue;
+        }
+        match ranges.last_mut() {
+          Some(last) if last.range.end.as_usize() == pos => last.range.end = AaRefPosition::from(pos + 1),
+          _ => ranges.push(AaRange {
+            range: Range::from_usize(pos, pos + 1),
+            letter: Aa::X,
+          }),
+        }
+      }
+
+      let length = ranges.iter().map(LetterRange::len).sum();
+      (length > 0).then(|| CdsAaRange {
+        cds_name: cds_tr.name.clone(),
+        letter: Aa::X,
+        ranges,
+        length,
+      })
+    })
+    .collect()
+}
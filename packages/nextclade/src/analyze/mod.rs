@@ -7,7 +7,10 @@ pub mod aa_del;
 pub mod aa_sub;
 pub mod aa_sub_min;
 pub mod abstract_mutation;
+pub mod clade_consensus;
+pub mod clade_mutation_prevalence;
 pub mod count_gaps;
+pub mod distance_matrix;
 pub mod divergence;
 pub mod find_aa_motifs;
 pub mod find_aa_motifs_changes;
@@ -27,4 +30,7 @@ pub mod nuc_sub;
 pub mod pcr_primer_changes;
 pub mod pcr_primers;
 pub mod phenotype;
+pub mod private_mutations;
+pub mod run_summary;
+pub mod site_coverage;
 pub mod virus_properties;
@@ -0,0 +1,162 @@
+use crate::analyze::aa_del::AaDel;
+use crate::analyze::aa_sub::AaSub;
+use crate::analyze::find_clade_founder::CladeNodeAttrFounderInfo;
+use crate::analyze::find_private_aa_mutations::PrivateAaMutations;
+use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use std::collections::BTreeMap;
+
+/// Private mutations of a sequence relative to some node of the reference tree
+#[derive(Clone, Copy, Debug)]
+pub struct PrivateMutations<'a> {
+  pub nuc: &'a PrivateNucMutations,
+  pub aa: &'a BTreeMap<String, PrivateAaMutations>,
+}
+
+impl<'a> PrivateMutations<'a> {
+  pub const fn from_founder(founder: &'a CladeNodeAttrFounderInfo) -> Self {
+    Self {
+      nuc: &founder.nuc_mutations,
+      aa: &founder.aa_mutations,
+    }
+  }
+
+  /// Substitution is matched by position and query letter only: when the node is mutated at the same site, the private
+  /// substitution starts from the letter of the node rather than from the reference letter
+  pub fn is_private_nuc_sub(&self, sub: &NucSub) -> bool {
+    self
+      .nuc
+      .private_substitutions
+      .iter()
+      .any(|private| is_same_nuc_sub(private, sub))
+  }
+
+  /// Deletion range is private only if each of its positions is a private deletion
+  pub fn is_private_nuc_del(&self, del: &NucDelRange) -> bool {
+    del
+      .range()
+      .iter()
+      .all(|pos| self.nuc.private_deletions.iter().any(|private| private.pos == pos))
+  }
+
+  /// Substitution is matched by CDS, position and query letter only, same as for nucleotides
+  pub fn is_private_aa_sub(&self, sub: &AaSub) -> bool {
+    self.aa.get(&sub.cds_name).is_some_and(|muts| {
+      muts
+        .private_substitutions
+        .iter()
+        .any(|private| is_same_aa_sub(private, sub))
+    })
+  }
+
+  /// Deletion is matched by CDS and position only
+  pub fn is_private_aa_del(&self, del: &AaDel) -> bool {
+    self.aa.get(&del.cds_name).is_some_and(|muts| {
+      muts
+        .private_deletions
+        .iter()
+        .any(|private| private.cds_name == del.cds_name && private.pos == del.pos)
+    })
+  }
+}
+
+/// Whether two nucleotide substitutions lead to the same letter at the same position, regardless of the letter they
+/// start from
+pub fn is_same_nuc_sub(left: &NucSub, right: &NucSub) -> bool {
+  left.pos == right.pos && left.qry_nuc == right.qry_nuc
+}
+
+/// Whether two amino acid substitutions lead to the same letter at the same position of the same CDS, regardless of
+/// the letter they start from
+pub fn is_same_aa_sub(left: &AaSub, right: &AaSub) -> bool {
+  left.cds_name == right.cds_name && left.pos == right.pos && left.qry_aa == right.qry_aa
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::Aa;
+  use crate::alphabet::nuc::Nuc;
+  use crate::analyze::nuc_del::NucDel;
+  use crate::coord::position::{AaRefPosition, NucRefGlobalPosition};
+  use crate::o;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use std::str::FromStr;
+
+  #[test]
+  fn determines_whether_mutations_are_private() -> Result<(), Report> {
+    let nuc = PrivateNucMutations {
+      private_substitutions: vec![NucSub::from_str("A10C")?],
+      private_deletions: (20..23)
+        .map(|pos| NucDel {
+          pos: NucRefGlobalPosition::from(pos),
+          ref_nuc: Nuc::A,
+        })
+        .collect(),
+      ..PrivateNucMutations::default()
+    };
+    let aa = BTreeMap::from([(
+      o!("S"),
+      PrivateAaMutations {
+        cds_name: o!("S"),
+        private_substitutions: vec![AaSub::from_str("S:N501Y")?],
+        ..PrivateAaMutations::default()
+      },
+    )]);
+    let muts = PrivateMutations { nuc: &nuc, aa: &aa };
+
+    assert_eq!(
+      [
+        muts.is_private_nuc_sub(&NucSub::from_str("A10C")?),
+        muts.is_private_nuc_sub(&NucSub::from_str("A11C")?),
+        muts.is_private_nuc_del(&NucDelRange::from_usize(20, 23)),
+        muts.is_private_nuc_del(&NucDelRange::from_usize(21, 25)),
+        muts.is_private_aa_sub(&AaSub::from_str("S:N501Y")?),
+        muts.is_private_aa_sub(&AaSub::from_str("ORF1a:N501Y")?),
+      ],
+      [true, false, true, false, true, false]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn matches_private_mutations_from_letter_of_node() -> Result<(), Report> {
+    // Node has A10G and N501T, query has A10C and N501Y: private mutations start from the letters of the node
+    let nuc = PrivateNucMutations {
+      private_substitutions: vec![NucSub::from_str("G10C")?],
+      ..PrivateNucMutations::default()
+    };
+    let aa = BTreeMap::from([(
+      o!("S"),
+      PrivateAaMutations {
+        cds_name: o!("S"),
+        private_substitutions: vec![AaSub::from_str("S:T501Y")?],
+        private_deletions: vec![AaDel {
+          cds_name: o!("S"),
+          pos: AaRefPosition::from(68),
+          ref_aa: Aa::T,
+        }],
+        ..PrivateAaMutations::default()
+      },
+    )]);
+    let muts = PrivateMutations { nuc: &nuc, aa: &aa };
+
+    assert_eq!(
+      [
+        muts.is_private_nuc_sub(&NucSub::from_str("A10C")?),
+        muts.is_private_nuc_sub(&NucSub::from_str("A10G")?),
+        muts.is_private_aa_sub(&AaSub::from_str("S:N501Y")?),
+        muts.is_private_aa_sub(&AaSub::from_str("S:N501T")?),
+        muts.is_private_aa_del(&AaDel {
+          cds_name: o!("S"),
+          pos: AaRefPosition::from(68),
+          ref_aa: Aa::H,
+        }),
+      ],
+      [true, false, true, false, true]
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,393 @@
+use crate::qc::qc_run::QcStatus;
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::datetime::date_iso_now;
+use crate::utils::info::this_package_version_str;
+use eyre::Report;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Maximum number of entries in the lists of most frequent mutations and hotspots
+pub const RUN_SUMMARY_MAX_TOP_ENTRIES: usize = 20;
+
+/// Number of equal-width bins in coverage histograms
+pub const RUN_SUMMARY_COVERAGE_BINS: usize = 10;
+
+/// Maximum number of sequence names listed as examples for each error category
+const MAX_ERROR_EXAMPLES: usize = 3;
+
+/// Aggregate statistics over all sequences of a run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummary {
+  pub nextclade_algo_version: String,
+  pub created_at: String,
+  pub total_sequences: usize,
+  pub total_analyzed: usize,
+  pub total_errors: usize,
+
+  /// Frequencies of clades, most frequent first
+  pub clades: Vec<RunSummaryFrequency>,
+
+  /// Frequencies of values of other clade-like attributes (e.g. lineages), by attribute name
+  pub clade_node_attrs: BTreeMap<String, Vec<RunSummaryFrequency>>,
+
+  /// Number of sequences in each QC status, overall and for each QC rule
+  pub qc: RunSummaryQc,
+
+  /// Most frequent nucleotide substitutions
+  pub top_nuc_substitutions: Vec<RunSummaryFrequency>,
+
+  /// Most frequent amino acid substitutions
+  pub top_aa_substitutions: Vec<RunSummaryFrequency>,
+
+  /// Histograms of coverage, by CDS
+  pub cds_coverage: BTreeMap<String, RunSummaryHistogram>,
+
+  /// Most frequent frame shifts
+  pub frame_shift_hotspots: Vec<RunSummaryFrequency>,
+
+  /// Most frequent premature stop codons, as reported by the QC
+  pub stop_codon_hotspots: Vec<RunSummaryFrequency>,
+
+  /// Sequences which failed to be analyzed, grouped by error category, most frequent first
+  pub errors: Vec<RunSummaryErrorCategory>,
+}
+
+/// Number of sequences with a given value (clade, mutation etc.). Fraction is relative to the number of analyzed
+/// sequences.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummaryFrequency {
+  pub value: String,
+  pub count: usize,
+  pub fraction: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummaryQcStatusCounts {
+  pub good: usize,
+  pub mediocre: usize,
+  pub bad: usize,
+}
+
+impl RunSummaryQcStatusCounts {
+  const fn add(&mut self, status: &QcStatus) {
+    match status {
+      QcStatus::Good => self.good += 1,
+      QcStatus::Mediocre => self.mediocre += 1,
+      QcStatus::Bad => self.bad += 1,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummaryQc {
+  pub overall: RunSummaryQcStatusCounts,
+
+  /// Status counts for each QC rule. Only sequences for which the rule was run are counted.
+  pub rules: BTreeMap<String, RunSummaryQcStatusCounts>,
+}
+
+/// Histogram of values in the range from 0 to 1, in equal-width bins. The last bin includes 1.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummaryHistogram {
+  pub counts: Vec<usize>,
+  pub mean: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RunSummaryErrorCategory {
+  pub category: String,
+  pub count: usize,
+  pub examples: Vec<String>,
+}
+
+/// Accumulates statistics of a run one sequence at a time, so that the results do not need to be kept in memory
+#[derive(Clone, Debug, Default)]
+pub struct RunSummaryBuilder {
+  total_analyzed: usize,
+  clades: BTreeMap<String, usize>,
+  clade_node_attrs: BTreeMap<String, BTreeMap<String, usize>>,
+  qc: RunSummaryQc,
+  nuc_substitutions: BTreeMap<String, usize>,
+  aa_substitutions: BTreeMap<String, usize>,
+  cds_coverage: BTreeMap<String, HistogramBuilder>,
+  frame_shifts: BTreeMap<String, usize>,
+  stop_codons: BTreeMap<String, usize>,
+  errors: BTreeMap<String, (usize, Vec<String>)>,
+}
+
+impl RunSummaryBuilder {
+  pub fn add_output(&mut self, output: &NextcladeOutputs) {
+    self.total_analyzed += 1;
+
+    if let Some(clade) = &output.clade {
+      *self.clades.entry(clade.clone()).or_default() += 1;
+    }
+
+    for (attr, value) in &output.custom_node_attributes {
+      *self
+        .clade_node_attrs
+        .entry(attr.clone())
+        .or_default()
+        .entry(value.clone())
+        .or_default() += 1;
+    }
+
+    let qc = &output.qc;
+    self.qc.overall.add(&qc.overall_status);
+    let rules = [
+      ("missingData", qc.missing_data.as_ref().map(|r| &r.status)),
+      ("mixedSites", qc.mixed_sites.as_ref().map(|r| &r.status)),
+      ("privateMutations", qc.private_mutations.as_ref().map(|r| &r.status)),
+      ("snpClusters", qc.snp_clusters.as_ref().map(|r| &r.status)),
+      ("frameShifts", qc.frame_shifts.as_ref().map(|r| &r.status)),
+      ("stopCodons", qc.stop_codons.as_ref().map(|r| &r.status)),
+    ];
+    for (rule, status) in rules {
+      if let Some(status) = status {
+        self.qc.rules.entry(rule.to_owned()).or_default().add(status);
+      }
+    }
+
+    for sub in &output.substitutions {
+      *self.nuc_substitutions.entry(sub.to_string()).or_default() += 1;
+    }
+
+    for sub in &output.aa_substitutions {
+      *self.aa_substitutions.entry(sub.to_string()).or_default() += 1;
+    }
+
+    for (cds, coverage) in &output.cds_coverage {
+      self.cds_coverage.entry(cds.clone()).or_default().add(*coverage);
+    }
+
+    for frame_shift in &output.frame_shifts {
+      let key = format!("{}:{}", frame_shift.cds_name, frame_shift.codon);
+      *self.frame_shifts.entry(key).or_default() += 1;
+    }
+
+    for stop_codon in qc.stop_codons.iter().flat_map(|r| &r.stop_codons) {
+      let key = format!("{}:{}", stop_codon.cds_name, stop_codon.codon + 1);
+      *self.stop_codons.entry(key).or_default() += 1;
+    }
+  }
+
+  pub fn add_error(&mut self, seq_name: &str, error: &Report) {
+    let category = error_category(&error.root_cause().to_string());
+    let (count, examples) = self.errors.entry(category).or_default();
+    *count += 1;
+    if examples.len() < MAX_ERROR_EXAMPLES {
+      examples.push(seq_name.to_owned());
+    }
+  }
+
+  pub fn build(&self) -> RunSummary {
+    let total_errors = self.errors.values().map(|(count, _)| count).sum();
+    let frequencies = |counts: &BTreeMap<String, usize>, limit: Option<usize>| {
+      let frequencies = counts
+        .iter()
+        .sorted_by(|(value1, count1), (value2, count2)| count2.cmp(count1).then_with(|| value1.cmp(value2)))
+        .map(|(value, count)| RunSummaryFrequency {
+          value: value.clone(),
+          count: *count,
+          fraction: *count as f64 / self.total_analyzed.max(1) as f64,
+        });
+      match limit {
+        Some(limit) => frequencies.take(limit).collect_vec(),
+        None => frequencies.collect_vec(),
+      }
+    };
+    let top = Some(RUN_SUMMARY_MAX_TOP_ENTRIES);
+
+    RunSummary {
+      nextclade_algo_version: this_package_version_str().to_owned(),
+      created_at: date_iso_now(),
+      total_sequences: self.total_analyzed + total_errors,
+      total_analyzed: self.total_analyzed,
+      total_errors,
+      clades: frequencies(&self.clades, None),
+      clade_node_attrs: self
+        .clade_node_attrs
+        .iter()
+        .map(|(attr, counts)| (attr.clone(), frequencies(counts, None)))
+        .collect(),
+      qc: self.qc.clone(),
+      top_nuc_substitutions: frequencies(&self.nuc_substitutions, top),
+      top_aa_substitutions: frequencies(&self.aa_substitutions, top),
+      cds_coverage: self
+        .cds_coverage
+        .iter()
+        .map(|(cds, histogram)| (cds.clone(), histogram.build()))
+        .collect(),
+      frame_shift_hotspots: frequencies(&self.frame_shifts, top),
+      stop_codon_hotspots: frequencies(&self.stop_codons, top),
+      errors: self
+        .errors
+        .iter()
+        .map(|(category, (count, examples))| RunSummaryErrorCategory {
+          category: category.clone(),
+          count: *count,
+          examples: examples.clone(),
+        })
+        .sorted_by(|a, b| b.count.cmp(&a.count).then_with(|| a.category.cmp(&b.category)))
+        .collect(),
+    }
+  }
+}
+
+/// Accumulates a histogram of values in the range from 0 to 1, one value at a time
+#[derive(Clone, Debug)]
+struct HistogramBuilder {
+  counts: [usize; RUN_SUMMARY_COVERAGE_BINS],
+  sum: f64,
+  total: usize,
+}
+
+impl Default for HistogramBuilder {
+  fn default() -> Self {
+    Self {
+      counts: [0; RUN_SUMMARY_COVERAGE_BINS],
+      sum: 0.0,
+      total: 0,
+    }
+  }
+}
+
+impl HistogramBuilder {
+  fn add(&mut self, value: f64) {
+    let bin = (value.clamp(0.0, 1.0) * RUN_SUMMARY_COVERAGE_BINS as f64) as usize;
+    self.counts[bin.min(RUN_SUMMARY_COVERAGE_BINS - 1)] += 1;
+    self.sum += value;
+    self.total += 1;
+  }
+
+  fn build(&self) -> RunSummaryHistogram {
+    let mean = if self.total == 0 {
+      0.0
+    } else {
+      self.sum / self.total as f64
+    };
+    RunSummaryHistogram {
+      counts: self.counts.to_vec(),
+      mean,
+    }
+  }
+}
+
+/// Extracts the category of an error from the message of its root cause, such that errors of the same kind in
+/// different sequences fall into the same category. The context added when processing a particular sequence is not part
+/// of the root cause. The category is the stable prefix of the message: the message up to the end of the first sentence
+/// or up to the first sequence-specific detail (a number or a quoted name), whichever comes first.
+///
+/// For example: "Unable to align: seed alignment covers 12.34% of the query sequence, which is less than expected 33%"
+/// becomes "Unable to align: seed alignment covers".
+pub fn error_category(message: &str) -> String {
+  let message = message.trim();
+  let prefix = message
+    .split(|c: char| c.is_ascii_digit() || c == '\'' || c == '"')
+    .next()
+    .unwrap_or(message);
+  let prefix = prefix.split_once(". ").map_or(prefix, |(first, _)| first);
+  prefix
+    .trim_end_matches(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::o;
+  use eyre::eyre;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn groups_errors_into_categories() {
+    let error = |seq_index: usize, seq_name: &str, message: String| {
+      eyre!(message).wrap_err(format!("When processing sequence #{seq_index} '{seq_name}'"))
+    };
+
+    let mut builder = RunSummaryBuilder::default();
+    builder.add_error(
+      "a",
+      &error(
+        2,
+        "a",
+        o!("Unable to align: sequence is too short. Details: sequence length: 10, min length allowed: 100."),
+      )
+      .wrap_err("When calculating seed matches"),
+    );
+    builder.add_error(
+      "b",
+      &error(
+        7,
+        "b",
+        o!("Unable to align: sequence is too short. Details: sequence length: 42, min length allowed: 100."),
+      ),
+    );
+    builder.add_error(
+      "c",
+      &error(
+        9,
+        "c",
+        o!("Unable to align: seed alignment covers 12.34% of the query sequence, which is less than expected 33.00%"),
+      ),
+    );
+    builder.add_error(
+      "d",
+      &error(
+        11,
+        "d",
+        o!("Unable to align: seed alignment covers 5.00% of the query sequence, which is less than expected 33.00%"),
+      ),
+    );
+    builder.add_error(
+      "e",
+      &error(12, "e", o!("Alignment band area (1.2M) exceeds limit (500K).")),
+    );
+
+    let summary = builder.build();
+    assert_eq!(summary.total_sequences, 5);
+    assert_eq!(summary.total_errors, 5);
+    assert_eq!(
+      summary.errors,
+      vec![
+        RunSummaryErrorCategory {
+          category: o!("Unable to align: seed alignment covers"),
+          count: 2,
+          examples: vec![o!("c"), o!("d")],
+        },
+        RunSummaryErrorCategory {
+          category: o!("Unable to align: sequence is too short"),
+          count: 2,
+          examples: vec![o!("a"), o!("b")],
+        },
+        RunSummaryErrorCategory {
+          category: o!("Alignment band area"),
+          count: 1,
+          examples: vec![o!("e")],
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn builds_coverage_histogram() {
+    let mut builder = HistogramBuilder::default();
+    for value in [0.0, 0.05, 0.5, 0.95, 1.0] {
+      builder.add(value);
+    }
+    assert_eq!(
+      builder.build(),
+      RunSummaryHistogram {
+        counts: vec![2, 0, 0, 0, 0, 1, 0, 0, 0, 2],
+        mean: 0.5,
+      }
+    );
+  }
+}
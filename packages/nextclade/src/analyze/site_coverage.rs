@@ -0,0 +1,235 @@
+use crate::alphabet::nuc::Nuc;
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::types::outputs::NextcladeOutputs;
+use itertools::izip;
+
+/// Counts of sequences by state of one site of the reference sequence
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SiteCoverage {
+  /// 0-based position in the reference sequence
+  pub pos: usize,
+  pub ref_nuc: Nuc,
+
+  /// Number of sequences in which this site is within the alignment range, i.e. not in unsequenced ends
+  pub sequenced: usize,
+
+  /// Number of sequences with `N` at this site
+  pub missing: usize,
+
+  /// Number of sequences with a deletion at this site
+  pub gap: usize,
+
+  /// Number of sequences with an ambiguous nucleotide other than `N` at this site
+  pub ambiguous: usize,
+
+  /// Number of sequences with each of nucleotides A, C, G, T at this site
+  pub acgt: [usize; 4],
+}
+
+impl SiteCoverage {
+  /// Number of sequences in which this site is sequenced and is not `N`
+  pub const fn covered(&self) -> usize {
+    self.sequenced.saturating_sub(self.missing)
+  }
+}
+
+/// Counts of sequences by state of each site of the reference sequence, across all analyzed sequences
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SiteCoverageProfile {
+  pub num_seqs: usize,
+  pub sites: Vec<SiteCoverage>,
+}
+
+/// Accumulates counts of sequenced, missing, deleted, ambiguous and A, C, G, T nucleotides at each site of the
+/// reference sequence, one sequence at a time.
+///
+/// Counts are built from ranges reported for each sequence, rather than from the aligned sequences. The nucleotide at a
+/// sequenced site which is not missing, deleted, ambiguous or substituted is the reference nucleotide.
+#[derive(Clone, Debug)]
+pub struct SiteCoverageBuilder {
+  ref_seq: Vec<Nuc>,
+  num_seqs: usize,
+
+  // Range counts are accumulated as differences between adjacent sites, to make adding a range independent of its length
+  sequenced: Vec<isize>,
+  missing: Vec<isize>,
+  gap: Vec<isize>,
+  ambiguous: Vec<isize>,
+
+  substitutions: Vec<[usize; 4]>,
+}
+
+impl SiteCoverageBuilder {
+  pub fn new(ref_seq: &[Nuc]) -> Self {
+    let len = ref_seq.len();
+    Self {
+      ref_seq: ref_seq.to_vec(),
+      num_seqs: 0,
+      sequenced: vec![0; len + 1],
+      missing: vec![0; len + 1],
+      gap: vec![0; len + 1],
+      ambiguous: vec![0; len + 1],
+      substitutions: vec![[0; 4]; len],
+    }
+  }
+
+  pub fn add_output(&mut self, output: &NextcladeOutputs) {
+    self.num_seqs += 1;
+
+    add_range(&mut self.sequenced, &output.alignment_range);
+    for missing in &output.missing {
+      add_range(&mut self.missing, &missing.range);
+    }
+    for del in &output.deletions {
+      add_range(&mut self.gap, del.range());
+    }
+    for non_acgtn in &output.non_acgtns {
+      add_range(&mut self.ambiguous, &non_acgtn.range);
+    }
+
+    for sub in &output.substitutions {
+      let pos = sub.pos.as_usize();
+      if let (Some(counts), Some(i)) = (self.substitutions.get_mut(pos), acgt_index(sub.qry_nuc)) {
+        counts[i] += 1;
+      }
+    }
+  }
+
+  pub fn build(&self) -> SiteCoverageProfile {
+    let sequenced = accumulate(&self.sequenced);
+    let missing = accumulate(&self.missing);
+    let gap = accumulate(&self.gap);
+    let ambiguous = accumulate(&self.ambiguous);
+
+    let sites = izip!(
+      self.ref_seq.iter(),
+      sequenced,
+      missing,
+      gap,
+      ambiguous,
+      &self.substitutions
+    )
+    .enumerate()
+    .map(|(pos, (ref_nuc, sequenced, missing, gap, ambiguous, substitutions))| {
+      let mut acgt = *substitutions;
+      if let Some(i) = acgt_index(*ref_nuc) {
+        let num_substituted: usize = substitutions.iter().sum();
+        acgt[i] = sequenced.saturating_sub(missing + gap + ambiguous + num_substituted);
+      }
+      SiteCoverage {
+        pos,
+        ref_nuc: *ref_nuc,
+        sequenced,
+        missing,
+        gap,
+        ambiguous,
+        acgt,
+      }
+    })
+    .collect();
+
+    SiteCoverageProfile {
+      num_seqs: self.num_seqs,
+      sites,
+    }
+  }
+}
+
+fn add_range(diffs: &mut [isize], range: &NucRefGlobalRange) {
+  let len = diffs.len() - 1;
+  let begin = range.begin.as_usize().min(len);
+  let end = range.end.as_usize().min(len);
+  if begin < end {
+    diffs[begin] += 1;
+    diffs[end] -= 1;
+  }
+}
+
+fn accumulate(diffs: &[isize]) -> Vec<usize> {
+  diffs[..diffs.len() - 1]
+    .iter()
+    .scan(0, |count, diff| {
+      *count += diff;
+      Some((*count).max(0) as usize)
+    })
+    .collect()
+}
+
+const fn acgt_index(nuc: Nuc) -> Option<usize> {
+  match nuc {
+    Nuc::A => Some(0),
+    Nuc::C => Some(1),
+    Nuc::G => Some(2),
+    Nuc::T => Some(3),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::analyze::letter_ranges::NucRange;
+  use crate::analyze::nuc_del::NucDelRange;
+  use crate::analyze::nuc_sub::NucSub;
+  use eyre::Report;
+  use pretty_assertions::assert_eq;
+  use std::str::FromStr;
+
+  #[test]
+  fn accumulates_range_counts() {
+    let mut diffs = vec![0; 6];
+    add_range(&mut diffs, &NucRefGlobalRange::from_usize(0, 3));
+    add_range(&mut diffs, &NucRefGlobalRange::from_usize(2, 5));
+    add_range(&mut diffs, &NucRefGlobalRange::from_usize(4, 10));
+    assert_eq!(accumulate(&diffs), vec![1, 1, 2, 1, 2]);
+  }
+
+  #[test]
+  fn counts_nucleotides_at_each_site() -> Result<(), Report> {
+    let mut builder = SiteCoverageBuilder::new(&to_nuc_seq("ACGTACGTAC")?);
+    builder.add_output(&NextcladeOutputs {
+      alignment_range: NucRefGlobalRange::from_usize(0, 10),
+      substitutions: vec![NucSub::from_str("C2T")?],
+      deletions: vec![NucDelRange::from_usize(4, 6)],
+      missing: vec![NucRange {
+        range: NucRefGlobalRange::from_usize(8, 9),
+        letter: Nuc::N,
+      }],
+      ..NextcladeOutputs::default()
+    });
+    builder.add_output(&NextcladeOutputs {
+      alignment_range: NucRefGlobalRange::from_usize(1, 10),
+      substitutions: vec![NucSub::from_str("C2G")?, NucSub::from_str("C10A")?],
+      non_acgtns: vec![NucRange {
+        range: NucRefGlobalRange::from_usize(2, 3),
+        letter: Nuc::R,
+      }],
+      ..NextcladeOutputs::default()
+    });
+    let profile = builder.build();
+
+    assert_eq!(profile.num_seqs, 2);
+    assert_eq!(
+      profile
+        .sites
+        .iter()
+        .map(|site| (site.sequenced, site.missing, site.gap, site.ambiguous, site.acgt))
+        .collect::<Vec<_>>(),
+      vec![
+        (1, 0, 0, 0, [1, 0, 0, 0]),
+        (2, 0, 0, 0, [0, 0, 1, 1]),
+        (2, 0, 0, 1, [0, 0, 1, 0]),
+        (2, 0, 0, 0, [0, 0, 0, 2]),
+        (2, 0, 1, 0, [1, 0, 0, 0]),
+        (2, 0, 1, 0, [0, 1, 0, 0]),
+        (2, 0, 0, 0, [0, 0, 2, 0]),
+        (2, 0, 0, 0, [0, 0, 0, 2]),
+        (2, 1, 0, 0, [1, 0, 0, 0]),
+        (2, 0, 0, 0, [1, 1, 0, 0]),
+      ]
+    );
+    Ok(())
+  }
+}
@@ -13,7 +13,7 @@ use crate::io::json::json_parse;
 use crate::io::schema_version::{SchemaVersion, SchemaVersionParams};
 use crate::qc::qc_config::QcConfig;
 use crate::run::params_general::NextcladeGeneralParamsOptional;
-use crate::tree::params::TreeBuilderParamsOptional;
+use crate::tree::params::{PlacementSiteWeight, TreeBuilderParamsOptional};
 use crate::utils::any::AnyType;
 use crate::{o, vec_of_owned};
 use eyre::{Report, WrapErr};
@@ -111,6 +111,11 @@ pub struct VirusProperties {
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub tree_builder_params: Option<TreeBuilderParamsOptional>,
 
+  /// Weights of genome sites in the phylogenetic placement distance, e.g. to down-weight known homoplastic or
+  /// hypermutable sites. Sites which are not listed have weight 1.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub placement_site_weights: Vec<PlacementSiteWeight>,
+
   /// Parameters for amino acid change detection and grouping.
   #[serde(default, skip_serializing_if = "Option::is_none")]
   pub aa_changes_params: Option<AaChangesParamsOptional>,
@@ -436,6 +441,7 @@ impl VirusProperties {
       general_params: None,
       alignment_params: None,
       tree_builder_params: None,
+      placement_site_weights: vec![],
       aa_changes_params: None,
       phenotype_data: Some(vec![PhenotypeData::example()]),
       aa_motifs: vec![AaMotifsDesc::example()],
@@ -127,6 +127,7 @@ mod coord_map_tests {
       proteins: vec![],
       exceptions: vec![],
       attributes: indexmap! {},
+      edits: vec![],
       compat_is_gene: false,
       color: None,
     }
@@ -317,6 +317,25 @@ pub struct ReferenceCoords;
 impl PositionLikeAttrs for ReferenceCoords {}
 impl CoordsMarker for ReferenceCoords {}
 
+#[derive(
+  Clone,
+  Copy,
+  Debug,
+  DeriveDisplay,
+  Default,
+  Eq,
+  PartialEq,
+  Ord,
+  PartialOrd,
+  Hash,
+  Serialize,
+  Deserialize,
+  schemars::JsonSchema,
+)]
+pub struct QueryCoords;
+impl PositionLikeAttrs for QueryCoords {}
+impl CoordsMarker for QueryCoords {}
+
 #[derive(
   Clone,
   Copy,
@@ -401,6 +420,10 @@ pub type NucAlnGlobalPosition = Position<AlignmentCoords, GlobalSpace, NucSpace>
 // to the beginning of the reference sequence.
 pub type NucRefGlobalPosition = Position<ReferenceCoords, GlobalSpace, NucSpace>;
 
+// Global nucleotide positions in coordinates of the unaligned query sequence. "Global" here means that the position is
+// relative to the beginning of the query sequence.
+pub type NucQryGlobalPosition = Position<QueryCoords, GlobalSpace, NucSpace>;
+
 // Local nucleotide positions in alignment coordinates. "Local" here means that the position is relative
 // to the beginning of a genetic feature, e.g. a gene or a CDS.
 pub type NucAlnLocalPosition = Position<AlignmentCoords, LocalSpace, NucSpace>;
@@ -463,6 +486,7 @@ macro_rules! impl_ops_for_pos {
 
 impl_ops_for_pos!(NucAlnGlobalPosition);
 impl_ops_for_pos!(NucRefGlobalPosition);
+impl_ops_for_pos!(NucQryGlobalPosition);
 impl_ops_for_pos!(NucAlnLocalPosition);
 impl_ops_for_pos!(NucRefLocalPosition);
 impl_ops_for_pos!(AaAlnPosition);
@@ -1,6 +1,6 @@
 use crate::coord::position::{
-  AaAlnPosition, AaRefPosition, NucAlnGlobalPosition, NucAlnLocalPosition, NucRefGlobalPosition, NucRefLocalPosition,
-  PositionLike,
+  AaAlnPosition, AaRefPosition, NucAlnGlobalPosition, NucAlnLocalPosition, NucQryGlobalPosition, NucRefGlobalPosition,
+  NucRefLocalPosition, PositionLike,
 };
 use assert2::assert;
 use auto_ops::impl_op_ex;
@@ -189,6 +189,7 @@ impl<P: PositionLike> Display for Range<P> {
 
 pub type NucAlnGlobalRange = Range<NucAlnGlobalPosition>;
 pub type NucRefGlobalRange = Range<NucRefGlobalPosition>;
+pub type NucQryGlobalRange = Range<NucQryGlobalPosition>;
 pub type NucAlnLocalRange = Range<NucAlnLocalPosition>;
 pub type NucRefLocalRange = Range<NucRefLocalPosition>;
 pub type AaAlnRange = Range<AaAlnPosition>;
@@ -214,6 +215,7 @@ macro_rules! impl_ops_for_range {
 
 impl_ops_for_range!(NucAlnGlobalRange, NucAlnGlobalPosition);
 impl_ops_for_range!(NucRefGlobalRange, NucRefGlobalPosition);
+impl_ops_for_range!(NucQryGlobalRange, NucQryGlobalPosition);
 impl_ops_for_range!(NucAlnLocalRange, NucAlnLocalPosition);
 impl_ops_for_range!(NucRefLocalRange, NucRefLocalPosition);
 impl_ops_for_range!(AaAlnRange, AaAlnPosition);
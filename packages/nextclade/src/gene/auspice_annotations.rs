@@ -41,6 +41,7 @@ pub fn convert_auspice_annotations_to_genes(anns: &AuspiceGenomeAnnotations) ->
         proteins: vec![],
         exceptions: vec![],
         attributes: IndexMap::default(),
+        edits: vec![],
         compat_is_gene: true,
         color: ann.color.clone(),
       };
@@ -2,6 +2,7 @@ use crate::coord::position::NucRefGlobalPosition;
 use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange, Range};
 use crate::features::feature::Feature;
 use crate::features::feature_group::FeatureGroup;
+use crate::gene::cds_edit::{CdsEdit, apply_cds_edits};
 use crate::gene::cds_segment::{CdsSegment, Truncation, WrappingPart};
 use crate::gene::frame::Frame;
 use crate::gene::gene::GeneStrand;
@@ -35,6 +36,9 @@ pub struct Cds {
   pub exceptions: Vec<String>,
   /// Additional GFF3 attributes as key-value pairs, merged from all segments.
   pub attributes: IndexMap<String, Vec<String>>,
+  /// Declared RNA editing and programmed ribosomal frameshift sites, already applied to the segments.
+  #[serde(default)]
+  pub edits: Vec<CdsEdit>,
   /// True when this CDS was synthesized from a gene record that had no child CDS.
   pub compat_is_gene: bool,
   /// Display color for the genome annotation viewer.
@@ -93,14 +97,6 @@ impl Cds {
       return make_internal_error!("CDS contains no segments")?;
     }
 
-    let segments = split_circular_cds_segments(&segments)?;
-
-    let mut proteins = vec![];
-    feature_group
-      .children
-      .iter()
-      .try_for_each(|child_feature_group| find_proteins_recursive(child_feature_group, &mut proteins))?;
-
     let attributes: IndexMap<String, Vec<String>> = {
       let mut attributes: IndexMap<String, Vec<String>> = indexmap! {};
       for segment in &segments {
@@ -114,6 +110,16 @@ impl Cds {
         .collect()
     };
 
+    let edits = CdsEdit::from_attributes(&attributes, &feature_group.name)?;
+    let segments = apply_cds_edits(&segments, &edits, &feature_group.name)?;
+    let segments = split_circular_cds_segments(&segments)?;
+
+    let mut proteins = vec![];
+    feature_group
+      .children
+      .iter()
+      .try_for_each(|child_feature_group| find_proteins_recursive(child_feature_group, &mut proteins))?;
+
     let exceptions = segments
       .iter()
       .flat_map(|segment| segment.exceptions.clone())
@@ -128,6 +134,7 @@ impl Cds {
       proteins,
       exceptions,
       attributes,
+      edits,
       compat_is_gene: false,
       color: None,
     })
@@ -186,7 +193,8 @@ impl Cds {
       gff_feature_type: feature.gff_feature_type.clone(),
     };
 
-    let segments = vec![cds_segment];
+    let edits = CdsEdit::from_attributes(&feature.attributes, &feature.name)?;
+    let segments = apply_cds_edits(&[cds_segment], &edits, &feature.name)?;
     let segments = split_circular_cds_segments(&segments)?;
 
     Ok(Self {
@@ -197,6 +205,7 @@ impl Cds {
       proteins: vec![protein],
       exceptions: feature.exceptions.clone(),
       attributes: feature.attributes.clone(),
+      edits,
       compat_is_gene: true,
       color: None,
     })
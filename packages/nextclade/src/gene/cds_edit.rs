@@ -0,0 +1,350 @@
+use crate::coord::position::NucRefGlobalPosition;
+use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange};
+use crate::gene::cds_segment::CdsSegment;
+use crate::gene::frame::Frame;
+use crate::gene::gene::GeneStrand;
+use crate::gene::phase::Phase;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Name of the GFF3 attribute which declares RNA editing site of a CDS, in the form `<position>:<count>`
+pub const RNA_EDITING_SITE_ATTR: &str = "rna_editing_site";
+
+/// Name of the GFF3 attribute which declares programmed ribosomal frameshift site of a CDS, in the form `<position>:<shift>`
+pub const RIBOSOMAL_SLIPPAGE_SITE_ATTR: &str = "ribosomal_slippage_site";
+
+/// Declared modification of the reading of a CDS, which is not encoded in the genome sequence itself.
+///
+/// Edits are applied to the CDS segments when the CDS is constructed, so that extraction, translation and
+/// mutation calling see the edited transcript and don't report the edit as a frame shift or an insertion.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum CdsEdit {
+  /// Co-transcriptional RNA editing: `count` non-templated copies of the nucleotide at position `pos` are inserted
+  /// right after it (e.g. G insertions on the G run in the P gene of paramyxoviruses, producing V and W mRNAs).
+  #[serde(rename_all = "camelCase")]
+  RnaEditing { pos: NucRefGlobalPosition, count: usize },
+
+  /// Programmed ribosomal frameshift at position `pos`: with negative `shift`, the ribosome slips back and re-reads
+  /// `-shift` nucleotides ending at `pos`; with positive `shift`, it skips `shift` nucleotides starting at `pos`.
+  #[serde(rename_all = "camelCase")]
+  RibosomalSlippage { pos: NucRefGlobalPosition, shift: isize },
+}
+
+impl CdsEdit {
+  /// Read edits declared in GFF3 attributes of a CDS. Positions in attributes are 1-based.
+  pub fn from_attributes(attributes: &IndexMap<String, Vec<String>>, cds_name: &str) -> Result<Vec<Self>, Report> {
+    let rna_editing = attributes
+      .get(RNA_EDITING_SITE_ATTR)
+      .into_iter()
+      .flatten()
+      .map(|value| {
+        let (pos, count) = parse_edit_site(value)?;
+        // Allow to spell out the inserted nucleotides, e.g. `1234:GG`, as well as to give their count, e.g. `1234:2`
+        let count = count.parse::<usize>().or_else(|_| {
+          if !count.is_empty() && count.chars().all(|c| c.is_ascii_alphabetic()) {
+            Ok(count.len())
+          } else {
+            make_error!("Expected a number of inserted nucleotides, but found '{count}'")
+          }
+        })?;
+        if count == 0 {
+          return make_error!("Number of inserted nucleotides must be positive");
+        }
+        Ok(Self::RnaEditing { pos, count })
+      })
+      .map(|edit| edit.wrap_err_with(|| edit_attr_error(RNA_EDITING_SITE_ATTR, cds_name)));
+
+    let ribosomal_slippage = attributes
+      .get(RIBOSOMAL_SLIPPAGE_SITE_ATTR)
+      .into_iter()
+      .flatten()
+      .map(|value| {
+        let (pos, shift) = parse_edit_site(value)?;
+        let shift = shift
+          .parse::<isize>()
+          .wrap_err_with(|| format!("Expected frame shift to be an integer, but found '{shift}'"))?;
+        if shift == 0 || shift.abs() > 2 {
+          return make_error!("Frame shift must be one of: -2, -1, 1, 2, but found {shift}");
+        }
+        Ok(Self::RibosomalSlippage { pos, shift })
+      })
+      .map(|edit| edit.wrap_err_with(|| edit_attr_error(RIBOSOMAL_SLIPPAGE_SITE_ATTR, cds_name)));
+
+    rna_editing.chain(ribosomal_slippage).collect()
+  }
+
+  pub const fn pos(&self) -> NucRefGlobalPosition {
+    match self {
+      Self::RnaEditing { pos, .. } | Self::RibosomalSlippage { pos, .. } => *pos,
+    }
+  }
+
+  /// Number of nucleotides this edit adds to the CDS transcript (negative if nucleotides are skipped)
+  pub const fn len_change(&self) -> isize {
+    match self {
+      Self::RnaEditing { count, .. } => *count as isize,
+      Self::RibosomalSlippage { shift, .. } => -*shift,
+    }
+  }
+}
+
+fn parse_edit_site(value: &str) -> Result<(NucRefGlobalPosition, &str), Report> {
+  let Some((pos, amount)) = value.trim().split_once(':') else {
+    return make_error!("Expected a value in the form '<position>:<amount>', but found '{value}'");
+  };
+  let pos = pos.trim().parse::<usize>().ok().filter(|pos| *pos > 0).map_or_else(
+    || make_error!("Expected a positive 1-based position, but found '{pos}'"),
+    Ok,
+  )?;
+  Ok((NucRefGlobalPosition::from(pos - 1), amount.trim()))
+}
+
+fn edit_attr_error(attr: &str, cds_name: &str) -> String {
+  format!("When parsing attribute '{attr}' of CDS '{cds_name}'")
+}
+
+/// Apply declared edits to the segments of a CDS.
+///
+/// Each edit splits the segment containing the edit site, such that the parts either overlap (nucleotides are read
+/// more than once) or leave a gap (nucleotides are skipped). This is the same way GenBank encodes ribosomal slippage
+/// and RNA editing with `join()` locations, so the rest of the pipeline handles edited CDSes without special cases.
+pub fn apply_cds_edits(segments: &[CdsSegment], edits: &[CdsEdit], cds_name: &str) -> Result<Vec<CdsSegment>, Report> {
+  if edits.is_empty() {
+    return Ok(segments.to_vec());
+  }
+
+  let mut segments = segments.to_vec();
+  for edit in edits {
+    let pos = edit.pos();
+    let Some(index) = segments.iter().position(|segment| segment.range.contains(pos)) else {
+      return make_error!(
+        "Genome annotation is invalid: In CDS '{cds_name}': edit site at position {} is outside of the CDS. Please report this to dataset authors.",
+        pos + 1
+      );
+    };
+    let parts = split_segment_at_edit(&segments[index], edit)
+      .wrap_err_with(|| format!("When applying edit at position {} to CDS '{cds_name}'", pos + 1))?;
+    segments.splice(index..=index, parts);
+  }
+
+  // Local coordinates and phases depend on the lengths of all preceding segments, so they need to be recomputed
+  let mut begin = 0;
+  for segment in &mut segments {
+    segment.range_local = NucRefLocalRange::from_usize(begin, begin + segment.len());
+    segment.phase = Phase::from_begin(segment.range_local.begin)?;
+    segment.frame = Frame::from_begin(segment.range.begin)?;
+    begin += segment.len();
+  }
+
+  Ok(segments)
+}
+
+/// Split a segment at the edit site into the parts in the order of reading (5' to 3' on the segment's strand).
+fn split_segment_at_edit(segment: &CdsSegment, edit: &CdsEdit) -> Result<Vec<CdsSegment>, Report> {
+  let (begin, end) = (segment.range.begin, segment.range.end);
+  let is_reverse = segment.strand == GeneStrand::Reverse;
+
+  let ranges = match *edit {
+    CdsEdit::RnaEditing { pos, count } => {
+      // Nucleotide at the edit site is read `1 + count` times
+      let site = NucRefGlobalRange::new(pos, pos + 1);
+      let (head, tail) = if is_reverse {
+        (NucRefGlobalRange::new(pos, end), NucRefGlobalRange::new(begin, pos + 1))
+      } else {
+        (NucRefGlobalRange::new(begin, pos + 1), NucRefGlobalRange::new(pos, end))
+      };
+      let repeats = std::iter::repeat_n(site, count - 1);
+      std::iter::once(head)
+        .chain(repeats)
+        .chain(std::iter::once(tail))
+        .collect_vec()
+    }
+    CdsEdit::RibosomalSlippage { pos, shift } if shift < 0 => {
+      // Ribosome slips back: nucleotides ending at the edit site are read again, once
+      let n_reread = shift.unsigned_abs() as isize;
+      let (head, tail) = if is_reverse {
+        (
+          NucRefGlobalRange::new(pos, end),
+          NucRefGlobalRange::new(begin, pos + n_reread),
+        )
+      } else {
+        (
+          NucRefGlobalRange::new(begin, pos + 1),
+          NucRefGlobalRange::new(pos + 1 - n_reread, end),
+        )
+      };
+      if tail.begin < begin || tail.end > end {
+        return make_error!("Re-read nucleotides extend beyond the CDS segment '{}'", segment.name);
+      }
+      vec![head, tail]
+    }
+    CdsEdit::RibosomalSlippage { pos, shift } => {
+      // Nucleotides starting at the edit site are skipped
+      let n_skipped = shift.unsigned_abs() as isize;
+      let (head, tail) = if is_reverse {
+        (
+          NucRefGlobalRange::new(pos + 1, end),
+          NucRefGlobalRange::new(begin, pos + 1 - n_skipped),
+        )
+      } else {
+        (
+          NucRefGlobalRange::new(begin, pos),
+          NucRefGlobalRange::new(pos + n_skipped, end),
+        )
+      };
+      if head.begin > head.end || tail.begin > tail.end || tail.begin < begin || tail.end > end {
+        return make_error!("Skipped nucleotides extend beyond the CDS segment '{}'", segment.name);
+      }
+      vec![head, tail]
+    }
+  };
+
+  // The edit is now encoded in the layout of the parts. Drop the attributes declaring it, so that it is not applied
+  // again when the parts are written to and then read from a GFF3 file.
+  let attributes: IndexMap<String, Vec<String>> = segment
+    .attributes
+    .iter()
+    .filter(|(key, _)| ![RNA_EDITING_SITE_ATTR, RIBOSOMAL_SLIPPAGE_SITE_ATTR].contains(&key.as_str()))
+    .map(|(key, values)| (key.clone(), values.clone()))
+    .collect();
+
+  Ok(
+    ranges
+      .into_iter()
+      .filter(|range| !range.is_empty())
+      .map(|range| CdsSegment {
+        range,
+        attributes: attributes.clone(),
+        ..segment.clone()
+      })
+      .collect_vec(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{from_nuc_seq, to_nuc_seq};
+  use crate::gene::cds::Cds;
+  use crate::gene::cds_segment::{Truncation, WrappingPart};
+  use crate::translate::extract::extract_cds_from_ref;
+  use crate::utils::error::report_to_string;
+  use indexmap::indexmap;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  fn create_cds(begin: isize, end: isize, strand: GeneStrand, attributes: &[(&str, &str)]) -> Result<Cds, Report> {
+    let segment = CdsSegment {
+      index: 0,
+      id: "".to_owned(),
+      name: "V".to_owned(),
+      range: NucRefGlobalRange::from_isize(begin, end),
+      range_local: NucRefLocalRange::from_isize(0, end - begin),
+      landmark: None,
+      wrapping_part: WrappingPart::NonWrapping,
+      strand,
+      frame: Frame::from_begin(NucRefGlobalPosition::from(begin))?,
+      phase: Phase::_0,
+      truncation: Truncation::default(),
+      exceptions: vec![],
+      attributes: indexmap! {},
+      source_record: None,
+      compat_is_gene: false,
+      color: None,
+      gff_seqid: None,
+      gff_source: None,
+      gff_feature_type: None,
+    };
+
+    let attributes: IndexMap<String, Vec<String>> = attributes
+      .iter()
+      .map(|(k, v)| ((*k).to_owned(), vec![(*v).to_owned()]))
+      .collect();
+    let edits = CdsEdit::from_attributes(&attributes, "V")?;
+    let segments = apply_cds_edits(&[segment], &edits, "V")?;
+
+    Ok(Cds {
+      id: "".to_owned(),
+      name: "V".to_owned(),
+      product: "".to_owned(),
+      segments,
+      proteins: vec![],
+      exceptions: vec![],
+      attributes,
+      edits,
+      compat_is_gene: false,
+      color: None,
+    })
+  }
+
+  #[rustfmt::skip]
+  #[rstest]
+  //                           0123456789012345
+  #[case::no_edits(            "ATGAAAGGGTTTCCC", GeneStrand::Forward, &[],                                 "ATGAAAGGGTTTCCC")]
+  #[case::rna_editing_one_g(   "ATGAAAGGTTTCCCA", GeneStrand::Forward, &[("rna_editing_site", "8:1")],      "ATGAAAGGGTTTCCCA")]
+  #[case::rna_editing_two_g(   "ATGAAGGTTTCCCAA", GeneStrand::Forward, &[("rna_editing_site", "7:GG")],     "ATGAAGGGGTTTCCCAA")]
+  #[case::slippage_minus_one(  "ATGAAACTTTCCCAA", GeneStrand::Forward, &[("ribosomal_slippage_site", "8:-1")], "ATGAAACTTTTCCCAA")]
+  #[case::slippage_minus_two(  "ATGAAACTTTCCCAA", GeneStrand::Forward, &[("ribosomal_slippage_site", "8:-2")], "ATGAAACTCTTTCCCAA")]
+  #[case::slippage_plus_one(   "ATGAAACTTTTCCCA", GeneStrand::Forward, &[("ribosomal_slippage_site", "8:1")], "ATGAAACTTTCCCA")]
+  #[case::rna_editing_reverse( "TGGGAAACCCCAT",   GeneStrand::Reverse, &[("rna_editing_site", "9:1")],      "ATGGGGGTTTCCCA")]
+  fn applies_cds_edits(
+    #[case] seq: &str,
+    #[case] strand: GeneStrand,
+    #[case] attributes: &[(&str, &str)],
+    #[case] expected: &str,
+  ) -> Result<(), Report> {
+    let seq = to_nuc_seq(seq)?;
+    let cds = create_cds(0, seq.len() as isize, strand, attributes)?;
+    let actual = from_nuc_seq(&extract_cds_from_ref(&seq, &cds));
+    assert_eq!(expected, actual);
+    assert_eq!(expected.len(), cds.len());
+    Ok(())
+  }
+
+  #[test]
+  fn recomputes_local_ranges_and_phases() -> Result<(), Report> {
+    let cds = create_cds(0, 14, GeneStrand::Forward, &[("rna_editing_site", "8:1")])?;
+    let actual = cds
+      .segments
+      .iter()
+      .map(|s| (s.range.clone(), s.range_local.clone(), s.phase.to_usize()))
+      .collect_vec();
+    let expected = vec![
+      (
+        NucRefGlobalRange::from_usize(0, 8),
+        NucRefLocalRange::from_usize(0, 8),
+        0,
+      ),
+      (
+        NucRefGlobalRange::from_usize(7, 14),
+        NucRefLocalRange::from_usize(8, 15),
+        1,
+      ),
+    ];
+    assert_eq!(expected, actual);
+    Ok(())
+  }
+
+  #[test]
+  fn rejects_edit_site_outside_of_cds() {
+    let result = create_cds(0, 14, GeneStrand::Forward, &[("rna_editing_site", "20:1")]);
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "Genome annotation is invalid: In CDS 'V': edit site at position 20 is outside of the CDS. Please report this to dataset authors."
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_frame_shift() {
+    let result = create_cds(0, 14, GeneStrand::Forward, &[("ribosomal_slippage_site", "8:0")]);
+    assert_eq!(
+      report_to_string(&result.unwrap_err()),
+      "When parsing attribute 'ribosomal_slippage_site' of CDS 'V': Frame shift must be one of: -2, -1, 1, 2, but found 0"
+    );
+  }
+}
@@ -3,10 +3,12 @@ use crate::features::feature_tree::FeatureTree;
 use crate::features::sequence_region::SequenceRegion;
 use crate::gene::auspice_annotations::convert_auspice_annotations_to_genes;
 use crate::gene::cds::Cds;
+use crate::gene::cds_edit::{RIBOSOMAL_SLIPPAGE_SITE_ATTR, RNA_EDITING_SITE_ATTR};
 use crate::gene::cds_segment::CdsSegment;
 use crate::gene::gene::{Gene, find_cdses};
 use crate::io::file::open_file_or_stdin;
 use crate::io::yaml::yaml_parse;
+use crate::translate::genetic_code::cds_transl_table;
 use crate::tree::tree::AuspiceGenomeAnnotations;
 use crate::utils::collections::take_exactly_one;
 use crate::utils::error::report_to_string;
@@ -177,13 +179,19 @@ impl GeneMap {
         eyre!(
           "Length of a CDS is expected to be divisible by 3, but the length of CDS '{}' is {} \
           (it consists of {n_segments} fragment(s) of length(s) {segment_lengths}). \
-          This is likely a mistake in genome annotation.",
+          This is likely a mistake in genome annotation. If the CDS undergoes RNA editing or programmed \
+          ribosomal frameshifting, declare the edit site using '{RNA_EDITING_SITE_ATTR}' or \
+          '{RIBOSOMAL_SLIPPAGE_SITE_ATTR}' attribute.",
           cds.name,
           cds.len()
         )
       })
     })?;
 
+    self
+      .iter_cdses()
+      .try_for_each(|cds| cds_transl_table(cds).map(|_| ()))?;
+
     let gene_name_dupes = self
       .iter_genes()
       .map(|x| &x.name)
@@ -1,5 +1,6 @@
 pub mod auspice_annotations;
 pub mod cds;
+pub mod cds_edit;
 pub mod cds_segment;
 pub mod frame;
 pub mod gene;
@@ -0,0 +1,382 @@
+// Binary values are little-endian in BAM format, regardless of the platform
+#![allow(clippy::little_endian_bytes)]
+
+use crate::alphabet::nuc::Nuc;
+use crate::io::file::create_file_or_stdout;
+use crate::io::sam::{SAM_MAPQ_UNAVAILABLE, SamRecord, sam_header, sam_name};
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use std::io::Write;
+use std::path::Path;
+
+/// Maximum size of uncompressed data in one BGZF block. Chosen such that the compressed block fits into 64 KiB even
+/// if the data is incompressible.
+const BGZF_MAX_BLOCK_DATA_SIZE: usize = 0xff00;
+
+/// Empty BGZF block, which marks the end of a BGZF file
+const BGZF_EOF: [u8; 28] = [
+  0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, 0x42, 0x43, 0x02, 0x00, 0x1b, 0x00, 0x03,
+  0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+/// Bin of unmapped records, as computed by `reg2bin(-1, 0)`
+const BAM_BIN_UNMAPPED: u16 = 4680;
+
+/// Encoding of nucleotides in BAM format, 4 bits per nucleotide: index of the letter in this string
+const BAM_NUC_CODES: &[u8; 16] = b"=ACMGRSVTWYHKDBN";
+
+/// Writes data in BGZF format: a series of gzip blocks, each of at most 64 KiB, as required by BAM format.
+///
+/// See: https://samtools.github.io/hts-specs/SAMv1.pdf, section 4.1
+pub struct BgzfWriter<W: Write> {
+  writer: W,
+  buf: Vec<u8>,
+  is_finished: bool,
+}
+
+impl<W: Write> BgzfWriter<W> {
+  pub fn new(writer: W) -> Self {
+    Self {
+      writer,
+      buf: Vec::with_capacity(BGZF_MAX_BLOCK_DATA_SIZE),
+      is_finished: false,
+    }
+  }
+
+  /// Compresses remaining data and writes the end-of-file marker. Subsequent calls do nothing.
+  pub fn finish(&mut self) -> Result<(), Report> {
+    if self.is_finished {
+      return Ok(());
+    }
+    self.write_block()?;
+    self.writer.write_all(&BGZF_EOF)?;
+    self.writer.flush()?;
+    self.is_finished = true;
+    Ok(())
+  }
+
+  fn write_block(&mut self) -> Result<(), Report> {
+    if self.buf.is_empty() {
+      return Ok(());
+    }
+
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(&self.buf)?;
+    let compressed = encoder.finish()?;
+
+    let mut crc = flate2::Crc::new();
+    crc.update(&self.buf);
+
+    // Total block size minus 1: 18 bytes of header, compressed data and 8 bytes of footer
+    let block_size = u16::try_from(compressed.len() + 25)?;
+    let [bsize_lo, bsize_hi] = block_size.to_le_bytes();
+    #[rustfmt::skip]
+    let header = [
+      0x1f, 0x8b, 0x08, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x06, 0x00, b'B', b'C', 0x02, 0x00, bsize_lo, bsize_hi,
+    ];
+
+    self.writer.write_all(&header)?;
+    self.writer.write_all(&compressed)?;
+    self.writer.write_all(&crc.sum().to_le_bytes())?;
+    self.writer.write_all(&(self.buf.len() as u32).to_le_bytes())?;
+    self.buf.clear();
+    Ok(())
+  }
+}
+
+impl<W: Write> Write for BgzfWriter<W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    let len = buf.len().min(BGZF_MAX_BLOCK_DATA_SIZE - self.buf.len());
+    self.buf.extend_from_slice(&buf[..len]);
+    if self.buf.len() >= BGZF_MAX_BLOCK_DATA_SIZE {
+      self.write_block().map_err(std::io::Error::other)?;
+    }
+    Ok(len)
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.writer.flush()
+  }
+}
+
+/// Writes pairwise alignments of query sequences to the reference sequence in BAM format
+pub struct BamWriter {
+  writer: BgzfWriter<Box<dyn Write + Send>>,
+}
+
+impl BamWriter {
+  pub fn new(filepath: impl AsRef<Path>, ref_seq_name: &str, ref_len: usize) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let mut writer = BgzfWriter::new(create_file_or_stdout(filepath)?);
+    writer
+      .write_all(&bam_header(ref_seq_name, ref_len)?)
+      .wrap_err_with(|| format!("When writing BAM header to file: {}", filepath.display()))?;
+    Ok(Self { writer })
+  }
+
+  pub fn write(&mut self, seq_name: &str, query: &[Nuc], output: &NextcladeOutputs) -> Result<(), Report> {
+    self.write_record(&SamRecord::from_alignment(seq_name, query, output))
+  }
+
+  pub fn write_nuc_error(&mut self, seq_name: &str) -> Result<(), Report> {
+    self.write_record(&SamRecord::unmapped(seq_name))
+  }
+
+  fn write_record(&mut self, record: &SamRecord) -> Result<(), Report> {
+    let encoded = bam_encode_record(record)?;
+    self
+      .writer
+      .write_all(&encoded)
+      .wrap_err_with(|| format!("When writing BAM record for sequence '{}'", record.qname))
+  }
+
+  pub fn finish(&mut self) -> Result<(), Report> {
+    self.writer.finish().wrap_err("When finalizing BAM file")
+  }
+}
+
+/// Encodes BAM header: SAM header text and the list of reference sequences, which consists of the only reference
+pub fn bam_header(ref_seq_name: &str, ref_len: usize) -> Result<Vec<u8>, Report> {
+  let ref_name = sam_name(ref_seq_name);
+  let text = sam_header(&ref_name, ref_len);
+
+  let mut buf = b"BAM\x01".to_vec();
+  buf.extend_from_slice(&u32::try_from(text.len())?.to_le_bytes());
+  buf.extend_from_slice(text.as_bytes());
+  buf.extend_from_slice(&1_u32.to_le_bytes());
+  buf.extend_from_slice(&u32::try_from(ref_name.len() + 1)?.to_le_bytes());
+  buf.extend_from_slice(ref_name.as_bytes());
+  buf.push(0);
+  buf.extend_from_slice(&u32::try_from(ref_len)?.to_le_bytes());
+  Ok(buf)
+}
+
+/// Encodes one alignment record in BAM format
+///
+/// See: https://samtools.github.io/hts-specs/SAMv1.pdf, section 4.2
+pub fn bam_encode_record(record: &SamRecord) -> Result<Vec<u8>, Report> {
+  let (ref_id, pos, mapq, bin) = match (record.pos, record.end()) {
+    (Some(pos), Some(end)) => (0_i32, i32::try_from(pos)?, SAM_MAPQ_UNAVAILABLE, reg2bin(pos, end)),
+    _ => (-1_i32, -1_i32, 0, BAM_BIN_UNMAPPED),
+  };
+
+  let mut buf = vec![];
+  buf.extend_from_slice(&ref_id.to_le_bytes());
+  buf.extend_from_slice(&pos.to_le_bytes());
+  buf.push(u8::try_from(record.qname.len() + 1)?);
+  buf.push(mapq);
+  buf.extend_from_slice(&bin.to_le_bytes());
+  buf.extend_from_slice(&u16::try_from(record.cigar.len())?.to_le_bytes());
+  buf.extend_from_slice(&record.flag.to_le_bytes());
+  buf.extend_from_slice(&u32::try_from(record.seq.len())?.to_le_bytes());
+  buf.extend_from_slice(&(-1_i32).to_le_bytes()); // next_refID
+  buf.extend_from_slice(&(-1_i32).to_le_bytes()); // next_pos
+  buf.extend_from_slice(&0_i32.to_le_bytes()); // tlen
+
+  buf.extend_from_slice(record.qname.as_bytes());
+  buf.push(0);
+
+  for (op, len) in &record.cigar {
+    let encoded = (u32::try_from(*len)? << 4) | op.to_bam_code();
+    buf.extend_from_slice(&encoded.to_le_bytes());
+  }
+
+  for pair in record.seq.chunks(2) {
+    let hi = bam_nuc_code(pair[0]);
+    let lo = pair.get(1).map_or(0, |nuc| bam_nuc_code(*nuc));
+    buf.push((hi << 4) | lo);
+  }
+
+  // Base qualities are not available
+  buf.extend(std::iter::repeat_n(0xff, record.seq.len()));
+
+  if let Some(alignment_score) = record.alignment_score {
+    buf.extend_from_slice(b"ASi");
+    buf.extend_from_slice(&alignment_score.to_le_bytes());
+  }
+
+  let block_size = u32::try_from(buf.len())?;
+  let mut result = block_size.to_le_bytes().to_vec();
+  result.extend(buf);
+  Ok(result)
+}
+
+fn bam_nuc_code(nuc: Nuc) -> u8 {
+  let letter = nuc.to_string().as_bytes().first().copied().unwrap_or(b'N');
+  BAM_NUC_CODES
+    .iter()
+    .position(|code| *code == letter)
+    .unwrap_or(BAM_NUC_CODES.len() - 1) as u8
+}
+
+/// Computes BAM bin of a 0-based, half-open range on the reference sequence
+///
+/// See: https://samtools.github.io/hts-specs/SAMv1.pdf, section 5.3
+const fn reg2bin(begin: usize, end: usize) -> u16 {
+  let end = if end > begin { end - 1 } else { begin };
+  // Offsets of the first bin at each level of the binning index: ((1 << (3 * level)) - 1) / 7
+  let bin = if begin >> 14 == end >> 14 {
+    4681 + (begin >> 14)
+  } else if begin >> 17 == end >> 17 {
+    585 + (begin >> 17)
+  } else if begin >> 20 == end >> 20 {
+    73 + (begin >> 20)
+  } else if begin >> 23 == end >> 23 {
+    9 + (begin >> 23)
+  } else if begin >> 26 == end >> 26 {
+    1 + (begin >> 26)
+  } else {
+    0
+  };
+  bin as u16
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::io::sam::CigarOp;
+  use crate::o;
+  use flate2::read::{DeflateDecoder, MultiGzDecoder};
+  use pretty_assertions::assert_eq;
+  use std::io::Read;
+
+  fn read_bytes<const N: usize>(data: &mut &[u8]) -> Result<[u8; N], Report> {
+    let mut bytes = [0; N];
+    data.read_exact(&mut bytes)?;
+    Ok(bytes)
+  }
+
+  fn read_vec(data: &mut &[u8], len: usize) -> Result<Vec<u8>, Report> {
+    let mut bytes = vec![0; len];
+    data.read_exact(&mut bytes)?;
+    Ok(bytes)
+  }
+
+  fn read_u16(data: &mut &[u8]) -> Result<u16, Report> {
+    Ok(u16::from_le_bytes(read_bytes(data)?))
+  }
+
+  fn read_u32(data: &mut &[u8]) -> Result<u32, Report> {
+    Ok(u32::from_le_bytes(read_bytes(data)?))
+  }
+
+  fn read_i32(data: &mut &[u8]) -> Result<i32, Report> {
+    Ok(i32::from_le_bytes(read_bytes(data)?))
+  }
+
+  /// Inflates BGZF blocks one by one, checking block headers, sizes and checksums. Returns uncompressed data and the
+  /// last block.
+  fn bgzf_inflate(mut data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), Report> {
+    let mut uncompressed = vec![];
+    let mut last_block = vec![];
+    while !data.is_empty() {
+      let block = data;
+      let header: [u8; 16] = read_bytes(&mut data)?;
+      assert_eq!(header[..4], [0x1f, 0x8b, 0x08, 0x04]);
+      assert_eq!(header[10..16], [0x06, 0x00, b'B', b'C', 0x02, 0x00]);
+      let block_size = read_u16(&mut data)? as usize + 1;
+
+      let compressed = read_vec(&mut data, block_size - 26)?;
+      let mut inflated = vec![];
+      DeflateDecoder::new(compressed.as_slice()).read_to_end(&mut inflated)?;
+
+      let mut crc = flate2::Crc::new();
+      crc.update(&inflated);
+      assert_eq!(read_u32(&mut data)?, crc.sum());
+      assert_eq!(read_u32(&mut data)? as usize, inflated.len());
+
+      uncompressed.extend(inflated);
+      last_block = block[..block_size].to_vec();
+    }
+    Ok((uncompressed, last_block))
+  }
+
+  #[test]
+  fn writes_bam_readable_back() -> Result<(), Report> {
+    let record = SamRecord {
+      qname: o!("seq1"),
+      flag: 0,
+      pos: Some(2),
+      cigar: vec![
+        (CigarOp::SoftClip, 1),
+        (CigarOp::Match, 3),
+        (CigarOp::Del, 2),
+        (CigarOp::Match, 1),
+      ],
+      seq: to_nuc_seq("TACGN")?,
+      alignment_score: Some(42),
+    };
+
+    let mut bgzf = BgzfWriter::new(vec![]);
+    bgzf.write_all(&bam_header("ref", 100)?)?;
+    bgzf.write_all(&bam_encode_record(&record)?)?;
+    bgzf.finish()?;
+
+    let (uncompressed, last_block) = bgzf_inflate(&bgzf.writer)?;
+    assert_eq!(last_block, BGZF_EOF);
+    let data = &mut uncompressed.as_slice();
+
+    assert_eq!(read_vec(data, 4)?, b"BAM\x01");
+    let l_text = read_u32(data)? as usize;
+    assert_eq!(String::from_utf8(read_vec(data, l_text)?)?, sam_header("ref", 100));
+    assert_eq!(read_u32(data)?, 1);
+    let l_name = read_u32(data)? as usize;
+    assert_eq!(read_vec(data, l_name)?, b"ref\0");
+    assert_eq!(read_u32(data)?, 100);
+
+    let block_size = read_u32(data)? as usize;
+    assert_eq!(block_size, data.len());
+    assert_eq!(read_i32(data)?, 0);
+    assert_eq!(read_i32(data)?, 2);
+    let [l_read_name, mapq] = read_bytes(data)?;
+    assert_eq!((l_read_name, mapq), (5, SAM_MAPQ_UNAVAILABLE));
+    assert_eq!(read_u16(data)?, reg2bin(2, 8));
+    let n_cigar_op = read_u16(data)?;
+    assert_eq!(read_u16(data)?, 0);
+    let l_seq = read_u32(data)? as usize;
+    assert_eq!(l_seq, 5);
+    assert_eq!([read_i32(data)?, read_i32(data)?, read_i32(data)?], [-1, -1, 0]);
+    assert_eq!(read_vec(data, l_read_name as usize)?, b"seq1\0");
+
+    let cigar = std::iter::repeat_with(|| read_u32(data).map(|op| (op >> 4, op & 0xf)))
+      .take(n_cigar_op as usize)
+      .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(cigar, vec![(1, 4), (3, 0), (2, 2), (1, 0)]);
+
+    // Nucleotides packed 2 per byte, high nibble first: T=8, A=1, C=2, G=4, N=15, padding=0
+    assert_eq!(read_vec(data, l_seq.div_ceil(2))?, vec![0x81, 0x24, 0xf0]);
+    assert_eq!(read_vec(data, l_seq)?, vec![0xff; l_seq]);
+
+    assert_eq!(read_vec(data, 3)?, b"ASi");
+    assert_eq!(read_i32(data)?, 42);
+    assert!(data.is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn writes_bgzf_readable_by_gzip_decoder() -> Result<(), Report> {
+    let data = (0..200_000).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+
+    let mut bgzf = BgzfWriter::new(vec![]);
+    bgzf.write_all(&data)?;
+    bgzf.finish()?;
+    let compressed = bgzf.writer;
+    assert!(compressed.ends_with(&BGZF_EOF));
+
+    let mut decompressed = vec![];
+    MultiGzDecoder::new(compressed.as_slice()).read_to_end(&mut decompressed)?;
+    assert_eq!(decompressed, data);
+    Ok(())
+  }
+
+  #[test]
+  fn computes_bins() {
+    assert_eq!(reg2bin(0, 1), 4681);
+    assert_eq!(reg2bin(0, 1 << 14), 4681);
+    assert_eq!(reg2bin(0, (1 << 14) + 1), 585);
+    assert_eq!(reg2bin(20000, 30000), 4682);
+  }
+}
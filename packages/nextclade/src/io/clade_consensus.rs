@@ -0,0 +1,104 @@
+use crate::alphabet::nuc::{Nuc, from_nuc_seq};
+use crate::analyze::clade_consensus::{CladeConsensus, CladeConsensusBuilder};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::fasta::FastaWriter;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use std::path::{Path, PathBuf};
+
+/// Accumulates nucleotide counts for each clade (or other clade-like attribute) and writes consensus sequences into a
+/// FASTA file and per-site support counts into a TSV file when finished
+pub struct CladeConsensusWriter {
+  fasta_filepath: Option<PathBuf>,
+  support_filepath: Option<PathBuf>,
+  ref_seq: Vec<Nuc>,
+  builder: CladeConsensusBuilder,
+}
+
+impl CladeConsensusWriter {
+  pub fn new(
+    ref_seq: &[Nuc],
+    fasta_filepath: Option<&PathBuf>,
+    support_filepath: Option<&PathBuf>,
+    attr: &str,
+    threshold: f64,
+  ) -> Result<Self, Report> {
+    Ok(Self {
+      fasta_filepath: fasta_filepath.cloned(),
+      support_filepath: support_filepath.cloned(),
+      ref_seq: ref_seq.to_vec(),
+      builder: CladeConsensusBuilder::new(attr, threshold)?,
+    })
+  }
+
+  pub fn write(&mut self, aligned_seq: &[Nuc], output: &NextcladeOutputs) {
+    self.builder.add_output(aligned_seq, output);
+  }
+
+  pub fn finish(&self) -> Result<(), Report> {
+    let consensuses = self.builder.build();
+    if let Some(fasta_filepath) = &self.fasta_filepath {
+      clade_consensus_write_fasta(fasta_filepath, &consensuses)?;
+    }
+    if let Some(support_filepath) = &self.support_filepath {
+      clade_consensus_write_support_tsv(support_filepath, &self.ref_seq, &consensuses)?;
+    }
+    Ok(())
+  }
+}
+
+pub fn clade_consensus_write_fasta(filepath: impl AsRef<Path>, consensuses: &[CladeConsensus]) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut writer = FastaWriter::from_path(filepath)?;
+  for consensus in consensuses {
+    writer
+      .write(&consensus.group, &from_nuc_seq(&consensus.seq), false)
+      .wrap_err_with(|| format!("When writing consensus sequences file: {}", filepath.display()))?;
+  }
+  writer.flush()
+}
+
+pub fn clade_consensus_write_support_tsv(
+  filepath: impl AsRef<Path>,
+  ref_seq: &[Nuc],
+  consensuses: &[CladeConsensus],
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let headers = [
+    "group",
+    "numSeqs",
+    "pos",
+    "ref",
+    "consensus",
+    "depth",
+    "A",
+    "C",
+    "G",
+    "T",
+    "gap",
+  ]
+  .map(str::to_owned);
+
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for consensus in consensuses {
+    for (pos, (nuc, support)) in consensus.seq.iter().zip(&consensus.support).enumerate() {
+      let [a, c, g, t, gap] = support.map(|count| count.to_string());
+      writer
+        .write([
+          consensus.group.clone(),
+          consensus.num_seqs.to_string(),
+          (pos + 1).to_string(),
+          ref_seq.get(pos).map(ToString::to_string).unwrap_or_default(),
+          nuc.to_string(),
+          support.iter().sum::<usize>().to_string(),
+          a,
+          c,
+          g,
+          t,
+          gap,
+        ])
+        .wrap_err_with(|| format!("When writing consensus support file: {}", filepath.display()))?;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::analyze::clade_mutation_prevalence::{CladeMutationPrevalenceBuilder, CladeMutations};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::json::{JsonPretty, json_write};
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The file produced using `nextclade run --output-clade-mutations-json`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CladeMutationPrevalenceJson {
+  pub clades: Vec<CladeMutations>,
+}
+
+/// Accumulates prevalence of mutations in each clade and writes it into TSV and/or JSON files when finished
+pub struct CladeMutationPrevalenceWriter {
+  tsv_filepath: Option<PathBuf>,
+  json_filepath: Option<PathBuf>,
+  builder: CladeMutationPrevalenceBuilder,
+}
+
+impl CladeMutationPrevalenceWriter {
+  pub fn new(tsv_filepath: Option<&PathBuf>, json_filepath: Option<&PathBuf>) -> Self {
+    Self {
+      tsv_filepath: tsv_filepath.cloned(),
+      json_filepath: json_filepath.cloned(),
+      builder: CladeMutationPrevalenceBuilder::default(),
+    }
+  }
+
+  pub fn write(&mut self, output: &NextcladeOutputs) {
+    self.builder.add_output(output);
+  }
+
+  pub fn finish(&self) -> Result<(), Report> {
+    let clades = self.builder.build();
+    if let Some(tsv_filepath) = &self.tsv_filepath {
+      clade_mutation_prevalence_write_tsv(tsv_filepath, &clades)?;
+    }
+    if let Some(json_filepath) = &self.json_filepath {
+      json_write(json_filepath, &CladeMutationPrevalenceJson { clades }, JsonPretty(true))?;
+    }
+    Ok(())
+  }
+}
+
+pub fn clade_mutation_prevalence_write_tsv(
+  filepath: impl AsRef<Path>,
+  clades: &[CladeMutations],
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let headers = [
+    "clade",
+    "numSeqs",
+    "type",
+    "mutation",
+    "cds",
+    "pos",
+    "count",
+    "frequency",
+    "countPrivate",
+    "countCladeDefining",
+  ]
+  .map(str::to_owned);
+
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for clade in clades {
+    for mutation in &clade.mutations {
+      writer
+        .write([
+          clade.clade.clone(),
+          clade.num_seqs.to_string(),
+          mutation.kind.to_string(),
+          mutation.mutation.clone(),
+          mutation.cds.clone().unwrap_or_default(),
+          mutation.pos.to_string(),
+          mutation.count.to_string(),
+          mutation.frequency.to_string(),
+          mutation.count_private.map(|c| c.to_string()).unwrap_or_default(),
+          mutation.count_clade_defining.map(|c| c.to_string()).unwrap_or_default(),
+        ])
+        .wrap_err_with(|| format!("When writing clade mutations file: {}", filepath.display()))?;
+    }
+  }
+  Ok(())
+}
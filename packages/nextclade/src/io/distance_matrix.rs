@@ -0,0 +1,95 @@
+use crate::analyze::distance_matrix::{
+  DistanceMatrixParams, DistanceMatrixSeq, calculate_distance, calculate_distance_matrix,
+};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::file::create_file_or_stdout;
+use clap::ValueEnum;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::io::Write;
+use std::path::Path;
+
+/// Format of the pairwise distance matrix file
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DistanceMatrixFormat {
+  /// Square matrix, with sequence names in the first row and in the first column
+  #[default]
+  Tsv,
+
+  /// One row per pair of sequences: `seqName1`, `seqName2` and `distance`
+  SparseTsv,
+
+  /// Square matrix in relaxed PHYLIP format
+  Phylip,
+}
+
+impl DistanceMatrixFormat {
+  pub const fn extension(self) -> &'static str {
+    match self {
+      Self::Tsv | Self::SparseTsv => "tsv",
+      Self::Phylip => "phy",
+    }
+  }
+}
+
+/// Calculates pairwise distances between sequences and writes them to a file.
+///
+/// In the sparse format, pairs are written as they are calculated, without keeping the matrix in memory, and pairs with
+/// distance above `max_distance`, if provided, are omitted.
+pub fn distance_matrix_write_to_file(
+  filepath: impl AsRef<Path>,
+  seqs: &[DistanceMatrixSeq],
+  params: DistanceMatrixParams,
+  format: DistanceMatrixFormat,
+  max_distance: Option<usize>,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  match format {
+    DistanceMatrixFormat::Tsv => write_tsv(filepath, seqs, &calculate_distance_matrix(seqs, params)),
+    DistanceMatrixFormat::SparseTsv => write_sparse_tsv(filepath, seqs, params, max_distance),
+    DistanceMatrixFormat::Phylip => write_phylip(filepath, seqs, &calculate_distance_matrix(seqs, params)),
+  }
+  .wrap_err_with(|| format!("When writing distance matrix file: {}", filepath.display()))
+}
+
+fn write_tsv(filepath: &Path, seqs: &[DistanceMatrixSeq], matrix: &[Vec<usize>]) -> Result<(), Report> {
+  let headers = std::iter::once("seqName".to_owned())
+    .chain(seqs.iter().map(|seq| seq.seq_name.clone()))
+    .collect_vec();
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for (seq, row) in seqs.iter().zip(matrix) {
+    writer.write(std::iter::once(seq.seq_name.clone()).chain(row.iter().map(ToString::to_string)))?;
+  }
+  Ok(())
+}
+
+fn write_sparse_tsv(
+  filepath: &Path,
+  seqs: &[DistanceMatrixSeq],
+  params: DistanceMatrixParams,
+  max_distance: Option<usize>,
+) -> Result<(), Report> {
+  let headers = ["seqName1", "seqName2", "distance"].map(str::to_owned);
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for (i, a) in seqs.iter().enumerate() {
+    for b in seqs.iter().skip(i + 1) {
+      let distance = calculate_distance(a, b, params);
+      if max_distance.is_none_or(|max_distance| distance <= max_distance) {
+        writer.write([a.seq_name.clone(), b.seq_name.clone(), distance.to_string()])?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Writes relaxed PHYLIP distance matrix. Whitespace in sequence names is replaced with underscores, because it is
+/// used as a separator.
+fn write_phylip(filepath: &Path, seqs: &[DistanceMatrixSeq], matrix: &[Vec<usize>]) -> Result<(), Report> {
+  let mut writer = create_file_or_stdout(filepath)?;
+  writeln!(writer, "{}", seqs.len())?;
+  for (seq, row) in seqs.iter().zip(matrix) {
+    let name = seq.seq_name.replace(char::is_whitespace, "_");
+    writeln!(writer, "{name} {}", row.iter().join(" "))?;
+  }
+  Ok(())
+}
@@ -234,23 +234,12 @@ pub struct FastaPeptideWriter {
 
 impl FastaPeptideWriter {
   pub fn new(gene_map: &GeneMap, output_translations: impl AsRef<str>) -> Result<Self, Report> {
-    let output_translations = output_translations.as_ref();
-
-    let mut tt = TinyTemplate::new();
-    tt.add_template("output_translations", output_translations)
-      .wrap_err_with(|| format!("When parsing template: {output_translations}"))?;
-
-    let writers = gene_map
-      .iter_cdses()
-      .map(|cds| -> Result<_, Report> {
-        let template_context = OutputTranslationsTemplateContext { cds: &cds.name };
-        let rendered_path = tt
-          .render("output_translations", &template_context)
-          .wrap_err_with(|| format!("When rendering output translations path template: '{output_translations}', using context: {template_context:?}"))?;
-        let out_gene_fasta_path = PathBuf::from_str(&rendered_path).wrap_err_with(|| format!("Invalid output translations path: '{rendered_path}'"))?;
+    let writers = output_translations_paths(gene_map, output_translations)?
+      .into_iter()
+      .map(|(cds_name, out_gene_fasta_path)| -> Result<_, Report> {
         trace!("Creating fasta writer to file '{}'", out_gene_fasta_path.display());
         let writer = FastaWriter::from_path(&out_gene_fasta_path)?;
-        Ok((cds.name.clone(), writer))
+        Ok((cds_name, writer))
       })
       .collect::<Result<FastaPeptideWritersMap, Report>>()?;
 
@@ -265,6 +254,30 @@ impl FastaPeptideWriter {
   }
 }
 
+/// Renders template of paths to output peptide FASTA files for each CDS in the genome annotation
+pub fn output_translations_paths(
+  gene_map: &GeneMap,
+  output_translations: impl AsRef<str>,
+) -> Result<BTreeMap<String, PathBuf>, Report> {
+  let output_translations = output_translations.as_ref();
+
+  let mut tt = TinyTemplate::new();
+  tt.add_template("output_translations", output_translations)
+    .wrap_err_with(|| format!("When parsing template: {output_translations}"))?;
+
+  gene_map
+    .iter_cdses()
+    .map(|cds| -> Result<_, Report> {
+      let template_context = OutputTranslationsTemplateContext { cds: &cds.name };
+      let rendered_path = tt
+        .render("output_translations", &template_context)
+        .wrap_err_with(|| format!("When rendering output translations path template: '{output_translations}', using context: {template_context:?}"))?;
+      let out_gene_fasta_path = PathBuf::from_str(&rendered_path).wrap_err_with(|| format!("Invalid output translations path: '{rendered_path}'"))?;
+      Ok((cds.name.clone(), out_gene_fasta_path))
+    })
+    .collect()
+}
+
 pub fn parse_fasta_header(header: &str) -> (String, String) {
   header.split_once(' ').map_or_else(
     || (header.to_owned(), String::new()),
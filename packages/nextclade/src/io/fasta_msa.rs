@@ -0,0 +1,198 @@
+use crate::align::insertions_strip::Insertion;
+use crate::alphabet::aa::Aa;
+use crate::alphabet::letter::Letter;
+use crate::gene::gene_map::GeneMap;
+use crate::io::fasta::{FastaWriter, output_translations_paths};
+use crate::make_internal_error;
+use crate::translate::translate_genes::CdsTranslation;
+use eyre::{Report, WrapErr};
+use log::trace;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Insertion columns of a multiple sequence alignment: for each reference position after which any of the sequences
+/// has an insertion, the length of the longest of these insertions
+pub type MsaInsertionColumns = BTreeMap<i32, usize>;
+
+/// Merges insertions of all sequences into insertion columns of a multiple sequence alignment
+pub fn msa_insertion_columns<'a, T: Letter<T> + 'a>(
+  insertions: impl IntoIterator<Item = &'a [Insertion<T>]>,
+) -> MsaInsertionColumns {
+  let mut columns = MsaInsertionColumns::new();
+  for seq_insertions in insertions {
+    for (pos, len) in insertion_lengths_by_pos(seq_insertions) {
+      let width = columns.entry(pos).or_default();
+      *width = (*width).max(len);
+    }
+  }
+  columns
+}
+
+/// Inserts insertions back into a sequence aligned to the reference sequence, such that the sequence contains all
+/// insertion columns of the multiple sequence alignment. Insertions are padded with gaps up to the width of the column.
+/// Insertions before the beginning of the reference sequence are aligned to the right, and all other insertions are
+/// aligned to the left, such that they stay adjacent to the aligned part of the sequence.
+pub fn msa_restore_insertions<T: Letter<T>>(
+  seq: &[T],
+  insertions: &[Insertion<T>],
+  columns: &MsaInsertionColumns,
+) -> Vec<T> {
+  let mut insertions_by_pos = BTreeMap::<i32, Vec<T>>::new();
+  for ins in insertions {
+    insertions_by_pos
+      .entry(ins.pos)
+      .or_default()
+      .extend_from_slice(&ins.ins);
+  }
+  let get_ins = |pos: i32| insertions_by_pos.get(&pos).map_or(&[][..], Vec::as_slice);
+
+  let mut result = Vec::with_capacity(seq.len() + columns.values().sum::<usize>());
+
+  for (&pos, &width) in columns.range(..0) {
+    let ins = get_ins(pos);
+    result.extend(std::iter::repeat_n(T::GAP, width.saturating_sub(ins.len())));
+    result.extend_from_slice(ins);
+  }
+
+  let mut columns = columns.range(0..).peekable();
+  for (pos, letter) in seq.iter().enumerate() {
+    result.push(*letter);
+    while let Some((&col_pos, &width)) = columns.next_if(|(col_pos, _)| **col_pos as usize <= pos) {
+      let ins = get_ins(col_pos);
+      result.extend_from_slice(ins);
+      result.extend(std::iter::repeat_n(T::GAP, width.saturating_sub(ins.len())));
+    }
+  }
+
+  result
+}
+
+/// Total length of insertions of one sequence at each position
+fn insertion_lengths_by_pos<T: Letter<T>>(insertions: &[Insertion<T>]) -> BTreeMap<i32, usize> {
+  let mut lengths = BTreeMap::<i32, usize>::new();
+  for ins in insertions {
+    *lengths.entry(ins.pos).or_default() += ins.len();
+  }
+  lengths
+}
+
+struct MsaRecord<T: Letter<T>> {
+  seq_name: String,
+  seq: Vec<T>,
+  insertions: Vec<Insertion<T>>,
+  is_reverse_complement: bool,
+}
+
+/// Accumulates aligned sequences along with their insertions and, when finished, writes them into a FASTA file as a
+/// multiple sequence alignment, with insertion columns merged from all sequences
+pub struct FastaMsaWriter<T: Letter<T>> {
+  writer: FastaWriter,
+  records: Vec<MsaRecord<T>>,
+}
+
+impl<T: Letter<T>> FastaMsaWriter<T> {
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    Ok(Self {
+      writer: FastaWriter::from_path(filepath)?,
+      records: vec![],
+    })
+  }
+
+  pub fn write(&mut self, seq_name: &str, seq: &[T], insertions: &[Insertion<T>], is_reverse_complement: bool) {
+    self.records.push(MsaRecord {
+      seq_name: seq_name.to_owned(),
+      seq: seq.to_vec(),
+      insertions: insertions.to_vec(),
+      is_reverse_complement,
+    });
+  }
+
+  /// Writes accumulated sequences. Subsequent calls only write sequences added since the previous call.
+  pub fn finish(&mut self) -> Result<(), Report> {
+    let columns = msa_insertion_columns(self.records.iter().map(|record| record.insertions.as_slice()));
+    for record in self.records.drain(..) {
+      let seq = msa_restore_insertions(&record.seq, &record.insertions, &columns);
+      self
+        .writer
+        .write(&record.seq_name, &T::from_seq(&seq), record.is_reverse_complement)
+        .wrap_err_with(|| format!("When writing aligned sequence '{}'", record.seq_name))?;
+    }
+    self.writer.flush()
+  }
+}
+
+/// Accumulates peptides and writes them as multiple sequence alignments with insertions, each CDS into a separate
+/// FASTA file
+pub struct FastaPeptideMsaWriter {
+  writers: BTreeMap<String, FastaMsaWriter<Aa>>,
+}
+
+impl FastaPeptideMsaWriter {
+  pub fn new(gene_map: &GeneMap, output_translations: impl AsRef<str>) -> Result<Self, Report> {
+    let writers = output_translations_paths(gene_map, output_translations)?
+      .into_iter()
+      .map(|(cds_name, out_gene_fasta_path)| -> Result<_, Report> {
+        trace!("Creating fasta writer to file '{}'", out_gene_fasta_path.display());
+        let writer = FastaMsaWriter::from_path(&out_gene_fasta_path)?;
+        Ok((cds_name, writer))
+      })
+      .collect::<Result<_, Report>>()?;
+
+    Ok(Self { writers })
+  }
+
+  pub fn write(&mut self, seq_name: &str, translation: &CdsTranslation) -> Result<(), Report> {
+    match self.writers.get_mut(&translation.name) {
+      None => make_internal_error!("Fasta file writer not found for gene '{}'", &translation.name),
+      Some(writer) => {
+        writer.write(seq_name, &translation.seq, &translation.insertions, false);
+        Ok(())
+      }
+    }
+  }
+
+  pub fn finish(&mut self) -> Result<(), Report> {
+    self.writers.values_mut().try_for_each(FastaMsaWriter::finish)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::{Nuc, from_nuc_seq, to_nuc_seq};
+  use pretty_assertions::assert_eq;
+
+  fn ins(pos: i32, seq: &str) -> Result<Insertion<Nuc>, Report> {
+    Ok(Insertion {
+      pos,
+      ins: to_nuc_seq(seq)?,
+    })
+  }
+
+  #[test]
+  fn restores_insertions_into_merged_columns() -> Result<(), Report> {
+    let insertions = [
+      vec![ins(-1, "T")?, ins(1, "A")?],
+      vec![ins(1, "CCC")?, ins(3, "GG")?],
+      vec![],
+      vec![ins(-1, "TT")?],
+    ];
+
+    let columns = msa_insertion_columns(insertions.iter().map(Vec::as_slice));
+    assert_eq!(columns, MsaInsertionColumns::from([(-1, 2), (1, 3), (3, 2)]));
+
+    let seqs = ["ACGT", "AC-T", "ACGT", "--GT"]
+      .into_iter()
+      .map(to_nuc_seq)
+      .collect::<Result<Vec<_>, _>>()?;
+
+    let actual = seqs
+      .iter()
+      .zip(&insertions)
+      .map(|(seq, insertions)| from_nuc_seq(&msa_restore_insertions(seq, insertions, &columns)))
+      .collect::<Vec<_>>();
+
+    assert_eq!(actual, vec!["-TACA--GT--", "--ACCCC-TGG", "--AC---GT--", "TT-----GT--"]);
+    Ok(())
+  }
+}
@@ -81,6 +81,17 @@ pub fn read_file_to_string(filepath: impl AsRef<Path>) -> Result<String, Report>
   Ok(data)
 }
 
+/// Reads entire file into a byte vector, decompressing if necessary
+pub fn read_file_to_bytes(filepath: impl AsRef<Path>) -> Result<Vec<u8>, Report> {
+  let filepath = filepath.as_ref();
+  let mut file = open_file_or_stdin(Some(&filepath))?;
+  let mut data = vec![];
+  file
+    .read_to_end(&mut data)
+    .wrap_err_with(|| format!("When reading file: {}", filepath.display()))?;
+  Ok(data)
+}
+
 /// Reads entire reader into a string.
 /// Compared to `std::fs::read_to_string` uses buffered reader
 pub fn read_reader_to_string(reader: impl Read) -> Result<String, Report> {
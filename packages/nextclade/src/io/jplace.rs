@@ -0,0 +1,120 @@
+use crate::io::json::{JsonPretty, json_write};
+use crate::io::nwk_writer::nwk_write_to_string_with_edge_nums;
+use crate::make_internal_report;
+use crate::tree::tree::AuspiceGraph;
+use crate::tree::tree_find_nearest_node::TreePlacementInfo;
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::info::this_package_version_str;
+use eyre::{Report, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Placement fields, in the order they appear in each entry of `p` array
+pub const JPLACE_FIELDS: [&str; 4] = ["edge_num", "like_weight_ratio", "distal_length", "pendant_length"];
+
+/// Phylogenetic placements in jplace format (version 3), as understood by gappa, guppy and other tools.
+///
+/// See: Matsen et al. (2012) "A format for phylogenetic placements", https://doi.org/10.1371/journal.pone.0031009
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Jplace {
+  /// Reference tree in Newick format with edge numbers in curly braces
+  pub tree: String,
+  pub placements: Vec<JplacePlacement>,
+  pub fields: Vec<String>,
+  pub version: u32,
+  pub metadata: JplaceMetadata,
+}
+
+/// Placements of one query sequence. Each entry of `p` contains values for the fields listed in `Jplace::fields`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JplacePlacement {
+  pub p: Vec<(usize, f64, f64, f64)>,
+  pub n: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JplaceMetadata {
+  pub invocation: String,
+  pub software: String,
+}
+
+impl Jplace {
+  /// Creates jplace placements of query sequences on the reference tree.
+  ///
+  /// The graph is expected to contain only the reference tree, i.e. new nodes should not be attached yet.
+  ///
+  /// Each query is placed onto the edges leading to its equally good placement candidates, weighted proportionally to
+  /// the candidates' placement priors. The query is attached at the candidate node itself, so the distal length is
+  /// always zero, and the pendant length is the length of the branch formed by the private mutations of the query.
+  pub fn from_outputs(graph: &AuspiceGraph, outputs: &[NextcladeOutputs], invocation: &str) -> Result<Self, Report> {
+    let (tree, edge_nums) = nwk_write_to_string_with_edge_nums(graph)?;
+
+    let placements = outputs
+      .iter()
+      .map(|output| {
+        let fallback_candidate = TreePlacementInfo {
+          node_key: output.nearest_node_id,
+          distance: 0.0,
+          prior: 1.0,
+        };
+
+        let candidates = if output.placement_candidates.is_empty() {
+          std::slice::from_ref(&fallback_candidate)
+        } else {
+          output.placement_candidates.as_slice()
+        };
+
+        let nearest_node_div = graph.get_node(output.nearest_node_id)?.payload().node_attrs.div;
+        let pendant_length = (output.divergence - nearest_node_div.unwrap_or_default()).max(0.0);
+
+        let total_prior: f64 = candidates.iter().map(|candidate| candidate.prior).sum();
+
+        let p = candidates
+          .iter()
+          .map(|candidate| {
+            let edge_num = edge_nums
+              .get(&candidate.node_key)
+              .copied()
+              .ok_or_else(|| make_internal_report!("Edge number not found for tree node '{}'", candidate.node_key))?;
+
+            let like_weight_ratio = if total_prior > 0.0 {
+              candidate.prior / total_prior
+            } else {
+              1.0 / candidates.len() as f64
+            };
+
+            Ok((edge_num, like_weight_ratio, 0.0, pendant_length))
+          })
+          .collect::<Result<Vec<_>, Report>>()?;
+
+        Ok(JplacePlacement {
+          p,
+          n: vec![output.seq_name.clone()],
+        })
+      })
+      .collect::<Result<Vec<_>, Report>>()?;
+
+    Ok(Self {
+      tree,
+      placements,
+      fields: JPLACE_FIELDS.iter().map(|&field| field.to_owned()).collect(),
+      version: 3,
+      metadata: JplaceMetadata {
+        invocation: invocation.to_owned(),
+        software: format!("nextclade {}", this_package_version_str()),
+      },
+    })
+  }
+}
+
+pub fn jplace_write_to_file(
+  filepath: impl AsRef<Path>,
+  graph: &AuspiceGraph,
+  outputs: &[NextcladeOutputs],
+  invocation: &str,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let jplace = Jplace::from_outputs(graph, outputs, invocation)?;
+  json_write(filepath, &jplace, JsonPretty(true))
+    .wrap_err_with(|| format!("When writing placements to jplace file: {}", filepath.display()))
+}
@@ -1,24 +1,38 @@
+pub mod bam;
+pub mod clade_consensus;
+pub mod clade_mutation_prevalence;
 pub mod compression;
 pub mod concat;
 pub mod console;
 pub mod csv;
 pub mod dataset;
+pub mod distance_matrix;
 pub mod fasta;
+pub mod fasta_msa;
 pub mod file;
 pub mod fs;
 pub mod genbank_tbl;
 pub mod gff3_encoding;
 pub mod gff3_reader;
 pub mod gff3_writer;
+pub mod jplace;
 pub mod json;
 pub mod json_schema;
 pub mod ndjson;
+pub mod nextclade_bed;
 pub mod nextclade_csv;
 pub mod nextclade_csv_column_config;
 pub mod nextclade_csv_row;
+pub mod nextclade_mutations_tsv;
+pub mod nwk_reader;
 pub mod nwk_writer;
 pub mod parse_pos;
+pub mod protobuf;
 pub mod results_json;
+pub mod run_summary;
+pub mod sam;
 pub mod schema_version;
+pub mod site_coverage;
+pub mod usher_mat;
 pub mod xlsx;
 pub mod yaml;
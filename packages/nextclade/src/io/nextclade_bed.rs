@@ -0,0 +1,230 @@
+use crate::coord::coord_map_cds_to_global::cds_range_to_ref_ranges;
+use crate::coord::position::PositionLike;
+use crate::coord::range::{NucRefGlobalRange, NucRefLocalRange};
+use crate::gene::gene::GeneStrand;
+use crate::gene::gene_map::GeneMap;
+use crate::io::file::create_file_or_stdout;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+/// Category of ranges of a sequence. Each category is written as a separate BED track, in the order of variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, EnumIter)]
+pub enum NextcladeBedTrack {
+  AlignmentRange,
+  Missing,
+  NonAcgtns,
+  Deletions,
+  FrameShifts,
+  UnsequencedCdsRanges,
+}
+
+impl NextcladeBedTrack {
+  pub const fn name(self) -> &'static str {
+    match self {
+      Self::AlignmentRange => "alignmentRange",
+      Self::Missing => "missing",
+      Self::NonAcgtns => "nonACGTNs",
+      Self::Deletions => "deletions",
+      Self::FrameShifts => "frameShifts",
+      Self::UnsequencedCdsRanges => "unsequencedCdsRanges",
+    }
+  }
+
+  pub const fn description(self) -> &'static str {
+    match self {
+      Self::AlignmentRange => "Aligned region of each sequence",
+      Self::Missing => "Missing nucleotides (N)",
+      Self::NonAcgtns => "Ambiguous nucleotides, other than N",
+      Self::Deletions => "Nucleotide deletions",
+      Self::FrameShifts => "Frame shifts",
+      Self::UnsequencedCdsRanges => "Unsequenced ranges of CDSs",
+    }
+  }
+}
+
+/// One interval on the reference sequence, which corresponds to a line of a BED file
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BedInterval {
+  pub range: NucRefGlobalRange,
+  pub name: String,
+  pub strand: Option<GeneStrand>,
+}
+
+/// Collects ranges of each sequence on the reference sequence and writes them as BED tracks, one track per category,
+/// when finished
+pub struct NextcladeBedWriter {
+  filepath: PathBuf,
+  ref_seq_name: String,
+  gene_map: GeneMap,
+  tracks: BTreeMap<NextcladeBedTrack, Vec<BedInterval>>,
+}
+
+impl NextcladeBedWriter {
+  pub fn new(filepath: impl AsRef<Path>, ref_seq_name: &str, gene_map: &GeneMap) -> Self {
+    Self {
+      filepath: filepath.as_ref().to_owned(),
+      ref_seq_name: ref_seq_name.to_owned(),
+      gene_map: gene_map.clone(),
+      tracks: BTreeMap::new(),
+    }
+  }
+
+  pub fn write(&mut self, output: &NextcladeOutputs) -> Result<(), Report> {
+    for (track, interval) in find_bed_intervals(output, &self.gene_map)
+      .wrap_err_with(|| format!("When preparing BED intervals for sequence '{}'", output.seq_name))?
+    {
+      self.tracks.entry(track).or_default().push(interval);
+    }
+    Ok(())
+  }
+
+  pub fn finish(&self) -> Result<(), Report> {
+    let mut file = create_file_or_stdout(&self.filepath)?;
+    bed_write_tracks(&mut file, &self.ref_seq_name, &self.tracks)
+      .wrap_err_with(|| format!("When writing BED file: {}", self.filepath.display()))
+  }
+}
+
+/// Lists ranges of a sequence on the reference sequence, by category. Unsequenced ranges of CDSs are mapped from codon
+/// coordinates to the reference sequence, and can be split into multiple intervals for CDSs with multiple segments.
+pub fn find_bed_intervals(
+  output: &NextcladeOutputs,
+  gene_map: &GeneMap,
+) -> Result<Vec<(NextcladeBedTrack, BedInterval)>, Report> {
+  let seq_name = &output.seq_name;
+  let interval = |range: &NucRefGlobalRange| BedInterval {
+    range: range.clone(),
+    name: seq_name.clone(),
+    strand: None,
+  };
+
+  let mut intervals = vec![(NextcladeBedTrack::AlignmentRange, interval(&output.alignment_range))];
+
+  intervals.extend(
+    output
+      .missing
+      .iter()
+      .map(|missing| (NextcladeBedTrack::Missing, interval(&missing.range))),
+  );
+
+  intervals.extend(
+    output
+      .non_acgtns
+      .iter()
+      .map(|non_acgtn| (NextcladeBedTrack::NonAcgtns, interval(&non_acgtn.range))),
+  );
+
+  intervals.extend(
+    output
+      .deletions
+      .iter()
+      .map(|del| (NextcladeBedTrack::Deletions, interval(del.range()))),
+  );
+
+  for frame_shift in &output.frame_shifts {
+    for range in &frame_shift.nuc_abs {
+      intervals.push((
+        NextcladeBedTrack::FrameShifts,
+        BedInterval {
+          range: range.clone(),
+          name: format!("{seq_name}:{}", frame_shift.cds_name),
+          strand: None,
+        },
+      ));
+    }
+  }
+
+  for (cds_name, ranges) in &output.aa_unsequenced_ranges {
+    let cds = gene_map.get_cds(cds_name)?;
+    for range in ranges {
+      let nuc_range = NucRefLocalRange::from_isize(range.begin.as_isize() * 3, range.end.as_isize() * 3);
+      for (range, strand) in cds_range_to_ref_ranges(cds, &nuc_range) {
+        intervals.push((
+          NextcladeBedTrack::UnsequencedCdsRanges,
+          BedInterval {
+            range,
+            name: format!("{seq_name}:{cds_name}"),
+            strand: Some(strand),
+          },
+        ));
+      }
+    }
+  }
+
+  Ok(intervals)
+}
+
+/// Writes BED tracks. Intervals are sorted by position within each track.
+///
+/// See: https://genome.ucsc.edu/FAQ/FAQformat.html#format1
+pub fn bed_write_tracks(
+  w: &mut impl Write,
+  ref_seq_name: &str,
+  tracks: &BTreeMap<NextcladeBedTrack, Vec<BedInterval>>,
+) -> Result<(), Report> {
+  for track in NextcladeBedTrack::iter() {
+    let Some(intervals) = tracks.get(&track) else {
+      continue;
+    };
+
+    writeln!(
+      w,
+      "track name=\"{}\" description=\"{}\"",
+      track.name(),
+      track.description()
+    )?;
+
+    for BedInterval { range, name, strand } in intervals.iter().sorted() {
+      let strand = match strand {
+        Some(GeneStrand::Forward) => "+",
+        Some(GeneStrand::Reverse) => "-",
+        None => ".",
+      };
+      writeln!(w, "{ref_seq_name}\t{}\t{}\t{name}\t0\t{strand}", range.begin, range.end)?;
+    }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn writes_one_track_per_category() -> Result<(), Report> {
+    let interval = |begin: usize, end: usize, name: &str, strand: Option<GeneStrand>| BedInterval {
+      range: NucRefGlobalRange::from_usize(begin, end),
+      name: name.to_owned(),
+      strand,
+    };
+    let tracks = BTreeMap::from([
+      (
+        NextcladeBedTrack::UnsequencedCdsRanges,
+        vec![interval(30, 90, "s1:ORF1", Some(GeneStrand::Reverse))],
+      ),
+      (
+        NextcladeBedTrack::AlignmentRange,
+        vec![interval(12, 1000, "s2", None), interval(0, 990, "s1", None)],
+      ),
+    ]);
+
+    let mut buf = vec![];
+    bed_write_tracks(&mut buf, "ref", &tracks)?;
+    assert_eq!(
+      String::from_utf8(buf)?,
+      "track name=\"alignmentRange\" description=\"Aligned region of each sequence\"\n\
+       ref\t0\t990\ts1\t0\t.\n\
+       ref\t12\t1000\ts2\t0\t.\n\
+       track name=\"unsequencedCdsRanges\" description=\"Unsequenced ranges of CDSs\"\n\
+       ref\t30\t90\ts1:ORF1\t0\t-\n"
+    );
+    Ok(())
+  }
+}
@@ -27,9 +27,13 @@ pub enum CsvColumnCategory {
   ErrsWarns,
   Qc,
   Primers,
+  AmbiguousAa,
   Dynamic,
 }
 
+/// Categories of CSV columns which are only written when requested explicitly
+const CSV_OPT_IN_CATEGORIES: &[CsvColumnCategory] = &[CsvColumnCategory::AmbiguousAa];
+
 pub type CsvColumnConfigMap = IndexMap<CsvColumnCategory, IndexMap<String, bool>>;
 
 // Configuration for enabling/disabling CSV columns or categories of them
@@ -71,8 +75,10 @@ impl CsvColumnConfig {
       }
     })?;
 
-    if output_columns_selection.is_empty() || categories.contains(&CsvColumnCategory::All) {
+    if output_columns_selection.is_empty() {
       Ok(Self::default())
+    } else if categories.contains(&CsvColumnCategory::All) {
+      Ok(Self::all())
     } else {
       let include_dynamic = categories.contains(&CsvColumnCategory::Dynamic);
 
@@ -97,12 +103,26 @@ impl CsvColumnConfig {
       })
     }
   }
+
+  /// Configuration with all columns, including opt-in categories
+  pub fn all() -> Self {
+    Self {
+      categories: CSV_COLUMN_CONFIG_MAP_DEFAULT.clone(),
+      ..Self::default()
+    }
+  }
 }
 
 impl Default for CsvColumnConfig {
   fn default() -> Self {
+    let categories = CSV_COLUMN_CONFIG_MAP_DEFAULT
+      .iter()
+      .filter(|(category, _)| !CSV_OPT_IN_CATEGORIES.contains(category))
+      .map(|(category, columns)| (category.clone(), columns.clone()))
+      .collect();
+
     Self {
-      categories: CSV_COLUMN_CONFIG_MAP_DEFAULT.clone(),
+      categories,
       individual: vec![],
       include_dynamic: true,
       include_clade_founder_muts: true,
@@ -118,6 +138,8 @@ pub static CSV_COLUMN_CONFIG_MAP_DEFAULT: LazyLock<CsvColumnConfigMap> = LazyLoc
       o!("index") => true,
       o!("seqName") => true,
       o!("clade") => true,
+      o!("cladeProbability") => true,
+      o!("cladePosterior") => true,
       o!("qc.overallScore") => true,
       o!("qc.overallStatus") => true,
       o!("totalSubstitutions") => true,
@@ -206,6 +228,10 @@ pub static CSV_COLUMN_CONFIG_MAP_DEFAULT: LazyLock<CsvColumnConfigMap> = LazyLoc
       o!("failedCdses") => true,
       o!("warnings") => true,
       o!("errors") => true,
+    },
+    CsvColumnCategory::AmbiguousAa => indexmap! {
+      o!("totalAmbiguousAa") => true,
+      o!("ambiguousAa") => true,
     }
   }
 });
@@ -14,7 +14,9 @@ use crate::o;
 use crate::qc::qc_config::StopCodonLocation;
 use crate::qc::qc_rule_snp_clusters::ClusteredSnp;
 use crate::translate::frame_shifts_translate::FrameShift;
-use crate::types::outputs::{NextcladeOutputs, PeptideWarning, PhenotypeValue};
+use crate::translate::translate_genes::AmbiguousAa;
+use crate::tree::tree_find_clade_posterior::CladePosterior;
+use crate::types::outputs::{NextcladeOutputs, NucWarning, PeptideWarning, PhenotypeValue};
 use crate::utils::num::is_int;
 use eyre::Report;
 use itertools::Itertools;
@@ -59,11 +61,14 @@ impl NextcladeResultsCsvRow {
       total_aminoacid_insertions,
       unknown_aa_ranges,
       total_unknown_aa,
+      ambiguous_aas,
+      total_ambiguous_aa,
       alignment_range,
       alignment_score,
       pcr_primer_changes,
       total_pcr_primer_changes,
       clade,
+      clade_posterior,
       private_nuc_mutations,
       private_aa_mutations,
       missing_cdses,
@@ -75,6 +80,7 @@ impl NextcladeResultsCsvRow {
       custom_node_attributes,
       is_reverse_complement,
       warnings,
+      nuc_warnings,
       aa_motifs,
       ref_nodes,
       ref_node_search_results,
@@ -175,6 +181,10 @@ impl NextcladeResultsCsvRow {
     self.add_entry("seqName", seq_name)?;
 
     self.add_entry("clade", &clade.as_deref().unwrap_or_default())?;
+    if let Some(clade_posterior) = clade_posterior {
+      self.add_entry("cladeProbability", &format_qc_score(clade_posterior.probability))?;
+      self.add_entry("cladePosterior", &format_clade_posterior(clade_posterior))?;
+    }
     self.add_entry("qc.overallScore", &format_qc_score(qc.overall_score))?;
     self.add_entry("qc.overallStatus", &qc.overall_status.to_string())?;
     self.add_entry("totalSubstitutions", &total_substitutions.to_string())?;
@@ -188,6 +198,7 @@ impl NextcladeResultsCsvRow {
     self.add_entry("totalAminoacidDeletions", &total_aminoacid_deletions.to_string())?;
     self.add_entry("totalAminoacidInsertions", &total_aminoacid_insertions.to_string())?;
     self.add_entry("totalUnknownAa", &total_unknown_aa.to_string())?;
+    self.add_entry("totalAmbiguousAa", &total_ambiguous_aa.to_string())?;
     self.add_entry("totalMissing", &total_missing.to_string())?;
     self.add_entry("totalNonACGTNs", &total_non_acgtns.to_string())?;
     self.add_entry("totalPcrPrimerChanges", &total_pcr_primer_changes.to_string())?;
@@ -300,6 +311,10 @@ impl NextcladeResultsCsvRow {
       "unknownAaRanges",
       &format_unknown_aa_ranges(unknown_aa_ranges, ARRAY_ITEM_DELIMITER),
     )?;
+    self.add_entry(
+      "ambiguousAa",
+      &format_ambiguous_aas(ambiguous_aas, ARRAY_ITEM_DELIMITER),
+    )?;
     self.add_entry("missing", &format_missings(missing, ARRAY_ITEM_DELIMITER))?;
     self.add_entry("nonACGTNs", &format_non_acgtns(non_acgtns, ARRAY_ITEM_DELIMITER))?;
     self.add_entry(
@@ -433,7 +448,11 @@ impl NextcladeResultsCsvRow {
     self.add_entry("failedCdses", &format_failed_cdses(missing_cdses, ARRAY_ITEM_DELIMITER))?;
     self.add_entry(
       "warnings",
-      &warnings.iter().map(|PeptideWarning { warning, .. }| warning).join(";"),
+      &nuc_warnings
+        .iter()
+        .map(|NucWarning { warning }| warning)
+        .chain(warnings.iter().map(|PeptideWarning { warning, .. }| warning))
+        .join(";"),
     )?;
     self.add_entry("errors", &"")?;
 
@@ -654,6 +673,25 @@ pub fn format_unknown_aa_ranges(unknown_aa_ranges: &[CdsAaRange], delimiter: &st
     .join(delimiter)
 }
 
+#[inline]
+pub fn format_ambiguous_aas(ambiguous_aas: &[AmbiguousAa], delimiter: &str) -> String {
+  ambiguous_aas
+    .iter()
+    .map(
+      |AmbiguousAa {
+         cds_name,
+         pos,
+         ref_aa,
+         qry_aas,
+       }: &AmbiguousAa| {
+        let qry_aas = qry_aas.iter().join("/");
+        let pos_one_based = pos + 1;
+        format!("{cds_name}:{ref_aa}{pos_one_based}{qry_aas}")
+      },
+    )
+    .join(delimiter)
+}
+
 #[inline]
 pub fn format_frame_shifts(frame_shifts: &[FrameShift], delimiter: &str) -> String {
   frame_shifts
@@ -699,6 +737,17 @@ pub fn format_failed_cdses(failed_cdses: &[String], delimiter: &str) -> String {
   failed_cdses.join(delimiter)
 }
 
+/// Formats clade posterior distribution as a list of `clade:probability`, sorted by decreasing probability
+#[inline]
+pub fn format_clade_posterior(clade_posterior: &CladePosterior) -> String {
+  clade_posterior
+    .distribution
+    .iter()
+    .sorted_by(|(_, p1), (_, p2)| p2.total_cmp(p1))
+    .map(|(clade, probability)| format!("{clade}:{}", format_qc_score(*probability)))
+    .join(ARRAY_ITEM_DELIMITER)
+}
+
 #[inline]
 pub fn format_qc_score(score: f64) -> String {
   if !is_int(score) {
@@ -0,0 +1,456 @@
+use crate::align::insertions_strip::{AaIns, Insertion};
+use crate::alphabet::aa::from_aa_seq;
+use crate::alphabet::nuc::{Nuc, from_nuc_seq};
+use crate::analyze::aa_del::AaDel;
+use crate::analyze::aa_sub::AaSub;
+use crate::analyze::nuc_del::NucDelRange;
+use crate::analyze::nuc_sub::NucSub;
+use crate::analyze::private_mutations::{PrivateMutations, is_same_aa_sub, is_same_nuc_sub};
+use crate::coord::position::PositionLike;
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::nextclade_csv_row::{
+  ARRAY_ITEM_DELIMITER, format_aa_insertions, format_frame_shifts, format_nuc_deletions, format_nuc_insertions,
+};
+use crate::o;
+use crate::translate::frame_shifts_translate::FrameShift;
+use crate::tree::tree::{AuspiceRefNodesDesc, CladeNodeAttrKeyDesc};
+use crate::types::outputs::NextcladeOutputs;
+use eyre::Report;
+use itertools::{Itertools, chain};
+use std::path::Path;
+use std::slice::from_ref;
+
+/// Writes mutations of each sequence in long format: one row per (sequence, mutation) pair
+pub struct NextcladeMutationsTsvFileWriter {
+  writer: CsvVecFileWriter,
+  founder_attrs: Vec<String>,
+  ref_nodes: AuspiceRefNodesDesc,
+}
+
+impl NextcladeMutationsTsvFileWriter {
+  pub fn new(
+    filepath: impl AsRef<Path>,
+    clade_node_attr_descs: &[CladeNodeAttrKeyDesc],
+    ref_nodes: &AuspiceRefNodesDesc,
+  ) -> Result<Self, Report> {
+    let founder_attrs = mutations_founder_attrs(clade_node_attr_descs);
+    let headers = mutations_headers(&founder_attrs, ref_nodes);
+
+    let writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+    Ok(Self {
+      writer,
+      founder_attrs,
+      ref_nodes: ref_nodes.clone(),
+    })
+  }
+
+  pub fn write(&mut self, output: &NextcladeOutputs) -> Result<(), Report> {
+    for row in format_mutation_rows(output, &self.founder_attrs, &self.ref_nodes) {
+      self.writer.write(row)?;
+    }
+    Ok(())
+  }
+}
+
+/// Clade and clade-like attributes, relative to the founders of which private mutations are reported
+pub fn mutations_founder_attrs(clade_node_attr_descs: &[CladeNodeAttrKeyDesc]) -> Vec<String> {
+  chain!(
+    [o!("clade")],
+    clade_node_attr_descs
+      .iter()
+      .filter(|desc| !desc.skip_as_reference)
+      .map(|desc| desc.name.clone())
+  )
+  .collect_vec()
+}
+
+/// Column headers of the mutations in long format, as produced by `format_mutation_rows()`
+pub fn mutations_headers(founder_attrs: &[String], ref_nodes: &AuspiceRefNodesDesc) -> Vec<String> {
+  chain!(
+    [
+      "index",
+      "seqName",
+      "clade",
+      "type",
+      "mutation",
+      "cds",
+      "pos",
+      "ref",
+      "qry",
+      "refCodon",
+      "qryCodon",
+      "isPrivate",
+      "isReversion",
+      "labels",
+    ]
+    .map(str::to_owned),
+    founder_attrs
+      .iter()
+      .map(|attr| format!("isPrivateRelativeToFounder['{attr}']")),
+    ref_nodes
+      .search
+      .iter()
+      .map(|desc| format!("isPrivateRelativeTo['{}']", desc.display_name_or_name())),
+  )
+  .collect_vec()
+}
+
+/// Mutation of a query sequence, as listed in the mutations TSV file
+enum Mutation<'a> {
+  NucSub(&'a NucSub),
+  NucDel(&'a NucDelRange),
+  NucIns(&'a Insertion<Nuc>),
+  AaSub(&'a AaSub),
+  AaDel(&'a AaDel),
+  AaIns(&'a AaIns),
+  FrameShift(&'a FrameShift),
+}
+
+/// Whether the mutation is among the private mutations. Not applicable to insertions and frame shifts.
+fn is_private(muts: &PrivateMutations, mutation: &Mutation) -> Option<bool> {
+  match mutation {
+    Mutation::NucSub(sub) => Some(muts.is_private_nuc_sub(sub)),
+    Mutation::NucDel(del) => Some(muts.is_private_nuc_del(del)),
+    Mutation::AaSub(sub) => Some(muts.is_private_aa_sub(sub)),
+    Mutation::AaDel(del) => Some(muts.is_private_aa_del(del)),
+    Mutation::NucIns(_) | Mutation::AaIns(_) | Mutation::FrameShift(_) => None,
+  }
+}
+
+/// Columns describing the mutation itself, as opposed to its relation to the reference tree
+#[derive(Default)]
+struct MutationColumns {
+  type_: &'static str,
+  name: String,
+  cds: String,
+  pos: String,
+  ref_: String,
+  qry: String,
+  ref_codon: String,
+  qry_codon: String,
+  is_reversion: Option<bool>,
+  labels: String,
+}
+
+pub fn format_mutation_rows(
+  output: &NextcladeOutputs,
+  founder_attrs: &[String],
+  ref_nodes: &AuspiceRefNodesDesc,
+) -> Vec<Vec<String>> {
+  let own = PrivateMutations {
+    nuc: &output.private_nuc_mutations,
+    aa: &output.private_aa_mutations,
+  };
+
+  let founders = founder_attrs.iter().map(|attr| {
+    let info = if attr == "clade" {
+      output.clade_founder_info.as_ref()
+    } else {
+      output.clade_node_attr_founder_info.get(attr)
+    };
+    info.map(PrivateMutations::from_founder)
+  });
+
+  let relatives = ref_nodes.search.iter().map(|desc| {
+    let nuc = output
+      .relative_nuc_mutations
+      .iter()
+      .find(|rel| rel.search.search.name == desc.name)
+      .and_then(|rel| rel.result.as_ref());
+    let aa = output
+      .relative_aa_mutations
+      .iter()
+      .find(|rel| rel.search.search.name == desc.name)
+      .and_then(|rel| rel.result.as_ref());
+    nuc.zip(aa).map(|(nuc, aa)| PrivateMutations {
+      nuc: &nuc.muts,
+      aa: &aa.muts,
+    })
+  });
+
+  let contexts = founders.chain(relatives).collect_vec();
+
+  // Reversions are not differences from the reference sequence, so they are only present among private mutations
+  let nuc_reversions = &output.private_nuc_mutations.reversion_substitutions;
+  let aa_reversions = output
+    .private_aa_mutations
+    .values()
+    .flat_map(|muts| &muts.reversion_substitutions);
+
+  let mutations = chain!(
+    output.substitutions.iter().chain(nuc_reversions).map(Mutation::NucSub),
+    output.deletions.iter().map(Mutation::NucDel),
+    output.insertions.iter().map(Mutation::NucIns),
+    output.aa_substitutions.iter().chain(aa_reversions).map(Mutation::AaSub),
+    output.aa_deletions.iter().map(Mutation::AaDel),
+    output.aa_insertions.iter().map(Mutation::AaIns),
+    output.frame_shifts.iter().map(Mutation::FrameShift),
+  );
+
+  mutations
+    .map(|mutation| {
+      let MutationColumns {
+        type_,
+        name,
+        cds,
+        pos,
+        ref_,
+        qry,
+        ref_codon,
+        qry_codon,
+        is_reversion,
+        labels,
+      } = mutation_columns(&mutation, output);
+
+      let context = contexts.iter().map(|muts| match muts {
+        Some(muts) => format_flag(is_private(muts, &mutation)),
+        None => o!("N/A"),
+      });
+
+      chain!(
+        [
+          output.index.to_string(),
+          output.seq_name.clone(),
+          output.clade.clone().unwrap_or_default(),
+          o!(type_),
+          name,
+          cds,
+          pos,
+          ref_,
+          qry,
+          ref_codon,
+          qry_codon,
+          format_flag(is_private(&own, &mutation)),
+          format_flag(is_reversion),
+          labels,
+        ],
+        context
+      )
+      .collect_vec()
+    })
+    .collect_vec()
+}
+
+fn mutation_columns(mutation: &Mutation, output: &NextcladeOutputs) -> MutationColumns {
+  match mutation {
+    Mutation::NucSub(sub) => {
+      let private = &output.private_nuc_mutations;
+      let labels = private
+        .labeled_substitutions
+        .iter()
+        .find(|labeled| is_same_nuc_sub(&labeled.substitution, sub))
+        .map(|labeled| labeled.labels.join(ARRAY_ITEM_DELIMITER))
+        .unwrap_or_default();
+      MutationColumns {
+        type_: "nucSub",
+        name: sub.to_string(),
+        pos: (sub.pos + 1).to_string(),
+        ref_: sub.ref_nuc.to_string(),
+        qry: sub.qry_nuc.to_string(),
+        is_reversion: Some(private.reversion_substitutions.contains(sub)),
+        labels,
+        ..MutationColumns::default()
+      }
+    }
+    Mutation::NucDel(del) => MutationColumns {
+      type_: "nucDel",
+      name: format_nuc_deletions(from_ref(*del), ""),
+      pos: (del.range().begin + 1).to_string(),
+      qry: o!("-"),
+      ..MutationColumns::default()
+    },
+    Mutation::NucIns(ins) => MutationColumns {
+      type_: "nucIns",
+      name: format_nuc_insertions(from_ref(*ins), ""),
+      pos: (ins.pos + 1).to_string(),
+      qry: from_nuc_seq(&ins.ins),
+      ..MutationColumns::default()
+    },
+    Mutation::AaSub(sub) => {
+      let private = output.private_aa_mutations.get(&sub.cds_name);
+      let labels = private
+        .and_then(|private| {
+          private
+            .labeled_substitutions
+            .iter()
+            .find(|labeled| is_same_aa_sub(&labeled.substitution, sub))
+        })
+        .map(|labeled| labeled.labels.join(ARRAY_ITEM_DELIMITER))
+        .unwrap_or_default();
+      let (ref_codon, qry_codon) = find_codons(output, &sub.cds_name, sub.pos.as_usize());
+      MutationColumns {
+        type_: "aaSub",
+        name: sub.to_string(),
+        cds: sub.cds_name.clone(),
+        pos: (sub.pos + 1).to_string(),
+        ref_: sub.ref_aa.to_string(),
+        qry: sub.qry_aa.to_string(),
+        ref_codon,
+        qry_codon,
+        is_reversion: Some(private.is_some_and(|private| private.reversion_substitutions.contains(sub))),
+        labels,
+      }
+    }
+    Mutation::AaDel(del) => {
+      let (ref_codon, qry_codon) = find_codons(output, &del.cds_name, del.pos.as_usize());
+      MutationColumns {
+        type_: "aaDel",
+        name: del.to_string(),
+        cds: del.cds_name.clone(),
+        pos: (del.pos + 1).to_string(),
+        ref_: del.ref_aa.to_string(),
+        qry: o!("-"),
+        ref_codon,
+        qry_codon,
+        ..MutationColumns::default()
+      }
+    }
+    Mutation::AaIns(ins) => MutationColumns {
+      type_: "aaIns",
+      name: format_aa_insertions(from_ref(*ins), ""),
+      cds: ins.cds.clone(),
+      pos: (ins.pos + 1).to_string(),
+      qry: from_aa_seq(&ins.ins),
+      ..MutationColumns::default()
+    },
+    Mutation::FrameShift(frame_shift) => MutationColumns {
+      type_: "frameShift",
+      name: format_frame_shifts(from_ref(*frame_shift), ""),
+      cds: frame_shift.cds_name.clone(),
+      pos: (frame_shift.codon.begin + 1).to_string(),
+      ..MutationColumns::default()
+    },
+  }
+}
+
+/// Finds reference and query nucleotide codons of an amino acid change
+fn find_codons(output: &NextcladeOutputs, cds_name: &str, pos: usize) -> (String, String) {
+  output
+    .aa_changes_groups
+    .iter()
+    .filter(|group| group.name == cds_name)
+    .flat_map(|group| &group.changes)
+    .find(|change| change.pos.as_usize() == pos)
+    .map(|change| (from_nuc_seq(&change.ref_triplet), from_nuc_seq(&change.qry_triplet)))
+    .unwrap_or_default()
+}
+
+fn format_flag(flag: Option<bool>) -> String {
+  flag.map_or_else(String::new, |flag| flag.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::analyze::aa_sub::AaSubLabeled;
+  use crate::analyze::find_clade_founder::CladeNodeAttrFounderInfo;
+  use crate::analyze::find_private_aa_mutations::PrivateAaMutations;
+  use crate::analyze::find_private_nuc_mutations::PrivateNucMutations;
+  use crate::analyze::nuc_sub::NucSubLabeled;
+  use crate::graph::node::GraphNodeKey;
+  use pretty_assertions::assert_eq;
+  use std::collections::BTreeMap;
+  use std::str::FromStr;
+
+  #[test]
+  fn formats_one_row_per_mutation() -> Result<(), Report> {
+    let sub = NucSub::from_str("A10C")?;
+    let reversion = NucSub::from_str("C15T")?;
+    let aa_sub = AaSub::from_str("S:N501Y")?;
+
+    let output = NextcladeOutputs {
+      index: 3,
+      seq_name: o!("seq"),
+      clade: Some(o!("20A")),
+      substitutions: vec![sub.clone()],
+      deletions: vec![NucDelRange::from_usize(20, 23)],
+      aa_substitutions: vec![aa_sub.clone()],
+      private_nuc_mutations: PrivateNucMutations {
+        private_substitutions: vec![sub.clone(), reversion.clone()],
+        reversion_substitutions: vec![reversion],
+        labeled_substitutions: vec![NucSubLabeled {
+          substitution: sub,
+          labels: vec![o!("20B"), o!("20C")],
+        }],
+        ..PrivateNucMutations::default()
+      },
+      private_aa_mutations: BTreeMap::from([(
+        o!("S"),
+        PrivateAaMutations {
+          cds_name: o!("S"),
+          private_substitutions: vec![aa_sub],
+          ..PrivateAaMutations::default()
+        },
+      )]),
+      ..NextcladeOutputs::default()
+    };
+
+    let rows = format_mutation_rows(&output, &[o!("clade")], &AuspiceRefNodesDesc::default());
+
+    #[rustfmt::skip]
+    let expected = vec![
+      vec!["3", "seq", "20A", "nucSub", "A10C", "", "10", "A", "C", "", "", "true", "false", "20B,20C", "N/A"],
+      vec!["3", "seq", "20A", "nucSub", "C15T", "", "15", "C", "T", "", "", "true", "true", "", "N/A"],
+      vec!["3", "seq", "20A", "nucDel", "21-23", "", "21", "", "-", "", "", "false", "", "", "N/A"],
+      vec!["3", "seq", "20A", "aaSub", "S:N501Y", "S", "501", "N", "Y", "", "", "true", "false", "", "N/A"],
+    ];
+
+    assert_eq!(rows, expected);
+    Ok(())
+  }
+
+  #[test]
+  fn matches_private_mutations_at_sites_mutated_in_node() -> Result<(), Report> {
+    // Nearest node and clade founder have G20 and T501, so the private mutations start from these letters rather than
+    // from the reference letters
+    let nuc = PrivateNucMutations {
+      private_substitutions: vec![NucSub::from_str("G20C")?],
+      labeled_substitutions: vec![NucSubLabeled {
+        substitution: NucSub::from_str("G20C")?,
+        labels: vec![o!("21J")],
+      }],
+      ..PrivateNucMutations::default()
+    };
+    let aa = BTreeMap::from([(
+      o!("S"),
+      PrivateAaMutations {
+        cds_name: o!("S"),
+        private_substitutions: vec![AaSub::from_str("S:T501Y")?],
+        labeled_substitutions: vec![AaSubLabeled {
+          substitution: AaSub::from_str("S:T501Y")?,
+          labels: vec![o!("21K")],
+        }],
+        ..PrivateAaMutations::default()
+      },
+    )]);
+
+    let output = NextcladeOutputs {
+      index: 0,
+      seq_name: o!("seq"),
+      substitutions: vec![NucSub::from_str("A20C")?],
+      aa_substitutions: vec![AaSub::from_str("S:N501Y")?],
+      private_nuc_mutations: nuc.clone(),
+      private_aa_mutations: aa.clone(),
+      clade_founder_info: Some(CladeNodeAttrFounderInfo {
+        key: o!("clade"),
+        value: o!("21K"),
+        node_key: GraphNodeKey::default(),
+        node_name: o!("founder"),
+        nuc_mutations: nuc,
+        aa_mutations: aa,
+      }),
+      ..NextcladeOutputs::default()
+    };
+
+    let rows = format_mutation_rows(&output, &[o!("clade")], &AuspiceRefNodesDesc::default());
+
+    #[rustfmt::skip]
+    let expected = vec![
+      vec!["0", "seq", "", "nucSub", "A20C", "", "20", "A", "C", "", "", "true", "false", "21J", "true"],
+      vec!["0", "seq", "", "aaSub", "S:N501Y", "S", "501", "N", "Y", "", "", "true", "false", "21K", "true"],
+    ];
+
+    assert_eq!(rows, expected);
+    Ok(())
+  }
+}
@@ -0,0 +1,369 @@
+use crate::io::fs::read_file_to_string;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Node of a tree parsed from Newick or Nexus format
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NwkNode {
+  pub name: Option<String>,
+  pub branch_length: Option<f64>,
+  pub children: Vec<NwkNode>,
+}
+
+impl NwkNode {
+  pub const fn is_leaf(&self) -> bool {
+    self.children.is_empty()
+  }
+}
+
+/// Reads a tree from Newick or Nexus file. The format is detected from the file contents.
+pub fn nwk_read_file(filepath: impl AsRef<Path>) -> Result<NwkNode, Report> {
+  let filepath = filepath.as_ref();
+  let data = read_file_to_string(filepath)?;
+  nwk_or_nexus_read_str(&data).wrap_err_with(|| format!("When reading tree file: {}", filepath.display()))
+}
+
+/// Parses a tree in either Newick or Nexus format. The format is detected from the contents.
+pub fn nwk_or_nexus_read_str(s: &str) -> Result<NwkNode, Report> {
+  if is_nexus_str(s) {
+    nexus_read_str(s)
+  } else {
+    nwk_read_str(s)
+  }
+}
+
+pub fn is_nexus_str(s: &str) -> bool {
+  s.trim_start()
+    .get(..6)
+    .is_some_and(|header| header.eq_ignore_ascii_case("#NEXUS"))
+}
+
+/// Parses a tree in Newick format.
+///
+/// Supports quoted labels, comments in square brackets (including annotations, like `[&R]` or `[&&NHX:...]`, which
+/// are ignored), and internal node labels.
+pub fn nwk_read_str(s: &str) -> Result<NwkNode, Report> {
+  let mut parser = NwkParser::new(s);
+  let node = parser.parse_node().wrap_err("When parsing Newick tree")?;
+  parser.skip_whitespace_and_comments()?;
+  match parser.peek() {
+    Some(';') | None => Ok(node),
+    Some(c) => make_error!(
+      "When parsing Newick tree: unexpected character '{c}' at position {}. Expected end of tree (';')",
+      parser.pos
+    ),
+  }
+}
+
+/// Parses the first tree of the TREES block of a Nexus file.
+///
+/// Taxon labels are replaced according to the TRANSLATE command, if present.
+pub fn nexus_read_str(s: &str) -> Result<NwkNode, Report> {
+  let commands = split_nexus_commands(s);
+
+  let mut in_trees_block = false;
+  let mut translate = BTreeMap::<String, String>::new();
+  for command in commands {
+    let (keyword, rest) = command
+      .split_once(char::is_whitespace)
+      .map_or((command.as_str(), ""), |(keyword, rest)| (keyword, rest.trim()));
+    let keyword = keyword.to_lowercase();
+
+    match keyword.as_str() {
+      "begin" => in_trees_block = rest.eq_ignore_ascii_case("trees"),
+      "end" | "endblock" => in_trees_block = false,
+      "translate" if in_trees_block => {
+        for entry in rest.split(',') {
+          if let Some((key, value)) = entry.trim().split_once(char::is_whitespace) {
+            translate.insert(key.to_owned(), unquote(value.trim()));
+          }
+        }
+      }
+      "tree" | "utree" if in_trees_block => {
+        let Some((_, nwk)) = rest.split_once('=') else {
+          return make_error!("When parsing Nexus file: TREE command is malformed: expected 'TREE <name> = <newick>'");
+        };
+        let mut tree = nwk_read_str(nwk).wrap_err("When parsing Nexus file")?;
+        if !translate.is_empty() {
+          translate_names(&mut tree, &translate);
+        }
+        return Ok(tree);
+      }
+      _ => {}
+    }
+  }
+
+  make_error!("When parsing Nexus file: no tree found. Expected a TREE command in the TREES block")
+}
+
+/// Splits Nexus contents into commands (terminated by ';'), removing comments and respecting quotes
+fn split_nexus_commands(s: &str) -> Vec<String> {
+  let mut commands = vec![];
+  let mut current = String::new();
+  let mut in_quotes = false;
+  let mut comment_depth = 0_usize;
+  // Skip the `#NEXUS` header
+  let s = s.trim_start().get(6..).unwrap_or_default();
+  for c in s.chars() {
+    match c {
+      '[' if !in_quotes => comment_depth += 1,
+      ']' if !in_quotes && comment_depth > 0 => comment_depth -= 1,
+      _ if comment_depth > 0 => {}
+      '\'' => {
+        in_quotes = !in_quotes;
+        current.push(c);
+      }
+      ';' if !in_quotes => {
+        let command = current.trim();
+        if !command.is_empty() {
+          commands.push(command.to_owned());
+        }
+        current.clear();
+      }
+      _ => current.push(c),
+    }
+  }
+  commands
+}
+
+fn translate_names(node: &mut NwkNode, translate: &BTreeMap<String, String>) {
+  if let Some(name) = &node.name
+    && let Some(translated) = translate.get(name)
+  {
+    node.name = Some(translated.clone());
+  }
+  for child in &mut node.children {
+    translate_names(child, translate);
+  }
+}
+
+fn unquote(s: &str) -> String {
+  s.strip_prefix('\'')
+    .and_then(|s| s.strip_suffix('\''))
+    .map_or_else(|| s.to_owned(), |s| s.replace("''", "'"))
+}
+
+struct NwkParser {
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl NwkParser {
+  fn new(source: &str) -> Self {
+    Self {
+      chars: source.chars().collect(),
+      pos: 0,
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn skip_whitespace_and_comments(&mut self) -> Result<(), Report> {
+    while let Some(c) = self.peek() {
+      if c.is_whitespace() {
+        self.pos += 1;
+      } else if c == '[' {
+        let start = self.pos;
+        let mut depth = 0_usize;
+        loop {
+          match self.peek() {
+            Some('[') => depth += 1,
+            Some(']') => depth -= 1,
+            Some(_) => {}
+            None => return make_error!("Unterminated comment starting at position {start}"),
+          }
+          self.pos += 1;
+          if depth == 0 {
+            break;
+          }
+        }
+      } else {
+        break;
+      }
+    }
+    Ok(())
+  }
+
+  /// Parses a node in an iterative manner, to avoid stack overflow on deep trees
+  fn parse_node(&mut self) -> Result<NwkNode, Report> {
+    // Stack of internal nodes which children are being parsed
+    let mut stack: Vec<NwkNode> = vec![];
+    loop {
+      self.skip_whitespace_and_comments()?;
+      if self.peek() == Some('(') {
+        self.pos += 1;
+        stack.push(NwkNode::default());
+        continue;
+      }
+
+      // Parse a leaf
+      let mut node = NwkNode::default();
+      self.parse_label_and_length(&mut node)?;
+
+      // Attach finished nodes to their parents, closing as many parentheses as necessary
+      loop {
+        self.skip_whitespace_and_comments()?;
+        let Some(mut parent) = stack.pop() else {
+          return Ok(node);
+        };
+        parent.children.push(node);
+        match self.peek() {
+          Some(',') => {
+            self.pos += 1;
+            stack.push(parent);
+            break;
+          }
+          Some(')') => {
+            self.pos += 1;
+            self.parse_label_and_length(&mut parent)?;
+            node = parent;
+          }
+          Some(c) => {
+            return make_error!(
+              "Unexpected character '{c}' at position {}. Expected ',' or ')'",
+              self.pos
+            );
+          }
+          None => return make_error!("Unexpected end of input. Expected ',' or ')'"),
+        }
+      }
+    }
+  }
+
+  fn parse_label_and_length(&mut self, node: &mut NwkNode) -> Result<(), Report> {
+    self.skip_whitespace_and_comments()?;
+    let label = self.parse_label()?;
+    if !label.is_empty() {
+      node.name = Some(label);
+    }
+
+    self.skip_whitespace_and_comments()?;
+    if self.peek() == Some(':') {
+      self.pos += 1;
+      self.skip_whitespace_and_comments()?;
+      let start = self.pos;
+      while self
+        .peek()
+        .is_some_and(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | 'e' | 'E'))
+      {
+        self.pos += 1;
+      }
+      let length: String = self.chars[start..self.pos].iter().collect();
+      let length = length
+        .parse::<f64>()
+        .wrap_err_with(|| format!("When parsing branch length '{length}' at position {start}"))?;
+      node.branch_length = Some(length);
+    }
+    Ok(())
+  }
+
+  fn parse_label(&mut self) -> Result<String, Report> {
+    let mut label = String::new();
+    if self.peek() == Some('\'') {
+      let start = self.pos;
+      self.pos += 1;
+      loop {
+        match self.peek() {
+          Some('\'') if self.chars.get(self.pos + 1) == Some(&'\'') => {
+            label.push('\'');
+            self.pos += 2;
+          }
+          Some('\'') => {
+            self.pos += 1;
+            break;
+          }
+          Some(c) => {
+            label.push(c);
+            self.pos += 1;
+          }
+          None => return make_error!("Unterminated quoted label starting at position {start}"),
+        }
+      }
+    } else {
+      while let Some(c) = self.peek() {
+        if c.is_whitespace() || matches!(c, '(' | ')' | ',' | ':' | ';' | '[') {
+          break;
+        }
+        label.push(c);
+        self.pos += 1;
+      }
+    }
+    Ok(label)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+
+  fn leaf(name: &str, branch_length: f64) -> NwkNode {
+    NwkNode {
+      name: Some(name.to_owned()),
+      branch_length: Some(branch_length),
+      children: vec![],
+    }
+  }
+
+  #[test]
+  fn parses_newick() -> Result<(), Report> {
+    let tree = nwk_read_str("((A:0.1,'B c':2e-1)95:0.3[&&NHX:S=x],C:1)root;\n")?;
+    assert_eq!(
+      tree,
+      NwkNode {
+        name: Some("root".to_owned()),
+        branch_length: None,
+        children: vec![
+          NwkNode {
+            name: Some("95".to_owned()),
+            branch_length: Some(0.3),
+            children: vec![leaf("A", 0.1), leaf("B c", 0.2)],
+          },
+          leaf("C", 1.0),
+        ],
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn parses_nexus_with_translate() -> Result<(), Report> {
+    let nexus = r"#NEXUS
+      [comment; with semicolon]
+      begin taxa;
+        dimensions ntax=2;
+      end;
+      begin trees;
+        translate 1 A, 2 'B c';
+        tree t1 = [&R] (1:0.5,2:1.5);
+      end;
+    ";
+    let tree = nwk_or_nexus_read_str(nexus)?;
+    assert_eq!(
+      tree,
+      NwkNode {
+        name: None,
+        branch_length: None,
+        children: vec![leaf("A", 0.5), leaf("B c", 1.5)],
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn rejects_unbalanced_newick() {
+    assert_eq!(
+      report_to_string(&nwk_read_str("((A:1,B:2);").unwrap_err()),
+      "When parsing Newick tree: Unexpected character ';' at position 10. Expected ',' or ')'"
+    );
+    assert_eq!(
+      report_to_string(&nwk_read_str("(A:1,B:2));").unwrap_err()),
+      "When parsing Newick tree: unexpected character ')' at position 9. Expected end of tree (';')"
+    );
+  }
+}
@@ -4,6 +4,8 @@ use crate::graph::node::{GraphNode, GraphNodeKey};
 use crate::graph::traits::{HasDivergence, HasName};
 use crate::io::file::create_file_or_stdout;
 use eyre::{Report, WrapErr};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::path::Path;
 
@@ -37,6 +39,19 @@ where
   Ok(writeln!(writer, "{};", convert_graph_to_nwk_string(graph)?)?)
 }
 
+/// Surrounds the node name with single quotes if it contains characters which have special meaning in Newick format,
+/// doubling the single quotes inside the name.
+pub fn nwk_quote_name(name: &str) -> Cow<'_, str> {
+  let needs_quotes = name
+    .chars()
+    .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | ':' | ',' | ';' | '[' | ']' | '\''));
+  if needs_quotes {
+    Cow::Owned(format!("'{}'", name.replace('\'', "''")))
+  } else {
+    Cow::Borrowed(name)
+  }
+}
+
 fn convert_graph_to_nwk_string<N, E, D>(graph: &Graph<N, E, D>) -> Result<String, Report>
 where
   N: GraphNode + HasDivergence + HasName,
@@ -44,13 +59,34 @@ where
 {
   let root_node_key = graph.get_exactly_one_root()?.key();
   let parent_div = 0.0;
-  convert_graph_to_nwk_recursive(graph, root_node_key, parent_div).wrap_err("When converting graph to Newick string")
+  convert_graph_to_nwk_recursive(graph, root_node_key, parent_div, None)
+    .wrap_err("When converting graph to Newick string")
+}
+
+/// Converts graph to Newick string with edge numbers, as used in jplace format (e.g. `(A:0.1{0},B:0.2{1}):0{2};`).
+///
+/// Edges are numbered in post-order. Each edge is identified by the node it leads to, so the returned map gives the
+/// edge number for every node key, including the root.
+pub fn nwk_write_to_string_with_edge_nums<N, E, D>(
+  graph: &Graph<N, E, D>,
+) -> Result<(String, BTreeMap<GraphNodeKey, usize>), Report>
+where
+  N: GraphNode + HasDivergence + HasName,
+  E: GraphEdge,
+{
+  let root_node_key = graph.get_exactly_one_root()?.key();
+  let parent_div = 0.0;
+  let mut edge_nums = BTreeMap::new();
+  let nwk = convert_graph_to_nwk_recursive(graph, root_node_key, parent_div, Some(&mut edge_nums))
+    .wrap_err("When converting graph to edge-numbered Newick string")?;
+  Ok((format!("{nwk};"), edge_nums))
 }
 
 fn convert_graph_to_nwk_recursive<N, E, D>(
   graph: &Graph<N, E, D>,
   node_key: GraphNodeKey,
   parent_div: f64,
+  mut edge_nums: Option<&mut BTreeMap<GraphNodeKey, usize>>,
 ) -> Result<String, Report>
 where
   N: GraphNode + HasDivergence + HasName,
@@ -59,15 +95,67 @@ where
   let node = graph.get_node(node_key)?.payload();
   let branch_length = node.divergence() - parent_div;
 
-  Ok(if graph.is_leaf_key(node_key) {
+  let nwk = if graph.is_leaf_key(node_key) {
     let name = node.name();
     format!("{name}:{branch_length}")
   } else {
     let children = graph
       .iter_child_keys_of_by_key(node_key)
-      .map(|child_key| convert_graph_to_nwk_recursive(graph, child_key, node.divergence()))
+      .map(|child_key| convert_graph_to_nwk_recursive(graph, child_key, node.divergence(), edge_nums.as_deref_mut()))
       .collect::<Result<Vec<String>, Report>>()?
       .join(",");
     format!("({children}):{branch_length}")
+  };
+
+  Ok(match edge_nums {
+    Some(edge_nums) => {
+      let edge_num = edge_nums.len();
+      edge_nums.insert(node_key, edge_num);
+      format!("{nwk}{{{edge_num}}}")
+    }
+    None => nwk,
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tree::tree::{AuspiceGraph, AuspiceGraphEdgePayload, AuspiceGraphMeta, AuspiceGraphNodePayload};
+  use pretty_assertions::assert_eq;
+
+  fn node(name: &str, div: f64) -> AuspiceGraphNodePayload {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.node_attrs.div = Some(div);
+    payload
+  }
+
+  #[test]
+  fn writes_newick_with_edge_nums() -> Result<(), Report> {
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", 0.0));
+    let inner = graph.add_node(node("inner", 1.0));
+    let a = graph.add_node(node("A", 3.0));
+    let b = graph.add_node(node("B", 1.5));
+    let c = graph.add_node(node("C", 2.0));
+    for (src, dst) in [(root, inner), (inner, a), (inner, b), (root, c)] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    let graph = graph.build()?;
+
+    let (nwk, edge_nums) = nwk_write_to_string_with_edge_nums(&graph)?;
+    assert_eq!(nwk, "((A:2{0},B:0.5{1}):1{2},C:2{3}):0{4};");
+    assert_eq!(
+      edge_nums,
+      BTreeMap::from([(a, 0), (b, 1), (inner, 2), (c, 3), (root, 4)])
+    );
+    assert_eq!(nwk_write_to_string(&graph)?, "((A:2,B:0.5):1,C:2):0;\n");
+    Ok(())
+  }
+
+  #[test]
+  fn quotes_names_with_special_characters() {
+    assert_eq!(nwk_quote_name("hCoV-19/USA/1/2020"), "hCoV-19/USA/1/2020");
+    assert_eq!(nwk_quote_name("A (B):1,2;"), "'A (B):1,2;'");
+    assert_eq!(nwk_quote_name("O'Neil"), "'O''Neil'");
+  }
+}
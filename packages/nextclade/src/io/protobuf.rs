@@ -0,0 +1,231 @@
+//! Minimal reader and writer of Protocol Buffers wire format, sufficient for simple message types.
+//!
+//! See: https://protobuf.dev/programming-guides/encoding/
+
+// Fixed-width values are little-endian on the wire, regardless of the platform
+#![allow(clippy::little_endian_bytes)]
+
+use crate::make_error;
+use eyre::Report;
+
+const WIRE_TYPE_VARINT: u8 = 0;
+const WIRE_TYPE_FIXED64: u8 = 1;
+const WIRE_TYPE_LEN: u8 = 2;
+const WIRE_TYPE_FIXED32: u8 = 5;
+
+/// Value of a field, as it is encoded on the wire
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtoValue<'a> {
+  Varint(u64),
+  Fixed64(u64),
+  Len(&'a [u8]),
+  Fixed32(u32),
+}
+
+impl<'a> ProtoValue<'a> {
+  pub fn as_varint(self) -> Result<u64, Report> {
+    match self {
+      ProtoValue::Varint(value) => Ok(value),
+      _ => make_error!("Protobuf: expected a varint field, but found {self:?}"),
+    }
+  }
+
+  pub fn as_bytes(self) -> Result<&'a [u8], Report> {
+    match self {
+      ProtoValue::Len(bytes) => Ok(bytes),
+      _ => make_error!("Protobuf: expected a length-delimited field, but found {self:?}"),
+    }
+  }
+
+  pub fn as_str(self) -> Result<&'a str, Report> {
+    Ok(std::str::from_utf8(self.as_bytes()?)?)
+  }
+
+  /// Reads values of a `repeated fixed32` field, which can be either packed or not
+  pub fn as_fixed32s(self) -> Result<Vec<u32>, Report> {
+    match self {
+      ProtoValue::Fixed32(value) => Ok(vec![value]),
+      ProtoValue::Len(bytes) if bytes.len() % 4 == 0 => Ok(
+        bytes
+          .chunks_exact(4)
+          .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap_or_default()))
+          .collect(),
+      ),
+      _ => make_error!("Protobuf: expected a fixed32 field, but found {self:?}"),
+    }
+  }
+}
+
+/// Iterates over fields of an encoded message
+pub struct ProtoReader<'a> {
+  buf: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> ProtoReader<'a> {
+  pub const fn new(buf: &'a [u8]) -> Self {
+    Self { buf, pos: 0 }
+  }
+
+  /// Reads next field number and value. Returns `None` at the end of the message.
+  pub fn next_field(&mut self) -> Result<Option<(u64, ProtoValue<'a>)>, Report> {
+    if self.pos >= self.buf.len() {
+      return Ok(None);
+    }
+
+    let tag = self.read_varint()?;
+    let field = tag >> 3;
+    let value = match (tag & 0b111) as u8 {
+      WIRE_TYPE_VARINT => ProtoValue::Varint(self.read_varint()?),
+      WIRE_TYPE_FIXED64 => ProtoValue::Fixed64(u64::from_le_bytes(self.read_array()?)),
+      WIRE_TYPE_LEN => {
+        let len = usize::try_from(self.read_varint()?)?;
+        ProtoValue::Len(self.read_bytes(len)?)
+      }
+      WIRE_TYPE_FIXED32 => ProtoValue::Fixed32(u32::from_le_bytes(self.read_array()?)),
+      wire_type => return make_error!("Protobuf: unsupported wire type {wire_type} of field {field}"),
+    };
+
+    Ok(Some((field, value)))
+  }
+
+  fn read_varint(&mut self) -> Result<u64, Report> {
+    let mut value = 0_u64;
+    for shift in (0..64).step_by(7) {
+      let Some(&byte) = self.buf.get(self.pos) else {
+        return make_error!("Protobuf: unexpected end of data when reading varint");
+      };
+      self.pos += 1;
+      value |= u64::from(byte & 0x7f) << shift;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+    make_error!("Protobuf: varint is too long")
+  }
+
+  fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], Report> {
+    let end = self.pos + len;
+    if end > self.buf.len() {
+      return make_error!(
+        "Protobuf: unexpected end of data: expected {len} bytes at position {}, but only {} remain",
+        self.pos,
+        self.buf.len() - self.pos
+      );
+    }
+    let bytes = &self.buf[self.pos..end];
+    self.pos = end;
+    Ok(bytes)
+  }
+
+  fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Report> {
+    let mut array = [0_u8; N];
+    array.copy_from_slice(self.read_bytes(N)?);
+    Ok(array)
+  }
+}
+
+/// Encodes fields of a message
+#[derive(Clone, Debug, Default)]
+pub struct ProtoWriter {
+  buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+  pub const fn new() -> Self {
+    Self { buf: vec![] }
+  }
+
+  pub fn into_bytes(self) -> Vec<u8> {
+    self.buf
+  }
+
+  pub fn write_varint_field(&mut self, field: u64, value: u64) {
+    self.write_tag(field, WIRE_TYPE_VARINT);
+    self.write_varint(value);
+  }
+
+  pub fn write_bytes_field(&mut self, field: u64, bytes: &[u8]) {
+    self.write_tag(field, WIRE_TYPE_LEN);
+    self.write_varint(bytes.len() as u64);
+    self.buf.extend_from_slice(bytes);
+  }
+
+  pub fn write_str_field(&mut self, field: u64, value: &str) {
+    self.write_bytes_field(field, value.as_bytes());
+  }
+
+  pub fn write_message_field(&mut self, field: u64, message: &ProtoWriter) {
+    self.write_bytes_field(field, &message.buf);
+  }
+
+  pub fn write_fixed32_field(&mut self, field: u64, value: u32) {
+    self.write_tag(field, WIRE_TYPE_FIXED32);
+    self.buf.extend_from_slice(&value.to_le_bytes());
+  }
+
+  /// Writes `repeated fixed32` field in packed encoding (default in proto3)
+  pub fn write_packed_fixed32_field(&mut self, field: u64, values: &[u32]) {
+    if values.is_empty() {
+      return;
+    }
+    let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+    self.write_bytes_field(field, &bytes);
+  }
+
+  fn write_tag(&mut self, field: u64, wire_type: u8) {
+    self.write_varint((field << 3) | u64::from(wire_type));
+  }
+
+  fn write_varint(&mut self, mut value: u64) {
+    while value >= 0x80 {
+      self.buf.push((value as u8 & 0x7f) | 0x80);
+      value >>= 7;
+    }
+    self.buf.push(value as u8);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn roundtrips_fields() -> Result<(), Report> {
+    let mut inner = ProtoWriter::new();
+    inner.write_packed_fixed32_field(4, &[1, 3]);
+
+    let mut writer = ProtoWriter::new();
+    writer.write_varint_field(1, 300);
+    writer.write_str_field(2, "abc");
+    writer.write_message_field(3, &inner);
+    writer.write_fixed32_field(5, 2);
+    let bytes = writer.into_bytes();
+
+    assert_eq!(
+      bytes,
+      vec![
+        0x08, 0xac, 0x02, // field 1: varint 300
+        0x12, 0x03, b'a', b'b', b'c', // field 2: "abc"
+        0x1a, 0x0a, 0x22, 0x08, 1, 0, 0, 0, 3, 0, 0, 0, // field 3: message with packed field 4
+        0x2d, 2, 0, 0, 0, // field 5: fixed32 2
+      ]
+    );
+
+    let mut reader = ProtoReader::new(&bytes);
+    assert_eq!(reader.next_field()?, Some((1, ProtoValue::Varint(300))));
+    assert_eq!(
+      reader.next_field()?.map(|(_, value)| value.as_str()).transpose()?,
+      Some("abc")
+    );
+    let (field, value) = reader.next_field()?.unwrap();
+    assert_eq!(field, 3);
+    let mut inner_reader = ProtoReader::new(value.as_bytes()?);
+    let (_, value) = inner_reader.next_field()?.unwrap();
+    assert_eq!(value.as_fixed32s()?, vec![1, 3]);
+    assert_eq!(reader.next_field()?, Some((5, ProtoValue::Fixed32(2))));
+    assert_eq!(reader.next_field()?, None);
+    Ok(())
+  }
+}
@@ -0,0 +1,204 @@
+use crate::analyze::run_summary::{
+  RUN_SUMMARY_COVERAGE_BINS, RunSummary, RunSummaryBuilder, RunSummaryFrequency, RunSummaryQcStatusCounts,
+};
+use crate::io::file::create_file_or_stdout;
+use crate::io::json::{JsonPretty, json_write};
+use crate::o;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Accumulates statistics of a run and writes them into JSON and/or Markdown files when finished
+pub struct RunSummaryWriter {
+  json_filepath: Option<PathBuf>,
+  markdown_filepath: Option<PathBuf>,
+  builder: RunSummaryBuilder,
+}
+
+impl RunSummaryWriter {
+  pub fn new(json_filepath: Option<&PathBuf>, markdown_filepath: Option<&PathBuf>) -> Self {
+    Self {
+      json_filepath: json_filepath.cloned(),
+      markdown_filepath: markdown_filepath.cloned(),
+      builder: RunSummaryBuilder::default(),
+    }
+  }
+
+  pub fn write(&mut self, output: &NextcladeOutputs) {
+    self.builder.add_output(output);
+  }
+
+  pub fn write_nuc_error(&mut self, seq_name: &str, error: &Report) {
+    self.builder.add_error(seq_name, error);
+  }
+
+  pub fn finish(&self) -> Result<(), Report> {
+    let summary = self.builder.build();
+    if let Some(json_filepath) = &self.json_filepath {
+      json_write(json_filepath, &summary, JsonPretty(true))?;
+    }
+    if let Some(markdown_filepath) = &self.markdown_filepath {
+      run_summary_write_markdown(markdown_filepath, &summary)?;
+    }
+    Ok(())
+  }
+}
+
+pub fn run_summary_write_markdown(filepath: impl AsRef<Path>, summary: &RunSummary) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut file = create_file_or_stdout(filepath)?;
+  file
+    .write_all(run_summary_to_markdown(summary).as_bytes())
+    .wrap_err_with(|| format!("When writing run summary to file: {}", filepath.display()))
+}
+
+pub fn run_summary_to_markdown(summary: &RunSummary) -> String {
+  let mut md = String::new();
+
+  let _ = writeln!(md, "# Nextclade run summary\n");
+  let _ = writeln!(
+    md,
+    "Nextclade {}, created at {}\n",
+    summary.nextclade_algo_version, summary.created_at
+  );
+  let _ = writeln!(
+    md,
+    "{}",
+    markdown_table(
+      &["Sequences", "Count"],
+      [
+        ["total", &summary.total_sequences.to_string()],
+        ["analyzed", &summary.total_analyzed.to_string()],
+        ["failed", &summary.total_errors.to_string()],
+      ]
+    )
+  );
+
+  section(&mut md, "Clades", &frequencies_table("clade", &summary.clades));
+  for (attr, frequencies) in &summary.clade_node_attrs {
+    section(&mut md, attr, &frequencies_table(attr, frequencies));
+  }
+
+  let qc_rows = std::iter::once(("overall", &summary.qc.overall))
+    .chain(summary.qc.rules.iter().map(|(rule, counts)| (rule.as_str(), counts)))
+    .map(|(rule, RunSummaryQcStatusCounts { good, mediocre, bad })| {
+      [rule.to_owned(), good.to_string(), mediocre.to_string(), bad.to_string()]
+    })
+    .collect_vec();
+  section(
+    &mut md,
+    "Quality control",
+    &markdown_table(&["rule", "good", "mediocre", "bad"], qc_rows),
+  );
+
+  section(
+    &mut md,
+    "Most frequent nucleotide substitutions",
+    &frequencies_table("mutation", &summary.top_nuc_substitutions),
+  );
+  section(
+    &mut md,
+    "Most frequent amino acid substitutions",
+    &frequencies_table("mutation", &summary.top_aa_substitutions),
+  );
+
+  let bin_width = 100 / RUN_SUMMARY_COVERAGE_BINS;
+  let coverage_headers = std::iter::once(o!("CDS"))
+    .chain(std::iter::once(o!("mean")))
+    .chain((0..RUN_SUMMARY_COVERAGE_BINS).map(|i| format!("{}-{}%", i * bin_width, (i + 1) * bin_width)))
+    .collect_vec();
+  let coverage_rows = summary
+    .cds_coverage
+    .iter()
+    .map(|(cds, histogram)| {
+      [cds.clone(), format_percent(histogram.mean)]
+        .into_iter()
+        .chain(histogram.counts.iter().map(ToString::to_string))
+        .collect_vec()
+    })
+    .collect_vec();
+  section(
+    &mut md,
+    "Coverage of CDSs",
+    &markdown_table(&coverage_headers, coverage_rows),
+  );
+
+  section(
+    &mut md,
+    "Frame shift hotspots",
+    &frequencies_table("frame shift", &summary.frame_shift_hotspots),
+  );
+  section(
+    &mut md,
+    "Premature stop codon hotspots",
+    &frequencies_table("stop codon", &summary.stop_codon_hotspots),
+  );
+
+  let error_rows = summary
+    .errors
+    .iter()
+    .map(|error| {
+      [
+        error.category.clone(),
+        error.count.to_string(),
+        error.examples.join(", "),
+      ]
+    })
+    .collect_vec();
+  section(
+    &mut md,
+    "Errors",
+    &markdown_table(&["category", "count", "examples"], error_rows),
+  );
+
+  md
+}
+
+fn section(md: &mut String, title: &str, content: &str) {
+  let _ = writeln!(md, "## {title}\n\n{content}");
+}
+
+fn frequencies_table(name: &str, frequencies: &[RunSummaryFrequency]) -> String {
+  let rows = frequencies
+    .iter()
+    .map(|f| [f.value.clone(), f.count.to_string(), format_percent(f.fraction)])
+    .collect_vec();
+  markdown_table(&[name, "count", "percent"], rows)
+}
+
+fn markdown_table<H, R, C>(headers: &[H], rows: impl IntoIterator<Item = R>) -> String
+where
+  H: AsRef<str>,
+  R: IntoIterator<Item = C>,
+  C: AsRef<str>,
+{
+  let rows = rows
+    .into_iter()
+    .map(|row| {
+      let cells = row
+        .into_iter()
+        .map(|cell| escape_markdown_cell(cell.as_ref()))
+        .join(" | ");
+      format!("| {cells} |")
+    })
+    .collect_vec();
+
+  if rows.is_empty() {
+    return o!("None\n");
+  }
+
+  let header = headers.iter().map(AsRef::as_ref).join(" | ");
+  let separator = headers.iter().map(|_| "---").join(" | ");
+  format!("| {header} |\n| {separator} |\n{}\n", rows.join("\n"))
+}
+
+fn escape_markdown_cell(cell: &str) -> String {
+  cell.replace('|', "\\|")
+}
+
+fn format_percent(fraction: f64) -> String {
+  format!("{:.1}%", fraction * 100.0)
+}
@@ -0,0 +1,293 @@
+use crate::align::insertions_strip::Insertion;
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::{Nuc, from_nuc_seq};
+use crate::coord::position::PositionLike;
+use crate::coord::range::NucRefGlobalRange;
+use crate::io::file::create_file_or_stdout;
+use crate::o;
+use crate::types::outputs::NextcladeOutputs;
+use crate::utils::info::this_package_version_str;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::io::Write;
+use std::path::Path;
+
+/// SAM flag: the read is unmapped
+pub const SAM_FLAG_UNMAPPED: u16 = 0x4;
+
+/// SAM flag: the read is reverse complemented
+pub const SAM_FLAG_REVERSE: u16 = 0x10;
+
+/// Mapping quality, which means "not available"
+pub const SAM_MAPQ_UNAVAILABLE: u8 = 255;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CigarOp {
+  Match,
+  Ins,
+  Del,
+  SoftClip,
+}
+
+impl CigarOp {
+  pub const fn to_char(self) -> char {
+    match self {
+      Self::Match => 'M',
+      Self::Ins => 'I',
+      Self::Del => 'D',
+      Self::SoftClip => 'S',
+    }
+  }
+
+  /// Numeric code of the operation in BAM format
+  pub const fn to_bam_code(self) -> u32 {
+    match self {
+      Self::Match => 0,
+      Self::Ins => 1,
+      Self::Del => 2,
+      Self::SoftClip => 4,
+    }
+  }
+
+  /// Whether the operation consumes positions of the reference sequence
+  pub const fn consumes_ref(self) -> bool {
+    matches!(self, Self::Match | Self::Del)
+  }
+}
+
+/// One record (line) of SAM file: a query sequence aligned to the reference sequence
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SamRecord {
+  pub qname: String,
+  pub flag: u16,
+
+  /// 0-based position of the first aligned reference nucleotide. None for unmapped records.
+  pub pos: Option<usize>,
+
+  pub cigar: Vec<(CigarOp, usize)>,
+  pub seq: Vec<Nuc>,
+  pub alignment_score: Option<i32>,
+}
+
+impl SamRecord {
+  /// Alignment record of a query sequence, from the query sequence aligned to the reference sequence with insertions
+  /// stripped, and from the insertions
+  pub fn from_alignment(seq_name: &str, query: &[Nuc], output: &NextcladeOutputs) -> Self {
+    let (cigar, seq) = sam_cigar_and_seq(query, &output.insertions, &output.alignment_range);
+    Self {
+      qname: sam_name(seq_name),
+      flag: if output.is_reverse_complement {
+        SAM_FLAG_REVERSE
+      } else {
+        0
+      },
+      pos: Some(output.alignment_range.begin.as_usize()),
+      cigar,
+      seq,
+      alignment_score: Some(output.alignment_score),
+    }
+  }
+
+  /// Record of a query sequence which failed to align
+  pub fn unmapped(seq_name: &str) -> Self {
+    Self {
+      qname: sam_name(seq_name),
+      flag: SAM_FLAG_UNMAPPED,
+      pos: None,
+      cigar: vec![],
+      seq: vec![],
+      alignment_score: None,
+    }
+  }
+
+  /// 0-based position on the reference sequence after the last aligned nucleotide. None for unmapped records.
+  pub fn end(&self) -> Option<usize> {
+    let ref_len: usize = self
+      .cigar
+      .iter()
+      .filter(|(op, _)| op.consumes_ref())
+      .map(|(_, len)| len)
+      .sum();
+    self.pos.map(|pos| pos + ref_len)
+  }
+
+  pub fn to_sam_line(&self, ref_name: &str) -> String {
+    let mut fields = vec![self.qname.clone(), self.flag.to_string()];
+    match self.pos {
+      Some(pos) => fields.extend([ref_name.to_owned(), (pos + 1).to_string()]),
+      None => fields.extend([o!("*"), o!("0")]),
+    }
+    fields.push(if self.pos.is_some() {
+      SAM_MAPQ_UNAVAILABLE.to_string()
+    } else {
+      o!("0")
+    });
+    fields.push(if self.cigar.is_empty() {
+      o!("*")
+    } else {
+      self
+        .cigar
+        .iter()
+        .map(|(op, len)| format!("{len}{}", op.to_char()))
+        .join("")
+    });
+    fields.extend([o!("*"), o!("0"), o!("0")]);
+    fields.push(if self.seq.is_empty() {
+      o!("*")
+    } else {
+      from_nuc_seq(&self.seq)
+    });
+    fields.push(o!("*"));
+    if let Some(alignment_score) = self.alignment_score {
+      fields.push(format!("AS:i:{alignment_score}"));
+    }
+    fields.join("\t")
+  }
+}
+
+/// Reconstructs CIGAR and the query sequence from the query sequence aligned to the reference sequence with insertions
+/// stripped, and from the insertions. Insertions before the beginning and after the end of the alignment range are
+/// written as soft clips.
+pub fn sam_cigar_and_seq(
+  query: &[Nuc],
+  insertions: &[Insertion<Nuc>],
+  alignment_range: &NucRefGlobalRange,
+) -> (Vec<(CigarOp, usize)>, Vec<Nuc>) {
+  let begin = alignment_range.begin.as_usize();
+  let end = alignment_range.end.as_usize().min(query.len());
+
+  let mut cigar = vec![];
+  let mut seq = vec![];
+  let mut push = |op: CigarOp, nucs: &[Nuc]| {
+    if op != CigarOp::Del {
+      seq.extend_from_slice(nucs);
+    }
+    match cigar.last_mut() {
+      Some((last_op, len)) if *last_op == op => *len += nucs.len(),
+      _ => cigar.push((op, nucs.len())),
+    }
+  };
+
+  let (leading, rest): (Vec<_>, Vec<_>) = insertions.iter().partition(|ins| (ins.pos as isize) < begin as isize);
+  let (trailing, inner): (Vec<_>, Vec<_>) = rest.into_iter().partition(|ins| ins.pos as usize + 1 >= end);
+
+  for ins in &leading {
+    push(CigarOp::SoftClip, &ins.ins);
+  }
+
+  let mut inner = inner.into_iter().peekable();
+  for pos in begin..end {
+    let nuc = &query[pos..=pos];
+    if nuc[0].is_gap() {
+      push(CigarOp::Del, nuc);
+    } else {
+      push(CigarOp::Match, nuc);
+    }
+    while let Some(ins) = inner.next_if(|ins| ins.pos as usize == pos) {
+      push(CigarOp::Ins, &ins.ins);
+    }
+  }
+
+  for ins in &trailing {
+    push(CigarOp::SoftClip, &ins.ins);
+  }
+
+  (cigar, seq)
+}
+
+/// Converts sequence name to a name valid in SAM and BAM files: takes the first word and limits length to 254
+/// characters. Empty names are replaced with `*`.
+pub fn sam_name(seq_name: &str) -> String {
+  let name = seq_name.split_whitespace().next().unwrap_or_default();
+  if name.is_empty() {
+    return o!("*");
+  }
+  name.chars().take(254).collect()
+}
+
+/// Text of SAM header, which is also embedded into BAM header
+pub fn sam_header(ref_name: &str, ref_len: usize) -> String {
+  format!(
+    "@HD\tVN:1.6\tSO:unsorted\n@SQ\tSN:{ref_name}\tLN:{ref_len}\n@PG\tID:nextclade\tPN:nextclade\tVN:{}\n",
+    this_package_version_str()
+  )
+}
+
+/// Writes pairwise alignments of query sequences to the reference sequence in SAM format
+pub struct SamWriter {
+  writer: Box<dyn Write + Send>,
+  ref_name: String,
+}
+
+impl SamWriter {
+  pub fn new(filepath: impl AsRef<Path>, ref_seq_name: &str, ref_len: usize) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let ref_name = sam_name(ref_seq_name);
+    let mut writer = create_file_or_stdout(filepath)?;
+    writer
+      .write_all(sam_header(&ref_name, ref_len).as_bytes())
+      .wrap_err_with(|| format!("When writing SAM header to file: {}", filepath.display()))?;
+    Ok(Self { writer, ref_name })
+  }
+
+  pub fn write(&mut self, seq_name: &str, query: &[Nuc], output: &NextcladeOutputs) -> Result<(), Report> {
+    self.write_record(&SamRecord::from_alignment(seq_name, query, output))
+  }
+
+  pub fn write_nuc_error(&mut self, seq_name: &str) -> Result<(), Report> {
+    self.write_record(&SamRecord::unmapped(seq_name))
+  }
+
+  fn write_record(&mut self, record: &SamRecord) -> Result<(), Report> {
+    writeln!(self.writer, "{}", record.to_sam_line(&self.ref_name))
+      .wrap_err_with(|| format!("When writing SAM record for sequence '{}'", record.qname))
+  }
+
+  pub fn finish(&mut self) -> Result<(), Report> {
+    self.writer.flush().wrap_err("When finalizing SAM file")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn reconstructs_cigar_with_insertions_deletions_and_soft_clips() -> Result<(), Report> {
+    //                      0123456789
+    let query = to_nuc_seq("--ACG--TT-")?;
+    let insertions = [
+      Insertion {
+        pos: 1,
+        ins: to_nuc_seq("GG")?,
+      },
+      Insertion {
+        pos: 3,
+        ins: to_nuc_seq("C")?,
+      },
+      Insertion {
+        pos: 8,
+        ins: to_nuc_seq("AAA")?,
+      },
+    ];
+
+    let (cigar, seq) = sam_cigar_and_seq(&query, &insertions, &NucRefGlobalRange::from_usize(2, 9));
+
+    assert_eq!(
+      cigar,
+      vec![
+        (CigarOp::SoftClip, 2),
+        (CigarOp::Match, 2),
+        (CigarOp::Ins, 1),
+        (CigarOp::Match, 1),
+        (CigarOp::Del, 2),
+        (CigarOp::Match, 2),
+        (CigarOp::SoftClip, 3),
+      ]
+    );
+    assert_eq!(from_nuc_seq(&seq), "GGACCGTTAAA");
+    Ok(())
+  }
+}
@@ -0,0 +1,172 @@
+use crate::alphabet::nuc::Nuc;
+use crate::analyze::site_coverage::{SiteCoverage, SiteCoverageBuilder, SiteCoverageProfile};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::io::file::create_file_or_stdout;
+use crate::types::outputs::NextcladeOutputs;
+use eyre::{Report, WrapErr};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Accumulates per-site coverage across all sequences and writes it into TSV and/or bedGraph files when finished
+pub struct SiteCoverageWriter {
+  tsv_filepath: Option<PathBuf>,
+  bedgraph_filepath: Option<PathBuf>,
+  ref_seq_name: String,
+  builder: SiteCoverageBuilder,
+}
+
+impl SiteCoverageWriter {
+  pub fn new(
+    ref_seq_name: &str,
+    ref_seq: &[Nuc],
+    tsv_filepath: Option<&PathBuf>,
+    bedgraph_filepath: Option<&PathBuf>,
+  ) -> Self {
+    Self {
+      tsv_filepath: tsv_filepath.cloned(),
+      bedgraph_filepath: bedgraph_filepath.cloned(),
+      ref_seq_name: ref_seq_name.to_owned(),
+      builder: SiteCoverageBuilder::new(ref_seq),
+    }
+  }
+
+  pub fn write(&mut self, output: &NextcladeOutputs) {
+    self.builder.add_output(output);
+  }
+
+  pub fn finish(&self) -> Result<(), Report> {
+    let profile = self.builder.build();
+    if let Some(tsv_filepath) = &self.tsv_filepath {
+      site_coverage_write_tsv(tsv_filepath, &profile)?;
+    }
+    if let Some(bedgraph_filepath) = &self.bedgraph_filepath {
+      site_coverage_write_bedgraph(bedgraph_filepath, &self.ref_seq_name, &profile)?;
+    }
+    Ok(())
+  }
+}
+
+/// Fraction of sequences in which a site is sequenced and is not `N`
+fn coverage_fraction(site: &SiteCoverage, num_seqs: usize) -> f64 {
+  if num_seqs == 0 {
+    return 0.0;
+  }
+  site.covered() as f64 / num_seqs as f64
+}
+
+pub fn site_coverage_write_tsv(filepath: impl AsRef<Path>, profile: &SiteCoverageProfile) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let headers = [
+    "pos",
+    "ref",
+    "numSeqs",
+    "sequenced",
+    "missing",
+    "gap",
+    "ambiguous",
+    "A",
+    "C",
+    "G",
+    "T",
+    "coverage",
+  ]
+  .map(str::to_owned);
+
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for site in &profile.sites {
+    let [a, c, g, t] = site.acgt.map(|count| count.to_string());
+    writer
+      .write([
+        (site.pos + 1).to_string(),
+        site.ref_nuc.to_string(),
+        profile.num_seqs.to_string(),
+        site.sequenced.to_string(),
+        site.missing.to_string(),
+        site.gap.to_string(),
+        site.ambiguous.to_string(),
+        a,
+        c,
+        g,
+        t,
+        coverage_fraction(site, profile.num_seqs).to_string(),
+      ])
+      .wrap_err_with(|| format!("When writing site coverage file: {}", filepath.display()))?;
+  }
+  Ok(())
+}
+
+/// Writes fraction of sequences covering each site of the reference sequence in bedGraph format. Adjacent sites with
+/// the same coverage are merged into one interval.
+///
+/// See: https://genome.ucsc.edu/goldenPath/help/bedgraph.html
+pub fn site_coverage_write_bedgraph(
+  filepath: impl AsRef<Path>,
+  ref_seq_name: &str,
+  profile: &SiteCoverageProfile,
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut file = create_file_or_stdout(filepath)?;
+  site_coverage_to_bedgraph(&mut file, ref_seq_name, profile)
+    .wrap_err_with(|| format!("When writing site coverage bedGraph file: {}", filepath.display()))
+}
+
+pub fn site_coverage_to_bedgraph(
+  w: &mut impl Write,
+  ref_seq_name: &str,
+  profile: &SiteCoverageProfile,
+) -> Result<(), Report> {
+  writeln!(
+    w,
+    "track type=bedGraph name=\"coverage\" description=\"Fraction of sequences covering each site\""
+  )?;
+  for interval in profile.sites.chunk_by(|a, b| a.covered() == b.covered()) {
+    let (Some(first), Some(last)) = (interval.first(), interval.last()) else {
+      continue;
+    };
+    writeln!(
+      w,
+      "{ref_seq_name}\t{}\t{}\t{}",
+      first.pos,
+      last.pos + 1,
+      coverage_fraction(first, profile.num_seqs)
+    )?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn merges_sites_with_same_coverage_into_bedgraph_intervals() -> Result<(), Report> {
+    let site = |pos: usize, sequenced: usize, missing: usize| SiteCoverage {
+      pos,
+      sequenced,
+      missing,
+      ..SiteCoverage::default()
+    };
+    let profile = SiteCoverageProfile {
+      num_seqs: 4,
+      sites: vec![
+        site(0, 2, 0),
+        site(1, 4, 2),
+        site(2, 4, 0),
+        site(3, 4, 0),
+        site(4, 4, 4),
+      ],
+    };
+
+    let mut buf = vec![];
+    site_coverage_to_bedgraph(&mut buf, "ref", &profile)?;
+    assert_eq!(
+      String::from_utf8(buf)?,
+      "track type=bedGraph name=\"coverage\" description=\"Fraction of sequences covering each site\"\n\
+       ref\t0\t2\t0.5\n\
+       ref\t2\t4\t1\n\
+       ref\t4\t5\t0\n"
+    );
+    Ok(())
+  }
+}
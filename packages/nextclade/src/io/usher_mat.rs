@@ -0,0 +1,607 @@
+use crate::alphabet::nuc::{Nuc, from_nuc};
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::graph::node::GraphNodeKey;
+use crate::io::compression::{CompressionType, guess_compression_from_filepath};
+use crate::io::file::{create_file_or_stdout, open_file_or_stdin};
+use crate::io::fs::has_extension;
+use crate::io::nwk_reader::{NwkNode, nwk_read_str};
+use crate::io::nwk_writer::nwk_quote_name;
+use crate::io::protobuf::{ProtoReader, ProtoWriter};
+use crate::make_error;
+use crate::tree::tree::{AuspiceGraph, AuspiceTree};
+use crate::tree::tree_import::{AugurNodeData, AugurNodeDataEntry, RefTreeImportInputs, convert_nwk_to_auspice_tree};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Mutation-annotated tree (MAT), as used by UShER, matUtils and Taxonium.
+///
+/// Mirrors the `data` message in UShER's `parsimony.proto`. Mutations and metadata are listed for every node of the
+/// Newick tree, in depth-first pre-order.
+///
+/// See: https://usher-wiki.readthedocs.io/en/latest/UShER.html#mutation-annotated-tree-mat
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsherMat {
+  pub newick: String,
+  pub node_mutations: Vec<Vec<UsherMut>>,
+  pub condensed_nodes: Vec<UsherCondensedNode>,
+
+  /// Clade annotations of every node. Non-empty only on the nodes where the clade begins.
+  pub metadata: Vec<Vec<String>>,
+}
+
+/// Nucleotide mutation on a branch. Nucleotides are encoded as 0, 1, 2, 3 for A, C, G, T.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsherMut {
+  /// 1-based position in the reference sequence
+  pub position: i32,
+  pub ref_nuc: u32,
+  pub par_nuc: u32,
+  pub mut_nuc: Vec<u32>,
+  pub chromosome: String,
+}
+
+/// Leaf of the Newick tree which stands for multiple identical samples
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UsherCondensedNode {
+  pub node_name: String,
+  pub condensed_leaves: Vec<String>,
+}
+
+impl UsherMat {
+  pub fn from_path(filepath: impl AsRef<Path>) -> Result<Self, Report> {
+    let filepath = filepath.as_ref();
+    let mut bytes = vec![];
+    open_file_or_stdin(Some(&filepath))?
+      .read_to_end(&mut bytes)
+      .wrap_err_with(|| format!("When reading file {}", filepath.display()))?;
+    Self::from_bytes(&bytes)
+      .wrap_err_with(|| format!("When parsing mutation-annotated tree file {}", filepath.display()))
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, Report> {
+    let mut mat = Self::default();
+    let mut reader = ProtoReader::new(bytes);
+    while let Some((field, value)) = reader.next_field()? {
+      match field {
+        1 => mat.newick = value.as_str()?.to_owned(),
+        2 => mat.node_mutations.push(read_mutation_list(value.as_bytes()?)?),
+        3 => mat.condensed_nodes.push(read_condensed_node(value.as_bytes()?)?),
+        4 => mat.metadata.push(read_node_metadata(value.as_bytes()?)?),
+        _ => {}
+      }
+    }
+    Ok(mat)
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut writer = ProtoWriter::new();
+    writer.write_str_field(1, &self.newick);
+
+    for muts in &self.node_mutations {
+      let mut mutation_list = ProtoWriter::new();
+      for m in muts {
+        let mut mutation = ProtoWriter::new();
+        mutation.write_varint_field(1, m.position as u64);
+        mutation.write_fixed32_field(2, m.ref_nuc);
+        mutation.write_fixed32_field(3, m.par_nuc);
+        mutation.write_packed_fixed32_field(4, &m.mut_nuc);
+        if !m.chromosome.is_empty() {
+          mutation.write_str_field(5, &m.chromosome);
+        }
+        mutation_list.write_message_field(1, &mutation);
+      }
+      writer.write_message_field(2, &mutation_list);
+    }
+
+    for condensed in &self.condensed_nodes {
+      let mut node = ProtoWriter::new();
+      node.write_str_field(1, &condensed.node_name);
+      for leaf in &condensed.condensed_leaves {
+        node.write_str_field(2, leaf);
+      }
+      writer.write_message_field(3, &node);
+    }
+
+    for annotations in &self.metadata {
+      let mut node = ProtoWriter::new();
+      for annotation in annotations {
+        node.write_str_field(1, annotation);
+      }
+      writer.write_message_field(4, &node);
+    }
+
+    writer.into_bytes()
+  }
+}
+
+fn read_mutation_list(bytes: &[u8]) -> Result<Vec<UsherMut>, Report> {
+  let mut muts = vec![];
+  let mut reader = ProtoReader::new(bytes);
+  while let Some((field, value)) = reader.next_field()? {
+    if field == 1 {
+      muts.push(read_mutation(value.as_bytes()?)?);
+    }
+  }
+  Ok(muts)
+}
+
+fn read_mutation(bytes: &[u8]) -> Result<UsherMut, Report> {
+  let mut m = UsherMut::default();
+  let mut reader = ProtoReader::new(bytes);
+  while let Some((field, value)) = reader.next_field()? {
+    match field {
+      1 => m.position = i32::try_from(value.as_varint()?)?,
+      2 => m.ref_nuc = value.as_fixed32s()?.first().copied().unwrap_or_default(),
+      3 => m.par_nuc = value.as_fixed32s()?.first().copied().unwrap_or_default(),
+      4 => m.mut_nuc.extend(value.as_fixed32s()?),
+      5 => m.chromosome = value.as_str()?.to_owned(),
+      _ => {}
+    }
+  }
+  Ok(m)
+}
+
+fn read_condensed_node(bytes: &[u8]) -> Result<UsherCondensedNode, Report> {
+  let mut node = UsherCondensedNode::default();
+  let mut reader = ProtoReader::new(bytes);
+  while let Some((field, value)) = reader.next_field()? {
+    match field {
+      1 => node.node_name = value.as_str()?.to_owned(),
+      2 => node.condensed_leaves.push(value.as_str()?.to_owned()),
+      _ => {}
+    }
+  }
+  Ok(node)
+}
+
+fn read_node_metadata(bytes: &[u8]) -> Result<Vec<String>, Report> {
+  let mut annotations = vec![];
+  let mut reader = ProtoReader::new(bytes);
+  while let Some((field, value)) = reader.next_field()? {
+    if field == 1 {
+      annotations.push(value.as_str()?.to_owned());
+    }
+  }
+  Ok(annotations)
+}
+
+/// Whether the file is a mutation-annotated tree, judging by extension `.pb` (optionally followed by a compression
+/// extension, e.g. `.pb.gz`)
+pub fn is_usher_mat_path(filepath: impl AsRef<Path>) -> bool {
+  let filepath = filepath.as_ref();
+  let (compression, _) = guess_compression_from_filepath(filepath);
+  if matches!(compression, CompressionType::None) {
+    has_extension(filepath, "pb")
+  } else {
+    has_extension(filepath.with_extension(""), "pb")
+  }
+}
+
+/// Whether the content is a mutation-annotated tree rather than one of the text tree formats. The serialized message
+/// starts with the Newick string field, which is checked to contain a complete Newick tree.
+pub fn is_usher_mat_bytes(bytes: &[u8]) -> bool {
+  let mut reader = ProtoReader::new(bytes);
+  matches!(
+    reader.next_field(),
+    Ok(Some((1, value))) if value.as_str().is_ok_and(|newick| newick.starts_with('(') && newick.trim_end().ends_with(';'))
+  )
+}
+
+pub fn usher_mat_write_to_file(filepath: impl AsRef<Path>, mat: &UsherMat) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let mut file = create_file_or_stdout(filepath)?;
+  file
+    .write_all(&mat.to_bytes())
+    .wrap_err_with(|| format!("When writing mutation-annotated tree file {}", filepath.display()))
+}
+
+const USHER_NUCS: [Nuc; 4] = [Nuc::A, Nuc::C, Nuc::G, Nuc::T];
+
+fn usher_nuc_to_nuc(code: u32) -> Result<Nuc, Report> {
+  match USHER_NUCS.get(code as usize) {
+    Some(nuc) => Ok(*nuc),
+    None => make_error!("Unknown nucleotide code in mutation-annotated tree: {code}. Expected one of: 0, 1, 2, 3"),
+  }
+}
+
+fn nuc_to_usher_nuc(nuc: Nuc) -> Option<u32> {
+  USHER_NUCS.iter().position(|n| n == &nuc).map(|code| code as u32)
+}
+
+/// Converts mutation-annotated tree to Auspice JSON.
+///
+/// Unnamed internal nodes are named `node_1`, `node_2`, etc. in pre-order, as UShER does. Condensed leaves are
+/// expanded into sibling leaves with the same mutations. The first clade annotation, if present, becomes the clade of
+/// all of the nodes in the subtree where it is declared. Sequences and node data in `inputs` are not used.
+pub fn convert_usher_mat_to_auspice_tree(mat: &UsherMat, inputs: &RefTreeImportInputs) -> Result<AuspiceTree, Report> {
+  let mut nwk = nwk_read_str(&mat.newick).wrap_err("When parsing tree of the mutation-annotated tree")?;
+
+  let mut node_data = AugurNodeData::default();
+  let mut num_nodes = 0_usize;
+  let mut num_internal = 0_usize;
+  let mut stack: Vec<(&mut NwkNode, Option<String>)> = vec![(&mut nwk, None)];
+  while let Some((node, parent_clade)) = stack.pop() {
+    let index = num_nodes;
+    num_nodes += 1;
+
+    if !node.is_leaf() {
+      num_internal += 1;
+    }
+    let name = match &node.name {
+      Some(name) => name.clone(),
+      None if node.is_leaf() => return make_error!("Mutation-annotated tree contains a tip without a name"),
+      None => format!("node_{num_internal}"),
+    };
+    node.name = Some(name.clone());
+
+    let muts = mat
+      .node_mutations
+      .get(index)
+      .into_iter()
+      .flatten()
+      .map(|m| {
+        let mut_nuc = m.mut_nuc.first().copied().unwrap_or_default();
+        Ok(format!(
+          "{}{}{}",
+          from_nuc(usher_nuc_to_nuc(m.par_nuc)?),
+          m.position,
+          from_nuc(usher_nuc_to_nuc(mut_nuc)?)
+        ))
+      })
+      .collect::<Result<Vec<String>, Report>>()
+      .wrap_err_with(|| format!("When reading mutations of node '{name}'"))?;
+
+    let clade = mat
+      .metadata
+      .get(index)
+      .and_then(|annotations| annotations.first())
+      .filter(|clade| !clade.is_empty())
+      .cloned()
+      .or(parent_clade);
+
+    node_data.nodes.insert(
+      name,
+      AugurNodeDataEntry {
+        muts: Some(muts),
+        clade_membership: clade.clone(),
+        ..AugurNodeDataEntry::default()
+      },
+    );
+
+    stack.extend(node.children.iter_mut().rev().map(|child| (child, clade.clone())));
+  }
+
+  if !mat.node_mutations.is_empty() && mat.node_mutations.len() != num_nodes {
+    return make_error!(
+      "Mutation-annotated tree is inconsistent: the tree has {num_nodes} nodes, but mutations are listed for {} nodes",
+      mat.node_mutations.len()
+    );
+  }
+
+  expand_condensed_nodes(&mut nwk, &mut node_data, &mat.condensed_nodes);
+
+  convert_nwk_to_auspice_tree(
+    &nwk,
+    &RefTreeImportInputs {
+      ref_seq: inputs.ref_seq,
+      gene_map: inputs.gene_map,
+      params: inputs.params,
+      sequences: None,
+      node_data: Some(&node_data),
+    },
+  )
+}
+
+fn expand_condensed_nodes(nwk: &mut NwkNode, node_data: &mut AugurNodeData, condensed_nodes: &[UsherCondensedNode]) {
+  if condensed_nodes.is_empty() {
+    return;
+  }
+
+  let condensed: BTreeMap<&str, &[String]> = condensed_nodes
+    .iter()
+    .map(|node| (node.node_name.as_str(), node.condensed_leaves.as_slice()))
+    .collect();
+
+  let mut stack = vec![nwk];
+  while let Some(node) = stack.pop() {
+    node.children = std::mem::take(&mut node.children)
+      .into_iter()
+      .flat_map(|child| {
+        let leaves = child
+          .name
+          .as_deref()
+          .filter(|_| child.is_leaf())
+          .and_then(|name| condensed.get(name));
+        match leaves {
+          None => vec![child],
+          Some(leaves) => {
+            let entry = child
+              .name
+              .as_ref()
+              .and_then(|name| node_data.nodes.remove(name))
+              .unwrap_or_default();
+            leaves
+              .iter()
+              .map(|leaf| {
+                node_data.nodes.insert(leaf.clone(), entry.clone());
+                NwkNode {
+                  name: Some(leaf.clone()),
+                  branch_length: child.branch_length,
+                  children: vec![],
+                }
+              })
+              .collect_vec()
+          }
+        }
+      })
+      .collect();
+    stack.extend(node.children.iter_mut());
+  }
+}
+
+/// Converts graph to mutation-annotated tree.
+///
+/// Only substitutions between A, C, G and T can be represented in the mutation-annotated tree, so other mutations
+/// (e.g. deletions) are omitted. Branch lengths are the numbers of mutations. Clades are annotated on the nodes where
+/// the clade differs from the clade of the parent.
+pub fn convert_graph_to_usher_mat(graph: &AuspiceGraph, ref_seq: &[Nuc]) -> Result<UsherMat, Report> {
+  let root_key = graph.get_exactly_one_root()?.key();
+
+  let mut node_mutations = vec![];
+  let mut metadata = vec![];
+  let mut stack: Vec<(GraphNodeKey, Option<String>)> = vec![(root_key, None)];
+  while let Some((key, parent_clade)) = stack.pop() {
+    let node = graph.get_node(key)?.payload();
+    node_mutations.push(get_usher_muts(node.branch_attrs.mutations.get("nuc"), ref_seq)?);
+
+    let clade = node.clade();
+    let annotation = if clade == parent_clade { None } else { clade.clone() };
+    metadata.push(vec![annotation.unwrap_or_default()]);
+
+    let children = graph.iter_child_keys_of_by_key(key).collect_vec();
+    stack.extend(children.into_iter().rev().map(|child| (child, clade.clone())));
+  }
+
+  let newick = format!("{};", convert_graph_to_usher_newick(graph, root_key, ref_seq)?);
+
+  Ok(UsherMat {
+    newick,
+    node_mutations,
+    condensed_nodes: vec![],
+    metadata,
+  })
+}
+
+fn get_usher_muts(muts: Option<&Vec<String>>, ref_seq: &[Nuc]) -> Result<Vec<UsherMut>, Report> {
+  muts
+    .into_iter()
+    .flatten()
+    .map(|m| NucSub::from_str(m))
+    .filter_map_ok(|m| {
+      let ref_nuc = nuc_to_usher_nuc(*ref_seq.get(m.pos.as_usize())?)?;
+      Some(UsherMut {
+        position: i32::try_from(m.pos.as_usize() + 1).ok()?,
+        ref_nuc,
+        par_nuc: nuc_to_usher_nuc(m.ref_nuc)?,
+        mut_nuc: vec![nuc_to_usher_nuc(m.qry_nuc)?],
+        chromosome: String::new(),
+      })
+    })
+    .collect()
+}
+
+/// Writes the tree topology of the graph in Newick format, with numbers of mutations as branch lengths. Nodes are
+/// visited in the same depth-first pre-order as the mutations and metadata are listed.
+fn convert_graph_to_usher_newick(
+  graph: &AuspiceGraph,
+  root_key: GraphNodeKey,
+  ref_seq: &[Nuc],
+) -> Result<String, Report> {
+  enum Step {
+    Open { key: GraphNodeKey, is_first_child: bool },
+    Close { key: GraphNodeKey },
+  }
+
+  let branch_length = |key: GraphNodeKey| -> Result<String, Report> {
+    if key == root_key {
+      return Ok(String::new());
+    }
+    let node = graph.get_node(key)?.payload();
+    let num_muts = get_usher_muts(node.branch_attrs.mutations.get("nuc"), ref_seq)?.len();
+    Ok(format!(":{num_muts}"))
+  };
+
+  let mut newick = String::new();
+  let mut stack = vec![Step::Open {
+    key: root_key,
+    is_first_child: true,
+  }];
+  while let Some(step) = stack.pop() {
+    match step {
+      Step::Open { key, is_first_child } => {
+        if !is_first_child {
+          newick.push(',');
+        }
+        if graph.is_leaf_key(key) {
+          let name = nwk_quote_name(&graph.get_node(key)?.payload().name);
+          newick += &name;
+          newick += &branch_length(key)?;
+        } else {
+          newick.push('(');
+          stack.push(Step::Close { key });
+          let children = graph.iter_child_keys_of_by_key(key).collect_vec();
+          stack.extend(children.into_iter().enumerate().rev().map(|(i, child)| Step::Open {
+            key: child,
+            is_first_child: i == 0,
+          }));
+        }
+      }
+      Step::Close { key } => {
+        let name = nwk_quote_name(&graph.get_node(key)?.payload().name);
+        newick.push(')');
+        newick += &name;
+        newick += &branch_length(key)?;
+      }
+    }
+  }
+  Ok(newick)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::align::params::AlignPairwiseParams;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::gene::gene_map::GeneMap;
+  use crate::tree::tree::AuspiceTreeNode;
+  use pretty_assertions::assert_eq;
+
+  fn mutation(position: i32, par_nuc: u32, mut_nuc: u32) -> UsherMut {
+    UsherMut {
+      position,
+      ref_nuc: par_nuc,
+      par_nuc,
+      mut_nuc: vec![mut_nuc],
+      chromosome: String::new(),
+    }
+  }
+
+  fn format_tree(node: &AuspiceTreeNode) -> String {
+    let muts = node
+      .branch_attrs
+      .mutations
+      .get("nuc")
+      .map(|muts| muts.join("+"))
+      .unwrap_or_default();
+    let clade = node
+      .node_attrs
+      .clade_membership
+      .as_ref()
+      .map(|clade| clade.value.clone())
+      .unwrap_or_default();
+    let children = if node.children.is_empty() {
+      String::new()
+    } else {
+      format!("({})", node.children.iter().map(format_tree).join(","))
+    };
+    format!("{children}{}[{muts}|{clade}]", node.name)
+  }
+
+  fn mat() -> UsherMat {
+    UsherMat {
+      newick: "(A:1,(B:1,C:0):1);".to_owned(),
+      node_mutations: vec![
+        vec![],
+        vec![mutation(2, 1, 3)],
+        vec![mutation(1, 0, 2)],
+        vec![mutation(4, 3, 0)],
+        vec![],
+      ],
+      condensed_nodes: vec![UsherCondensedNode {
+        node_name: "C".to_owned(),
+        condensed_leaves: vec!["C1".to_owned(), "C2".to_owned()],
+      }],
+      metadata: vec![
+        vec!["X".to_owned()],
+        vec![String::new()],
+        vec!["Y".to_owned()],
+        vec![String::new()],
+        vec![String::new()],
+      ],
+    }
+  }
+
+  #[test]
+  fn parses_protobuf() -> Result<(), Report> {
+    #[rustfmt::skip]
+    let bytes = [
+      0x0a, 0x06, b'(', b'A', b',', b'B', b')', b';', // newick
+      0x12, 0x00, // root: no mutations
+      0x12, 0x14, 0x0a, 0x12, // A: mutation list with one mutation:
+        0x08, 0x05, // position: 5
+        0x15, 0x00, 0x00, 0x00, 0x00, // ref_nuc: A
+        0x1d, 0x00, 0x00, 0x00, 0x00, // par_nuc: A
+        0x22, 0x04, 0x02, 0x00, 0x00, 0x00, // mut_nuc: [G]
+      0x12, 0x00, // B: no mutations
+      0x22, 0x03, 0x0a, 0x01, b'X', // root: clade annotation
+    ];
+    assert_eq!(
+      UsherMat::from_bytes(&bytes)?,
+      UsherMat {
+        newick: "(A,B);".to_owned(),
+        node_mutations: vec![vec![], vec![mutation(5, 0, 2)], vec![]],
+        condensed_nodes: vec![],
+        metadata: vec![vec!["X".to_owned()]],
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn roundtrips_protobuf() -> Result<(), Report> {
+    let mat = mat();
+    assert_eq!(UsherMat::from_bytes(&mat.to_bytes())?, mat);
+    Ok(())
+  }
+
+  #[test]
+  fn detects_mat_by_content() {
+    assert!(is_usher_mat_bytes(&mat().to_bytes()));
+    assert!(!is_usher_mat_bytes(b"(A:1,B:2)root;"));
+    assert!(!is_usher_mat_bytes(b"\n((A:1,B:2):1,C:3);"));
+    assert!(!is_usher_mat_bytes(b"{\"version\": \"v2\"}"));
+  }
+
+  #[test]
+  fn converts_mat_to_auspice_and_back() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq("ACGT")?;
+    let inputs = RefTreeImportInputs {
+      ref_seq: &ref_seq,
+      gene_map: &GeneMap::new(),
+      params: &AlignPairwiseParams::default(),
+      sequences: None,
+      node_data: None,
+    };
+
+    let tree = convert_usher_mat_to_auspice_tree(&mat(), &inputs)?;
+    assert_eq!(
+      format_tree(&tree.tree),
+      "(A[C2T|X],(B[T4A|Y],C1[|Y],C2[|Y])node_2[A1G|Y])node_1[|X]"
+    );
+
+    let graph = AuspiceGraph::from_auspice_tree(tree)?;
+    let exported = convert_graph_to_usher_mat(&graph, &ref_seq)?;
+    assert_eq!(exported.newick, "(A:1,(B:1,C1:0,C2:0)node_2:1)node_1;");
+    assert_eq!(
+      exported.node_mutations,
+      vec![
+        vec![],
+        vec![mutation(2, 1, 3)],
+        vec![mutation(1, 0, 2)],
+        vec![UsherMut {
+          ref_nuc: 3,
+          ..mutation(4, 3, 0)
+        }],
+        vec![],
+        vec![],
+      ]
+    );
+    assert_eq!(
+      exported.metadata,
+      vec![
+        vec!["X".to_owned()],
+        vec![String::new()],
+        vec!["Y".to_owned()],
+        vec![String::new()],
+        vec![String::new()],
+        vec![String::new()],
+      ]
+    );
+    Ok(())
+  }
+}
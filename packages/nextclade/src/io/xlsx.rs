@@ -1,18 +1,27 @@
+use crate::io::fs::ensure_dir;
 use crate::io::nextclade_csv::prepare_headers;
 use crate::io::nextclade_csv_column_config::CsvColumnConfig;
 use crate::io::nextclade_csv_row::NextcladeResultsCsvRow;
+use crate::io::nextclade_mutations_tsv::{format_mutation_rows, mutations_founder_attrs, mutations_headers};
+use crate::qc::qc_run::QcStatus;
 use crate::run::nextclade_wasm::AnalysisInitialData;
 use crate::types::outputs::{
   NextcladeErrorOutputs, NextcladeOutputOrError, NextcladeOutputs, combine_outputs_and_errors_sorted,
 };
 use crate::utils::string::truncate_left;
-use eyre::Report;
-use rust_xlsxwriter::{Workbook, Worksheet};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use rust_xlsxwriter::{Color, Format, Workbook, Worksheet};
+use std::collections::BTreeMap;
+use std::path::Path;
 
 pub const EXCEL_SHEET_NAME_LEN_MAX: usize = 31;
 
 pub const DEFAULT_NEXTCLADE_XLSX_SHEET_NAME: &str = "nextclade";
 
+/// Maximum number of rows in a sheet (1,048,576), including the header row
+pub const EXCEL_ROWS_MAX: usize = 1 << 20;
+
 pub fn results_to_excel_sheet(
   outputs: &[NextcladeOutputs],
   errors: &[NextcladeErrorOutputs],
@@ -52,6 +61,276 @@ pub fn results_to_excel_sheet(
   Ok(sheet)
 }
 
+/// Creates a workbook with multiple sheets: a summary of the run, the main results (same columns as in CSV/TSV
+/// outputs), mutations in long format (one row per mutation), QC details and errors.
+pub fn results_to_excel_book(
+  outputs: &[NextcladeOutputs],
+  errors: &[NextcladeErrorOutputs],
+  initial_data: &AnalysisInitialData,
+  column_config: &CsvColumnConfig,
+) -> Result<Workbook, Report> {
+  let results = results_to_excel_sheet_formatted(outputs, errors, initial_data, column_config)?;
+
+  let outputs = outputs.iter().sorted_by_key(|output| output.index).collect_vec();
+  let errors = errors.iter().sorted_by_key(|error| error.index).collect_vec();
+
+  let mut book = Workbook::new();
+  book.push_worksheet(summary_to_excel_sheet(&outputs, &errors)?);
+  book.push_worksheet(results);
+  for sheet in mutations_to_excel_sheets(&outputs, initial_data)? {
+    book.push_worksheet(sheet);
+  }
+  book.push_worksheet(qc_to_excel_sheet(&outputs)?);
+  book.push_worksheet(errors_to_excel_sheet(&errors)?);
+  Ok(book)
+}
+
+/// Main results sheet, with frozen header and colored QC status
+fn results_to_excel_sheet_formatted(
+  outputs: &[NextcladeOutputs],
+  errors: &[NextcladeErrorOutputs],
+  initial_data: &AnalysisInitialData,
+  column_config: &CsvColumnConfig,
+) -> Result<Worksheet, Report> {
+  let mut sheet = results_to_excel_sheet(outputs, errors, initial_data, column_config)?;
+  sheet.set_name("results")?;
+  freeze_header(&mut sheet)?;
+
+  let headers: Vec<String> = prepare_headers(
+    &initial_data.clade_node_attr_key_descs,
+    &initial_data.phenotype_attr_keys,
+    &initial_data.ref_nodes,
+    &initial_data.aa_motif_keys,
+    column_config,
+  );
+
+  // Rows are in the same order as in `results_to_excel_sheet()`
+  if let Some(icol) = headers.iter().position(|header| header == "qc.overallStatus") {
+    let outputs_or_errors = combine_outputs_and_errors_sorted(outputs, errors);
+    for (irow, (_, output_or_error)) in outputs_or_errors.iter().enumerate() {
+      if let NextcladeOutputOrError::Outputs(output) = output_or_error {
+        write_qc_status(&mut sheet, (irow + 1) as u32, icol as u16, &output.qc.overall_status)?;
+      }
+    }
+  }
+
+  Ok(sheet)
+}
+
+fn summary_to_excel_sheet(
+  outputs: &[&NextcladeOutputs],
+  errors: &[&NextcladeErrorOutputs],
+) -> Result<Worksheet, Report> {
+  let mut sheet = Worksheet::new();
+  sheet.set_name("summary")?;
+  let bold = Format::new().set_bold();
+
+  let mut irow = 0;
+  for (key, value) in [
+    ("Total sequences", outputs.len() + errors.len()),
+    ("Analyzed", outputs.len()),
+    ("Errors", errors.len()),
+  ] {
+    sheet.write_string_with_format(irow, 0, key, &bold)?;
+    sheet.write_number(irow, 1, value as f64)?;
+    irow += 1;
+  }
+
+  irow += 1;
+  sheet.write_string_with_format(irow, 0, "QC status", &bold)?;
+  sheet.write_string_with_format(irow, 1, "count", &bold)?;
+  irow += 1;
+  for status in [QcStatus::Good, QcStatus::Mediocre, QcStatus::Bad] {
+    let count = outputs
+      .iter()
+      .filter(|output| output.qc.overall_status == status)
+      .count();
+    write_qc_status(&mut sheet, irow, 0, &status)?;
+    sheet.write_number(irow, 1, count as f64)?;
+    irow += 1;
+  }
+
+  irow += 1;
+  sheet.write_string_with_format(irow, 0, "clade", &bold)?;
+  sheet.write_string_with_format(irow, 1, "count", &bold)?;
+  irow += 1;
+  let clade_counts: BTreeMap<&str, usize> = outputs
+    .iter()
+    .filter_map(|output| output.clade.as_deref())
+    .counts()
+    .into_iter()
+    .collect();
+  for (clade, count) in clade_counts {
+    sheet.write_string(irow, 0, clade)?;
+    sheet.write_number(irow, 1, count as f64)?;
+    irow += 1;
+  }
+
+  sheet.autofit();
+  Ok(sheet)
+}
+
+/// Mutations in long format, with the same columns as in the mutations TSV output: one row per mutation of each
+/// sequence.
+///
+/// Large runs have more mutations than a sheet can hold (`EXCEL_ROWS_MAX`), so the rows are split across as many sheets
+/// as needed: "mutations", "mutations_2", "mutations_3" etc.
+fn mutations_to_excel_sheets(
+  outputs: &[&NextcladeOutputs],
+  initial_data: &AnalysisInitialData,
+) -> Result<Vec<Worksheet>, Report> {
+  let ref_nodes = &initial_data.ref_nodes;
+  let founder_attrs = mutations_founder_attrs(&initial_data.clade_node_attr_key_descs);
+  let headers = mutations_headers(&founder_attrs, ref_nodes);
+  let headers = headers.iter().map(String::as_str).collect_vec();
+
+  let rows = outputs
+    .iter()
+    .flat_map(|output| format_mutation_rows(output, &founder_attrs, ref_nodes));
+
+  write_table_split("mutations", &headers, rows, EXCEL_ROWS_MAX)
+}
+
+/// Writes a table, splitting it across as many sheets of at most `max_rows` rows (including the header) as needed.
+/// Sheets are named `name`, `name_2`, `name_3` etc. At least one sheet is written, even if there are no rows.
+fn write_table_split(
+  name: &str,
+  headers: &[&str],
+  rows: impl IntoIterator<Item = impl IntoIterator<Item = String>>,
+  max_rows: usize,
+) -> Result<Vec<Worksheet>, Report> {
+  let mut sheets = vec![];
+  for (i, chunk) in (&rows.into_iter().chunks(max_rows - 1)).into_iter().enumerate() {
+    let mut sheet = Worksheet::new();
+    sheet.set_name(sheet_name_numbered(name, i))?;
+    write_table(&mut sheet, headers, chunk)?;
+    sheets.push(sheet);
+  }
+
+  if sheets.is_empty() {
+    let mut sheet = Worksheet::new();
+    sheet.set_name(name)?;
+    write_table(&mut sheet, headers, std::iter::empty::<Vec<String>>())?;
+    sheets.push(sheet);
+  }
+
+  Ok(sheets)
+}
+
+fn sheet_name_numbered(name: &str, i: usize) -> String {
+  if i == 0 {
+    name.to_owned()
+  } else {
+    format!("{name}_{}", i + 1)
+  }
+}
+
+/// Scores and statuses of each QC rule
+fn qc_to_excel_sheet(outputs: &[&NextcladeOutputs]) -> Result<Worksheet, Report> {
+  let headers = [
+    "index",
+    "seqName",
+    "qc.overallScore",
+    "qc.overallStatus",
+    "qc.missingData.score",
+    "qc.missingData.status",
+    "qc.mixedSites.score",
+    "qc.mixedSites.status",
+    "qc.privateMutations.score",
+    "qc.privateMutations.status",
+    "qc.snpClusters.score",
+    "qc.snpClusters.status",
+    "qc.frameShifts.score",
+    "qc.frameShifts.status",
+    "qc.stopCodons.score",
+    "qc.stopCodons.status",
+  ];
+
+  let mut sheet = Worksheet::new();
+  sheet.set_name("qc")?;
+  write_table(&mut sheet, &headers, std::iter::empty::<Vec<String>>())?;
+
+  for (irow, output) in outputs.iter().enumerate() {
+    let irow = (irow + 1) as u32;
+    let qc = &output.qc;
+    sheet.write_number(irow, 0, output.index as f64)?;
+    sheet.write_string(irow, 1, &output.seq_name)?;
+    sheet.write_number(irow, 2, qc.overall_score)?;
+    write_qc_status(&mut sheet, irow, 3, &qc.overall_status)?;
+
+    let rules = [
+      qc.missing_data.as_ref().map(|r| (r.score, &r.status)),
+      qc.mixed_sites.as_ref().map(|r| (r.score, &r.status)),
+      qc.private_mutations.as_ref().map(|r| (r.score, &r.status)),
+      qc.snp_clusters.as_ref().map(|r| (r.score, &r.status)),
+      qc.frame_shifts.as_ref().map(|r| (r.score, &r.status)),
+      qc.stop_codons.as_ref().map(|r| (r.score, &r.status)),
+    ];
+    for (irule, rule) in rules.into_iter().enumerate() {
+      if let Some((score, status)) = rule {
+        let icol = (4 + 2 * irule) as u16;
+        sheet.write_number(irow, icol, score)?;
+        write_qc_status(&mut sheet, irow, icol + 1, status)?;
+      }
+    }
+  }
+
+  Ok(sheet)
+}
+
+fn errors_to_excel_sheet(errors: &[&NextcladeErrorOutputs]) -> Result<Worksheet, Report> {
+  let rows = errors
+    .iter()
+    .map(|error| [error.index.to_string(), error.seq_name.clone(), error.errors.join(";")]);
+  let mut sheet = Worksheet::new();
+  sheet.set_name("errors")?;
+  write_table(&mut sheet, &["index", "seqName", "errors"], rows)?;
+  Ok(sheet)
+}
+
+/// Writes bold header with frozen pane, followed by rows of strings
+fn write_table(
+  sheet: &mut Worksheet,
+  headers: &[&str],
+  rows: impl IntoIterator<Item = impl IntoIterator<Item = String>>,
+) -> Result<(), Report> {
+  let bold = Format::new().set_bold();
+  for (icol, header) in headers.iter().enumerate() {
+    sheet.write_string_with_format(0, icol as u16, *header, &bold)?;
+  }
+  for (irow, row) in rows.into_iter().enumerate() {
+    for (icol, value) in row.into_iter().enumerate() {
+      sheet.write_string((irow + 1) as u32, icol as u16, value)?;
+    }
+  }
+  freeze_header(sheet)
+}
+
+fn freeze_header(sheet: &mut Worksheet) -> Result<(), Report> {
+  sheet.set_freeze_panes(1, 0)?;
+  Ok(())
+}
+
+/// Writes QC status, with background color of the corresponding status (same colors as in Nextclade Web)
+fn write_qc_status(sheet: &mut Worksheet, row: u32, col: u16, status: &QcStatus) -> Result<(), Report> {
+  let color = match status {
+    QcStatus::Good => 0x0068_b844,
+    QcStatus::Mediocre => 0x00e4_902f,
+    QcStatus::Bad => 0x00da_4e3c,
+  };
+  let format = Format::new().set_background_color(Color::RGB(color));
+  sheet.write_string_with_format(row, col, status.to_string(), &format)?;
+  Ok(())
+}
+
+pub fn book_save_to_file(book: &mut Workbook, filepath: impl AsRef<Path>) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  ensure_dir(filepath)?;
+  book
+    .save(filepath)
+    .wrap_err_with(|| format!("When writing Excel file: {}", filepath.display()))
+}
+
 pub fn book_save_to_buffer(book: &mut Workbook) -> Result<Vec<u8>, Report> {
   let buf = book.save_to_buffer()?;
   Ok(buf)
@@ -83,3 +362,23 @@ pub fn sanitize_sheet_name(name: &str) -> String {
 
   sanitized
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn splits_table_across_sheets() -> Result<(), Report> {
+    let rows = (0..5).map(|i| [i.to_string()]);
+    let sheets = write_table_split("mutations", &["i"], rows, 3)?;
+    assert_eq!(
+      sheets.iter().map(Worksheet::name).collect_vec(),
+      vec!["mutations", "mutations_2", "mutations_3"]
+    );
+
+    let sheets = write_table_split("mutations", &["i"], std::iter::empty::<Vec<String>>(), 3)?;
+    assert_eq!(sheets.iter().map(Worksheet::name).collect_vec(), vec!["mutations"]);
+    Ok(())
+  }
+}
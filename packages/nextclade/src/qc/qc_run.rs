@@ -16,7 +16,7 @@ use std::collections::BTreeMap;
 /// Overall quality category derived from a numeric QC score.
 ///
 /// Thresholds: 0-29 = Good, 30-99 = Mediocre, 100+ = Bad.
-#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "lowercase")]
 #[derive(Default)]
 pub enum QcStatus {
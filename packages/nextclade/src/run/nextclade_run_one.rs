@@ -1,6 +1,5 @@
 use crate::align::align::align_nuc;
 use crate::align::insertions_strip::{AaIns, NucIns, get_aa_insertions, insertions_strip};
-use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::aa_changes_find::aa_changes_find;
@@ -24,7 +23,7 @@ use crate::analyze::find_relative_aa_mutations::{RelativeAaMutations, find_relat
 use crate::analyze::find_relative_nuc_mutations::{RelativeNucMutations, find_relative_nuc_mutations};
 use crate::analyze::letter_composition::get_letter_composition;
 use crate::analyze::letter_ranges::{
-  CdsAaRange, NucRange, find_aa_letter_ranges, find_letter_ranges, find_letter_ranges_by,
+  CdsAaRange, NucRange, find_letter_ranges, find_letter_ranges_by, find_unknown_aa_ranges,
 };
 use crate::analyze::nuc_alignment::NucAlignment;
 use crate::analyze::nuc_changes::{FindNucChangesOutput, find_nuc_changes};
@@ -47,10 +46,11 @@ use crate::run::nextclade_wasm::{AnalysisOutput, Nextclade};
 use crate::translate::aa_alignment_ranges::{GatherAaAlignmentRangesResult, gather_aa_alignment_ranges};
 use crate::translate::frame_shifts_flatten::frame_shifts_flatten;
 use crate::translate::frame_shifts_translate::FrameShift;
-use crate::translate::translate_genes::{Translation, translate_genes};
+use crate::translate::translate_genes::{AmbiguousAa, Translation, translate_genes};
 use crate::tree::tree_find_ancestors_of_interest::{AncestralSearchResult, graph_find_ancestors_of_interest};
-use crate::tree::tree_find_nearest_node::graph_find_nearest_nodes;
-use crate::types::outputs::{NextcladeOutputs, PeptideWarning, PhenotypeValue};
+use crate::tree::tree_find_clade_posterior::{CladePosterior, graph_find_clade_posterior};
+use crate::tree::tree_find_nearest_node::{PlacementQuery, TreePlacementInfo, graph_find_nearest_nodes};
+use crate::types::outputs::{NextcladeOutputs, NucWarning, PeptideWarning, PhenotypeValue};
 use crate::utils::num::float_collapse_zero;
 use eyre::Report;
 use indexmap::indexmap;
@@ -74,6 +74,8 @@ struct NextcladeResultWithAa {
   total_frame_shifts: usize,
   unknown_aa_ranges: Vec<CdsAaRange>,
   total_unknown_aa: usize,
+  ambiguous_aas: Vec<AmbiguousAa>,
+  total_ambiguous_aa: usize,
   aa_alignment_ranges: BTreeMap<String, Vec<AaRefRange>>,
   aa_unsequenced_ranges: BTreeMap<String, Vec<AaRefRange>>,
   cds_coverage: BTreeMap<String, f64>,
@@ -83,6 +85,7 @@ struct NextcladeResultWithAa {
 #[derive(Default)]
 struct NextcladeResultWithGraph {
   clade: Option<String>,
+  clade_posterior: Option<CladePosterior>,
   private_nuc_mutations: PrivateNucMutations,
   private_aa_mutations: BTreeMap<String, PrivateAaMutations>,
   divergence: f64,
@@ -90,6 +93,7 @@ struct NextcladeResultWithGraph {
   nearest_node_id: GraphNodeKey,
   nearest_node_name: String,
   nearest_nodes: Option<Vec<String>>,
+  placement_candidates: Vec<TreePlacementInfo>,
   ref_node_search_results: Vec<AncestralSearchResult>,
   relative_nuc_mutations: Vec<RelativeNucMutations>,
   relative_aa_mutations: Vec<RelativeAaMutations>,
@@ -109,10 +113,12 @@ pub fn nextclade_run_one(
     ref_record,
     seed_index,
     gap_open_close_nuc,
+    nuc_substitution_scores,
     virus_properties,
     params,
     gene_map,
     gap_open_close_aa,
+    aa_substitution_scores,
     ref_translation,
     aa_motifs_ref,
     graph,
@@ -130,6 +136,7 @@ pub fn nextclade_run_one(
     ref_seq,
     seed_index,
     gap_open_close_nuc,
+    nuc_substitution_scores,
     &params.alignment,
   )?;
 
@@ -183,6 +190,8 @@ pub fn nextclade_run_one(
     total_frame_shifts,
     unknown_aa_ranges,
     total_unknown_aa,
+    ambiguous_aas,
+    total_ambiguous_aa,
     aa_alignment_ranges,
     aa_unsequenced_ranges,
     cds_coverage,
@@ -196,6 +205,7 @@ pub fn nextclade_run_one(
       &coord_map_global,
       &alignment_range,
       gap_open_close_aa,
+      aa_substitution_scores,
       &params.alignment,
     )?;
 
@@ -242,9 +252,15 @@ pub fn nextclade_run_one(
     let total_aminoacid_deletions = aa_deletions.len();
     let total_aminoacid_insertions = aa_insertions.len();
 
-    let unknown_aa_ranges = find_aa_letter_ranges(&translation, Aa::X);
+    let unknown_aa_ranges = find_unknown_aa_ranges(&translation);
     let total_unknown_aa = unknown_aa_ranges.iter().map(|r| r.length).sum();
 
+    let ambiguous_aas = translation
+      .cdses()
+      .flat_map(|cds| cds.ambiguous_aas.iter().cloned())
+      .collect_vec();
+    let total_ambiguous_aa = ambiguous_aas.len();
+
     let GatherAaAlignmentRangesResult {
       aa_alignment_ranges,
       aa_unsequenced_ranges,
@@ -303,6 +319,8 @@ pub fn nextclade_run_one(
       total_frame_shifts,
       unknown_aa_ranges,
       total_unknown_aa,
+      ambiguous_aas,
+      total_ambiguous_aa,
       aa_alignment_ranges,
       aa_unsequenced_ranges,
       cds_coverage,
@@ -312,8 +330,17 @@ pub fn nextclade_run_one(
     NextcladeResultWithAa::default()
   };
 
+  let nuc_warnings = alignment
+    .chimera
+    .iter()
+    .map(|chimera| NucWarning {
+      warning: format!("When processing sequence #{index} '{seq_name}': {}", chimera.describe()),
+    })
+    .collect_vec();
+
   let NextcladeResultWithGraph {
     clade,
+    clade_posterior,
     private_nuc_mutations,
     private_aa_mutations,
     clade_founder_info,
@@ -326,23 +353,54 @@ pub fn nextclade_run_one(
     nearest_node_id,
     nearest_node_name,
     nearest_nodes,
+    placement_candidates,
   } = if let Some(graph) = graph {
-    let nearest_node_candidates =
-      graph_find_nearest_nodes(graph, &substitutions, &missing, &deletions, &alignment_range)?;
+    let nearest_node_candidates = graph_find_nearest_nodes(
+      graph,
+      &PlacementQuery {
+        nuc_subs: &substitutions,
+        missing: &missing,
+        deletions: &deletions,
+        non_acgtns: &non_acgtns,
+        aln_range: &alignment_range,
+      },
+      ref_seq,
+      &virus_properties.placement_site_weights,
+      &params.tree_builder,
+    )?;
     let nearest_node_id = nearest_node_candidates[0].node_key;
     let nearest_node = graph.get_node(nearest_node_id)?.payload();
     let nearest_node_name = nearest_node.name.clone();
 
+    // Choose all nodes with distance equal to the distance of the nearest node
+    let placement_candidates = nearest_node_candidates
+      .iter()
+      .take_while(|n| n.is_as_near_as(&nearest_node_candidates[0]))
+      .cloned()
+      .collect_vec();
+
     let nearest_nodes = params.general.include_nearest_node_info.then_some(
-      nearest_node_candidates
+      placement_candidates
         .iter()
-        // Choose all nodes with distance equal to the distance of the nearest node
-        .filter(|n| n.distance == nearest_node_candidates[0].distance)
         .map(|n| Ok(graph.get_node(n.node_key)?.payload().name.clone()))
         .collect::<Result<Vec<String>, Report>>()?,
     );
 
-    let clade = nearest_node.clade();
+    let clade_posterior = params
+      .tree_builder
+      .probabilistic_clade_assignment
+      .then(|| graph_find_clade_posterior(graph, &nearest_node_candidates, &params.tree_builder))
+      .transpose()?;
+
+    // Clade and its founder are taken from the same node: the nearest node, or, if probabilistic clade assignment is
+    // enabled, the nearest of the placement candidates which supports the assigned clade
+    let (clade, clade_node_key) = match &clade_posterior {
+      Some(clade_posterior) => (
+        clade_posterior.clade.clone(),
+        clade_posterior.clade_node_key.unwrap_or(nearest_node_id),
+      ),
+      None => (nearest_node.clade(), nearest_node_id),
+    };
 
     let clade_node_attr_descs = graph.data.meta.clade_node_attr_descs();
     let clade_node_attrs = nearest_node.get_clade_node_attrs(clade_node_attr_descs);
@@ -386,7 +444,7 @@ pub fn nextclade_run_one(
         ref_seq.len(),
       );
 
-    let clade_founder_info = find_clade_founder(graph, nearest_node_id, clade.as_ref(), &nuc_params, &aa_params)?;
+    let clade_founder_info = find_clade_founder(graph, clade_node_key, clade.as_ref(), &nuc_params, &aa_params)?;
 
     let clade_node_attr_founder_info =
       find_clade_node_attrs_founders(graph, nearest_node_id, clade_node_attr_descs, &nuc_params, &aa_params)?;
@@ -408,6 +466,7 @@ pub fn nextclade_run_one(
 
     NextcladeResultWithGraph {
       clade,
+      clade_posterior,
       private_nuc_mutations,
       private_aa_mutations,
       clade_founder_info,
@@ -420,6 +479,7 @@ pub fn nextclade_run_one(
       nearest_node_id,
       nearest_node_name,
       nearest_nodes,
+      placement_candidates,
     }
   } else {
     NextcladeResultWithGraph::default()
@@ -444,6 +504,7 @@ pub fn nextclade_run_one(
     .unwrap_or_default();
 
   let is_reverse_complement = alignment.is_reverse_complement;
+  let chimera = alignment.chimera;
 
   let len_unaligned = qry_seq.len();
   let len_aligned = alignment.qry_seq.len();
@@ -493,6 +554,8 @@ pub fn nextclade_run_one(
       total_aminoacid_insertions,
       unknown_aa_ranges,
       total_unknown_aa,
+      ambiguous_aas,
+      total_ambiguous_aa,
       aa_changes_groups,
       nuc_to_aa_muts,
       alignment_range,
@@ -502,6 +565,7 @@ pub fn nextclade_run_one(
       pcr_primer_changes,
       total_pcr_primer_changes,
       warnings,
+      nuc_warnings,
       missing_cdses: missing_genes,
       coverage,
       cds_coverage,
@@ -509,6 +573,7 @@ pub fn nextclade_run_one(
       aa_motifs_changes,
       qc,
       clade,
+      clade_posterior,
       private_nuc_mutations,
       private_aa_mutations,
       clade_founder_info,
@@ -523,7 +588,10 @@ pub fn nextclade_run_one(
       nearest_node_id,
       nearest_node_name,
       nearest_nodes,
+      placement_candidates,
       is_reverse_complement,
+      chimera,
+      chimera_parts: vec![],
       annotation,
     },
   })
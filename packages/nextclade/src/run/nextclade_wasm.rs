@@ -1,5 +1,8 @@
+use crate::align::chimera::AlignmentChimera;
 use crate::align::gap_open::{GapScoreMap, get_gap_open_close_scores_codon_aware, get_gap_open_close_scores_flat};
 use crate::align::seed_match::CodonSpacedIndex;
+use crate::align::substitution_matrix::SubstitutionScores;
+use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq};
 use crate::alphabet::nuc::{Nuc, to_nuc_seq, to_nuc_seq_replacing};
 use crate::analyze::find_aa_motifs::find_aa_motifs;
@@ -7,9 +10,10 @@ use crate::analyze::find_aa_motifs_changes::AaMotifsMap;
 use crate::analyze::pcr_primers::PcrPrimer;
 use crate::analyze::phenotype::get_phenotype_attr_descs;
 use crate::analyze::virus_properties::{AaMotifsDesc, PhenotypeAttrDesc, VirusProperties};
+use crate::coord::position::PositionLike;
 use crate::gene::gene_map::{GeneMap, filter_gene_map};
 use crate::graph::graph::Graph;
-use crate::io::fasta::{FastaRecord, read_one_fasta_from_str};
+use crate::io::fasta::{FastaRecord, parse_fasta_header, read_one_fasta_from_str};
 use crate::io::nextclade_csv_column_config::CsvColumnConfig;
 use crate::io::nwk_writer::nwk_write_to_string;
 use crate::run::nextclade_run_one::nextclade_run_one;
@@ -20,7 +24,8 @@ use crate::translate::translate_genes_ref::translate_genes_ref;
 use crate::tree::tree::{AuspiceGraph, AuspiceRefNodesDesc, AuspiceTree, CladeNodeAttrKeyDesc, check_ref_seq_mismatch};
 use crate::tree::tree_builder::graph_attach_new_nodes_in_place;
 use crate::tree::tree_preprocess::graph_preprocess_in_place;
-use crate::types::outputs::NextcladeOutputs;
+use crate::types::outputs::{NextcladeOutputs, NucWarning};
+use crate::utils::error::report_to_string;
 use crate::utils::option::{OptionMapRefFallible, find_some};
 use eyre::{Report, WrapErr, eyre};
 use itertools::Itertools;
@@ -277,6 +282,7 @@ pub struct Nextclade {
   pub ref_seq: Vec<Nuc>,
   pub seed_index: CodonSpacedIndex,
   pub gap_open_close_nuc: Vec<i32>,
+  pub nuc_substitution_scores: SubstitutionScores,
   pub virus_properties: VirusProperties,
   pub primers: Vec<PcrPrimer>,
   pub params: NextcladeInputParams,
@@ -284,6 +290,7 @@ pub struct Nextclade {
   // If genome annotation is provided
   pub gene_map: GeneMap,
   pub gap_open_close_aa: Vec<i32>,
+  pub aa_substitution_scores: SubstitutionScores,
   pub ref_translation: Translation,
   pub aa_motifs_ref: AaMotifsMap,
   pub aa_motifs_descs: Vec<AaMotifsDesc>,
@@ -326,6 +333,10 @@ impl Nextclade {
     let params = NextcladeInputParams::from_optional(params, &virus_properties)?;
     let ref_seq = to_nuc_seq(&ref_record.seq).wrap_err("When converting reference sequence")?;
     let seed_index = CodonSpacedIndex::from_sequence(&ref_seq);
+    let nuc_substitution_scores =
+      SubstitutionScores::new::<Nuc>(&params.alignment).wrap_err("When preparing nucleotide substitution matrix")?;
+    let aa_substitution_scores =
+      SubstitutionScores::new::<Aa>(&params.alignment).wrap_err("When preparing amino acid substitution matrix")?;
 
     if let Some(tree) = &tree
       && let Some(tree_ref) = tree.root_sequence()
@@ -400,11 +411,13 @@ impl Nextclade {
       ref_seq,
       seed_index,
       gap_open_close_nuc,
+      nuc_substitution_scores,
       virus_properties,
       primers,
       params,
       gene_map,
       gap_open_close_aa,
+      aa_substitution_scores,
       ref_translation,
       aa_motifs_ref,
       aa_motifs_descs,
@@ -443,6 +456,32 @@ impl Nextclade {
     nextclade_run_one(input.index, &input.seq_name, &qry_seq, self)
   }
 
+  /// Splits a query sequence detected as chimeric into parts, one per block of seed matches, and analyzes each part
+  /// independently. Results of the parts are nested into the results of the original sequence, and the parts which
+  /// could not be analyzed are reported as warnings of the original sequence. Does nothing if the sequence is not
+  /// chimeric.
+  pub fn run_chimera_parts_in_place(&self, input: &FastaRecord, analysis_result: &mut NextcladeOutputs) {
+    let Some(chimera) = &analysis_result.chimera else {
+      return;
+    };
+
+    for part in split_chimeric_record(input, chimera, analysis_result.is_reverse_complement) {
+      match self.run(&part) {
+        Ok(AnalysisOutput {
+          analysis_result: part_result,
+          ..
+        }) => analysis_result.chimera_parts.push(part_result),
+        Err(report) => analysis_result.nuc_warnings.push(NucWarning {
+          warning: format!(
+            "When processing chimeric sequence part '{}': {}",
+            part.seq_name,
+            report_to_string(&report)
+          ),
+        }),
+      }
+    }
+  }
+
   pub fn get_output_trees(&mut self, results: Vec<NextcladeOutputs>) -> Result<Option<OutputTrees>, Report> {
     if let Some(graph) = &mut self.graph {
       graph_attach_new_nodes_in_place(graph, results, self.ref_seq.len(), &self.params.tree_builder)?;
@@ -455,6 +494,52 @@ impl Nextclade {
   }
 }
 
+/// Cuts the query sequence into parts, such that each part contains exactly one block of a chimera. The boundaries
+/// between parts are placed in the middle of the unmatched regions between consecutive blocks.
+pub fn split_chimeric_record(
+  input: &FastaRecord,
+  chimera: &AlignmentChimera,
+  is_reverse_complement: bool,
+) -> Vec<FastaRecord> {
+  let seq_len = input.seq.len();
+  let (seq_id, seq_desc) = parse_fasta_header(&input.seq_name);
+
+  let mut boundaries = vec![0];
+  for (left, right) in chimera.blocks.iter().tuple_windows() {
+    let left_end = left.qry_range.end.as_usize();
+    let right_begin = right.qry_range.begin.as_usize();
+    let boundary = usize::midpoint(left_end, right_begin).clamp(*boundaries.last().unwrap(), seq_len);
+    boundaries.push(boundary);
+  }
+  boundaries.push(seq_len);
+
+  boundaries
+    .iter()
+    .tuple_windows()
+    .enumerate()
+    .map(|(i, (&begin, &end))| {
+      // Block ranges are in the orientation which was used for alignment
+      let (begin, end) = if is_reverse_complement {
+        (seq_len - end, seq_len - begin)
+      } else {
+        (begin, end)
+      };
+
+      let seq_name = if seq_desc.is_empty() {
+        format!("{seq_id}_part{}", i + 1)
+      } else {
+        format!("{seq_id}_part{} {seq_desc}", i + 1)
+      };
+
+      FastaRecord {
+        index: input.index,
+        seq_name,
+        seq: input.seq.get(begin..end).unwrap_or_default().to_owned(),
+      }
+    })
+    .collect_vec()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct OutputTrees {
   auspice: AuspiceTree,
@@ -0,0 +1,284 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::alphabet::aa::{Aa, to_aa};
+use crate::alphabet::letter::Letter;
+use crate::alphabet::nuc::Nuc;
+use crate::gene::cds::Cds;
+use crate::gene::cds_segment::Truncation;
+use crate::make_error;
+use eyre::{Report, WrapErr};
+use itertools::{Itertools, iproduct};
+
+/// Name of the GFF3 attribute which sets genetic code of a CDS, as in GenBank and INSDC
+pub const TRANSL_TABLE_ATTR: &str = "transl_table";
+
+/// Genetic code (translation table), as defined by NCBI.
+///
+/// Codons are listed in the NCBI order: TTT, TTC, TTA, TTG, TCT, ..., GGG (nucleotides ordered as T, C, A, G).
+/// See: https://www.ncbi.nlm.nih.gov/Taxonomy/Utils/wprintgc.cgi
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GeneticCode {
+  pub id: u8,
+  pub name: &'static str,
+  pub amino_acids: &'static [u8; 64],
+  pub starts: &'static [u8; 64],
+}
+
+#[rustfmt::skip]
+static GENETIC_CODES: &[GeneticCode] = &[
+  GeneticCode { id: 1,  name: "Standard",                                  amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"---M------**--*----M---------------M----------------------------" },
+  GeneticCode { id: 2,  name: "Vertebrate Mitochondrial",                  amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG", starts: b"----------**--------------------MMMM----------**---M------------" },
+  GeneticCode { id: 3,  name: "Yeast Mitochondrial",                       amino_acids: b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"----------**----------------------MM---------------M------------" },
+  GeneticCode { id: 4,  name: "Mold, Protozoan, Coelenterate Mitochondrial and Mycoplasma/Spiroplasma", amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"--MM------**-------M------------MMMM---------------M------------" },
+  GeneticCode { id: 5,  name: "Invertebrate Mitochondrial",                amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG", starts: b"---M------**--------------------MMMM---------------M------------" },
+  GeneticCode { id: 6,  name: "Ciliate, Dasycladacean and Hexamita Nuclear", amino_acids: b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"-----------------------------------M----------------------------" },
+  GeneticCode { id: 9,  name: "Echinoderm and Flatworm Mitochondrial",     amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: b"-----------------------------------M---------------M------------" },
+  GeneticCode { id: 10, name: "Euplotid Nuclear",                          amino_acids: b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"-----------------------------------M----------------------------" },
+  GeneticCode { id: 11, name: "Bacterial, Archaeal and Plant Plastid",     amino_acids: b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"---M------**--*----M------------MMMM---------------M------------" },
+  GeneticCode { id: 12, name: "Alternative Yeast Nuclear",                 amino_acids: b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"-------------------M---------------M----------------------------" },
+  GeneticCode { id: 13, name: "Ascidian Mitochondrial",                    amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG", starts: b"---M------------------------------MM---------------M------------" },
+  GeneticCode { id: 14, name: "Alternative Flatworm Mitochondrial",        amino_acids: b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: b"-----------------------------------M----------------------------" },
+  GeneticCode { id: 16, name: "Chlorophycean Mitochondrial",               amino_acids: b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"-----------------------------------M----------------------------" },
+  GeneticCode { id: 21, name: "Trematode Mitochondrial",                   amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG", starts: b"-----------------------------------M---------------M------------" },
+  GeneticCode { id: 22, name: "Scenedesmus obliquus Mitochondrial",        amino_acids: b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"-----------------------------------M----------------------------" },
+  GeneticCode { id: 23, name: "Thraustochytrium Mitochondrial",            amino_acids: b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"--------------------------------M--M---------------M------------" },
+  GeneticCode { id: 24, name: "Rhabdopleuridae Mitochondrial",             amino_acids: b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG", starts: b"---M---------------M---------------M---------------M------------" },
+  GeneticCode { id: 25, name: "Candidate Division SR1 and Gracilibacteria", amino_acids: b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG", starts: b"---M-------------------------------M---------------M------------" },
+];
+
+impl GeneticCode {
+  pub fn from_id(id: u8) -> Result<&'static GeneticCode, Report> {
+    GENETIC_CODES.iter().find(|code| code.id == id).map_or_else(
+      || {
+        make_error!(
+          "Unknown genetic code: {id}. Supported genetic codes (NCBI translation table numbers) are: {}",
+          GENETIC_CODES.iter().map(|code| code.id).join(", ")
+        )
+      },
+      Ok,
+    )
+  }
+
+  /// Finds genetic code to use for translation of a given CDS. The `transl_table` attribute of the CDS takes
+  /// precedence over the genetic code set for the whole dataset.
+  ///
+  /// Returns `None` if the genetic code is not set explicitly. In this case the built-in standard code is used,
+  /// without alternative start codons.
+  pub fn for_cds(cds: &Cds, params: &AlignPairwiseParams) -> Result<Option<&'static GeneticCode>, Report> {
+    let id = match cds_transl_table(cds)? {
+      Some(id) => Some(id),
+      None => params.genetic_code,
+    };
+    id.map(GeneticCode::from_id).transpose()
+  }
+
+  /// Standard genetic code (NCBI translation table 1)
+  pub fn standard() -> &'static GeneticCode {
+    &GENETIC_CODES[0]
+  }
+
+  /// Translates a codon. Ambiguous nucleotides are resolved if all possible codons encode the same amino acid.
+  pub fn decode(&self, triplet: &[Nuc]) -> Aa {
+    self.decode_with(triplet, self.amino_acids)
+  }
+
+  /// Lists amino acids which an ambiguous codon can encode, if there is more than one.
+  ///
+  /// Only codons consisting of fully or partially determined nucleotides are considered (e.g. `AAR`, `RAY`), because
+  /// codons containing `N` or gaps encode too many amino acids for the list to be informative.
+  pub fn decode_ambiguous(&self, triplet: &[Nuc]) -> Option<Vec<Aa>> {
+    if triplet.len() != 3 || triplet.iter().any(|nuc| nuc.is_unknown() || nuc.is_gap()) {
+      return None;
+    }
+    let candidates = self.candidates(triplet, self.amino_acids);
+    (candidates.len() > 1).then_some(candidates)
+  }
+
+  /// Translates the first codon of a CDS. Alternative start codons are translated as methionine.
+  pub fn decode_start(&self, triplet: &[Nuc]) -> Aa {
+    match self.decode_with(triplet, self.starts) {
+      Aa::M => Aa::M,
+      _ => self.decode(triplet),
+    }
+  }
+
+  fn decode_with(&self, triplet: &[Nuc], table: &[u8; 64]) -> Aa {
+    if triplet.iter().all(Letter::is_gap) {
+      return Aa::Gap;
+    }
+
+    match self.candidates(triplet, table).as_slice() {
+      [aa] => *aa,
+      _ => Aa::X,
+    }
+  }
+
+  /// Lists distinct amino acids encoded by all possible resolutions of ambiguous nucleotides in a codon
+  fn candidates(&self, triplet: &[Nuc], table: &[u8; 64]) -> Vec<Aa> {
+    let [n1, n2, n3] = triplet else {
+      return vec![];
+    };
+
+    iproduct!(expand_nuc(*n1), expand_nuc(*n2), expand_nuc(*n3))
+      .map(|(i1, i2, i3)| to_aa(table[i1 * 16 + i2 * 4 + i3] as char).unwrap_or(Aa::X))
+      .unique()
+      .sorted()
+      .collect_vec()
+  }
+}
+
+/// Reads and validates genetic code set in the `transl_table` attribute of a CDS, if any
+pub fn cds_transl_table(cds: &Cds) -> Result<Option<u8>, Report> {
+  let Some(values) = cds.attributes.get(TRANSL_TABLE_ATTR) else {
+    return Ok(None);
+  };
+
+  let ids = values
+    .iter()
+    .map(|value| {
+      value.trim().parse::<u8>().wrap_err_with(|| {
+        format!(
+          "When parsing attribute '{TRANSL_TABLE_ATTR}={value}' of CDS '{}'",
+          cds.name
+        )
+      })
+    })
+    .collect::<Result<Vec<u8>, Report>>()?;
+
+  match ids.iter().unique().collect_vec().as_slice() {
+    [] => Ok(None),
+    [id] => {
+      GeneticCode::from_id(**id).wrap_err_with(|| format!("When reading genetic code of CDS '{}'", cds.name))?;
+      Ok(Some(**id))
+    }
+    _ => make_error!(
+      "CDS '{}' has conflicting values of attribute '{TRANSL_TABLE_ATTR}': {}",
+      cds.name,
+      ids.iter().join(", ")
+    ),
+  }
+}
+
+/// Whether the first codon of a CDS is present, i.e. the CDS is not truncated at its 5' end
+pub fn cds_has_start_codon(cds: &Cds) -> bool {
+  cds
+    .segments
+    .first()
+    .is_some_and(|segment| matches!(segment.truncation, Truncation::None | Truncation::ThreePrime(_)))
+}
+
+/// Lists indices (in T, C, A, G order) of canonical nucleotides compatible with a given, possibly ambiguous, nucleotide
+const fn expand_nuc(nuc: Nuc) -> &'static [usize] {
+  const T: usize = 0;
+  const C: usize = 1;
+  const A: usize = 2;
+  const G: usize = 3;
+  match nuc {
+    Nuc::T => &[T],
+    Nuc::C => &[C],
+    Nuc::A => &[A],
+    Nuc::G => &[G],
+    Nuc::W => &[A, T],
+    Nuc::S => &[C, G],
+    Nuc::M => &[A, C],
+    Nuc::K => &[G, T],
+    Nuc::R => &[A, G],
+    Nuc::Y => &[C, T],
+    Nuc::B => &[C, G, T],
+    Nuc::D => &[A, G, T],
+    Nuc::H => &[A, C, T],
+    Nuc::V => &[A, C, G],
+    Nuc::N => &[T, C, A, G],
+    Nuc::Gap => &[],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::aa::to_aa_seq;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::translate::translate::decode;
+  use pretty_assertions::assert_eq;
+  use rstest::rstest;
+
+  #[rstest]
+  #[case::standard_stop(1, "TGA", Aa::Stop)]
+  #[case::vertebrate_mito_tryptophan(2, "TGA", Aa::W)]
+  #[case::vertebrate_mito_stop(2, "AGA", Aa::Stop)]
+  #[case::vertebrate_mito_methionine(2, "ATA", Aa::M)]
+  #[case::mycoplasma_tryptophan(4, "TGA", Aa::W)]
+  #[case::bacterial_stop(11, "TGA", Aa::Stop)]
+  #[case::ambiguous_resolved(2, "AGR", Aa::Stop)]
+  #[case::ambiguous_resolved_differently(1, "AGR", Aa::R)]
+  #[case::ambiguous_conflicting(1, "ATR", Aa::X)]
+  #[case::unknown(11, "ANA", Aa::X)]
+  #[case::gap(11, "---", Aa::Gap)]
+  #[case::partial_gap(11, "A--", Aa::X)]
+  fn decodes_codon(#[case] id: u8, #[case] codon: &str, #[case] expected: Aa) -> Result<(), Report> {
+    let code = GeneticCode::from_id(id)?;
+    assert_eq!(code.decode(&to_nuc_seq(codon)?), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  #[case::bacterial_alternative_start(11, "GTG", Aa::M)]
+  #[case::bacterial_regular_start(11, "ATG", Aa::M)]
+  #[case::bacterial_not_a_start(11, "GCG", Aa::A)]
+  #[case::mycoplasma_alternative_start(4, "TTA", Aa::M)]
+  #[case::ciliate_no_alternative_start(6, "GTG", Aa::V)]
+  fn decodes_start_codon(#[case] id: u8, #[case] codon: &str, #[case] expected: Aa) -> Result<(), Report> {
+    let code = GeneticCode::from_id(id)?;
+    assert_eq!(code.decode_start(&to_nuc_seq(codon)?), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  fn standard_code_agrees_with_builtin_decoder_on_canonical_codons() -> Result<(), Report> {
+    let code = GeneticCode::from_id(1)?;
+    for codon in iproduct!("TCAG".chars(), "TCAG".chars(), "TCAG".chars()) {
+      let codon = to_nuc_seq(&format!("{}{}{}", codon.0, codon.1, codon.2))?;
+      assert_eq!(code.decode(&codon), decode(&codon));
+    }
+    Ok(())
+  }
+
+  #[rstest]
+  #[case::resolved(1, "AAR", None)]
+  #[case::two_amino_acids(1, "AAS", Some("KN"))]
+  #[case::three_amino_acids(1, "VAY", Some("DHN"))]
+  #[case::stop_or_amino_acid(1, "TAS", Some("Y*"))]
+  #[case::unknown(1, "AAN", None)]
+  #[case::partial_gap(1, "AA-", None)]
+  fn decodes_ambiguous_codon(
+    #[case] id: u8,
+    #[case] codon: &str,
+    #[case] expected: Option<&str>,
+  ) -> Result<(), Report> {
+    let code = GeneticCode::from_id(id)?;
+    let expected = expected.map(to_aa_seq).transpose()?;
+    assert_eq!(code.decode_ambiguous(&to_nuc_seq(codon)?), expected);
+    Ok(())
+  }
+
+  #[rstest]
+  fn standard_code_resolves_all_codons_resolved_by_builtin_decoder() -> Result<(), Report> {
+    let code = GeneticCode::standard();
+    let nucs = "ACGTRYSWKMBDHVN";
+    for codon in iproduct!(nucs.chars(), nucs.chars(), nucs.chars()) {
+      let codon = to_nuc_seq(&format!("{}{}{}", codon.0, codon.1, codon.2))?;
+      let builtin = decode(&codon);
+      if builtin != Aa::X {
+        assert_eq!(code.decode(&codon), builtin);
+      }
+    }
+    Ok(())
+  }
+
+  #[rstest]
+  fn rejects_unknown_genetic_code() {
+    assert_eq!(
+      GeneticCode::from_id(7).unwrap_err().to_string(),
+      "Unknown genetic code: 7. Supported genetic codes (NCBI translation table numbers) are: 1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 16, 21, 22, 23, 24, 25"
+    );
+  }
+}
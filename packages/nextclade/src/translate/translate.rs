@@ -3,6 +3,8 @@ use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::{serde_deserialize_seq, serde_serialize_seq};
 use crate::alphabet::nuc::Nuc;
 use crate::gene::cds::Cds;
+use crate::translate::genetic_code::{GeneticCode, cds_has_start_codon};
+use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 
 pub const fn decode(triplet: &[Nuc]) -> Aa {
@@ -200,7 +202,10 @@ pub struct CdsPeptide {
 }
 
 /// Translates a nucleotide sequence of a gene into the corresponding aminoacid sequence (peptide)
-pub fn translate(gene_nuc_seq: &[Nuc], cds: &Cds, params: &AlignPairwiseParams) -> CdsPeptide {
+pub fn translate(gene_nuc_seq: &[Nuc], cds: &Cds, params: &AlignPairwiseParams) -> Result<CdsPeptide, Report> {
+  let genetic_code = GeneticCode::for_cds(cds, params).wrap_err("When choosing genetic code for translation")?;
+  let has_start_codon = cds_has_start_codon(cds);
+
   // NOTE: rounds the result to the multiple of 3 (floor) so that translation does not overrun the buffer
   let peptide_length = gene_nuc_seq.len() / 3;
 
@@ -208,7 +213,15 @@ pub fn translate(gene_nuc_seq: &[Nuc], cds: &Cds, params: &AlignPairwiseParams)
   for i_aa in 0..peptide_length {
     let i_nuc = i_aa * 3;
     let triplet: &[Nuc] = &gene_nuc_seq[i_nuc..(i_nuc + 3)];
-    let aminoacid = decode(triplet);
+    let aminoacid = match genetic_code {
+      // Fall back to expanding ambiguous nucleotides for the codons which are not in the built-in table
+      None => match decode(triplet) {
+        Aa::X => GeneticCode::standard().decode(triplet),
+        aa => aa,
+      },
+      Some(genetic_code) if i_aa == 0 && has_start_codon => genetic_code.decode_start(triplet),
+      Some(genetic_code) => genetic_code.decode(triplet),
+    };
     peptide.push(aminoacid);
     if params.no_translate_past_stop && aminoacid == Aa::Stop {
       break;
@@ -216,8 +229,32 @@ pub fn translate(gene_nuc_seq: &[Nuc], cds: &Cds, params: &AlignPairwiseParams)
   }
   peptide.shrink_to_fit();
 
-  CdsPeptide {
+  Ok(CdsPeptide {
     name: cds.name.clone(),
     seq: peptide,
-  }
+  })
+}
+
+/// Finds codons in a gene which are translated to `X` because of ambiguous nucleotides, but which encode only a few
+/// distinct amino acids (e.g. `AAS` encodes either K or N). Returns peptide positions along with these amino acids.
+pub fn find_ambiguous_codons(
+  gene_nuc_seq: &[Nuc],
+  peptide: &CdsPeptide,
+  cds: &Cds,
+  params: &AlignPairwiseParams,
+) -> Result<Vec<(usize, Vec<Aa>)>, Report> {
+  let genetic_code = GeneticCode::for_cds(cds, params)?.unwrap_or_else(GeneticCode::standard);
+  Ok(
+    peptide
+      .seq
+      .iter()
+      .enumerate()
+      .filter(|(_, aa)| **aa == Aa::X)
+      .filter_map(|(i_aa, _)| {
+        let i_nuc = i_aa * 3;
+        let triplet = &gene_nuc_seq[i_nuc..(i_nuc + 3)];
+        genetic_code.decode_ambiguous(triplet).map(|aas| (i_aa, aas))
+      })
+      .collect(),
+  )
 }
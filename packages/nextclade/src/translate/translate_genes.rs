@@ -2,13 +2,14 @@ use crate::align::align::align_aa;
 use crate::align::insertions_strip::{Insertion, insertions_strip};
 use crate::align::params::AlignPairwiseParams;
 use crate::align::remove_gaps::remove_gaps_in_place;
+use crate::align::substitution_matrix::SubstitutionScores;
 use crate::alphabet::aa::Aa;
 use crate::alphabet::letter::{Letter, serde_deserialize_seq, serde_serialize_seq};
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::count_gaps::GapCounts;
 use crate::coord::coord_map_global::CoordMapGlobal;
 use crate::coord::coord_map_local::CoordMapLocal;
-use crate::coord::position::PositionLike;
+use crate::coord::position::{AaRefPosition, PositionLike};
 use crate::coord::range::{AaRefRange, NucRefGlobalRange, Range};
 use crate::gene::cds::Cds;
 use crate::gene::gene::Gene;
@@ -17,7 +18,7 @@ use crate::translate::aa_alignment_ranges::calculate_aa_alignment_ranges_in_plac
 use crate::translate::extract::extract_cds_from_aln;
 use crate::translate::frame_shifts_detect::frame_shifts_detect;
 use crate::translate::frame_shifts_translate::{FrameShift, frame_shifts_transform_coordinates};
-use crate::translate::translate::translate;
+use crate::translate::translate::{find_ambiguous_codons, translate};
 use crate::types::outputs::PeptideWarning;
 use crate::utils::collections::{first, last};
 use crate::utils::error::report_to_string;
@@ -138,6 +139,19 @@ pub struct CdsTranslation {
   pub frame_shifts: Vec<FrameShift>,
   pub alignment_ranges: Vec<AaRefRange>,
   pub unsequenced_ranges: Vec<AaRefRange>,
+  pub ambiguous_aas: Vec<AmbiguousAa>,
+}
+
+/// Amino acid position where the query codon contains ambiguous nucleotides, such that it can encode one of several
+/// amino acids. The amino acid is `X` in the peptide, but it is reported separately from unknown amino acids.
+#[derive(Clone, Debug, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AmbiguousAa {
+  pub cds_name: String,
+  pub pos: AaRefPosition,
+  pub ref_aa: Aa,
+  /// Amino acids which the query codon can encode
+  pub qry_aas: Vec<Aa>,
 }
 
 /// Results of the aminoacid alignment parameters estimation
@@ -229,6 +243,7 @@ pub fn translate_cds(
   cds: &Cds,
   ref_cds_translation: &CdsTranslation,
   gap_open_close_aa: &[i32],
+  aa_substitution_scores: &SubstitutionScores,
   coord_map_global: &CoordMapGlobal,
   params: &AlignPairwiseParams,
 ) -> Result<CdsTranslation, Report> {
@@ -270,7 +285,8 @@ pub fn translate_cds(
   // Strip all GAP characters to "forget" gaps introduced during alignment
   remove_gaps_in_place(&mut qry_cds_seq);
 
-  let query_peptide = translate(&qry_cds_seq, cds, params);
+  let query_peptide = translate(&qry_cds_seq, cds, params)?;
+  let ambiguous_codons = find_ambiguous_codons(&qry_cds_seq, &query_peptide, cds, params)?;
 
   // Instead of performing seed matching, like we do for nucleotide alignment, here we estimate parameters
   // by counting gaps in the aligned nucleotide sequences;
@@ -280,6 +296,7 @@ pub fn translate_cds(
     &query_peptide.seq,
     &ref_cds_translation.seq,
     gap_open_close_aa,
+    aa_substitution_scores,
     &aa_params,
     band_width,
     mean_shift,
@@ -289,6 +306,21 @@ pub fn translate_cds(
 
   mask_peptide_frame_shifts_in_place(&mut stripped.qry_seq, &frame_shifts);
 
+  let qry_to_ref = qry_to_ref_positions(&alignment.qry_seq, &alignment.ref_seq);
+  let ambiguous_aas = ambiguous_codons
+    .into_iter()
+    .filter_map(|(qry_pos, qry_aas)| {
+      let pos = qry_to_ref.get(qry_pos).copied().flatten()?;
+      let in_frame_shift = frame_shifts.iter().any(|fs| fs.codon.contains(pos));
+      (stripped.qry_seq[pos.as_usize()] == Aa::X && !in_frame_shift).then(|| AmbiguousAa {
+        cds_name: cds.name.clone(),
+        pos,
+        ref_aa: ref_cds_translation.seq[pos.as_usize()],
+        qry_aas,
+      })
+    })
+    .collect_vec();
+
   Ok(CdsTranslation {
     name: cds.name.clone(),
     seq: stripped.qry_seq,
@@ -296,9 +328,26 @@ pub fn translate_cds(
     frame_shifts,
     alignment_ranges: vec![],
     unsequenced_ranges: vec![],
+    ambiguous_aas,
   })
 }
 
+/// Maps positions in the unaligned query peptide to the positions in the reference peptide, given the pairwise
+/// alignment of the two. Query positions which fall into insertions have no counterpart in the reference.
+fn qry_to_ref_positions(qry_aln: &[Aa], ref_aln: &[Aa]) -> Vec<Option<AaRefPosition>> {
+  let mut ref_pos = 0;
+  let mut result = vec![];
+  for (qry, reff) in qry_aln.iter().zip(ref_aln) {
+    if !qry.is_gap() {
+      result.push((!reff.is_gap()).then(|| AaRefPosition::from(ref_pos)));
+    }
+    if !reff.is_gap() {
+      ref_pos += 1;
+    }
+  }
+  result
+}
+
 /// Translates all requested genes
 ///
 /// NOTE: we handle translation errors as warnings, so we return a collection of `Results` as is, to handle elsewhere
@@ -310,6 +359,7 @@ pub fn translate_genes(
   coord_map_global: &CoordMapGlobal,
   global_alignment_range: &NucRefGlobalRange,
   gap_open_close_aa: &[i32],
+  aa_substitution_scores: &SubstitutionScores,
   params: &AlignPairwiseParams,
 ) -> Result<Translation, Report> {
   let genes: IndexMap<String, GeneTranslation> = gene_map
@@ -328,6 +378,7 @@ pub fn translate_genes(
             cds,
             ref_cds_translation,
             gap_open_close_aa,
+            aa_substitution_scores,
             coord_map_global,
             params,
           ) {
@@ -5,7 +5,7 @@ use crate::gene::gene_map::GeneMap;
 use crate::translate::extract::extract_cds_from_ref;
 use crate::translate::translate::translate;
 use crate::translate::translate_genes::{CdsTranslation, GeneTranslation, Translation};
-use eyre::Report;
+use eyre::{Report, WrapErr};
 
 /// Translates genes in reference sequence
 pub fn translate_genes_ref(
@@ -21,10 +21,10 @@ pub fn translate_genes_ref(
         .iter()
         .map(|cds| {
           let nucs = extract_cds_from_ref(ref_seq, cds);
-          let tr = translate(&nucs, cds, params);
+          let tr = translate(&nucs, cds, params)?;
           let len = tr.seq.len();
 
-          (
+          Ok((
             cds.name.clone(),
             CdsTranslation {
               name: cds.name.clone(),
@@ -33,21 +33,23 @@ pub fn translate_genes_ref(
               frame_shifts: vec![],
               alignment_ranges: vec![Range::from_usize(0, len)],
               unsequenced_ranges: vec![],
+              ambiguous_aas: vec![],
             },
-          )
+          ))
         })
-        .collect();
+        .collect::<Result<_, Report>>()
+        .wrap_err_with(|| format!("When translating reference sequence of gene '{}'", gene.name))?;
 
-      (
+      Ok((
         gene.name.clone(),
         GeneTranslation {
           gene: gene.clone(),
           cdses,
           warnings: vec![],
         },
-      )
+      ))
     })
-    .collect();
+    .collect::<Result<_, Report>>()?;
 
   Ok(Translation { genes })
 }
@@ -4,7 +4,13 @@ pub mod split_muts2;
 pub mod tree;
 pub mod tree_attach_new_nodes;
 pub mod tree_builder;
+pub mod tree_dataset;
 pub mod tree_find_ancestors_of_interest;
 pub mod tree_find_clade_founder;
+pub mod tree_find_clade_posterior;
 pub mod tree_find_nearest_node;
+pub mod tree_find_transmission_clusters;
+pub mod tree_import;
+pub mod tree_info;
 pub mod tree_preprocess;
+pub mod tree_prune;
@@ -1,3 +1,4 @@
+use crate::coord::range::NucRefGlobalRange;
 use clap::Parser;
 use optfield::optfield;
 use ordered_float::OrderedFloat;
@@ -18,6 +19,48 @@ pub struct TreeBuilderParams {
 
   #[clap(long)]
   pub masked_muts_weight: OrderedFloat<f64>,
+
+  /// Account for ambiguous nucleotides (e.g. R, Y) in the query sequence during phylogenetic placement.
+  ///
+  /// By default, ambiguous nucleotides are treated as if they were the same as in the reference. With this flag, an
+  /// ambiguous nucleotide contributes to the placement distance the probability that it differs from the nucleotide of
+  /// the tree node, assuming that all nucleotides compatible with the ambiguity code are equally likely.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub placement_ambiguous_nucs: bool,
+
+  /// Weight of a deleted site, which differs between the query sequence and a tree node, in the placement distance.
+  ///
+  /// By default (0), deletions are not counted, and the sites deleted in the query sequence are considered
+  /// undetermined.
+  #[clap(long)]
+  pub placement_deletions_weight: OrderedFloat<f64>,
+
+  /// Assign clades probabilistically, by aggregating clade labels over all near-optimal placements, rather than by
+  /// taking the clade of the single nearest node.
+  ///
+  /// Placement candidates are weighted by their placement priors and by distance (see
+  /// `--clade-assignment-distance-penalty`). The most specific clade which, together with its subclades, reaches
+  /// `--clade-assignment-threshold` is assigned. When placements are ambiguous, e.g. for low-coverage samples, this
+  /// falls back to a parent clade. The posterior probabilities of clades are added to the outputs.
+  #[clap(long)]
+  #[clap(num_args=0..=1, default_missing_value = "true")]
+  pub probabilistic_clade_assignment: bool,
+
+  /// Minimum posterior probability of a clade (including its subclades) to be assigned, when
+  /// `--probabilistic-clade-assignment` is enabled.
+  #[clap(long)]
+  pub clade_assignment_threshold: OrderedFloat<f64>,
+
+  /// Maximum difference between the distance of a placement candidate and the distance of the nearest node, for the
+  /// candidate to be considered in `--probabilistic-clade-assignment`.
+  #[clap(long)]
+  pub clade_assignment_max_extra_distance: usize,
+
+  /// Factor by which weight of a placement candidate is multiplied for every unit of distance beyond the distance of
+  /// the nearest node, in `--probabilistic-clade-assignment`.
+  #[clap(long)]
+  pub clade_assignment_distance_penalty: OrderedFloat<f64>,
 }
 
 #[allow(clippy::derivable_impls)]
@@ -26,6 +69,27 @@ impl Default for TreeBuilderParams {
     Self {
       without_greedy_tree_builder: false,
       masked_muts_weight: OrderedFloat(0.05),
+      placement_ambiguous_nucs: false,
+      placement_deletions_weight: OrderedFloat(0.0),
+      probabilistic_clade_assignment: false,
+      clade_assignment_threshold: OrderedFloat(0.9),
+      clade_assignment_max_extra_distance: 2,
+      clade_assignment_distance_penalty: OrderedFloat(0.1),
     }
   }
 }
+
+/// Weight of a range of genome sites in the phylogenetic placement distance
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementSiteWeight {
+  /// Range of sites (0-based, end exclusive)
+  pub range: NucRefGlobalRange,
+
+  /// Weight of a difference at these sites. Differences at other sites have weight 1.
+  pub weight: OrderedFloat<f64>,
+
+  /// Reason for the weight, e.g. "homoplastic site"
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub description: Option<String>,
+}
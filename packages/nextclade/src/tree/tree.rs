@@ -236,6 +236,8 @@ pub struct TreeNodeTempData {
   pub private_mutations: BranchMutations,
   pub aa_substitutions: BTreeMap<String, BTreeMap<AaRefPosition, Aa>>,
   pub aa_mutations: BTreeMap<String, BTreeMap<AaRefPosition, Aa>>,
+  /// Whether the node was created when attaching query sequences (query nodes and internal nodes splitting branches)
+  pub is_created: bool,
 }
 
 /// Data payload for a node in the phylogenetic reference tree graph.
@@ -315,6 +317,13 @@ impl AuspiceGraphNodePayload {
     self.node_attrs.other[key.as_ref()] = json!({ "value": value.as_ref() });
   }
 
+  /// Removes clade-like node attribute, if present
+  pub fn remove_clade_node_attr(&mut self, key: impl AsRef<str>) {
+    if let Some(other) = self.node_attrs.other.as_object_mut() {
+      other.remove(key.as_ref());
+    }
+  }
+
   /// Extracts clade-like node attributes, given a list of key descriptions
   pub fn get_clade_node_attrs(&self, clade_node_attr_descs: &[CladeNodeAttrKeyDesc]) -> BTreeMap<String, String> {
     clade_node_attr_descs
@@ -918,7 +927,8 @@ impl AuspiceTreeMeta {
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub enum DivergenceUnits {
   NumSubstitutionsPerYearPerSite,
   #[default]
@@ -926,6 +936,14 @@ pub enum DivergenceUnits {
 }
 
 impl DivergenceUnits {
+  /// Human-readable name of the unit
+  pub const fn description(self) -> &'static str {
+    match self {
+      DivergenceUnits::NumSubstitutionsPerYearPerSite => "substitutions per site",
+      DivergenceUnits::NumSubstitutionsPerYear => "number of substitutions",
+    }
+  }
+
   ///
   /// Guesses the unit of measurement of divergence, based on the greatest value of divergence on the tree
   ///
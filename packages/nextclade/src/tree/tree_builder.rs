@@ -247,6 +247,7 @@ pub fn attach_to_internal_node(
   //generated auspice payload for new node
   let mut new_graph_node = create_new_auspice_node(result, new_private_mutations, divergence_new_node);
   new_graph_node.tmp.private_mutations = new_private_mutations.clone();
+  new_graph_node.tmp.is_created = true;
 
   // Create and add the new node to the graph.
   let new_node_key = graph.add_node(new_graph_node);
@@ -367,6 +368,7 @@ pub fn knit_into_graph(
     let new_internal_node = {
       let mut new_internal_node: AuspiceGraphNodePayload = target_node_auspice.to_owned();
       new_internal_node.tmp.private_mutations = muts_common_branch;
+      new_internal_node.tmp.is_created = true;
       new_internal_node.node_attrs.div = Some(divergence_middle_node);
       new_internal_node.branch_attrs.mutations =
         convert_private_mutations_to_node_branch_attrs(&new_internal_node.tmp.private_mutations);
@@ -0,0 +1,135 @@
+use crate::graph::node::GraphNodeKey;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload, TreeNodeAttr};
+use crate::tree::tree_builder::convert_private_mutations_to_node_branch_attrs;
+use eyre::Report;
+
+/// Prepares the graph with attached query sequences for use as the reference tree of subsequent runs, in place.
+///
+/// - branch mutations of all nodes are regenerated from their private mutations, so that they are consistent with
+///   the topology after attachment
+/// - nodes created during attachment (query sequences and internal nodes created when splitting branches) inherit
+///   clade and placement prior of their parent, if they have none
+/// - query sequences become "Reference" nodes
+///
+/// Expects query sequences to be already attached with `graph_attach_new_nodes_in_place()`.
+pub fn graph_prepare_dataset_tree_in_place(graph: &mut AuspiceGraph) -> Result<(), Report> {
+  let root_key = graph.get_exactly_one_root()?.key();
+
+  // Traverse pre-order, so that parents are updated before their children
+  let mut stack: Vec<GraphNodeKey> = vec![root_key];
+  while let Some(node_key) = stack.pop() {
+    let parent = graph.parent_of_by_key(node_key).map(|parent| {
+      (
+        parent.payload().node_attrs.clade_membership.clone(),
+        parent.payload().node_attrs.placement_prior.clone(),
+      )
+    });
+
+    let node = graph.get_node_mut(node_key)?.payload_mut();
+    node.branch_attrs.mutations = convert_private_mutations_to_node_branch_attrs(&node.tmp.private_mutations);
+
+    if node.tmp.is_created {
+      if let Some((parent_clade, parent_prior)) = parent {
+        if node.node_attrs.clade_membership.is_none() {
+          node.node_attrs.clade_membership = parent_clade;
+        }
+        if node.node_attrs.placement_prior.is_none() {
+          node.node_attrs.placement_prior = parent_prior;
+        }
+      }
+      if is_query_node(node) {
+        node.node_attrs.node_type = Some(TreeNodeAttr::new("Reference"));
+      }
+    }
+
+    stack.extend(graph.iter_child_keys_of_by_key(node_key));
+  }
+
+  Ok(())
+}
+
+fn is_query_node(payload: &AuspiceGraphNodePayload) -> bool {
+  payload
+    .node_attrs
+    .node_type
+    .as_ref()
+    .is_some_and(|node_type| node_type.value == "New")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::analyze::find_private_nuc_mutations::BranchMutations;
+  use crate::analyze::nuc_sub::NucSub;
+  use crate::tree::tree::{AuspiceGraphEdgePayload, AuspiceGraphMeta, TreeNodeAttrF64};
+  use pretty_assertions::assert_eq;
+  use std::collections::BTreeMap;
+  use std::str::FromStr;
+
+  fn node(
+    name: &str,
+    node_type: &str,
+    clade: Option<&str>,
+    prior: Option<f64>,
+    is_created: bool,
+  ) -> AuspiceGraphNodePayload {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.tmp.is_created = is_created;
+    payload.node_attrs.node_type = Some(TreeNodeAttr::new(node_type));
+    payload.node_attrs.clade_membership = clade.map(TreeNodeAttr::new);
+    payload.node_attrs.placement_prior = prior.map(TreeNodeAttrF64::new);
+    payload
+  }
+
+  fn summary(graph: &AuspiceGraph, name: &str) -> (String, Option<String>, Option<f64>, Vec<String>) {
+    let node = graph.iter_node_payloads().find(|node| node.name == name).unwrap();
+    (
+      node.node_attrs.node_type.as_ref().unwrap().value.clone(),
+      node.clade(),
+      node.node_attrs.placement_prior.as_ref().map(|prior| prior.value),
+      node.branch_attrs.mutations["nuc"].clone(),
+    )
+  }
+
+  #[test]
+  fn propagates_clades_and_priors_to_created_nodes() -> Result<(), Report> {
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", "Reference", Some("A"), Some(-1.0), false));
+    let copy = graph.add_node(node("copy", "Reference", None, Some(-2.0), true));
+    let ref_leaf = graph.add_node(node("ref", "Reference", Some("B"), Some(-2.0), false));
+    let ref_unlabeled = graph.add_node(node("ref_unlabeled", "Reference", None, None, false));
+    let mut qry = node("qry", "New", None, None, true);
+    qry.tmp.private_mutations = BranchMutations {
+      nuc_muts: vec![NucSub::from_str("C3T")?],
+      aa_muts: BTreeMap::new(),
+    };
+    let qry = graph.add_node(qry);
+    for (src, dst) in [(root, copy), (copy, ref_leaf), (copy, qry), (root, ref_unlabeled)] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    let mut graph = graph.build()?;
+
+    graph_prepare_dataset_tree_in_place(&mut graph)?;
+
+    assert_eq!(
+      [
+        summary(&graph, "copy"),
+        summary(&graph, "ref"),
+        summary(&graph, "qry"),
+        summary(&graph, "ref_unlabeled"),
+      ],
+      [
+        ("Reference".to_owned(), Some("A".to_owned()), Some(-2.0), vec![]),
+        ("Reference".to_owned(), Some("B".to_owned()), Some(-2.0), vec![]),
+        (
+          "Reference".to_owned(),
+          Some("A".to_owned()),
+          Some(-2.0),
+          vec!["C3T".to_owned()]
+        ),
+        ("Reference".to_owned(), None, None, vec![]),
+      ]
+    );
+    Ok(())
+  }
+}
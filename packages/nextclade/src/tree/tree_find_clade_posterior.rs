@@ -0,0 +1,231 @@
+use crate::graph::node::GraphNodeKey;
+use crate::tree::params::TreeBuilderParams;
+use crate::tree::tree::AuspiceGraph;
+use crate::tree::tree_find_nearest_node::{PLACEMENT_DISTANCE_TOLERANCE, TreePlacementInfo};
+use eyre::Report;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Result of probabilistic clade assignment
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CladePosterior {
+  /// The most specific clade which, together with its subclades, has posterior probability at or above the threshold
+  pub clade: Option<String>,
+
+  /// Posterior probability of the assigned clade, including its subclades
+  pub probability: f64,
+
+  /// Posterior probability of each clade, not including its subclades
+  pub distribution: BTreeMap<String, f64>,
+
+  /// Nearest of the placement candidates which supports the assigned clade. The clade founder is searched from here.
+  #[serde(skip)]
+  #[schemars(skip)]
+  pub clade_node_key: Option<GraphNodeKey>,
+}
+
+/// Assigns clade by aggregating clade labels over near-optimal placements of a query sample.
+///
+/// Each placement candidate with distance at most `clade_assignment_max_extra_distance` away from the best one is
+/// weighted by its placement prior and by `clade_assignment_distance_penalty` to the power of the extra distance. A
+/// candidate supports its own clade, as well as all clades of its ancestors. The assigned clade is the deepest one
+/// which is supported with at least `clade_assignment_threshold` probability, so that when candidates disagree,
+/// the assignment falls back to their common parent clade.
+///
+/// Expects candidates sorted by distance, as returned by `graph_find_nearest_nodes()`.
+pub fn graph_find_clade_posterior(
+  graph: &AuspiceGraph,
+  nearest_node_candidates: &[TreePlacementInfo],
+  params: &TreeBuilderParams,
+) -> Result<CladePosterior, Report> {
+  let Some(best) = nearest_node_candidates.first() else {
+    return Ok(CladePosterior::default());
+  };
+
+  let max_extra_distance = params.clade_assignment_max_extra_distance as f64 + PLACEMENT_DISTANCE_TOLERANCE;
+  let penalty = *params.clade_assignment_distance_penalty;
+
+  let candidates = nearest_node_candidates
+    .iter()
+    .take_while(|candidate| candidate.distance - best.distance <= max_extra_distance)
+    .map(|candidate| {
+      let extra_distance = (candidate.distance - best.distance).max(0.0);
+      (candidate.node_key, candidate.prior * penalty.powf(extra_distance))
+    })
+    .collect_vec();
+
+  let total_weight: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+
+  let mut distribution = BTreeMap::<String, f64>::new();
+  let mut support = BTreeMap::<String, f64>::new();
+  let mut depth = BTreeMap::<String, usize>::new();
+  let mut nearest_supporter = BTreeMap::<String, GraphNodeKey>::new();
+  for (node_key, weight) in &candidates {
+    // Fall back to uniform weights if all of the weights vanish
+    let probability = if total_weight > 0.0 {
+      weight / total_weight
+    } else {
+      1.0 / candidates.len() as f64
+    };
+
+    if let Some(clade) = graph.get_node(*node_key)?.payload().clade() {
+      *distribution.entry(clade).or_default() += probability;
+    }
+
+    for (clade_depth, clade) in find_clade_lineage(graph, *node_key)?.into_iter().enumerate() {
+      *support.entry(clade.clone()).or_default() += probability;
+      nearest_supporter.entry(clade.clone()).or_insert(*node_key);
+      let d = depth.entry(clade).or_default();
+      *d = (*d).max(clade_depth);
+    }
+  }
+
+  let threshold = *params.clade_assignment_threshold;
+  let assigned = support
+    .iter()
+    .filter(|(_, probability)| **probability >= threshold)
+    .max_by(|(clade1, probability1), (clade2, probability2)| {
+      depth[*clade1]
+        .cmp(&depth[*clade2])
+        .then(probability1.total_cmp(probability2))
+    });
+
+  Ok(match assigned {
+    Some((clade, probability)) => CladePosterior {
+      clade: Some(clade.clone()),
+      probability: *probability,
+      distribution,
+      clade_node_key: nearest_supporter.get(clade).copied(),
+    },
+    None => CladePosterior {
+      clade: None,
+      probability: 0.0,
+      distribution,
+      clade_node_key: None,
+    },
+  })
+}
+
+/// Lists distinct clades on the path from the root to a given node, in this order
+fn find_clade_lineage(graph: &AuspiceGraph, node_key: GraphNodeKey) -> Result<Vec<String>, Report> {
+  let mut clades = vec![];
+  let mut current = Some(node_key);
+  while let Some(key) = current {
+    if let Some(clade) = graph.get_node(key)?.payload().clade() {
+      clades.push(clade);
+    }
+    current = graph.parent_key_of_by_key(key);
+  }
+  Ok(clades.into_iter().rev().unique().collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tree::tree::{AuspiceGraphEdgePayload, AuspiceGraphMeta, AuspiceGraphNodePayload, TreeNodeAttr};
+  use ordered_float::OrderedFloat;
+  use pretty_assertions::assert_eq;
+
+  fn node(name: &str, clade: &str) -> AuspiceGraphNodePayload {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.node_attrs.clade_membership = Some(TreeNodeAttr::new(clade));
+    payload
+  }
+
+  //       root (A)
+  //      /        \
+  //   a1 (A)      b (B)
+  //             /   |   \
+  //         c1 (C) c2 (C) d1 (D)
+  fn graph() -> Result<AuspiceGraph, Report> {
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", "A"));
+    let a1 = graph.add_node(node("a1", "A"));
+    let b = graph.add_node(node("b", "B"));
+    let c1 = graph.add_node(node("c1", "C"));
+    let c2 = graph.add_node(node("c2", "C"));
+    let d1 = graph.add_node(node("d1", "D"));
+    for (src, dst) in [(root, a1), (root, b), (b, c1), (b, c2), (b, d1)] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    graph.build()
+  }
+
+  fn candidate(graph: &AuspiceGraph, name: &str, distance: f64) -> TreePlacementInfo {
+    let node_key = graph.iter_nodes().find(|n| n.payload().name == name).unwrap().key();
+    TreePlacementInfo {
+      node_key,
+      distance,
+      prior: 1.0,
+    }
+  }
+
+  fn params(threshold: f64) -> TreeBuilderParams {
+    TreeBuilderParams {
+      clade_assignment_threshold: OrderedFloat(threshold),
+      clade_assignment_max_extra_distance: 1,
+      clade_assignment_distance_penalty: OrderedFloat(0.5),
+      ..TreeBuilderParams::default()
+    }
+  }
+
+  #[test]
+  fn assigns_specific_clade_when_confident() -> Result<(), Report> {
+    let graph = graph()?;
+    let candidates = vec![
+      candidate(&graph, "c1", 3.0),
+      candidate(&graph, "c2", 3.0),
+      candidate(&graph, "d1", 4.0),
+      candidate(&graph, "a1", 6.0),
+    ];
+    let posterior = graph_find_clade_posterior(&graph, &candidates, &params(0.75))?;
+    assert_eq!(
+      posterior,
+      CladePosterior {
+        clade: Some("C".to_owned()),
+        probability: 0.8,
+        distribution: BTreeMap::from([("C".to_owned(), 0.8), ("D".to_owned(), 0.2)]),
+        clade_node_key: Some(candidates[0].node_key),
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn falls_back_to_parent_clade_when_ambiguous() -> Result<(), Report> {
+    let graph = graph()?;
+    let candidates = vec![
+      candidate(&graph, "c1", 3.0),
+      candidate(&graph, "c2", 3.0),
+      candidate(&graph, "d1", 4.0),
+      candidate(&graph, "a1", 6.0),
+    ];
+    let posterior = graph_find_clade_posterior(&graph, &candidates, &params(0.9))?;
+    assert_eq!(
+      posterior,
+      CladePosterior {
+        clade: Some("B".to_owned()),
+        probability: 1.0,
+        distribution: BTreeMap::from([("C".to_owned(), 0.8), ("D".to_owned(), 0.2)]),
+        clade_node_key: Some(candidates[0].node_key),
+      }
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn takes_clade_node_from_nearest_candidate_supporting_assigned_clade() -> Result<(), Report> {
+    let graph = graph()?;
+    let candidates = vec![
+      candidate(&graph, "d1", 3.0),
+      candidate(&graph, "c1", 3.0),
+      candidate(&graph, "c2", 3.0),
+    ];
+    let posterior = graph_find_clade_posterior(&graph, &candidates, &params(0.6))?;
+    assert_eq!(posterior.clade.as_deref(), Some("C"));
+    assert_eq!(posterior.clade_node_key, Some(candidates[1].node_key));
+    Ok(())
+  }
+}
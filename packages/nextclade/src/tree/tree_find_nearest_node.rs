@@ -1,44 +1,62 @@
+use crate::alphabet::letter::Letter;
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::is_sequenced::is_nuc_sequenced;
 use crate::analyze::letter_ranges::NucRange;
 use crate::analyze::nuc_del::NucDelRange;
 use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::{NucRefGlobalPosition, PositionLike};
 use crate::coord::range::NucRefGlobalRange;
 use crate::graph::node::GraphNodeKey;
+use crate::tree::params::{PlacementSiteWeight, TreeBuilderParams};
 use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload};
 use eyre::Report;
 use itertools::Itertools;
+use std::collections::BTreeMap;
 use traversal::DftPre;
 
+/// Placement distances which differ by less than this are considered equal
+pub const PLACEMENT_DISTANCE_TOLERANCE: f64 = 1e-9;
+
 /// Distance and placement prior for a ref tree node
+#[derive(Clone, Debug)]
 pub struct TreePlacementInfo {
   pub node_key: GraphNodeKey,
-  pub distance: i64,
+  pub distance: f64,
   pub prior: f64, // prior in non-log scale
 }
 
+impl TreePlacementInfo {
+  /// Whether the placement distance is the same as of the other placement, up to rounding errors
+  pub fn is_as_near_as(&self, other: &TreePlacementInfo) -> bool {
+    (self.distance - other.distance).abs() < PLACEMENT_DISTANCE_TOLERANCE
+  }
+}
+
+/// Query sample, as seen by phylogenetic placement
+pub struct PlacementQuery<'a> {
+  pub nuc_subs: &'a [NucSub],
+  pub missing: &'a [NucRange],
+  pub deletions: &'a [NucDelRange],
+  pub non_acgtns: &'a [NucRange],
+  pub aln_range: &'a NucRefGlobalRange,
+}
+
 /// For a given query sample, finds nearest node on the reference tree (according to the distance metric)
 pub fn graph_find_nearest_nodes(
   graph: &AuspiceGraph,
-  qry_nuc_subs: &[NucSub],
-  qry_missing: &[NucRange],
-  qry_deletions: &[NucDelRange],
-  aln_range: &NucRefGlobalRange,
+  qry: &PlacementQuery,
+  ref_seq: &[Nuc],
+  site_weights: &[PlacementSiteWeight],
+  params: &TreeBuilderParams,
 ) -> Result<Vec<TreePlacementInfo>, Report> {
   let masked_ranges = graph.data.meta.placement_mask_ranges();
+  let metric = PlacementDistance::new(qry, ref_seq, masked_ranges, site_weights, params);
 
   // Iterate over tree nodes and calculate distance metric between the sample and each node
   let nodes_by_placement_score = DftPre::new(graph.get_exactly_one_root()?, |node| graph.iter_children_of(node))
     .map(|(_, node)| {
       let node_payload = node.payload();
-      let distance = tree_calculate_node_distance(
-        node_payload,
-        qry_nuc_subs,
-        qry_missing,
-        qry_deletions,
-        aln_range,
-        masked_ranges,
-      );
+      let distance = tree_calculate_node_distance(node_payload, &metric);
       let prior = get_prior(node_payload);
       TreePlacementInfo {
         node_key: node.key(),
@@ -46,14 +64,14 @@ pub fn graph_find_nearest_nodes(
         prior,
       }
     })
-    .sorted_by(|a, b| a.distance.cmp(&b.distance).then(b.prior.total_cmp(&a.prior)))
+    .sorted_by(|a, b| a.distance.total_cmp(&b.distance).then(b.prior.total_cmp(&a.prior)))
     .collect_vec();
 
   Ok(if nodes_by_placement_score.is_empty() {
     // Unlikely case: if there's no nodes, return parent
     vec![TreePlacementInfo {
       node_key: graph.get_exactly_one_root()?.key(),
-      distance: 0,
+      distance: 0.0,
       prior: 1.0,
     }]
   } else {
@@ -73,68 +91,138 @@ fn get_prior(node: &AuspiceGraphNodePayload) -> f64 {
   )
 }
 
-/// Calculates distance metric between a given query sample and a tree node
-fn tree_calculate_node_distance(
-  node: &AuspiceGraphNodePayload,
-  qry_nuc_subs: &[NucSub],
-  qry_missing: &[NucRange],
-  qry_deletions: &[NucDelRange],
-  aln_range: &NucRefGlobalRange,
-  masked_ranges: &[NucRefGlobalRange],
-) -> i64 {
-  let mut shared_differences = 0_i64;
-  let mut shared_sites = 0_i64;
-
-  // Mask effectively turns query mutations into missing
-  // Rest of logic is the same once qry_nuc_subs and qry_missing are mutated
-  // Remove from qry_nuc_subs all mutations that are masked
-  let masked_qry_nuc_subs = qry_nuc_subs
-    .iter()
-    .filter(|sub| !masked_ranges.iter().any(|range| range.contains(sub.pos)))
-    .collect_vec();
+/// Distance metric between a given query sample and tree nodes.
+///
+/// The distance is a sum over sites, where the query and the node differ, of the site weights. Sites which are masked,
+/// missing in the query or (unless deletions are counted) deleted in the query are undetermined and are not counted.
+/// Ambiguous nucleotides, if enabled, contribute the probability of a difference. The parts of the calculation which
+/// depend only on the query are done once, on construction.
+pub struct PlacementDistance<'a> {
+  qry: &'a PlacementQuery<'a>,
+  ref_seq: &'a [Nuc],
+  masked_ranges: &'a [NucRefGlobalRange],
+  site_weights: &'a [PlacementSiteWeight],
+  ambiguous_nucs: bool,
+  deletions_weight: f64,
+
+  /// States of the query at determined sites, where it differs from the reference
+  qry_states: BTreeMap<NucRefGlobalPosition, Nuc>,
+
+  /// Distance between the query and the reference
+  ref_distance: f64,
+}
 
-  // Add all masked ranges to qry_missing
-  let masked_qry_missing = masked_ranges
-    .iter()
-    .map(|range| NucRange {
-      range: range.clone(),
-      letter: Nuc::N,
-    })
-    .chain(qry_missing.iter().cloned())
-    .collect_vec();
+impl<'a> PlacementDistance<'a> {
+  pub fn new(
+    qry: &'a PlacementQuery<'a>,
+    ref_seq: &'a [Nuc],
+    masked_ranges: &'a [NucRefGlobalRange],
+    site_weights: &'a [PlacementSiteWeight],
+    params: &TreeBuilderParams,
+  ) -> Self {
+    let mut metric = Self {
+      qry,
+      ref_seq,
+      masked_ranges,
+      site_weights,
+      ambiguous_nucs: params.placement_ambiguous_nucs,
+      deletions_weight: *params.placement_deletions_weight,
+      qry_states: BTreeMap::new(),
+      ref_distance: 0.0,
+    };
+
+    let subs = qry.nuc_subs.iter().map(|sub| (sub.pos, sub.qry_nuc));
+
+    let ambiguous = qry
+      .non_acgtns
+      .iter()
+      .filter(|_| metric.ambiguous_nucs)
+      .flat_map(|range| range.range.iter().map(|pos| (pos, range.letter)));
+
+    let deletions = qry
+      .deletions
+      .iter()
+      .filter(|_| metric.counts_deletions())
+      .flat_map(|del| del.range().iter().map(|pos| (pos, Nuc::Gap)));
+
+    let qry_states: BTreeMap<NucRefGlobalPosition, Nuc> = subs
+      .chain(ambiguous)
+      .chain(deletions)
+      .filter(|(pos, _)| metric.is_determined(*pos))
+      .collect();
+
+    metric.ref_distance = qry_states
+      .iter()
+      .map(|(pos, qry_nuc)| metric.site_weight(*pos) * metric.nuc_difference(*qry_nuc, metric.ref_nuc(*pos)))
+      .sum();
+    metric.qry_states = qry_states;
+
+    metric
+  }
 
-  for qmut in &masked_qry_nuc_subs {
-    let node_mut = node.tmp.substitutions.get(&qmut.pos);
-    if let Some(node_mut) = node_mut {
-      // position is also mutated in node
-      if qmut.qry_nuc == *node_mut {
-        shared_differences += 1; // the exact mutation is shared between node and seq
-      } else {
-        shared_sites += 1; // the same position is mutated, but the states are different
-      }
-    }
+  fn counts_deletions(&self) -> bool {
+    self.deletions_weight > 0.0
   }
 
-  // determine the number of sites that are mutated in the node but missing or deleted in seq.
-  // for these we can't tell whether the node agrees with seq
-  let mut undetermined_sites = 0_i64;
-  for pos in node.tmp.substitutions.keys() {
-    if !is_nuc_sequenced(*pos, &masked_qry_missing, aln_range)
-      || qry_deletions.iter().any(|del| del.range().contains(*pos))
-    {
-      undetermined_sites += 1;
+  fn is_determined(&self, pos: NucRefGlobalPosition) -> bool {
+    !self.masked_ranges.iter().any(|range| range.contains(pos))
+      && is_nuc_sequenced(pos, self.qry.missing, self.qry.aln_range)
+      && (self.counts_deletions() || !self.qry.deletions.iter().any(|del| del.range().contains(pos)))
+  }
+
+  fn site_weight(&self, pos: NucRefGlobalPosition) -> f64 {
+    self
+      .site_weights
+      .iter()
+      .find(|site_weight| site_weight.range.contains(pos))
+      .map_or(1.0, |site_weight| *site_weight.weight)
+  }
+
+  fn ref_nuc(&self, pos: NucRefGlobalPosition) -> Nuc {
+    self.ref_seq.get(pos.as_usize()).copied().unwrap_or(Nuc::N)
+  }
+
+  /// Probability that the query nucleotide differs from the node nucleotide, with deletions weighted separately
+  fn nuc_difference(&self, qry_nuc: Nuc, node_nuc: Nuc) -> f64 {
+    if qry_nuc == node_nuc {
+      return 0.0;
+    }
+    if qry_nuc.is_gap() || node_nuc.is_gap() {
+      return self.deletions_weight;
     }
+    let compatible = qry_nuc.canonical_nucs();
+    if !self.ambiguous_nucs || qry_nuc.is_acgt() || !compatible.contains(&node_nuc) {
+      return 1.0;
+    }
+    1.0 - 1.0 / compatible.len() as f64
   }
+}
 
-  let total_node_muts = node.tmp.substitutions.len() as i64;
-  let total_seq_muts = masked_qry_nuc_subs.len() as i64;
+/// Calculates distance metric between a given query sample and a tree node
+pub fn tree_calculate_node_distance(node: &AuspiceGraphNodePayload, metric: &PlacementDistance) -> f64 {
+  let node_deletions = node
+    .tmp
+    .mutations
+    .iter()
+    .filter(|(_, nuc)| metric.counts_deletions() && nuc.is_gap());
 
-  // calculate distance from set overlaps.
-  total_node_muts + total_seq_muts - 2 * shared_differences - shared_sites - undetermined_sites
+  // Start with the distance to the reference, and adjust it at the sites where the node differs from the reference
+  let mut distance = metric.ref_distance;
+  for (pos, node_nuc) in node.tmp.substitutions.iter().chain(node_deletions) {
+    if !metric.is_determined(*pos) {
+      continue;
+    }
+    let ref_nuc = metric.ref_nuc(*pos);
+    let qry_nuc = metric.qry_states.get(pos).copied().unwrap_or(ref_nuc);
+    distance +=
+      metric.site_weight(*pos) * (metric.nuc_difference(qry_nuc, *node_nuc) - metric.nuc_difference(qry_nuc, ref_nuc));
+  }
+  distance
 }
 
 #[cfg(test)]
 mod tests {
+  #![allow(clippy::float_cmp)]
   use std::collections::BTreeMap;
 
   use crate::alphabet::nuc::Nuc;
@@ -143,7 +231,9 @@ mod tests {
   use super::*;
   use crate::coord::position::NucRefGlobalPosition;
   use crate::coord::range::Range;
+  use approx::assert_abs_diff_eq;
   use eyre::Report;
+  use ordered_float::OrderedFloat;
   use pretty_assertions::assert_eq;
   use rstest::{fixture, rstest};
 
@@ -187,6 +277,27 @@ mod tests {
     }
   }
 
+  /// Calculates distance with default parameters, against the reference of 100 'A's
+  fn calculate_distance(
+    node: &AuspiceGraphNodePayload,
+    qry_nuc_subs: &[NucSub],
+    qry_missing: &[NucRange],
+    qry_deletions: &[NucDelRange],
+    aln_range: &NucRefGlobalRange,
+    masked_ranges: &[NucRefGlobalRange],
+  ) -> f64 {
+    let qry = PlacementQuery {
+      nuc_subs: qry_nuc_subs,
+      missing: qry_missing,
+      deletions: qry_deletions,
+      non_acgtns: &[],
+      aln_range,
+    };
+    let ref_seq = vec![Nuc::A; 100];
+    let metric = PlacementDistance::new(&qry, &ref_seq, masked_ranges, &[], &TreeBuilderParams::default());
+    tree_calculate_node_distance(node, &metric)
+  }
+
   fn simple_qry_nuc_subs() -> Vec<NucSub> {
     vec![
       NucSub {
@@ -247,7 +358,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -256,7 +367,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 0);
+    assert_eq!(result, 0.0);
 
     Ok(())
   }
@@ -270,7 +381,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -279,7 +390,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 3);
+    assert_eq!(result, 3.0);
 
     Ok(())
   }
@@ -293,7 +404,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -302,7 +413,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 5);
+    assert_eq!(result, 5.0);
 
     Ok(())
   }
@@ -316,7 +427,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -325,7 +436,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 5);
+    assert_eq!(result, 5.0);
 
     Ok(())
   }
@@ -339,7 +450,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -348,7 +459,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 4);
+    assert_eq!(result, 4.0);
 
     Ok(())
   }
@@ -362,7 +473,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -371,7 +482,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 3);
+    assert_eq!(result, 3.0);
 
     Ok(())
   }
@@ -385,7 +496,7 @@ mod tests {
     let masked_ranges = vec![NucRefGlobalRange::from_usize(0, 100)];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -394,7 +505,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 0);
+    assert_eq!(result, 0.0);
 
     Ok(())
   }
@@ -412,7 +523,7 @@ mod tests {
     ];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -421,7 +532,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 3);
+    assert_eq!(result, 3.0);
 
     Ok(())
   }
@@ -435,7 +546,7 @@ mod tests {
     let masked_ranges = vec![NucRefGlobalRange::from_usize(12, 13)];
 
     let qry_deletions: Vec<NucDelRange> = vec![];
-    let result = tree_calculate_node_distance(
+    let result = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -444,7 +555,7 @@ mod tests {
       &masked_ranges,
     );
 
-    assert_eq!(result, 3);
+    assert_eq!(result, 3.0);
 
     Ok(())
   }
@@ -458,7 +569,7 @@ mod tests {
     let masked_ranges = vec![];
 
     let no_deletions: Vec<NucDelRange> = vec![];
-    let distance_without_del = tree_calculate_node_distance(
+    let distance_without_del = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -466,10 +577,10 @@ mod tests {
       &aln_range,
       &masked_ranges,
     );
-    assert_eq!(distance_without_del, 5);
+    assert_eq!(distance_without_del, 5.0);
 
     let qry_deletions = vec![NucDelRange::from_usize(10, 25)];
-    let distance_with_del = tree_calculate_node_distance(
+    let distance_with_del = calculate_distance(
       &node,
       &qry_nuc_subs,
       &qry_missing,
@@ -477,8 +588,131 @@ mod tests {
       &aln_range,
       &masked_ranges,
     );
-    assert_eq!(distance_with_del, 2);
+    assert_eq!(distance_with_del, 2.0);
+
+    Ok(())
+  }
 
+  #[rstest]
+  fn site_weights_scale_differences() -> Result<(), Report> {
+    let node = node_with_simple_nuc_subs();
+    let qry_nuc_subs = simple_qry_nuc_subs();
+    let aln_range = NucRefGlobalRange::from_usize(0, 100);
+    let qry = PlacementQuery {
+      nuc_subs: &qry_nuc_subs,
+      missing: &[],
+      deletions: &[],
+      non_acgtns: &[],
+      aln_range: &aln_range,
+    };
+    let site_weights = vec![
+      PlacementSiteWeight {
+        range: NucRefGlobalRange::from_usize(3, 4),
+        weight: OrderedFloat(0.0),
+        description: None,
+      },
+      PlacementSiteWeight {
+        range: NucRefGlobalRange::from_usize(12, 16),
+        weight: OrderedFloat(0.5),
+        description: Some("homoplastic".to_owned()),
+      },
+    ];
+    let ref_seq = vec![Nuc::A; 100];
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &site_weights, &TreeBuilderParams::default());
+    assert_eq!(tree_calculate_node_distance(&node, &metric), 3.5);
+    Ok(())
+  }
+
+  #[rstest]
+  fn fractional_site_weights_sum_up() -> Result<(), Report> {
+    let node = node_with_simple_nuc_subs();
+    let qry_nuc_subs = simple_qry_nuc_subs();
+    let aln_range = NucRefGlobalRange::from_usize(0, 100);
+    let qry = PlacementQuery {
+      nuc_subs: &qry_nuc_subs,
+      missing: &[],
+      deletions: &[],
+      non_acgtns: &[],
+      aln_range: &aln_range,
+    };
+    let site_weights = [(3, 0.1), (7, 0.2), (15, 0.7), (23, 0.3)]
+      .into_iter()
+      .map(|(pos, weight)| PlacementSiteWeight {
+        range: NucRefGlobalRange::from_usize(pos, pos + 1),
+        weight: OrderedFloat(weight),
+        description: None,
+      })
+      .collect_vec();
+    let ref_seq = vec![Nuc::A; 100];
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &site_weights, &TreeBuilderParams::default());
+    assert_abs_diff_eq!(tree_calculate_node_distance(&node, &metric), 2.3, epsilon = 1e-9);
+    Ok(())
+  }
+
+  #[rstest]
+  fn ambiguous_nucs_contribute_probability_of_difference() -> Result<(), Report> {
+    let node = node_with_simple_nuc_subs();
+    let aln_range = NucRefGlobalRange::from_usize(0, 100);
+    let non_acgtns = vec![
+      NucRange {
+        range: Range::from_usize(15, 16),
+        letter: Nuc::K,
+      },
+      NucRange {
+        range: Range::from_usize(23, 24),
+        letter: Nuc::R,
+      },
+      NucRange {
+        range: Range::from_usize(60, 61),
+        letter: Nuc::R,
+      },
+    ];
+    let qry = PlacementQuery {
+      nuc_subs: &[],
+      missing: &[],
+      deletions: &[],
+      non_acgtns: &non_acgtns,
+      aln_range: &aln_range,
+    };
+    let ref_seq = vec![Nuc::A; 100];
+
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &[], &TreeBuilderParams::default());
+    assert_eq!(tree_calculate_node_distance(&node, &metric), 5.0);
+
+    let params = TreeBuilderParams {
+      placement_ambiguous_nucs: true,
+      ..TreeBuilderParams::default()
+    };
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &[], &params);
+    assert_eq!(tree_calculate_node_distance(&node, &metric), 4.5);
+    Ok(())
+  }
+
+  #[rstest]
+  fn deletions_are_counted_when_weighted() -> Result<(), Report> {
+    let mut node = node_with_simple_nuc_subs();
+    node.tmp.mutations = node.tmp.substitutions.clone();
+    node.tmp.mutations.insert(40.into(), Nuc::Gap);
+    let aln_range = NucRefGlobalRange::from_usize(0, 100);
+    let qry_deletions = vec![NucDelRange::from_usize(12, 16)];
+    let qry = PlacementQuery {
+      nuc_subs: &[],
+      missing: &[],
+      deletions: &qry_deletions,
+      non_acgtns: &[],
+      aln_range: &aln_range,
+    };
+    let ref_seq = vec![Nuc::A; 100];
+
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &[], &TreeBuilderParams::default());
+    assert_eq!(tree_calculate_node_distance(&node, &metric), 3.0);
+
+    let params = TreeBuilderParams {
+      placement_deletions_weight: OrderedFloat(0.5),
+      ..TreeBuilderParams::default()
+    };
+    let metric = PlacementDistance::new(&qry, &ref_seq, &[], &[], &params);
+    assert_eq!(tree_calculate_node_distance(&node, &metric), 5.5);
     Ok(())
   }
 }
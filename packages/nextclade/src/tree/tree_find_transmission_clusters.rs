@@ -0,0 +1,315 @@
+use crate::graph::node::{GraphNodeKey, Node};
+use crate::io::csv::{CsvVecFileWriter, VecWriter};
+use crate::tree::tree::{AuspiceColoring, AuspiceGraph, AuspiceGraphNodePayload};
+use crate::tree::tree_prune::is_new_node;
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the node attribute containing ID of the transmission cluster of a query sequence
+pub const TRANSMISSION_CLUSTER_ATTR: &str = "transmission_cluster";
+
+/// Group of query sequences which are putatively linked by transmission
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransmissionCluster {
+  pub id: String,
+  pub node_keys: Vec<GraphNodeKey>,
+  pub seq_names: Vec<String>,
+}
+
+/// Groups query sequences into putative transmission clusters, using the tree with query sequences attached.
+///
+/// The distance between two sequences is the number of nucleotide substitutions (between A, C, G and T) on the path
+/// between them in the tree. Two sequences belong to the same cluster if they are connected by a chain of sequences,
+/// each of which is at most `max_snp_distance` away from the next one (single linkage). Clusters of a single sequence
+/// are not reported.
+///
+/// Clusters are sorted by size (largest first) and numbered in this order.
+pub fn graph_find_transmission_clusters(
+  graph: &AuspiceGraph,
+  max_snp_distance: usize,
+) -> Result<Vec<TransmissionCluster>, Report> {
+  let new_keys = graph
+    .iter_nodes()
+    .filter(|node| is_query_node(graph, node))
+    .map(Node::key)
+    .collect_vec();
+
+  let index_of: BTreeMap<GraphNodeKey, usize> = new_keys.iter().enumerate().map(|(i, key)| (*key, i)).collect();
+
+  // Disjoint-set forest over query sequences
+  let mut components = (0..new_keys.len()).collect_vec();
+  for (i, key) in new_keys.iter().enumerate() {
+    for neighbor in find_new_nodes_within_distance(graph, *key, max_snp_distance)? {
+      union(&mut components, i, index_of[&neighbor]);
+    }
+  }
+
+  let mut groups = BTreeMap::<usize, Vec<(String, GraphNodeKey)>>::new();
+  for (i, key) in new_keys.iter().enumerate() {
+    let name = graph.get_node(*key)?.payload().name.clone();
+    groups.entry(find(&mut components, i)).or_default().push((name, *key));
+  }
+
+  let clusters = groups
+    .into_values()
+    .filter(|members| members.len() > 1)
+    .map(|mut members| {
+      members.sort();
+      members
+    })
+    .sorted_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)))
+    .enumerate()
+    .map(|(i, members)| {
+      let (seq_names, node_keys) = members.into_iter().unzip();
+      TransmissionCluster {
+        id: format!("cluster_{}", i + 1),
+        node_keys,
+        seq_names,
+      }
+    })
+    .collect();
+
+  Ok(clusters)
+}
+
+/// Adds transmission cluster IDs as a node attribute of the clustered query sequences, along with its coloring.
+///
+/// Cluster IDs left over from previous runs (e.g. when the reference tree was produced with `--output-tree-dataset`)
+/// are removed. Cluster members which are not in the graph are skipped, so that the clusters found on a graph can also
+/// be annotated on a copy of it taken before some of the query sequences were attached.
+pub fn graph_annotate_transmission_clusters_in_place(
+  graph: &mut AuspiceGraph,
+  clusters: &[TransmissionCluster],
+) -> Result<(), Report> {
+  let cluster_ids: BTreeMap<GraphNodeKey, &str> = clusters
+    .iter()
+    .flat_map(|cluster| cluster.node_keys.iter().map(|key| (*key, cluster.id.as_str())))
+    .collect();
+
+  for node in graph.iter_nodes_mut() {
+    let cluster_id = cluster_ids.get(&node.key()).copied();
+    let payload = node.payload_mut();
+    payload.remove_clade_node_attr(TRANSMISSION_CLUSTER_ATTR);
+    if let Some(cluster_id) = cluster_id {
+      payload.set_clade_node_attr(TRANSMISSION_CLUSTER_ATTR, cluster_id);
+    }
+  }
+
+  let colorings = &mut graph.data.meta.colorings;
+  let has_coloring = colorings
+    .iter()
+    .any(|coloring| coloring.key == TRANSMISSION_CLUSTER_ATTR);
+  if !clusters.is_empty() && !has_coloring {
+    colorings.push(AuspiceColoring {
+      type_: "categorical".to_owned(),
+      key: TRANSMISSION_CLUSTER_ATTR.to_owned(),
+      title: "Transmission cluster".to_owned(),
+      scale: vec![],
+      other: serde_json::Value::default(),
+    });
+  }
+
+  Ok(())
+}
+
+/// Writes transmission clusters as TSV, one cluster per row
+pub fn transmission_clusters_write_tsv(
+  filepath: impl AsRef<Path>,
+  clusters: &[TransmissionCluster],
+) -> Result<(), Report> {
+  let filepath = filepath.as_ref();
+  let headers = ["clusterId", "size", "seqNames"].map(str::to_owned);
+  let mut writer = CsvVecFileWriter::new(filepath, b'\t', &headers)?;
+  for cluster in clusters {
+    writer
+      .write([
+        cluster.id.clone(),
+        cluster.seq_names.len().to_string(),
+        cluster.seq_names.join(","),
+      ])
+      .wrap_err_with(|| format!("When writing transmission clusters file: {}", filepath.display()))?;
+  }
+  Ok(())
+}
+
+/// Finds query sequences at most `max_snp_distance` away from a given node, by traversing the tree in all directions
+fn find_new_nodes_within_distance(
+  graph: &AuspiceGraph,
+  start_key: GraphNodeKey,
+  max_snp_distance: usize,
+) -> Result<Vec<GraphNodeKey>, Report> {
+  let mut found = vec![];
+  let mut stack = vec![(start_key, None, 0_usize)];
+  while let Some((key, came_from, distance)) = stack.pop() {
+    let node = graph.get_node(key)?;
+    if key != start_key && is_query_node(graph, node) {
+      found.push(key);
+    }
+
+    if let Some(parent_key) = graph.parent_key_of_by_key(key)
+      && came_from != Some(parent_key)
+    {
+      let distance = distance + count_branch_snps(node.payload());
+      if distance <= max_snp_distance {
+        stack.push((parent_key, Some(key), distance));
+      }
+    }
+
+    for child in graph.iter_children_of_by_key(key) {
+      if came_from != Some(child.key()) {
+        let distance = distance + count_branch_snps(child.payload());
+        if distance <= max_snp_distance {
+          stack.push((child.key(), Some(key), distance));
+        }
+      }
+    }
+  }
+  Ok(found)
+}
+
+/// Whether the node is a query sequence. Internal nodes created when attaching query sequences can be copies of
+/// other query sequences, so only leaves are considered.
+fn is_query_node(graph: &AuspiceGraph, node: &Node<AuspiceGraphNodePayload>) -> bool {
+  is_new_node(node.payload()) && graph.is_leaf_key(node.key())
+}
+
+/// Counts substitutions between A, C, G and T on the branch leading to the node
+fn count_branch_snps(payload: &AuspiceGraphNodePayload) -> usize {
+  payload
+    .tmp
+    .private_mutations
+    .nuc_muts
+    .iter()
+    .filter(|sub| sub.ref_nuc.is_acgt() && sub.qry_nuc.is_acgt())
+    .count()
+}
+
+fn find(components: &mut [usize], i: usize) -> usize {
+  let mut root = i;
+  while components[root] != root {
+    root = components[root];
+  }
+  // Path compression
+  let mut current = i;
+  while components[current] != root {
+    let next = components[current];
+    components[current] = root;
+    current = next;
+  }
+  root
+}
+
+fn union(components: &mut [usize], a: usize, b: usize) {
+  let root_a = find(components, a);
+  let root_b = find(components, b);
+  if root_a != root_b {
+    components[root_b] = root_a;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::analyze::nuc_sub::NucSub;
+  use crate::tree::tree::{AuspiceGraphEdgePayload, AuspiceGraphMeta, TreeNodeAttr};
+  use pretty_assertions::assert_eq;
+  use std::str::FromStr;
+
+  fn node(name: &str, is_new: bool, muts: &[&str]) -> Result<AuspiceGraphNodePayload, Report> {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.node_attrs.node_type = Some(TreeNodeAttr::new(if is_new { "New" } else { "Reference" }));
+    payload.tmp.private_mutations.nuc_muts = muts.iter().map(|m| NucSub::from_str(m)).try_collect()?;
+    Ok(payload)
+  }
+
+  //            root
+  //          /      \
+  //    x (2 SNPs)   y (5 SNPs)
+  //     /   |  \        |
+  //   q1   q2   q3     q4
+  //  (0)  (1)  (2, one of which is a deletion)
+  fn graph() -> Result<AuspiceGraph, Report> {
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", false, &[])?);
+    let x = graph.add_node(node("x", false, &["A1C", "A2C"])?);
+    let y = graph.add_node(node("y", false, &["A3C", "A4C", "A5C", "A6C", "A7C"])?);
+    let q1 = graph.add_node(node("q1", true, &[])?);
+    let q2 = graph.add_node(node("q2", true, &["A10T"])?);
+    let q3 = graph.add_node(node("q3", true, &["A11T", "A12-"])?);
+    let q4 = graph.add_node(node("q4", true, &[])?);
+    for (src, dst) in [(root, x), (root, y), (x, q1), (x, q2), (x, q3), (y, q4)] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    graph.build()
+  }
+
+  fn seq_names(clusters: &[TransmissionCluster]) -> Vec<(&str, Vec<&str>)> {
+    clusters
+      .iter()
+      .map(|cluster| {
+        (
+          cluster.id.as_str(),
+          cluster.seq_names.iter().map(String::as_str).collect(),
+        )
+      })
+      .collect()
+  }
+
+  #[test]
+  fn clusters_sequences_within_distance() -> Result<(), Report> {
+    let graph = graph()?;
+    let clusters = graph_find_transmission_clusters(&graph, 1)?;
+    assert_eq!(seq_names(&clusters), vec![("cluster_1", vec!["q1", "q2", "q3"])]);
+    Ok(())
+  }
+
+  #[test]
+  fn merges_clusters_across_the_tree_with_larger_distance() -> Result<(), Report> {
+    let graph = graph()?;
+    assert_eq!(seq_names(&graph_find_transmission_clusters(&graph, 0)?), vec![]);
+    assert_eq!(
+      seq_names(&graph_find_transmission_clusters(&graph, 7)?),
+      vec![("cluster_1", vec!["q1", "q2", "q3", "q4"])]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn replaces_previous_cluster_annotations() -> Result<(), Report> {
+    let mut graph = graph()?;
+    let q4 = graph
+      .iter_nodes()
+      .find(|node| node.payload().name == "q4")
+      .unwrap()
+      .key();
+    graph
+      .get_node_mut(q4)?
+      .payload_mut()
+      .set_clade_node_attr(TRANSMISSION_CLUSTER_ATTR, "cluster_7");
+
+    let clusters = graph_find_transmission_clusters(&graph, 1)?;
+    graph_annotate_transmission_clusters_in_place(&mut graph, &clusters)?;
+    graph_annotate_transmission_clusters_in_place(&mut graph, &clusters)?;
+
+    let annotations = graph
+      .iter_node_payloads()
+      .filter_map(|node| Some((node.name.as_str(), node.get_clade_node_attr(TRANSMISSION_CLUSTER_ATTR)?)))
+      .collect_vec();
+    assert_eq!(
+      annotations,
+      vec![("q1", "cluster_1"), ("q2", "cluster_1"), ("q3", "cluster_1")]
+    );
+
+    let num_colorings = graph
+      .data
+      .meta
+      .colorings
+      .iter()
+      .filter(|coloring| coloring.key == TRANSMISSION_CLUSTER_ATTR)
+      .count();
+    assert_eq!(num_colorings, 1);
+    Ok(())
+  }
+}
@@ -0,0 +1,608 @@
+use crate::align::params::AlignPairwiseParams;
+use crate::alphabet::aa::{Aa, from_aa};
+use crate::alphabet::nuc::{Nuc, to_nuc_seq_replacing};
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::position::PositionLike;
+use crate::gene::gene_map::GeneMap;
+use crate::io::fasta::FastaRecord;
+use crate::io::json::json_parse;
+use crate::io::nwk_reader::{NwkNode, is_nexus_str, nwk_or_nexus_read_str};
+use crate::make_error;
+use crate::translate::extract::extract_cds_from_ref;
+use crate::translate::translate::translate;
+use crate::tree::tree::{
+  AuspiceColoring, AuspiceTree, AuspiceTreeMeta, AuspiceTreeNode, TreeBranchAttrs, TreeNodeAttr, TreeNodeAttrs,
+};
+use eyre::{Report, WrapErr};
+use itertools::Itertools;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+/// Format of a reference tree file
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RefTreeFormat {
+  AuspiceJson,
+  Newick,
+  Nexus,
+}
+
+impl RefTreeFormat {
+  /// Detects format of a reference tree from the file contents
+  pub fn guess_from_str(s: &str) -> Self {
+    if s.trim_start().starts_with('{') {
+      Self::AuspiceJson
+    } else if is_nexus_str(s) {
+      Self::Nexus
+    } else {
+      Self::Newick
+    }
+  }
+}
+
+/// Node data JSON, as produced by `augur ancestral`, `augur translate`, `augur clades` and similar tools
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AugurNodeData {
+  #[serde(default)]
+  pub nodes: BTreeMap<String, AugurNodeDataEntry>,
+
+  #[serde(flatten)]
+  pub other: serde_json::Value,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AugurNodeDataEntry {
+  /// Nucleotide mutations on the branch leading to the node
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub muts: Option<Vec<String>>,
+
+  /// Amino acid mutations on the branch leading to the node, keyed by CDS name
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub aa_muts: Option<BTreeMap<String, Vec<String>>>,
+
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub clade_membership: Option<String>,
+
+  /// Reconstructed sequence of the node
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub sequence: Option<String>,
+
+  #[serde(flatten)]
+  pub other: BTreeMap<String, serde_json::Value>,
+}
+
+impl AugurNodeData {
+  pub fn from_str(s: impl AsRef<str>) -> Result<Self, Report> {
+    json_parse(s).wrap_err("When parsing node data JSON")
+  }
+}
+
+/// Data required to convert a Newick or Nexus tree to Auspice JSON
+pub struct RefTreeImportInputs<'a> {
+  pub ref_seq: &'a [Nuc],
+  pub gene_map: &'a GeneMap,
+  pub params: &'a AlignPairwiseParams,
+
+  /// Sequences of tree nodes, aligned to the reference. Sequences of the internal nodes, which are not provided, are
+  /// reconstructed using Fitch parsimony.
+  pub sequences: Option<&'a [FastaRecord]>,
+
+  /// Mutations, clade labels and other attributes of tree nodes
+  pub node_data: Option<&'a AugurNodeData>,
+}
+
+/// Reads a reference tree in any of the supported formats: Auspice JSON v2, Newick or Nexus. Newick and Nexus trees
+/// are converted to Auspice JSON, using node sequences and node data from `inputs`.
+pub fn ref_tree_read_str(s: &str, inputs: &RefTreeImportInputs) -> Result<AuspiceTree, Report> {
+  match RefTreeFormat::guess_from_str(s) {
+    RefTreeFormat::AuspiceJson => AuspiceTree::from_str(s),
+    RefTreeFormat::Newick | RefTreeFormat::Nexus => {
+      let nwk = nwk_or_nexus_read_str(s)?;
+      convert_nwk_to_auspice_tree(&nwk, inputs).wrap_err("When converting reference tree to Auspice JSON")
+    }
+  }
+}
+
+/// Tree node stored in a flat array, in pre-order
+#[derive(Debug)]
+struct ImportNode {
+  name: String,
+  branch_length: Option<f64>,
+  parent: Option<usize>,
+  children: Vec<usize>,
+}
+
+/// Converts a tree in Newick format to Auspice JSON.
+///
+/// Nucleotide mutations on branches are found from the node sequences, if provided either as a FASTA or in node data,
+/// and otherwise are taken from node data.
+/// Amino acid mutations are taken from node data, if provided, and otherwise are found by translating genes along the
+/// tree. Node divergence is calculated from branch lengths, or from the number of nucleotide mutations if the tree has
+/// no branch lengths.
+pub fn convert_nwk_to_auspice_tree(nwk: &NwkNode, inputs: &RefTreeImportInputs) -> Result<AuspiceTree, Report> {
+  let nodes = flatten_nwk_tree(nwk)?;
+  let node_data = inputs.node_data.map(|node_data| &node_data.nodes);
+
+  // Sequences reconstructed by `augur ancestral` can also come in node data
+  let node_data_sequences = node_data
+    .map(|node_data| {
+      node_data
+        .iter()
+        .filter_map(|(name, entry)| entry.sequence.as_ref().map(|seq| (name, seq)))
+        .enumerate()
+        .map(|(index, (name, seq))| FastaRecord {
+          seq_name: name.clone(),
+          seq: seq.clone(),
+          index,
+        })
+        .collect_vec()
+    })
+    .filter(|sequences| !sequences.is_empty());
+
+  let nuc_muts = if let Some(sequences) = inputs.sequences.or(node_data_sequences.as_deref()) {
+    reconstruct_nuc_muts(&nodes, inputs.ref_seq, sequences)?
+  } else if let Some(node_data) = node_data {
+    nodes
+      .iter()
+      .map(|node| {
+        node_data
+          .get(&node.name)
+          .and_then(|entry| entry.muts.as_ref())
+          .into_iter()
+          .flatten()
+          .map(|m| NucSub::from_str(m).wrap_err_with(|| format!("When parsing mutations of node '{}'", node.name)))
+          .collect::<Result<Vec<NucSub>, Report>>()
+      })
+      .collect::<Result<Vec<_>, Report>>()?
+  } else {
+    return make_error!(
+      "Reference tree in Newick or Nexus format does not contain mutations. Node sequences or node data are required to convert it to Auspice JSON."
+    );
+  };
+
+  let has_aa_muts_in_node_data =
+    node_data.is_some_and(|node_data| node_data.values().any(|entry| entry.aa_muts.is_some()));
+  let aa_muts = if has_aa_muts_in_node_data {
+    nodes
+      .iter()
+      .map(|node| {
+        node_data
+          .and_then(|node_data| node_data.get(&node.name))
+          .and_then(|entry| entry.aa_muts.clone())
+          .unwrap_or_default()
+      })
+      .collect_vec()
+  } else {
+    find_aa_muts(&nodes, &nuc_muts, inputs)?
+  };
+
+  let has_branch_lengths = nodes.iter().skip(1).any(|node| node.branch_length.is_some());
+  let mut divs = vec![0.0; nodes.len()];
+  for (i, node) in nodes.iter().enumerate().skip(1) {
+    let parent_div = node.parent.map_or(0.0, |parent| divs[parent]);
+    let branch_length = if has_branch_lengths {
+      node.branch_length.unwrap_or_default()
+    } else {
+      nuc_muts[i].len() as f64
+    };
+    divs[i] = parent_div + branch_length;
+  }
+
+  let mut auspice_nodes = nodes
+    .iter()
+    .enumerate()
+    .map(|(i, node)| {
+      let entry = node_data.and_then(|node_data| node_data.get(&node.name));
+
+      let mut mutations = aa_muts[i].clone();
+      mutations.retain(|_, muts| !muts.is_empty());
+      if !nuc_muts[i].is_empty() {
+        mutations.insert("nuc".to_owned(), nuc_muts[i].iter().map(ToString::to_string).collect());
+      }
+
+      let other_attrs = entry
+        .map(|entry| {
+          entry
+            .other
+            .iter()
+            .filter_map(|(key, value)| value.as_str().map(|value| (key.clone(), json!({ "value": value }))))
+            .collect::<serde_json::Map<_, _>>()
+        })
+        .filter(|attrs| !attrs.is_empty())
+        .map_or_else(serde_json::Value::default, serde_json::Value::Object);
+
+      AuspiceTreeNode {
+        name: node.name.clone(),
+        branch_attrs: TreeBranchAttrs {
+          mutations,
+          ..TreeBranchAttrs::default()
+        },
+        node_attrs: TreeNodeAttrs {
+          div: Some(divs[i]),
+          clade_membership: entry
+            .and_then(|entry| entry.clade_membership.as_deref())
+            .map(TreeNodeAttr::new),
+          other: other_attrs,
+          ..TreeNodeAttrs::default()
+        },
+        children: vec![],
+        other: serde_json::Value::default(),
+      }
+    })
+    .map(Some)
+    .collect_vec();
+
+  let has_clades = auspice_nodes
+    .iter()
+    .flatten()
+    .any(|node| node.node_attrs.clade_membership.is_some());
+
+  // Assemble the tree bottom-up. Children always come after their parents in pre-order.
+  // Children are attached in reverse order, so they are reversed back when the node is complete.
+  for i in (1..nodes.len()).rev() {
+    let node = auspice_nodes[i].take();
+    if let (Some(mut node), Some(parent)) = (node, nodes[i].parent)
+      && let Some(parent) = &mut auspice_nodes[parent]
+    {
+      node.children.reverse();
+      parent.children.push(node);
+    }
+  }
+  let Some(Some(mut tree)) = auspice_nodes.into_iter().next() else {
+    return make_error!("Reference tree is empty");
+  };
+  tree.children.reverse();
+
+  let colorings = if has_clades {
+    vec![AuspiceColoring {
+      type_: "categorical".to_owned(),
+      key: "clade_membership".to_owned(),
+      title: "Clade".to_owned(),
+      scale: vec![],
+      other: serde_json::Value::default(),
+    }]
+  } else {
+    vec![]
+  };
+
+  Ok(AuspiceTree {
+    version: Some("v2".to_owned()),
+    meta: AuspiceTreeMeta {
+      colorings,
+      panels: vec!["tree".to_owned()],
+      ..AuspiceTreeMeta::default()
+    },
+    tree,
+    root_sequence: None,
+    other: serde_json::Value::default(),
+  })
+}
+
+/// Flattens the tree into an array, in pre-order. Unnamed internal nodes and internal nodes labeled with support
+/// values are given names in `NODE_0000000` format, in the same way as augur does.
+fn flatten_nwk_tree(nwk: &NwkNode) -> Result<Vec<ImportNode>, Report> {
+  let mut nodes = Vec::<ImportNode>::new();
+  let mut names = BTreeSet::<String>::new();
+  let mut num_unnamed = 0_usize;
+
+  let mut stack = vec![(nwk, None)];
+  while let Some((nwk_node, parent)) = stack.pop() {
+    let index = nodes.len();
+
+    let name = match &nwk_node.name {
+      Some(name) if nwk_node.is_leaf() || name.parse::<f64>().is_err() => name.clone(),
+      _ if nwk_node.is_leaf() => return make_error!("Reference tree contains a tip without a name"),
+      _ => {
+        let name = format!("NODE_{num_unnamed:07}");
+        num_unnamed += 1;
+        name
+      }
+    };
+
+    if !names.insert(name.clone()) {
+      return make_error!(
+        "Reference tree contains multiple nodes with the same name: '{name}'. Node names are required to be unique."
+      );
+    }
+
+    nodes.push(ImportNode {
+      name,
+      branch_length: nwk_node.branch_length,
+      parent,
+      children: vec![],
+    });
+    if let Some(parent) = parent {
+      nodes[parent].children.push(index);
+    }
+
+    stack.extend(nwk_node.children.iter().rev().map(|child| (child, Some(index))));
+  }
+
+  Ok(nodes)
+}
+
+const NUC_BIT_GAP: u8 = 1 << 4;
+const NUC_BITS_ALL: u8 = 0b1_1111;
+
+/// Represents unambiguous nucleotides and gap as bits, and everything else as a set of all possible states
+const fn nuc_to_bits(nuc: Nuc) -> u8 {
+  match nuc {
+    Nuc::A => 1,
+    Nuc::C => 1 << 1,
+    Nuc::G => 1 << 2,
+    Nuc::T => 1 << 3,
+    Nuc::Gap => NUC_BIT_GAP,
+    _ => NUC_BITS_ALL,
+  }
+}
+
+const fn bits_to_nuc(bits: u8) -> Nuc {
+  match bits {
+    1 => Nuc::A,
+    0b10 => Nuc::C,
+    0b100 => Nuc::G,
+    0b1000 => Nuc::T,
+    NUC_BIT_GAP => Nuc::Gap,
+    _ => Nuc::N,
+  }
+}
+
+/// Finds nucleotide mutations on every branch of the tree, given sequences of the tips and (optionally) of the
+/// internal nodes. Missing and ambiguous states are reconstructed using Fitch parsimony. Differences between the
+/// reference sequence and the root are placed onto the branch leading to the root.
+fn reconstruct_nuc_muts(
+  nodes: &[ImportNode],
+  ref_seq: &[Nuc],
+  sequences: &[FastaRecord],
+) -> Result<Vec<Vec<NucSub>>, Report> {
+  let sequences: BTreeMap<&str, Vec<Nuc>> = sequences
+    .iter()
+    .map(|record| {
+      let seq = to_nuc_seq_replacing(&record.seq);
+      if seq.len() != ref_seq.len() {
+        return make_error!(
+          "Sequence '{}' has length {}, but the reference sequence has length {}. Sequences of tree nodes are expected to be aligned to the reference sequence.",
+          record.seq_name,
+          seq.len(),
+          ref_seq.len()
+        );
+      }
+      Ok((record.seq_name.as_str(), seq))
+    })
+    .collect::<Result<_, Report>>()?;
+
+  let node_seqs = nodes.iter().map(|node| sequences.get(node.name.as_str())).collect_vec();
+
+  let num_tips_without_seq = nodes
+    .iter()
+    .zip(&node_seqs)
+    .filter(|(node, seq)| node.children.is_empty() && seq.is_none())
+    .count();
+  if num_tips_without_seq > 0 {
+    warn!(
+      "When converting reference tree: {num_tips_without_seq} tip(s) of the tree have no sequence. They will be assumed to have the same sequence as their parents."
+    );
+  }
+
+  let mut muts = vec![vec![]; nodes.len()];
+  let mut sets = vec![0_u8; nodes.len()];
+  let mut states = vec![0_u8; nodes.len()];
+  for (pos, &ref_nuc) in ref_seq.iter().enumerate() {
+    let ref_bits = nuc_to_bits(ref_nuc);
+    if ref_bits == NUC_BITS_ALL {
+      // Mutations cannot be expressed relative to an ambiguous reference nucleotide
+      continue;
+    }
+
+    // Bottom-up pass: sets of most parsimonious states
+    for i in (0..nodes.len()).rev() {
+      let own_bits = node_seqs[i].map_or(NUC_BITS_ALL, |seq| nuc_to_bits(seq[pos]));
+      sets[i] = if own_bits != NUC_BITS_ALL || nodes[i].children.is_empty() {
+        own_bits
+      } else {
+        let intersection = nodes[i].children.iter().fold(NUC_BITS_ALL, |acc, &c| acc & sets[c]);
+        if intersection == 0 {
+          nodes[i].children.iter().fold(0, |acc, &c| acc | sets[c])
+        } else {
+          intersection
+        }
+      };
+    }
+
+    // Top-down pass: choose states, preferring the state of the parent
+    for i in 0..nodes.len() {
+      let parent_bits = nodes[i].parent.map_or(ref_bits, |parent| states[parent]);
+      states[i] = if sets[i] & parent_bits == 0 {
+        1 << sets[i].trailing_zeros()
+      } else {
+        parent_bits
+      };
+
+      if states[i] != parent_bits {
+        muts[i].push(NucSub {
+          pos: pos.into(),
+          ref_nuc: bits_to_nuc(parent_bits),
+          qry_nuc: bits_to_nuc(states[i]),
+        });
+      }
+    }
+  }
+
+  Ok(muts)
+}
+
+/// Finds amino acid mutations on every branch of the tree, by translating the CDSes affected by the nucleotide
+/// mutations of each node
+fn find_aa_muts(
+  nodes: &[ImportNode],
+  nuc_muts: &[Vec<NucSub>],
+  inputs: &RefTreeImportInputs,
+) -> Result<Vec<BTreeMap<String, Vec<String>>>, Report> {
+  let mut aa_muts = vec![BTreeMap::new(); nodes.len()];
+  if inputs.gene_map.is_empty() {
+    return Ok(aa_muts);
+  }
+
+  let cdses = inputs.gene_map.iter_cdses().collect_vec();
+  let mut seq = inputs.ref_seq.to_vec();
+
+  let translate_cds = |seq: &[Nuc], cds_index: usize| -> Result<Vec<Aa>, Report> {
+    let cds = cdses[cds_index];
+    let nucs = extract_cds_from_ref(seq, cds);
+    Ok(translate(&nucs, cds, inputs.params)?.seq)
+  };
+
+  // Depth-first traversal, applying mutations of each node when entering it and reverting them when leaving
+  let mut stack = vec![(0, false)];
+  while let Some((i, visited)) = stack.pop() {
+    if visited {
+      for m in nuc_muts[i].iter().rev() {
+        seq[m.pos.as_usize()] = m.ref_nuc;
+      }
+      continue;
+    }
+
+    let affected_cdses = cdses
+      .iter()
+      .enumerate()
+      .filter(|(_, cds)| {
+        nuc_muts[i]
+          .iter()
+          .any(|m| cds.segments.iter().any(|segment| segment.range.contains(m.pos)))
+      })
+      .map(|(cds_index, _)| cds_index)
+      .collect_vec();
+
+    let parent_peptides = affected_cdses
+      .iter()
+      .map(|&cds_index| translate_cds(&seq, cds_index))
+      .collect::<Result<Vec<_>, Report>>()?;
+
+    for m in &nuc_muts[i] {
+      seq[m.pos.as_usize()] = m.qry_nuc;
+    }
+
+    for (&cds_index, parent_peptide) in affected_cdses.iter().zip(parent_peptides) {
+      let peptide = translate_cds(&seq, cds_index)?;
+      let muts = parent_peptide
+        .iter()
+        .zip(&peptide)
+        .enumerate()
+        .filter(|(_, (parent_aa, aa))| parent_aa != aa)
+        .map(|(pos, (&parent_aa, &aa))| format!("{}{}{}", from_aa(parent_aa), pos + 1, from_aa(aa)))
+        .collect_vec();
+      if !muts.is_empty() {
+        aa_muts[i].insert(cdses[cds_index].name.clone(), muts);
+      }
+    }
+
+    stack.push((i, true));
+    stack.extend(nodes[i].children.iter().rev().map(|&child| (child, false)));
+  }
+
+  Ok(aa_muts)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::alphabet::nuc::to_nuc_seq;
+  use crate::io::nwk_reader::nwk_read_str;
+  use crate::utils::error::report_to_string;
+  use pretty_assertions::assert_eq;
+
+  fn fasta(records: &[(&str, &str)]) -> Vec<FastaRecord> {
+    records
+      .iter()
+      .enumerate()
+      .map(|(index, (seq_name, seq))| FastaRecord {
+        seq_name: (*seq_name).to_owned(),
+        seq: (*seq).to_owned(),
+        index,
+      })
+      .collect()
+  }
+
+  fn format_tree(node: &AuspiceTreeNode) -> String {
+    let muts = node
+      .branch_attrs
+      .mutations
+      .get("nuc")
+      .map(|muts| muts.join("+"))
+      .unwrap_or_default();
+    let children = if node.children.is_empty() {
+      String::new()
+    } else {
+      format!("({})", node.children.iter().map(format_tree).join(","))
+    };
+    format!("{children}{}[{muts}]", node.name)
+  }
+
+  #[test]
+  fn converts_nwk_with_tip_sequences() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq("ACGTA")?;
+    let nwk = nwk_read_str("((A:1,B:1)90:1,C:2);")?;
+    let sequences = fasta(&[("A", "ACGTT"), ("B", "ACGTT"), ("C", "CCNT-")]);
+    let inputs = RefTreeImportInputs {
+      ref_seq: &ref_seq,
+      gene_map: &GeneMap::new(),
+      params: &AlignPairwiseParams::default(),
+      sequences: Some(&sequences),
+      node_data: None,
+    };
+
+    let tree = convert_nwk_to_auspice_tree(&nwk, &inputs)?;
+    assert_eq!(
+      format_tree(&tree.tree),
+      "((A[],B[])NODE_0000001[],C[A1C+T5-])NODE_0000000[A5T]"
+    );
+    assert_eq!(tree.tree.children[1].node_attrs.div, Some(2.0));
+    Ok(())
+  }
+
+  #[test]
+  fn converts_nwk_with_node_data() -> Result<(), Report> {
+    let ref_seq = to_nuc_seq("ACGTA")?;
+    let nwk = nwk_read_str("(A,(B,C)inner)root;")?;
+    let node_data = AugurNodeData::from_str(
+      r#"{
+        "nodes": {
+          "inner": { "muts": ["C2T"], "clade_membership": "X" },
+          "B": { "muts": ["G3A"], "clade_membership": "X", "country": "Y", "numdate": 2020.5 }
+        }
+      }"#,
+    )?;
+    let inputs = RefTreeImportInputs {
+      ref_seq: &ref_seq,
+      gene_map: &GeneMap::new(),
+      params: &AlignPairwiseParams::default(),
+      sequences: None,
+      node_data: Some(&node_data),
+    };
+
+    let tree = convert_nwk_to_auspice_tree(&nwk, &inputs)?;
+    assert_eq!(format_tree(&tree.tree), "(A[],(B[G3A],C[])inner[C2T])root[]");
+
+    let b = &tree.tree.children[1].children[0];
+    assert_eq!(b.node_attrs.div, Some(2.0));
+    assert_eq!(
+      b.node_attrs.clade_membership.as_ref().map(|clade| clade.value.as_str()),
+      Some("X")
+    );
+    assert_eq!(b.node_attrs.other, json!({ "country": { "value": "Y" } }));
+    assert_eq!(tree.meta.colorings.len(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn rejects_duplicate_names() -> Result<(), Report> {
+    let nwk = nwk_read_str("(A,A);")?;
+    assert_eq!(
+      report_to_string(&flatten_nwk_tree(&nwk).unwrap_err()),
+      "Reference tree contains multiple nodes with the same name: 'A'. Node names are required to be unique."
+    );
+    Ok(())
+  }
+}
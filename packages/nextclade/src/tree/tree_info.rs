@@ -0,0 +1,377 @@
+use crate::analyze::aa_sub::AaSub;
+use crate::analyze::nuc_sub::NucSub;
+use crate::coord::range::NucRefGlobalRange;
+use crate::graph::node::GraphNodeKey;
+use crate::tree::tree::{AuspiceGraph, AuspiceGraphNodePayload, AuspiceRefNodesDesc, DivergenceUnits};
+use eyre::Report;
+use itertools::Itertools;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Summary of a reference tree, as seen by Nextclade after preprocessing
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeInfo {
+  pub num_nodes: usize,
+  pub num_leaves: usize,
+
+  /// Whether the root sequence embedded in the tree (`.root_sequence.nuc`) is identical to the reference sequence.
+  /// Absent if the tree contains no root sequence.
+  pub root_sequence_matches_ref: Option<bool>,
+
+  /// Unit of measurement of divergence, as guessed from the greatest divergence on the tree
+  pub divergence_units: DivergenceUnits,
+  pub max_divergence: f64,
+
+  pub placement_mask_ranges: Vec<NucRefGlobalRange>,
+  pub ref_nodes: AuspiceRefNodesDesc,
+  pub clades: Vec<TreeCladeInfo>,
+}
+
+/// Clade present on the reference tree, along with the nodes which found it
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeCladeInfo {
+  pub clade: String,
+  pub num_nodes: usize,
+  pub num_leaves: usize,
+
+  /// Earliest nodes of the clade. There is more than one founder if the clade is not monophyletic.
+  pub founders: Vec<TreeCladeFounderInfo>,
+}
+
+/// Founder node of a clade and the mutations on the branch leading to it
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeCladeFounderInfo {
+  pub node_name: String,
+  pub nuc_mutations: Vec<NucSub>,
+  pub aa_mutations: Vec<AaSub>,
+}
+
+/// Gathers summary information about a preprocessed reference tree
+pub fn graph_get_tree_info(graph: &AuspiceGraph, root_sequence_matches_ref: Option<bool>) -> Result<TreeInfo, Report> {
+  // The founder of a node's clade is its earliest ancestor of the same clade, or the node itself. Earliest nodes of each
+  // clade on the path from the root to the current node are tracked during a single depth-first traversal.
+  let mut path_founders = BTreeMap::<String, GraphNodeKey>::new();
+  let mut clades = BTreeMap::<String, (usize, usize, BTreeSet<GraphNodeKey>)>::new();
+  let mut stack = vec![(graph.get_exactly_one_root()?.key(), false)];
+  while let Some((node_key, visited)) = stack.pop() {
+    let Some(clade) = graph.get_node(node_key)?.payload().clade() else {
+      if !visited {
+        stack.extend(
+          graph
+            .iter_child_keys_of_by_key(node_key)
+            .map(|child_key| (child_key, false)),
+        );
+      }
+      continue;
+    };
+
+    if visited {
+      // The node is no longer on the path
+      if path_founders.get(&clade) == Some(&node_key) {
+        path_founders.remove(&clade);
+      }
+      continue;
+    }
+
+    stack.push((node_key, true));
+    stack.extend(
+      graph
+        .iter_child_keys_of_by_key(node_key)
+        .map(|child_key| (child_key, false)),
+    );
+
+    let founder_key = *path_founders.entry(clade.clone()).or_insert(node_key);
+    let (num_nodes, num_leaves, founders) = clades.entry(clade).or_default();
+    *num_nodes += 1;
+    if graph.is_leaf_key(node_key) {
+      *num_leaves += 1;
+    }
+    founders.insert(founder_key);
+  }
+
+  let clades = clades
+    .into_iter()
+    .map(|(clade, (num_nodes, num_leaves, founder_keys))| {
+      let founders = founder_keys
+        .into_iter()
+        .map(|key| {
+          let node = graph.get_node(key)?.payload();
+          Ok(TreeCladeFounderInfo {
+            node_name: node.name.clone(),
+            nuc_mutations: node.tmp.private_mutations.nuc_muts.clone(),
+            aa_mutations: node.tmp.private_mutations.aa_muts.values().flatten().cloned().collect(),
+          })
+        })
+        .collect::<Result<_, Report>>()?;
+      Ok(TreeCladeInfo {
+        clade,
+        num_nodes,
+        num_leaves,
+        founders,
+      })
+    })
+    .collect::<Result<_, Report>>()?;
+
+  Ok(TreeInfo {
+    num_nodes: graph.num_nodes(),
+    num_leaves: graph.num_leaves(),
+    root_sequence_matches_ref,
+    divergence_units: graph.data.tmp.divergence_units,
+    max_divergence: graph.data.tmp.max_divergence,
+    placement_mask_ranges: graph.data.meta.placement_mask_ranges().to_vec(),
+    ref_nodes: graph.data.meta.reference_nodes().clone(),
+    clades,
+  })
+}
+
+/// Mutation to search for on the reference tree, e.g. `C241T` or `S:E484K`
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TreeMutationQuery {
+  Nuc(NucSub),
+  Aa(AaSub),
+}
+
+impl FromStr for TreeMutationQuery {
+  type Err = Report;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s.contains(':') {
+      Ok(Self::Aa(AaSub::from_str(s)?))
+    } else {
+      Ok(Self::Nuc(NucSub::from_str(s)?))
+    }
+  }
+}
+
+impl Display for TreeMutationQuery {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Nuc(sub) => write!(f, "{sub}"),
+      Self::Aa(sub) => write!(f, "{sub}"),
+    }
+  }
+}
+
+impl TreeMutationQuery {
+  /// Whether the node carries the mutation, i.e. has the query letter at the position, relative to the reference
+  pub fn is_carried_by(&self, node: &AuspiceGraphNodePayload) -> bool {
+    match self {
+      Self::Nuc(sub) => node.tmp.mutations.get(&sub.pos) == Some(&sub.qry_nuc),
+      Self::Aa(sub) => {
+        node
+          .tmp
+          .aa_mutations
+          .get(&sub.cds_name)
+          .and_then(|muts| muts.get(&sub.pos))
+          == Some(&sub.qry_aa)
+      }
+    }
+  }
+
+  /// Whether the mutation occurs on the branch leading to the node
+  pub fn is_on_branch_of(&self, node: &AuspiceGraphNodePayload) -> bool {
+    let muts = &node.tmp.private_mutations;
+    match self {
+      Self::Nuc(sub) => muts
+        .nuc_muts
+        .iter()
+        .any(|mu| mu.pos == sub.pos && mu.qry_nuc == sub.qry_nuc),
+      Self::Aa(sub) => muts
+        .aa_muts
+        .get(&sub.cds_name)
+        .is_some_and(|muts| muts.iter().any(|mu| mu.pos == sub.pos && mu.qry_aa == sub.qry_aa)),
+    }
+  }
+}
+
+/// Parameters of the search for nodes on the reference tree
+#[derive(Clone, Debug, Default)]
+pub struct TreeQuery {
+  /// Mutations the nodes should carry. A node matches if it carries all of them.
+  pub mutations: Vec<TreeMutationQuery>,
+
+  /// Only report nodes on the branch to which the mutations occur, rather than all nodes carrying them
+  pub on_branch: bool,
+
+  /// Only report nodes belonging to this clade
+  pub clade: Option<String>,
+}
+
+/// Node of the reference tree matching a query
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeQueryMatch {
+  pub node_name: String,
+  pub clade: Option<String>,
+  pub is_leaf: bool,
+}
+
+/// Finds nodes of a preprocessed reference tree, matching the query. Nodes are returned in depth-first pre-order.
+pub fn graph_query_nodes(graph: &AuspiceGraph, query: &TreeQuery) -> Result<Vec<TreeQueryMatch>, Report> {
+  let root_key = graph.get_exactly_one_root()?.key();
+
+  let mut matches = vec![];
+  let mut stack = vec![root_key];
+  while let Some(key) = stack.pop() {
+    let node = graph.get_node(key)?;
+    if is_query_match(node.payload(), query) {
+      matches.push(TreeQueryMatch {
+        node_name: node.payload().name.clone(),
+        clade: node.payload().clade(),
+        is_leaf: graph.is_leaf_key(key),
+      });
+    }
+    // Reverse, so that children are visited in their original order
+    stack.extend(graph.iter_child_keys_of_by_key(key).collect_vec().into_iter().rev());
+  }
+
+  Ok(matches)
+}
+
+fn is_query_match(payload: &AuspiceGraphNodePayload, query: &TreeQuery) -> bool {
+  if let Some(clade) = &query.clade
+    && payload.clade().as_ref() != Some(clade)
+  {
+    return false;
+  }
+  query.mutations.iter().all(|mutation| {
+    if query.on_branch {
+      mutation.is_on_branch_of(payload)
+    } else {
+      mutation.is_carried_by(payload)
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::analyze::find_private_nuc_mutations::BranchMutations;
+  use crate::o;
+  use crate::tree::tree::{AuspiceGraphEdgePayload, AuspiceGraphMeta, TreeNodeAttr};
+  use pretty_assertions::assert_eq;
+
+  fn node(name: &str, clade: &str, muts: &[&str], private_muts: &[&str]) -> Result<AuspiceGraphNodePayload, Report> {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.node_attrs.clade_membership = Some(TreeNodeAttr::new(clade));
+
+    let parse = |muts: &[&str]| -> Result<(Vec<NucSub>, Vec<AaSub>), Report> {
+      let (aa, nuc): (Vec<&str>, Vec<&str>) = muts.iter().partition(|mu| mu.contains(':'));
+      Ok((
+        nuc.into_iter().map(NucSub::from_str).try_collect()?,
+        aa.into_iter().map(AaSub::from_str).try_collect()?,
+      ))
+    };
+
+    let (nuc, aa) = parse(muts)?;
+    payload.tmp.mutations = nuc.iter().map(|sub| (sub.pos, sub.qry_nuc)).collect();
+    for sub in aa {
+      payload
+        .tmp
+        .aa_mutations
+        .entry(sub.cds_name)
+        .or_default()
+        .insert(sub.pos, sub.qry_aa);
+    }
+
+    let (nuc, aa) = parse(private_muts)?;
+    payload.tmp.private_mutations = BranchMutations {
+      nuc_muts: nuc,
+      aa_muts: aa
+        .into_iter()
+        .into_group_map_by(|sub| sub.cds_name.clone())
+        .into_iter()
+        .collect(),
+    };
+
+    Ok(payload)
+  }
+
+  //        root (A)
+  //       /       \
+  //   x (B: C3T)   a1 (A)
+  //    /     \
+  //  b1 (B)  b2 (B: S:E4K)
+  fn graph() -> Result<AuspiceGraph, Report> {
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", "A", &[], &[])?);
+    let x = graph.add_node(node("x", "B", &["C3T"], &["C3T"])?);
+    let a1 = graph.add_node(node("a1", "A", &[], &[])?);
+    let b1 = graph.add_node(node("b1", "B", &["C3T"], &[])?);
+    let b2 = graph.add_node(node("b2", "B", &["C3T", "S:E4K"], &["S:E4K"])?);
+    for (src, dst) in [(root, x), (root, a1), (x, b1), (x, b2)] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    graph.build()
+  }
+
+  fn names(matches: &[TreeQueryMatch]) -> Vec<&str> {
+    matches.iter().map(|m| m.node_name.as_str()).collect()
+  }
+
+  #[test]
+  fn summarizes_clades() -> Result<(), Report> {
+    let info = graph_get_tree_info(&graph()?, Some(true))?;
+    let clades = info
+      .clades
+      .iter()
+      .map(|clade| {
+        (
+          clade.clade.as_str(),
+          clade.num_nodes,
+          clade.num_leaves,
+          clade
+            .founders
+            .iter()
+            .map(|founder| (founder.node_name.as_str(), founder.nuc_mutations.iter().join(",")))
+            .collect_vec(),
+        )
+      })
+      .collect_vec();
+    assert_eq!(
+      (info.num_nodes, info.num_leaves, clades),
+      (
+        5,
+        3,
+        vec![("A", 2, 1, vec![("root", o!(""))]), ("B", 3, 2, vec![("x", o!("C3T"))])]
+      )
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn finds_nodes_carrying_mutations() -> Result<(), Report> {
+    let graph = graph()?;
+    let query = |mutations: &[&str], on_branch: bool, clade: Option<&str>| -> Result<Vec<TreeQueryMatch>, Report> {
+      let query = TreeQuery {
+        mutations: mutations
+          .iter()
+          .map(|mu| TreeMutationQuery::from_str(mu))
+          .try_collect()?,
+        on_branch,
+        clade: clade.map(str::to_owned),
+      };
+      graph_query_nodes(&graph, &query)
+    };
+
+    assert_eq!(names(&query(&["C3T"], false, None)?), vec!["x", "b1", "b2"]);
+    assert_eq!(names(&query(&["C3T"], true, None)?), vec!["x"]);
+    assert_eq!(names(&query(&["C3T", "S:E4K"], false, None)?), vec!["b2"]);
+    assert_eq!(names(&query(&["S:E4K"], false, Some("A"))?), Vec::<&str>::new());
+    assert_eq!(names(&query(&[], false, Some("A"))?), vec!["root", "a1"]);
+    assert_eq!(
+      query(&["S:E4K"], true, None)?,
+      vec![TreeQueryMatch {
+        node_name: o!("b2"),
+        clade: Some(o!("B")),
+        is_leaf: true,
+      }]
+    );
+    Ok(())
+  }
+}
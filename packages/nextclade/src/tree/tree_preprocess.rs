@@ -10,6 +10,7 @@ use crate::graph::node::GraphNodeKey;
 use crate::make_error;
 use crate::translate::translate_genes::Translation;
 use crate::tree::tree::{AuspiceColoring, AuspiceGraph, AuspiceGraphNodePayload, AuspiceTreeMeta, ScaleKey};
+use crate::tree::tree_prune::COLLAPSED_NODE_TYPE;
 use crate::utils::collections::concat_to_vec;
 use eyre::{Report, WrapErr};
 use itertools::Itertools;
@@ -257,7 +258,11 @@ pub fn add_auspice_metadata_in_place(meta: &mut AuspiceTreeMeta, has_pcr_primers
       key: "Node type".to_owned(),
       title: "Node type".to_owned(),
       type_: "categorical".to_owned(),
-      scale: vec![pair("New", "#ff6961"), pair("Reference", "#999999")],
+      scale: vec![
+        pair("New", "#ff6961"),
+        pair("Reference", "#999999"),
+        pair(COLLAPSED_NODE_TYPE, "#cccccc"),
+      ],
       other: serde_json::Value::default(),
     },
     AuspiceColoring {
@@ -283,6 +288,13 @@ pub fn add_auspice_metadata_in_place(meta: &mut AuspiceTreeMeta, has_pcr_primers
     });
   }
 
+  // Replace colorings and filters which are already present, e.g. when the metadata is added more than once or when
+  // the reference tree was itself produced by Nextclade
+  meta.colorings.retain(|coloring| {
+    !new_colorings
+      .iter()
+      .any(|new_coloring| new_coloring.key == coloring.key)
+  });
   meta.colorings = concat_to_vec(&new_colorings, &meta.colorings);
 
   meta.display_defaults.branch_label = Some("clade".to_owned());
@@ -299,6 +311,7 @@ pub fn add_auspice_metadata_in_place(meta: &mut AuspiceTreeMeta, has_pcr_primers
 
   new_filters.push("Has PCR primer changes".to_owned());
 
+  meta.filters.retain(|filter| !new_filters.contains(filter));
   meta.filters = concat_to_vec(&new_filters, &meta.filters);
 
   meta.geo_resolutions = None;
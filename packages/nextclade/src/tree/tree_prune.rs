@@ -0,0 +1,294 @@
+use crate::graph::node::GraphNodeKey;
+use crate::make_internal_report;
+use crate::tree::tree::{
+  AuspiceGraph, AuspiceGraphNodePayload, AuspiceTree, AuspiceTreeNode, TreeBranchAttrs, TreeNodeAttr, TreeNodeAttrs,
+};
+use crate::tree::tree_find_clade_founder::graph_find_clade_founder;
+use eyre::Report;
+use itertools::Itertools;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// Value of the "Node type" attribute of nodes which summarize collapsed parts of the reference tree
+pub const COLLAPSED_NODE_TYPE: &str = "Collapsed";
+
+/// Converts graph to Auspice tree, keeping only the parts of the reference tree around placed (new) nodes.
+///
+/// For each placed node the following nodes are kept:
+///  - the node itself and all of its ancestors up to the root
+///  - up to `num_neighbors` nearest reference tips, searched within the clade of the placed node (i.e. not beyond the
+///    clade founder), along with the nodes connecting them to the placed node
+///
+/// Children of kept nodes which are not kept themselves are collapsed into one summary node per parent.
+pub fn convert_graph_to_pruned_auspice_tree(graph: &AuspiceGraph, num_neighbors: usize) -> Result<AuspiceTree, Report> {
+  let root_key = graph.get_exactly_one_root()?.key();
+
+  let mut keep = BTreeSet::<GraphNodeKey>::new();
+  for node in graph.iter_nodes().filter(|node| is_new_node(node.payload())) {
+    let node_key = node.key();
+
+    let mut current = Some(node_key);
+    while let Some(key) = current {
+      if !keep.insert(key) {
+        break; // The rest of the path to the root is already kept
+      }
+      current = graph.parent_key_of_by_key(key);
+    }
+
+    let clade_founder_key = match node.payload().clade() {
+      Some(clade) => graph_find_clade_founder(graph, node_key, clade)?,
+      None => root_key,
+    };
+
+    keep.extend(find_nearest_ref_tips(graph, node_key, clade_founder_key, num_neighbors));
+  }
+
+  let num_tips = count_tips(graph, root_key);
+
+  // Convert kept nodes to Auspice format, leaving `children` arrays empty. They are filled during traversal.
+  let mut new_nodes: BTreeMap<GraphNodeKey, AuspiceTreeNode> = keep
+    .iter()
+    .map(|&key| {
+      let node = graph.get_node(key)?;
+      Ok((key, AuspiceTreeNode::from_graph_node_payload(node.payload(), vec![])))
+    })
+    .collect::<Result<_, Report>>()?;
+
+  // Traverse kept nodes post-order
+  let mut stack = vec![(root_key, false)];
+  while let Some((node_key, visited)) = stack.pop() {
+    let (kept_children, collapsed_children): (Vec<GraphNodeKey>, Vec<GraphNodeKey>) = graph
+      .iter_child_keys_of_by_key(node_key)
+      .partition(|child_key| keep.contains(child_key));
+
+    if !visited {
+      stack.push((node_key, true));
+      stack.extend(kept_children.into_iter().map(|child_key| (child_key, false)));
+    } else {
+      let mut new_children = kept_children
+        .iter()
+        .map(|child_key| {
+          new_nodes
+            .remove(child_key)
+            .ok_or_else(|| make_internal_report!("Node '{child_key}' is expected, but not found"))
+        })
+        .collect::<Result<Vec<AuspiceTreeNode>, Report>>()?;
+
+      if !collapsed_children.is_empty() {
+        let parent = graph.get_node(node_key)?.payload();
+        let collapsed = collapsed_children
+          .iter()
+          .map(|child_key| Ok((graph.get_node(*child_key)?.payload(), num_tips[child_key])))
+          .collect::<Result<Vec<_>, Report>>()?;
+        new_children.push(create_collapsed_node(parent, &collapsed));
+      }
+
+      let new_node = new_nodes
+        .get_mut(&node_key)
+        .ok_or_else(|| make_internal_report!("Node '{node_key}' is expected, but not found"))?;
+
+      new_node.children = new_children;
+    }
+  }
+
+  let tree = new_nodes
+    .remove(&root_key)
+    .ok_or_else(|| make_internal_report!("Root node not found"))?;
+
+  Ok(AuspiceTree {
+    version: graph.data.auspice_tree_version.clone(),
+    meta: graph.data.meta.clone(),
+    tree,
+    root_sequence: None,
+    other: graph.data.other.clone(),
+  })
+}
+
+/// Whether the node is a query sequence placed onto the reference tree
+pub fn is_new_node(payload: &AuspiceGraphNodePayload) -> bool {
+  payload
+    .node_attrs
+    .node_type
+    .as_ref()
+    .is_some_and(|node_type| node_type.value == "New")
+}
+
+/// Breadth-first search for the nearest reference tips around a given node, without leaving the subtree of
+/// `scope_root_key`. Returns the tips found, along with the nodes on the paths leading to them.
+fn find_nearest_ref_tips(
+  graph: &AuspiceGraph,
+  start_key: GraphNodeKey,
+  scope_root_key: GraphNodeKey,
+  num_neighbors: usize,
+) -> Vec<GraphNodeKey> {
+  let mut found = vec![];
+  if num_neighbors == 0 {
+    return found;
+  }
+
+  let mut predecessors = BTreeMap::<GraphNodeKey, GraphNodeKey>::new();
+  let mut visited = BTreeSet::from([start_key]);
+  let mut queue = VecDeque::from([start_key]);
+  let mut num_found = 0;
+
+  while let Some(key) = queue.pop_front() {
+    if key != start_key && graph.is_leaf_key(key) {
+      let is_ref = graph.get_node(key).is_ok_and(|node| !is_new_node(node.payload()));
+      if is_ref {
+        // Remember the tip and the path which leads to it
+        let mut current = key;
+        found.push(current);
+        while let Some(&prev) = predecessors.get(&current) {
+          found.push(prev);
+          current = prev;
+        }
+        num_found += 1;
+        if num_found >= num_neighbors {
+          break;
+        }
+      }
+    }
+
+    let parent = (key != scope_root_key)
+      .then(|| graph.parent_key_of_by_key(key))
+      .flatten();
+
+    for next in parent.into_iter().chain(graph.iter_child_keys_of_by_key(key)) {
+      if visited.insert(next) {
+        predecessors.insert(next, key);
+        queue.push_back(next);
+      }
+    }
+  }
+
+  found
+}
+
+/// Counts tips in the subtrees of all nodes
+fn count_tips(graph: &AuspiceGraph, root_key: GraphNodeKey) -> BTreeMap<GraphNodeKey, usize> {
+  let mut num_tips = BTreeMap::new();
+  let mut stack = vec![(root_key, false)];
+  while let Some((node_key, visited)) = stack.pop() {
+    if !visited {
+      stack.push((node_key, true));
+      stack.extend(
+        graph
+          .iter_child_keys_of_by_key(node_key)
+          .map(|child_key| (child_key, false)),
+      );
+    } else {
+      let n = graph
+        .iter_child_keys_of_by_key(node_key)
+        .map(|child_key| num_tips.get(&child_key).copied().unwrap_or_default())
+        .sum::<usize>()
+        .max(usize::from(graph.is_leaf_key(node_key)));
+      num_tips.insert(node_key, n);
+    }
+  }
+  num_tips
+}
+
+/// Creates a tip which summarizes collapsed children of a kept node
+fn create_collapsed_node(
+  parent: &AuspiceGraphNodePayload,
+  collapsed: &[(&AuspiceGraphNodePayload, usize)],
+) -> AuspiceTreeNode {
+  let num_tips: usize = collapsed.iter().map(|(_, n)| n).sum();
+
+  // Only show clade if all of the collapsed children belong to the same clade
+  let clade = collapsed
+    .iter()
+    .map(|(child, _)| child.clade())
+    .all_equal_value()
+    .ok()
+    .flatten();
+
+  AuspiceTreeNode {
+    name: format!("{}_collapsed_{num_tips}", parent.name),
+    branch_attrs: TreeBranchAttrs::default(),
+    node_attrs: TreeNodeAttrs {
+      div: parent.node_attrs.div,
+      clade_membership: clade.as_deref().map(TreeNodeAttr::new),
+      node_type: Some(TreeNodeAttr::new(COLLAPSED_NODE_TYPE)),
+      other: json!({ "Collapsed tips": { "value": num_tips.to_string() } }),
+      ..TreeNodeAttrs::default()
+    },
+    children: vec![],
+    other: serde_json::Value::default(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::tree::tree::{AuspiceGraphEdgePayload, AuspiceGraphMeta};
+  use pretty_assertions::assert_eq;
+
+  fn node(name: &str, clade: &str, is_new: bool) -> AuspiceGraphNodePayload {
+    let mut payload = AuspiceGraphNodePayload::new(name);
+    payload.node_attrs.clade_membership = Some(TreeNodeAttr::new(clade));
+    payload.node_attrs.node_type = Some(TreeNodeAttr::new(if is_new { "New" } else { "Reference" }));
+    payload
+  }
+
+  fn format_tree(node: &AuspiceTreeNode) -> String {
+    if node.children.is_empty() {
+      node.name.clone()
+    } else {
+      format!("({}){}", node.children.iter().map(format_tree).join(","), node.name)
+    }
+  }
+
+  #[test]
+  #[allow(clippy::many_single_char_names)]
+  fn prunes_tree_around_new_nodes() -> Result<(), Report> {
+    //          root (A)
+    //         /        \
+    //      a (A)       b (B)
+    //     / | \        /   \
+    //    c  d  q      e     f
+    //                / \
+    //               g   h
+    let mut graph = AuspiceGraph::new(AuspiceGraphMeta::default());
+    let root = graph.add_node(node("root", "A", false));
+    let a = graph.add_node(node("a", "A", false));
+    let b = graph.add_node(node("b", "B", false));
+    let c = graph.add_node(node("c", "A", false));
+    let d = graph.add_node(node("d", "A", false));
+    let q = graph.add_node(node("q", "A", true));
+    let e = graph.add_node(node("e", "B", false));
+    let f = graph.add_node(node("f", "B", false));
+    let g = graph.add_node(node("g", "B", false));
+    let h = graph.add_node(node("h", "B", false));
+    for (src, dst) in [
+      (root, a),
+      (root, b),
+      (a, c),
+      (a, d),
+      (a, q),
+      (b, e),
+      (b, f),
+      (e, g),
+      (e, h),
+    ] {
+      graph.add_edge(src, dst, AuspiceGraphEdgePayload::new())?;
+    }
+    let graph = graph.build()?;
+
+    let tree = convert_graph_to_pruned_auspice_tree(&graph, 1)?;
+    assert_eq!(format_tree(&tree.tree), "((c,q,a_collapsed_1)a,root_collapsed_3)root");
+
+    let tree = convert_graph_to_pruned_auspice_tree(&graph, 0)?;
+    assert_eq!(format_tree(&tree.tree), "((q,a_collapsed_2)a,root_collapsed_3)root");
+    assert_eq!(
+      tree.tree.children[1]
+        .node_attrs
+        .clade_membership
+        .as_ref()
+        .map(|clade| clade.value.as_str()),
+      Some("B")
+    );
+
+    Ok(())
+  }
+}
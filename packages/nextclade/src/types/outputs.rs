@@ -1,3 +1,4 @@
+use crate::align::chimera::AlignmentChimera;
 use crate::align::insertions_strip::{AaIns, Insertion};
 use crate::alphabet::nuc::Nuc;
 use crate::analyze::aa_changes_group::AaChangesGroup;
@@ -19,8 +20,11 @@ use crate::graph::node::GraphNodeKey;
 use crate::io::json::json_parse;
 use crate::qc::qc_run::QcResult;
 use crate::translate::frame_shifts_translate::FrameShift;
+use crate::translate::translate_genes::AmbiguousAa;
 use crate::tree::tree::AuspiceRefNodesDesc;
 use crate::tree::tree_find_ancestors_of_interest::AncestralSearchResult;
+use crate::tree::tree_find_clade_posterior::CladePosterior;
+use crate::tree::tree_find_nearest_node::TreePlacementInfo;
 use eyre::{Report, WrapErr};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
@@ -35,6 +39,14 @@ pub struct PeptideWarning {
   pub warning: String,
 }
 
+/// A warning about the nucleotide sequence as a whole, rather than about a particular CDS
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NucWarning {
+  /// Warning message text
+  pub warning: String,
+}
+
 /// Result for a single phenotype value
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
@@ -48,7 +60,7 @@ pub struct PhenotypeValue {
 }
 
 /// Single element in `.results` array in nextclade.json file, produced by `nextclade run --output-json`. This corresponds to a single sequence in the inputs.
-#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[schemars(title = "ResultJson")]
 pub struct NextcladeOutputs {
@@ -114,6 +126,10 @@ pub struct NextcladeOutputs {
   pub unknown_aa_ranges: Vec<CdsAaRange>,
   /// Total count of unknown (X) amino acids
   pub total_unknown_aa: usize,
+  /// Amino acids encoded by codons with ambiguous nucleotides, which can be one of several amino acids
+  pub ambiguous_aas: Vec<AmbiguousAa>,
+  /// Total count of ambiguous amino acids
+  pub total_ambiguous_aa: usize,
   /// Groups of adjacent amino acid changes with surrounding nucleotide context
   pub aa_changes_groups: Vec<AaChangesGroup>,
   /// Amino acid substitutions caused by each nucleotide mutation, keyed by CDS name
@@ -130,9 +146,13 @@ pub struct NextcladeOutputs {
   pub pcr_primer_changes: Vec<PcrPrimerChange>,
   /// Total count of mutations in PCR primer regions
   pub total_pcr_primer_changes: usize,
-  /// Assigned clade label from the nearest reference tree node
+  /// Assigned clade label from the nearest reference tree node (or from placement candidates, if probabilistic clade
+  /// assignment is enabled)
   #[serde(skip_serializing_if = "Option::is_none")]
   pub clade: Option<String>,
+  /// Posterior probabilities of clades, if probabilistic clade assignment is enabled
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub clade_posterior: Option<CladePosterior>,
   /// Nucleotide mutations not shared with the nearest reference tree node, subdivided into reversions, labeled, and unlabeled
   pub private_nuc_mutations: PrivateNucMutations,
   /// Per-CDS amino acid mutations not shared with the nearest reference tree node
@@ -151,6 +171,9 @@ pub struct NextcladeOutputs {
   pub relative_aa_mutations: Vec<RelativeAaMutations>,
   /// Non-fatal warnings encountered during analysis
   pub warnings: Vec<PeptideWarning>,
+  /// Non-fatal warnings about the nucleotide sequence, encountered during alignment
+  #[serde(default)]
+  pub nuc_warnings: Vec<NucWarning>,
   /// CDS names that failed translation
   pub missing_cdses: Vec<String>,
   /// Evolutionary divergence from the root of the reference tree
@@ -170,8 +193,18 @@ pub struct NextcladeOutputs {
   /// Names of equidistant nearest tree nodes when multiple candidates exist
   #[serde(skip_serializing_if = "Option::is_none")]
   pub nearest_nodes: Option<Vec<String>>,
+  /// Equidistant placement candidates on the reference tree, with their placement priors
+  #[serde(skip)]
+  pub placement_candidates: Vec<TreePlacementInfo>,
   /// Whether the sequence was reverse-complemented before analysis
   pub is_reverse_complement: bool,
+  /// Incompatible blocks of seed matches, if the sequence is likely chimeric
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub chimera: Option<AlignmentChimera>,
+  /// Results of the separate analysis of each part of a chimeric sequence, if splitting of chimeric sequences is
+  /// enabled. Parts are identified by the suffix `_part<N>` of the sequence ID and share the index of this sequence.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub chimera_parts: Vec<NextcladeOutputs>,
   /// Computed phenotype scores as defined in the dataset configuration
   pub phenotype_values: Option<Vec<PhenotypeValue>>,
   /// Amino acid motifs detected in the query sequence, keyed by motif name